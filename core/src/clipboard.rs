@@ -0,0 +1,205 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Reading the system clipboard, which the Scala version did through java.awt.  Uses the
+//! Wayland or X11 selection tools (wl-paste, xclip, xsel) when present, else asks the terminal
+//! for the clipboard with an OSC 52 query (which many terminals support, including over ssh,
+//! though some need it enabled in their settings).
+use anyhow::anyhow;
+use base64::Engine;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use termion::raw::IntoRawMode;
+
+/// How long to wait for the terminal to answer an OSC 52 query (some never do).
+const OSC52_TIMEOUT_MILLIS: i32 = 2000;
+const URI_SCHEMES_WITHOUT_SLASHES: [&str; 6] = ["mailto", "urn", "tel", "news", "doi", "magnet"];
+const QUOTATION_MARKS: [(char, char); 5] = [('"', '"'), ('\u{201C}', '\u{201D}'), ('\'', '\''), ('\u{2018}', '\u{2019}'), ('\u{00AB}', '\u{00BB}')];
+
+/// What some text (usually from the clipboard) seems to be, to decide what to make of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipboardContentKind {
+    /// A single "word" like https://example.com/a or mailto:someone@example.com .
+    Uri,
+    /// Text in quotation marks, or several lines (like a passage copied from a web page).
+    Quote,
+    PlainText,
+}
+
+pub fn get_clipboard_content() -> Result<String, anyhow::Error> {
+    let mut commands: Vec<Vec<&str>> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push(vec!["wl-paste", "--no-newline"]);
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        commands.push(vec!["xclip", "-selection", "clipboard", "-out"]);
+        commands.push(vec!["xsel", "--clipboard", "--output"]);
+    }
+    for command in commands {
+        if let Some(content) = run_selection_tool(&command) {
+            return Ok(content);
+        }
+    }
+    match read_with_osc52()? {
+        Some(content) => Ok(content),
+        None => Err(anyhow!(
+            "Unable to read the clipboard.  Install wl-clipboard (for Wayland) or xclip or xsel \
+            (for X11), or use a terminal that answers OSC 52 clipboard queries (which might need \
+            enabling in its settings)."
+        )),
+    }
+}
+
+/// Returns None if the tool is not installed or fails (as xclip does when the clipboard is
+/// empty), so the next way can be tried.
+fn run_selection_tool(command: &[&str]) -> Option<String> {
+    let output = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        None
+    }
+}
+
+/// Returns None if the terminal did not answer in time.
+fn read_with_osc52() -> Result<Option<String>, anyhow::Error> {
+    let mut tty = match std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty") {
+        Ok(tty) => tty,
+        // (No terminal, as when run from a script: so no way to ask.)
+        Err(_) => return Ok(None),
+    };
+    let mut response: Vec<u8> = Vec::new();
+    {
+        // (Raw mode so the answer is not echoed, and arrives without waiting for Enter.)
+        let mut raw_tty = tty.try_clone()?.into_raw_mode()?;
+        raw_tty.write_all(b"\x1b]52;c;?\x07")?;
+        raw_tty.flush()?;
+        let mut byte = [0u8; 1];
+        while wait_for_input(&tty, OSC52_TIMEOUT_MILLIS)? {
+            if tty.read(&mut byte)? == 0 {
+                break;
+            }
+            response.push(byte[0]);
+            if response.ends_with(b"\x07") || response.ends_with(b"\x1b\\") {
+                break;
+            }
+        }
+    }
+    Ok(parse_osc52_response(&response))
+}
+
+#[cfg(unix)]
+fn wait_for_input(tty: &std::fs::File, timeout_millis: i32) -> Result<bool, anyhow::Error> {
+    use std::os::unix::io::AsRawFd;
+    let mut poll_fd = libc::pollfd {
+        fd: tty.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // (Safe: poll only reads and writes the one pollfd passed to it.)
+    let ready = unsafe { libc::poll(&mut poll_fd, 1, timeout_millis) };
+    if ready < 0 {
+        return Err(anyhow!("Error waiting for the terminal: {}", std::io::Error::last_os_error()));
+    }
+    Ok(ready > 0)
+}
+
+#[cfg(not(unix))]
+fn wait_for_input(_tty: &std::fs::File, _timeout_millis: i32) -> Result<bool, anyhow::Error> {
+    Ok(false)
+}
+
+/// Parses an answer like ESC ] 52 ; c ; <base64> BEL (or ending with ESC \ instead of BEL).
+fn parse_osc52_response(response: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(response);
+    let start = text.find("\x1b]52;")? + "\x1b]52;".len();
+    let after_selection = &text[start..];
+    let data_start = after_selection.find(';')? + 1;
+    let data = after_selection[data_start..]
+        .trim_end_matches('\x07')
+        .trim_end_matches("\x1b\\");
+    let decoded = base64::engine::general_purpose::STANDARD.decode(data).ok()?;
+    Some(String::from_utf8_lossy(&decoded).to_string())
+}
+
+pub fn get_content_kind(text: &str) -> ClipboardContentKind {
+    let text = text.trim();
+    if is_uri(text) {
+        ClipboardContentKind::Uri
+    } else if strip_quotation_marks(text) != text
+        || text.lines().filter(|line| !line.trim().is_empty()).count() > 1
+    {
+        ClipboardContentKind::Quote
+    } else {
+        ClipboardContentKind::PlainText
+    }
+}
+
+/// True for one "word" starting with a scheme (RFC 3986: a letter, then letters, digits, "+",
+/// "-" or "."), then "://" or, for schemes like mailto, just ":".
+pub fn is_uri(text: &str) -> bool {
+    if text.is_empty() || text.chars().any(char::is_whitespace) {
+        return false;
+    }
+    let Some((scheme, rest)) = text.split_once(':') else {
+        return false;
+    };
+    let mut scheme_chars = scheme.chars();
+    let scheme_ok = scheme_chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme_chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    scheme_ok
+        && !rest.is_empty()
+        && (rest.starts_with("//")
+            || URI_SCHEMES_WITHOUT_SLASHES.contains(&scheme.to_lowercase().as_str()))
+}
+
+/// Returns text without one pair of enclosing quotation marks, if it has them.
+pub fn strip_quotation_marks(text: &str) -> &str {
+    let text = text.trim();
+    for (open, close) in QUOTATION_MARKS {
+        if text.chars().count() >= 2 && text.starts_with(open) && text.ends_with(close) {
+            return text[open.len_utf8()..text.len() - close.len_utf8()].trim();
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_content_kind() {
+        assert_eq!(get_content_kind("https://example.com/a?b=c\n"), ClipboardContentKind::Uri);
+        assert_eq!(get_content_kind("mailto:someone@example.com"), ClipboardContentKind::Uri);
+        assert_eq!(get_content_kind("file:///tmp/x.txt"), ClipboardContentKind::Uri);
+        assert_eq!(get_content_kind("see https://example.com"), ClipboardContentKind::PlainText);
+        assert_eq!(get_content_kind("note: buy milk"), ClipboardContentKind::PlainText);
+        assert_eq!(get_content_kind("12:30"), ClipboardContentKind::PlainText);
+        assert_eq!(get_content_kind("\u{201C}To be, or not.\u{201D}"), ClipboardContentKind::Quote);
+        assert_eq!(get_content_kind("line one\n\nline two"), ClipboardContentKind::Quote);
+        assert_eq!(strip_quotation_marks(" \"To be\" "), "To be");
+        assert_eq!(strip_quotation_marks("\u{00AB}Oui\u{00BB}"), "Oui");
+        assert_eq!(strip_quotation_marks("\""), "\"");
+    }
+
+    #[test]
+    fn test_parse_osc52_response() {
+        // ("aGVsbG8=" is "hello" in base64.)
+        assert_eq!(parse_osc52_response(b"\x1b]52;c;aGVsbG8=\x07"), Some("hello".to_string()));
+        assert_eq!(parse_osc52_response(b"\x1b]52;c;aGVsbG8=\x1b\\"), Some("hello".to_string()));
+        assert_eq!(parse_osc52_response(b""), None);
+        assert_eq!(parse_osc52_response(b"\x1b]52;c;not base64!\x07"), None);
+    }
+}
@@ -0,0 +1,406 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Importing browser bookmarks from the "Netscape bookmark file" HTML format, which Firefox,
+//! Chromium and others export.  Each folder becomes an entity with a group of the same name
+//! (as with the outline import in import_export.rs), and each bookmark a URI entity in that
+//! group (as from the "external web page" attribute form).
+use crate::model::database::Database;
+use crate::util::Util;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    pub uri: String,
+    /// In milliseconds, from the ADD_DATE attribute (which is in seconds).
+    pub add_date: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BookmarkFolder {
+    pub name: String,
+    pub add_date: Option<i64>,
+    pub items: Vec<BookmarkItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookmarkItem {
+    Folder(BookmarkFolder),
+    Bookmark(Bookmark),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookmarkImportCounts {
+    pub folders: usize,
+    pub created: usize,
+    /// Bookmarks whose URI was already on a URI entity, which was added to the folder's group
+    /// instead of creating another.
+    pub linked: usize,
+}
+
+/// Returns the top-level folder, named from the file's H1 heading (or "Bookmarks").  Tolerates
+/// the format's unclosed DT and P tags, and ignores anything it does not use (like icons).
+pub fn parse_bookmarks_html(html: &str) -> BookmarkFolder {
+    let mut stack: Vec<BookmarkFolder> = vec![BookmarkFolder {
+        name: "Bookmarks".to_string(),
+        ..Default::default()
+    }];
+    // A folder heading (H3) seen but whose DL has not started yet:
+    let mut pending_folder: Option<BookmarkFolder> = None;
+    let mut rest = html;
+    while let Some(tag_start) = rest.find('<') {
+        let Some(tag_length) = rest[tag_start..].find('>') else {
+            break;
+        };
+        let tag = &rest[tag_start + 1..tag_start + tag_length];
+        rest = &rest[tag_start + tag_length + 1..];
+        let tag_name = tag
+            .split(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or("")
+            .to_ascii_uppercase();
+        match tag_name.as_str() {
+            "H1" | "H3" => {
+                let (text, after) = take_text_until_close(rest, &tag_name);
+                rest = after;
+                if tag_name == "H1" {
+                    if !text.is_empty() {
+                        stack[0].name = text;
+                    }
+                } else {
+                    pending_folder = Some(BookmarkFolder {
+                        name: text,
+                        add_date: get_date_attribute(tag, "ADD_DATE"),
+                        items: Vec::new(),
+                    });
+                }
+            }
+            "DL" => {
+                // (The outermost DL is the top-level folder's own list.)
+                if let Some(folder) = pending_folder.take() {
+                    stack.push(folder);
+                }
+            }
+            "/DL" if stack.len() > 1 => {
+                let folder = stack.pop().unwrap();
+                stack.last_mut().unwrap().items.push(BookmarkItem::Folder(folder));
+            }
+            "A" => {
+                let (text, after) = take_text_until_close(rest, "A");
+                rest = after;
+                if let Some(uri) = get_attribute(tag, "HREF") {
+                    let name = if text.is_empty() { uri.clone() } else { text };
+                    stack.last_mut().unwrap().items.push(BookmarkItem::Bookmark(Bookmark {
+                        name,
+                        uri,
+                        add_date: get_date_attribute(tag, "ADD_DATE"),
+                    }));
+                }
+            }
+            _ => {}
+        }
+    }
+    // (In case of missing closing tags.)
+    while stack.len() > 1 {
+        let folder = stack.pop().unwrap();
+        stack.last_mut().unwrap().items.push(BookmarkItem::Folder(folder));
+    }
+    stack.pop().unwrap()
+}
+
+/// Returns the decoded text before the next tag, and what follows it if it is the closing tag
+/// for tag_name (else what starts with that next tag, so a missing closing tag does not swallow
+/// the rest of the file).  Titles have no tags inside ("<" in them is written as "&lt;"), and
+/// this only looks at the text and the tag after it, so parsing stays linear in the file size.
+fn take_text_until_close<'a>(html: &'a str, tag_name: &str) -> (String, &'a str) {
+    let end = html.find('<').unwrap_or(html.len());
+    let close = format!("</{}>", tag_name);
+    let after = match html.get(end..end + close.len()) {
+        Some(next) if next.eq_ignore_ascii_case(&close) => &html[end + close.len()..],
+        _ => &html[end..],
+    };
+    (decode_html_entities(html[..end].trim()), after)
+}
+
+/// Gets an attribute's value from inside a tag like: A HREF="https://x" ADD_DATE="1600000000"
+fn get_attribute(tag: &str, name: &str) -> Option<String> {
+    let upper = tag.to_ascii_uppercase();
+    let pattern = format!("{}=\"", name);
+    let mut search_from = 0;
+    while let Some(found) = upper[search_from..].find(&pattern) {
+        let start = search_from + found;
+        // (Be sure it is the whole attribute name, not the end of a longer one.)
+        if start == 0 || upper[..start].ends_with(char::is_whitespace) {
+            let value_start = start + pattern.len();
+            let value_length = tag[value_start..].find('"')?;
+            return Some(decode_html_entities(&tag[value_start..value_start + value_length]));
+        }
+        search_from = start + pattern.len();
+    }
+    None
+}
+
+fn get_date_attribute(tag: &str, name: &str) -> Option<i64> {
+    let seconds: i64 = get_attribute(tag, name)?.trim().parse().ok()?;
+    // (Some browsers have written microseconds here instead of seconds.)
+    if seconds > 100_000_000_000_000 {
+        Some(seconds / 1000)
+    } else {
+        Some(seconds * 1000)
+    }
+}
+
+pub fn decode_html_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded: Option<(char, usize)> = rest.find(';').and_then(|semicolon| {
+            let entity = &rest[1..semicolon];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{00A0}'),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32)
+                }
+                _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+                _ => None,
+            };
+            c.map(|c| (c, semicolon + 1))
+        });
+        match decoded {
+            Some((c, length)) => {
+                result.push(c);
+                rest = &rest[length..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Adds root_folder_in to the entity containing_entity_id_in, as an entity with a group of
+/// the folder's name, and so on for the folders within it.  A bookmark whose URI is already on
+/// a URI entity is linked (by adding that entity to the group) rather than re-created, as are
+/// repeats within the file.  Observation dates are the bookmarks' and folders' add dates,
+/// where given, otherwise default_date_in.
+pub fn import_bookmarks(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    containing_entity_id_in: i64,
+    root_folder_in: &BookmarkFolder,
+    make_them_public_in: Option<bool>,
+    default_date_in: i64,
+) -> Result<BookmarkImportCounts, anyhow::Error> {
+    let db = db.borrow();
+    let has_relation_type_id =
+        db.find_relation_type(transaction.clone(), Util::THE_HAS_RELATION_TYPE_NAME)?;
+    let mut counts = BookmarkImportCounts::default();
+    let folder_date = root_folder_in.add_date.unwrap_or(default_date_in);
+    let (folder_entity_id, _, _) = db.create_entity_and_add_has_local_relation_to_it(
+        transaction.clone(),
+        containing_entity_id_in,
+        &root_folder_in.name,
+        folder_date,
+        make_them_public_in,
+    )?;
+    import_folder(
+        &*db,
+        transaction,
+        folder_entity_id,
+        has_relation_type_id,
+        root_folder_in,
+        make_them_public_in,
+        default_date_in,
+        &mut counts,
+    )?;
+    Ok(counts)
+}
+
+/// Creates the group for folder_in on folder_entity_id_in, and fills it.
+fn import_folder(
+    db: &dyn Database,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    folder_entity_id_in: i64,
+    has_relation_type_id_in: i64,
+    folder_in: &BookmarkFolder,
+    make_them_public_in: Option<bool>,
+    default_date_in: i64,
+    counts: &mut BookmarkImportCounts,
+) -> Result<(), anyhow::Error> {
+    counts.folders += 1;
+    let (group_id, _) = db.create_group_and_relation_to_group(
+        transaction.clone(),
+        folder_entity_id_in,
+        has_relation_type_id_in,
+        &folder_in.name,
+        true,
+        None,
+        folder_in.add_date.unwrap_or(default_date_in),
+        None,
+    )?;
+    for item in &folder_in.items {
+        match item {
+            BookmarkItem::Folder(subfolder) => {
+                let subfolder_entity_id =
+                    db.create_entity(transaction.clone(), &subfolder.name, None, make_them_public_in)?;
+                db.add_entity_to_group(transaction.clone(), group_id, subfolder_entity_id, None)?;
+                import_folder(
+                    db,
+                    transaction.clone(),
+                    subfolder_entity_id,
+                    has_relation_type_id_in,
+                    subfolder,
+                    make_them_public_in,
+                    default_date_in,
+                    counts,
+                )?;
+            }
+            BookmarkItem::Bookmark(bookmark) => {
+                let existing = db.find_uri_entity_ids(transaction.clone(), &bookmark.uri)?;
+                let entity_id = match existing.first() {
+                    Some(id) => {
+                        counts.linked += 1;
+                        *id
+                    }
+                    None => {
+                        counts.created += 1;
+                        db.create_uri_entity(
+                            transaction.clone(),
+                            &bookmark.name,
+                            &bookmark.uri,
+                            bookmark.add_date.unwrap_or(default_date_in),
+                            make_them_public_in,
+                            None,
+                        )?
+                    }
+                };
+                // (Unless it is already there, as for a repeat within one folder.)
+                if !db.is_entity_in_group(transaction.clone(), group_id, entity_id)? {
+                    db.add_entity_to_group(transaction.clone(), group_id, entity_id, None)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+
+    const SAMPLE: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file. -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks Menu</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1600000000" LAST_MODIFIED="1600000001">Rust &amp; friends</H3>
+    <DL><p>
+        <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1600000100" ICON="data:image/png;base64,AAA=">Rust</A>
+        <DT><H3>Empty</H3>
+        <DL><p>
+        </DL><p>
+        <DT><A HREF="https://docs.rs/?q=a&amp;b" LAST_ADD_DATE="5">docs.rs</A>
+        <DD>A description, which is ignored.
+    </DL><p>
+    <DT><A HREF="https://example.com/" ADD_DATE="1600000200"></A>
+</DL><p>
+"#;
+
+    #[test]
+    fn test_parse_bookmarks_html() {
+        let root = parse_bookmarks_html(SAMPLE);
+        assert_eq!(root.name, "Bookmarks Menu");
+        assert_eq!(root.items.len(), 2);
+        let BookmarkItem::Folder(ref folder) = root.items[0] else {
+            panic!("expected a folder: {:?}", root.items[0]);
+        };
+        assert_eq!(folder.name, "Rust & friends");
+        assert_eq!(folder.add_date, Some(1_600_000_000_000));
+        assert_eq!(
+            folder.items[0],
+            BookmarkItem::Bookmark(Bookmark {
+                name: "Rust".to_string(),
+                uri: "https://www.rust-lang.org/".to_string(),
+                add_date: Some(1_600_000_100_000),
+            })
+        );
+        assert!(matches!(&folder.items[1], BookmarkItem::Folder(f) if f.name == "Empty" && f.items.is_empty()));
+        assert_eq!(
+            folder.items[2],
+            BookmarkItem::Bookmark(Bookmark {
+                name: "docs.rs".to_string(),
+                uri: "https://docs.rs/?q=a&b".to_string(),
+                add_date: None,
+            })
+        );
+        // (A bookmark without a title is named by its URI.)
+        assert!(matches!(&root.items[1], BookmarkItem::Bookmark(b) if b.name == "https://example.com/"));
+        assert_eq!(decode_html_entities("a &lt;b&gt; &#233;&#x41; & c"), "a <b> éA & c");
+    }
+
+    #[test]
+    fn test_parse_bookmarks_html_with_missing_closing_tag() {
+        let html = "<DL><p>\n<DT><A HREF=\"https://a.example\">A, unclosed\n\
+            <DT><a href=\"https://b.example\">B</a>\n</DL>";
+        let root = parse_bookmarks_html(html);
+        let uris: Vec<String> = root
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                BookmarkItem::Bookmark(b) => Some(b.uri.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(uris, vec!["https://a.example", "https://b.example"]);
+    }
+
+    #[test]
+    fn test_import_bookmarks_links_duplicates() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> =
+            Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let entity_id = db_b.create_entity(tx.clone(), "bookmark import test", None, None).unwrap();
+        let (existing_id, _) = db_b
+            .add_uri_entity_with_uri_attribute(tx.clone(), entity_id, "already here", "https://example.com/", 5, None, None)
+            .unwrap();
+        assert_eq!(db_b.find_uri_entity_ids(tx.clone(), "https://example.com/").unwrap(), vec![existing_id]);
+
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let root = parse_bookmarks_html(SAMPLE);
+        let counts = import_bookmarks(db_dyn, tx.clone(), entity_id, &root, Some(false), 1000).unwrap();
+        assert_eq!(counts, BookmarkImportCounts { folders: 3, created: 2, linked: 1 });
+
+        let rust_ids = db_b.find_uri_entity_ids(tx.clone(), "https://www.rust-lang.org/").unwrap();
+        assert_eq!(rust_ids.len(), 1);
+        let groups = db_b.get_containing_groups_ids(tx.clone(), existing_id).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(db_b.get_group_entry_ids(tx.clone(), groups[0], 0, None).unwrap().len(), 2);
+        let rust_groups = db_b.get_containing_groups_ids(tx.clone(), rust_ids[0]).unwrap();
+        assert_eq!(db_b.get_group_entry_ids(tx.clone(), rust_groups[0], 0, None).unwrap().len(), 3);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
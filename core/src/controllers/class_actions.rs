@@ -0,0 +1,297 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Runs a class's actions (rows of the Action table, whose scripts are in the language of
+//! action_script.rs) on an entity of that class.  EntityScriptHost is what a script can
+//! reach: the entity's own quantity, date, boolean and text attributes.  When a script sets
+//! one the entity doesn't have, it is added like the class's template entity has it (with the
+//! template's unit, for a quantity), so scripts can't invent attribute types.
+use crate::controllers::csv_import::{self, TemplateAttributeForm, TemplateAttributeType};
+use crate::model::action_script::{ActionScript, ActionScriptHost, ScriptValue};
+use crate::model::attribute_with_valid_and_observed_dates::AttributeWithValidAndObservedDates;
+use crate::model::boolean_attribute::BooleanAttribute;
+use crate::model::database::Database;
+use crate::model::date_attribute::DateAttribute;
+use crate::model::entity::Entity;
+use crate::model::quantity_attribute::QuantityAttribute;
+use crate::model::text_attribute::TextAttribute;
+use anyhow::anyhow;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub struct EntityScriptHost<'t> {
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<'t, Postgres>>>>,
+    entity_id: i64,
+    observation_date: i64,
+    template_types: Vec<TemplateAttributeType>,
+    /// How many attributes the script updated or added.
+    pub changes: usize,
+}
+
+/// One of the entity's attributes that a script can see.
+struct FoundAttribute {
+    id: i64,
+    attr_type_id: i64,
+    value: ScriptValue,
+    unit_id: Option<i64>,
+    valid_on_date: Option<i64>,
+}
+
+impl<'t> EntityScriptHost<'t> {
+    pub fn new(
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<'t, Postgres>>>>,
+        entity_id_in: i64,
+        observation_date_in: i64,
+    ) -> Result<EntityScriptHost<'t>, anyhow::Error> {
+        let class_id = Entity::new2(db.clone(), transaction.clone(), entity_id_in)?.get_class_id(transaction.clone())?;
+        let template_types = match class_id {
+            Some(class_id) => csv_import::read_template_attribute_types(db.clone(), transaction.clone(), class_id)?,
+            None => Vec::new(),
+        };
+        Ok(EntityScriptHost {
+            db,
+            transaction,
+            entity_id: entity_id_in,
+            observation_date: observation_date_in,
+            template_types,
+            changes: 0,
+        })
+    }
+
+    /// The entity's attributes whose type is named type_name_in, in their displayed order.
+    fn find_attributes(&self, type_name_in: &str) -> Result<Vec<FoundAttribute>, anyhow::Error> {
+        let tx = self.transaction.clone();
+        let entity = Entity::new2(self.db.clone(), tx.clone(), self.entity_id)?;
+        let (attribute_tuples, _) = entity.get_sorted_attributes(tx.clone(), 0, 0, false)?;
+        let mut found: Vec<FoundAttribute> = Vec::new();
+        for (_, attribute) in attribute_tuples {
+            let mut attribute = attribute.borrow_mut();
+            let attr_type_id = attribute.get_attr_type_id(tx.clone())?;
+            let type_name = self.db.borrow().get_entity_name(tx.clone(), attr_type_id)?.unwrap_or_default();
+            if !type_name.eq_ignore_ascii_case(type_name_in.trim()) {
+                continue;
+            }
+            let id = attribute.get_id();
+            let any = attribute.as_any_mut();
+            let (value, unit_id, valid_on_date) = if let Some(quantity) = any.downcast_mut::<QuantityAttribute>() {
+                (
+                    ScriptValue::Number(quantity.get_number(tx.clone())?),
+                    Some(quantity.get_unit_id(tx.clone())?),
+                    quantity.get_valid_on_date(tx.clone())?,
+                )
+            } else if let Some(date) = any.downcast_mut::<DateAttribute>() {
+                (ScriptValue::Date(date.get_date(tx.clone())?), None, None)
+            } else if let Some(boolean) = any.downcast_mut::<BooleanAttribute>() {
+                (
+                    ScriptValue::Boolean(boolean.get_boolean(tx.clone())?),
+                    None,
+                    boolean.get_valid_on_date(tx.clone())?,
+                )
+            } else if let Some(text) = any.downcast_mut::<TextAttribute>() {
+                (
+                    ScriptValue::Text(text.get_text(tx.clone())?),
+                    None,
+                    text.get_valid_on_date(tx.clone())?,
+                )
+            } else {
+                continue;
+            };
+            found.push(FoundAttribute {
+                id,
+                attr_type_id,
+                value,
+                unit_id,
+                valid_on_date,
+            });
+        }
+        Ok(found)
+    }
+}
+
+fn same_form(a: &ScriptValue, b: &ScriptValue) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+impl ActionScriptHost for EntityScriptHost<'_> {
+    fn get_entity_name(&mut self) -> Result<String, anyhow::Error> {
+        Entity::new2(self.db.clone(), self.transaction.clone(), self.entity_id)?.get_name(self.transaction.clone())
+    }
+
+    fn get_attribute(&mut self, type_name_in: &str) -> Result<Option<ScriptValue>, anyhow::Error> {
+        Ok(self.find_attributes(type_name_in)?.into_iter().next().map(|a| a.value))
+    }
+
+    fn set_attribute(&mut self, type_name_in: &str, value_in: &ScriptValue) -> Result<(), anyhow::Error> {
+        let tx = self.transaction.clone();
+        let db = self.db.borrow();
+        let existing = self.find_attributes(type_name_in)?;
+        if let Some(found) = existing.iter().find(|a| same_form(&a.value, value_in)) {
+            match value_in {
+                ScriptValue::Number(n) => {
+                    db.update_quantity_attribute(
+                        tx,
+                        found.id,
+                        self.entity_id,
+                        found.attr_type_id,
+                        found.unit_id.unwrap_or_default(),
+                        *n,
+                        found.valid_on_date,
+                        self.observation_date,
+                    )?;
+                }
+                ScriptValue::Date(date) => {
+                    db.update_date_attribute(tx, found.id, self.entity_id, *date, found.attr_type_id)?;
+                }
+                ScriptValue::Boolean(b) => {
+                    db.update_boolean_attribute(
+                        tx,
+                        found.id,
+                        self.entity_id,
+                        found.attr_type_id,
+                        *b,
+                        found.valid_on_date,
+                        self.observation_date,
+                    )?;
+                }
+                ScriptValue::Text(t) => {
+                    db.update_text_attribute(
+                        tx,
+                        found.id,
+                        self.entity_id,
+                        found.attr_type_id,
+                        t,
+                        found.valid_on_date,
+                        self.observation_date,
+                    )?;
+                }
+                ScriptValue::Duration(_) => return Err(anyhow!("a duration can't be stored")),
+            }
+            self.changes += 1;
+            return Ok(());
+        }
+        if !existing.is_empty() {
+            return Err(anyhow!(
+                "the entity's \"{}\" attribute can't hold a {}",
+                type_name_in,
+                value_in.type_name()
+            ));
+        }
+        let template_type = self
+            .template_types
+            .iter()
+            .find(|t| {
+                t.name.eq_ignore_ascii_case(type_name_in.trim())
+                    && matches!(
+                        (&t.form, value_in),
+                        (TemplateAttributeForm::Quantity { .. }, ScriptValue::Number(_))
+                            | (TemplateAttributeForm::Date, ScriptValue::Date(_))
+                            | (TemplateAttributeForm::Boolean, ScriptValue::Boolean(_))
+                            | (TemplateAttributeForm::Text, ScriptValue::Text(_))
+                    )
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "the entity has no \"{}\" attribute to set, and its class's template entity has none like it to add",
+                    type_name_in
+                )
+            })?;
+        let attr_type_id = template_type.attr_type_id;
+        match (value_in, &template_type.form) {
+            (ScriptValue::Number(n), TemplateAttributeForm::Quantity { unit_id, .. }) => {
+                db.create_quantity_attribute(tx, self.entity_id, attr_type_id, *unit_id, *n, None, self.observation_date, None)?;
+            }
+            (ScriptValue::Date(date), _) => {
+                db.create_date_attribute(tx, self.entity_id, attr_type_id, *date, None)?;
+            }
+            (ScriptValue::Boolean(b), _) => {
+                db.create_boolean_attribute(tx, self.entity_id, attr_type_id, *b, None, self.observation_date, None)?;
+            }
+            (ScriptValue::Text(t), _) => {
+                db.create_text_attribute(tx, self.entity_id, attr_type_id, t, None, self.observation_date, None)?;
+            }
+            _ => return Err(anyhow!("a {} can't be stored", value_in.type_name())),
+        }
+        self.changes += 1;
+        Ok(())
+    }
+}
+
+/// Runs action_in on entity_id_in, returning what the script said and how many attributes
+/// it changed.  The caller commits transaction if this succeeds: any error leaves the
+/// entity as it was, once the transaction is rolled back.
+pub fn run_action(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    entity_id_in: i64,
+    action_in: &str,
+    now_in: i64,
+) -> Result<(Vec<String>, usize), anyhow::Error> {
+    let script = ActionScript::parse(action_in)?;
+    let mut host = EntityScriptHost::new(db, transaction, entity_id_in, now_in)?;
+    let said = script.run(&mut host, now_in)?;
+    Ok((said, host.changes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+    use crate::util::Util;
+
+    #[test]
+    fn test_run_action() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let (class_id, template_id) = db_b.create_class_and_its_template_entity(tx.clone(), "action task").unwrap();
+        let due_type = db_b.create_entity(tx.clone(), "due", None, None).unwrap();
+        let done_type = db_b.create_entity(tx.clone(), "done", None, None).unwrap();
+        db_b.create_date_attribute(tx.clone(), template_id, due_type, 0, None).unwrap();
+        db_b.create_boolean_attribute(tx.clone(), template_id, done_type, false, None, 0, None)
+            .unwrap();
+        let day = 24 * 60 * 60 * 1000;
+        let task = db_b.create_entity(tx.clone(), "write report", Some(class_id), None).unwrap();
+        let due_id = db_b.create_date_attribute(tx.clone(), task, due_type, 10 * day, None).unwrap();
+
+        let postpone = "set \"due\" = get(\"due\") + 1 week";
+        let action_id = db_b.create_action(tx.clone(), class_id, "postpone a week", postpone).unwrap();
+        db_b.create_action(tx.clone(), class_id, "mark done", "set \"done\" = true\nsay name + \" is done\"")
+            .unwrap();
+        let actions = db_b.get_actions(tx.clone(), class_id).unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].1, "mark done");
+        assert_eq!(actions[1], (action_id, "postpone a week".to_string(), postpone.to_string()));
+
+        let (said, changes) = run_action(db_dyn.clone(), tx.clone(), task, &actions[1].2, 20 * day).unwrap();
+        assert!(said.is_empty());
+        assert_eq!(changes, 1);
+        let mut due = DateAttribute::new2(db_dyn.clone(), tx.clone(), due_id).unwrap();
+        assert_eq!(due.get_date(tx.clone()).unwrap(), 17 * day);
+
+        // "done" is not on the task yet, so comes from the template.
+        let (said, changes) = run_action(db_dyn.clone(), tx.clone(), task, &actions[0].2, 20 * day).unwrap();
+        assert_eq!(said, vec!["write report is done".to_string()]);
+        assert_eq!(changes, 1);
+        let mut host = EntityScriptHost::new(db_dyn.clone(), tx.clone(), task, 0).unwrap();
+        assert_eq!(host.get_attribute("DONE").unwrap(), Some(ScriptValue::Boolean(true)));
+        let error = run_action(db_dyn.clone(), tx.clone(), task, "set \"priority\" = 1", 0).unwrap_err();
+        assert!(error.to_string().contains("template entity has none like it"));
+
+        db_b.update_action(tx.clone(), action_id, "postpone", "stop").unwrap();
+        assert_eq!(db_b.delete_action(tx.clone(), action_id).unwrap(), 1);
+        assert_eq!(db_b.get_actions(tx.clone(), class_id).unwrap().len(), 1);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
@@ -13,9 +13,11 @@
 // incremental compilation faster when only one has changed (or editing faster w/ rust-analyzer).
 
 use crate::controllers::main_menu::MainMenu;
+use crate::credentials;
 use crate::model::database::Database;
 use crate::model::entity::Entity;
 use crate::model::has_id::HasId;
+use crate::model::navigation_history::NavigationHistory;
 use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
 use crate::util::Util;
 use crate::TextUI;
@@ -48,6 +50,7 @@ use anyhow::anyhow;
 //use std::collections::HashMap;
 //use std::fs::File;
 use std::path::Path;
+use zeroize::Zeroizing;
 
 /// This Controller is for user-interactive things.  The Controller class in the web module
 /// is for the REST API.  For shared code that does not fit
@@ -75,6 +78,9 @@ pub struct Controller {
     // putting this in a var instead of recalculating it every time (too frequent) inside find_default_display_entity_id:
     pub(crate) show_public_private_status_preference: Option<bool>,
     default_display_entity_id: Option<i64>,
+    // Where the user has been, for going back and forward (see navigation_history.rs, and
+    // record_visit in controller6.rs).  Menus take &self, hence the RefCell.
+    pub(crate) navigation: RefCell<NavigationHistory>,
     move_farther_count: i32,
     move_farthest_count: i32,
 }
@@ -107,12 +113,14 @@ impl Controller {
              //(see comment in call to expect() in get_default_entity() .)
              db.borrow().get_user_preference_entity_id(None, Util::DEFAULT_ENTITY_PREFERENCE, None)
                  .expect("Faiure in call to get_user_preference_entity_id()");
+        let navigation = RefCell::new(db.borrow().get_navigation_history(None)?);
         Ok(Controller {
             ui: Rc::new(ui),
             force_user_pass_prompt,
             db,
             show_public_private_status_preference,
             default_display_entity_id,
+            navigation,
             move_farther_count: 25,
             move_farthest_count: 50,
         })
@@ -198,8 +206,16 @@ impl Controller {
         // most likely to cause an error as default by being missing, so the system can respond by prompting
         // the user in some other way for a use.
         //let default_entity_info = self.get_default_entity();
-        let mut default_entity: Option<Entity> = match self.get_default_entity() {
-            Some((_, default_entity)) => Some(default_entity),
+        // Reopen where the user left off, if that is still there, else at the default entity.
+        let last_location = self.navigation.borrow().current();
+        let last_entity: Option<Entity> = match last_location {
+            None => None,
+            Some(location) => self
+                .entity_for_location(location)
+                .expect("Failure in call to entity_for_location."),
+        };
+        let mut default_entity: Option<Entity> = match last_entity.or_else(|| self.get_default_entity().map(|(_, e)| e)) {
+            Some(entity) => Some(entity),
             None => {
                 self.ui.display_text1("To get started, you probably want to find or create an \
                           entity (such as with your own name, to track information \
@@ -264,7 +280,7 @@ impl Controller {
 
             Self::prompt_for_user_pass_and_login(ui)
         } else if default_username.is_some() && default_password.is_some() {
-            // idea: perhaps this could be enhanced and tested to allow a username parameter, but prompt for a password, if/when need exists.
+            // (Deprecated, since others can see command-line arguments: see main.rs.)
             let user = default_username.unwrap_or_else(|| {
                 ui.display_text1("How could username be absent? Just checked and it was there.");
                 std::process::exit(1);
@@ -273,10 +289,7 @@ impl Controller {
                 ui.display_text1("How could password be absent? Just checked and it was there.");
                 std::process::exit(1);
             });
-            let db_result = PostgreSQLDatabase::new(user, pass);
-            // not attempting to clear that password variable because
-            // maybe the default kind is less intended to be secure, anyway?
-            db_result
+            PostgreSQLDatabase::new(user, pass)
         } else {
             Self::try_other_logins_or_prompt(ui)
         }
@@ -294,7 +307,7 @@ impl Controller {
                 }
                 Some(username) => {
                     let pwd = ui.ask_for_string4(vec!["Password"], None, "".to_string(), true);
-                    match pwd {
+                    match pwd.map(Zeroizing::new) {
                         None => {
                             //user probably wants out
                             std::process::exit(1);
@@ -314,67 +327,131 @@ impl Controller {
         }
     }
 
-    /// Tries the system username & default password, & if that doesn't work, prompts user.
+    /// Tries, in order: the system username with a password from the PostgreSQL password file
+    /// (~/.pgpass or PGPASSFILE); the encrypted OneModel credentials file if there is one; then
+    /// prompts the user (offering to save what worked, in a new encrypted credentials file).
+    /// See credentials.rs.
     fn try_other_logins_or_prompt(ui: &TextUI) -> Result<Rc<RefCell<dyn Database>>, anyhow::Error> {
+        let default_username = Util::get_default_username().unwrap_or_else(|e| {
+            eprintln!("Unable to get the default username.  Underlying error is: \"{}\"", e);
+            "".to_string()
+        });
+        if !default_username.is_empty() {
+            if let Some(db) = Self::try_pgpass_login(default_username.as_str()) {
+                ui.display_text2("(Using the password from the PostgreSQL password file...)", false);
+                return Ok(db);
+            }
+        }
+        if let Some(db) = Self::try_credentials_file_login(ui) {
+            return Ok(db);
+        }
         // (this loop is to simulate recursion, and let the user retry entering username/password)
         loop {
-            // try logging in with some obtainable default values first, to save user the trouble, like if pwd is blank
-            let (default_username, default_password) = Util::get_default_user_login().unwrap_or_else(|e| {
-                eprintln!("Unable to get default username/password.  Trying blank username, and password \"x\" instead.  Underlying error is: \"{}\"", e);
-                ("".to_string(), "x")
-            });
-            let db_with_system_name_blank_pwd =
-                PostgreSQLDatabase::new(default_username.as_str(), default_password);
-            if db_with_system_name_blank_pwd.is_ok() {
-                ui.display_text2("(Using default user info...)", false);
-                break db_with_system_name_blank_pwd;
-            } else {
-                let usr = ui.ask_for_string3(vec!["Username"], None, default_username);
-                match usr {
-                    None => {
-                        // seems like the user wants out
-                        std::process::exit(1);
-                    }
-                    Some(username) => {
-                        let db_connected_with_default_pwd =
-                            PostgreSQLDatabase::new(username.as_str(), default_password);
-                        if db_connected_with_default_pwd.is_ok() {
-                            break db_connected_with_default_pwd;
-                        } else {
-                            let pwd = ui.ask_for_string4(vec!["Password"], None, "".to_string(), true);
-                            match pwd {
-                                None => {
-                                    // seems like the user wants out
-                                    std::process::exit(1);
-                                }
-                                Some(password) => {
-                                    let db_with_user_entered_pwd = PostgreSQLDatabase::new(
-                                        username.as_str(),
-                                        password.as_str(),
-                                    );
-                                    match db_with_user_entered_pwd {
-                                        Ok(db) => break Ok(db),
-                                        Err(e) => {
-                                            let msg = format!("Login failed; retrying ({} to quit if needed):  {}", ui.how_quit(), e.to_string());
-                                            ui.display_text2(msg.as_str(), false)
-                                        }
-                                    }
-                                    //%%AND: IN RUST instead of setting to null & doing gc(), could
-                                    // look into the "zeroize" and "secrecy" crates for that, per an article
-                                    // i just (20221201) read in "this week in Rust" rss feed,
-                                    // "Rust Foundation - Secure App Development with rust's Memory Model", at
-                                    //  https://foundation.rust-lang.org/news/secure-app-development-with-rust-s-memory-model/
-                                    // OR: no need because Rust will clean up anyway? will it be reused
-                                    // or could hang around to be exploited?
-                                }
-                            }
+            let usr = ui.ask_for_string3(vec!["Username"], None, default_username.clone());
+            let Some(username) = usr else {
+                // seems like the user wants out
+                std::process::exit(1);
+            };
+            if username != default_username {
+                if let Some(db) = Self::try_pgpass_login(username.as_str()) {
+                    break Ok(db);
+                }
+            }
+            let pwd = ui.ask_for_string4(vec!["Password"], None, "".to_string(), true);
+            let Some(password) = pwd.map(Zeroizing::new) else {
+                // seems like the user wants out
+                std::process::exit(1);
+            };
+            match PostgreSQLDatabase::new(username.as_str(), password.as_str()) {
+                Ok(db) => {
+                    Self::offer_to_save_credentials(ui, username.as_str(), password.as_str());
+                    break Ok(db);
+                }
+                Err(e) => {
+                    let msg = format!("Login failed; retrying ({} to quit if needed):  {}", ui.how_quit(), e.to_string());
+                    ui.display_text2(msg.as_str(), false)
+                }
+            }
+            // (The password is zeroized here as it goes out of scope.  The database connection
+            // pool keeps its own copy, to open new connections.)
+        }
+    }
+
+    fn try_pgpass_login(username: &str) -> Option<Rc<RefCell<dyn Database>>> {
+        let found = credentials::find_pgpass_password(
+            credentials::DEFAULT_HOST,
+            credentials::get_port(),
+            PostgreSQLDatabase::db_name(username).as_str(),
+            username,
+        );
+        match found {
+            Ok(Some(password)) => PostgreSQLDatabase::new(username, password.as_str()).ok(),
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("Unable to read the PostgreSQL password file: {}", e);
+                None
+            }
+        }
+    }
+
+    fn try_credentials_file_login(ui: &TextUI) -> Option<Rc<RefCell<dyn Database>>> {
+        let path = credentials::get_credentials_file_path().filter(|p| p.exists())?;
+        loop {
+            let prompt = format!(
+                "Passphrase for the OneModel credentials file {} (or just Enter to skip it)",
+                path.display()
+            );
+            let passphrase = ui
+                .ask_for_string4(vec![prompt.as_str()], None, "".to_string(), true)
+                .map(Zeroizing::new)?;
+            match credentials::load_encrypted_credentials(&path, passphrase.as_str()) {
+                Err(e) => ui.display_text2(e.to_string().as_str(), false),
+                Ok((username, password)) => {
+                    return match PostgreSQLDatabase::new(username.as_str(), password.as_str()) {
+                        Ok(db) => Some(db),
+                        Err(e) => {
+                            ui.display_text2(
+                                format!("Login with the saved credentials failed:  {}", e).as_str(),
+                                false,
+                            );
+                            None
                         }
-                    }
+                    };
                 }
             }
         }
     }
 
+    fn offer_to_save_credentials(ui: &TextUI, username: &str, password: &str) {
+        let Some(path) = credentials::get_credentials_file_path().filter(|p| !p.exists()) else {
+            return;
+        };
+        let prompt = format!(
+            "Save this login in a file encrypted with a passphrase ({}), to use next time \
+            instead of the database password?  (Or put it in ~/.pgpass to not be asked at all.)",
+            path.display()
+        );
+        if ui.ask_yes_no_question(prompt.as_str(), "n", false) != Some(true) {
+            return;
+        }
+        let passphrase = ui
+            .ask_for_string4(vec!["New passphrase for the credentials file"], None, "".to_string(), true)
+            .map(Zeroizing::new);
+        let again = ui
+            .ask_for_string4(vec!["The same passphrase again"], None, "".to_string(), true)
+            .map(Zeroizing::new);
+        match (passphrase, again) {
+            (Some(p), Some(a)) if p == a => {
+                match credentials::save_encrypted_credentials(&path, p.as_str(), username, password) {
+                    Ok(()) => ui.display_text2(format!("Saved {}.", path.display()).as_str(), false),
+                    Err(e) => ui.display_text2(format!("Unable to save {}:  {}", path.display(), e).as_str(), false),
+                }
+            }
+            (Some(_), Some(_)) => ui.display_text2("The passphrases did not match; not saved.", false),
+            _ => {}
+        }
+    }
+
     // Idea: show_public_private_status_preference, refresh_public_private_status_preference, and
     // find_default_display_entity_id(), feel awkward. Needs something better, but I'm not sure
     // what, at the moment.  It was created this way as a sort of cache because looking it up every
@@ -474,6 +551,15 @@ impl Controller {
                 false,
             );
             self.default_attribute_copying(&mut entity, None);
+            // The class' rules can't all be met before the entity has attributes, so say what
+            // they still require, to be added next.
+            let problems = db_in.borrow().get_class_rule_problems(None, entity.get_id())?;
+            if !problems.is_empty() {
+                self.ui.display_text1(&format!(
+                    "The new entity does not yet meet the rules of its class:\n    {}",
+                    problems.join("\n    ")
+                ));
+            }
             Ok(Some(entity))
         } else {
             Ok(None)
@@ -791,7 +877,7 @@ impl Controller {
                     self.try_asking_and_saving(
                         db_in,
                         error_msg_in,
-                        &ask_and_save_in,
+                        ask_and_save_in,
                         default_name_in,
         //%%%%%%%%
                         type_in,
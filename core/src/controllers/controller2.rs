@@ -539,6 +539,7 @@ impl Controller {
             create_instance_choice,
             swap_objects_to_display_choice,
             link_to_remote_instance_choice,
+            search_for_entity_by_query_choice,
         ): (
             Vec<String>,
             u16,
@@ -551,6 +552,7 @@ impl Controller {
             u16,
             u16,
             u16,
+            u16,
         ) = self.get_choice_list(
             object_type_in,
             previous_selection_desc_in.as_deref(),
@@ -635,6 +637,15 @@ impl Controller {
                 None => Ok(None),
             }
             //%%Ok(Some((x, false, String::new())))
+        } else if answer == usize::from(search_for_entity_by_query_choice)
+            && entity_and_most_attr_type_names.contains(&object_type_in)
+            && answer <= choices.len()
+        {
+            let idw = self.ask_for_query_and_search_for_entity(db_in)?;
+            match idw {
+                Some(w) => Ok(Some((w, false, String::new()))),
+                None => Ok(None),
+            }
         } else if answer == usize::from(search_for_entity_by_id_choice) && answer <= choices.len() {
             let idw = self.search_by_id(db_in, Util::ENTITY_TYPE)?;
             //%%Ok(result.map(|id| (id, false, String::new())))
@@ -850,6 +861,7 @@ impl Controller {
         u16,
         u16,
         u16,
+        u16,
     ) {
         // Attempt to keep these straight even though the size of the list, hence their option #'s on the menu,
         // is conditional:
@@ -863,6 +875,7 @@ impl Controller {
         let mut create_relation_type_choice_num = 1;
         let mut create_class_choice_num = 1;
         let mut create_instance_choice_num = 1;
+        let mut search_for_entity_by_query_choice_num = 1;
         let mut choice_list = vec![Util::LIST_NEXT_ITEMS_PROMPT.to_string()];
         if previous_selection_desc_in.is_some() {
             choice_list.push(format!(
@@ -880,6 +893,7 @@ impl Controller {
             create_relation_type_choice_num += 1;
             create_class_choice_num += 1;
             create_instance_choice_num += 1;
+            search_for_entity_by_query_choice_num += 1;
         }
         //idea: use match instead of if: can it do || ?
         if entity_and_most_attr_type_names.contains(&object_type_in) {
@@ -908,6 +922,8 @@ impl Controller {
             swap_objects_to_display_choice_num += 5;
             choice_list.push("Link to entity in a separate (REMOTE) OM instance...".to_string());
             link_to_remote_instance_choice_num += 6;
+            choice_list.push("Search for existing entities by query (class, attributes, relations...)...".to_string());
+            search_for_entity_by_query_choice_num += 7;
         } else if Util::RELATION_ATTR_TYPE_NAMES.contains(&object_type_in) {
             // These choice #s are only hit by the conditions below, when they should be...:
             choice_list.push(Util::menutext_create_relation_type());
@@ -937,6 +953,7 @@ impl Controller {
             create_instance_choice_num,
             swap_objects_to_display_choice_num,
             link_to_remote_instance_choice_num,
+            search_for_entity_by_query_choice_num,
         )
     }
 }
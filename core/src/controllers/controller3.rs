@@ -12,6 +12,7 @@
 // Controller code is split between controller.rs and controller2.rs - controller6.rs, to make
 // incremental compilation faster when only one has changed (or editing faster w/ rust-analyzer).
 
+use crate::clipboard::{self, ClipboardContentKind};
 use crate::controllers::main_menu::MainMenu;
 use crate::model::database::Database;
 use crate::model::entity::Entity;
@@ -63,7 +64,7 @@ impl Controller {
             Option<String>,
             bool,
         ) = user_selection.find_relation_to_and_group(None)?;
-        let sub_entity_selected: Option<Entity> = None;
+        let mut sub_entity_selected: Option<Entity> = None;
         if group_id.is_some()
             && !more_than_one_available
             && user_selection.get_attribute_count(None, self.db.borrow().include_archived_entities())? == 1
@@ -77,7 +78,10 @@ impl Controller {
             //Was, in scala: IF ADDING ANY OPTIONAL PARAMETERS, be sure they
             //are also passed along in the recursive call(s)
             // w/in this method!
-            self.ui.display_text1("not yet implemented");
+            // (Until the QuickGroupMenu below is ported, a simple list of the group's entries.)
+            if let Some(sole_group_id) = group_id {
+                sub_entity_selected = self.group_entries_menu(sole_group_id)?;
+            }
             //%%
             //QuickGroupMenu::new(self.ui.clone(), Rc::new(self.clone())).quick_group_menu(
             //    &Group::new2(user_selection.get_db(), None, group_id.unwrap()),
@@ -363,7 +367,7 @@ impl Controller {
     /// and in that case is handled specially here.
     /// Returns None if user wants out (or attr_form_in parm was an abortive mistake?); returns the created Attribute
     /// if successful.
-    fn add_attribute(
+    pub fn add_attribute(
         &self,
         entity_in: &mut Entity,
         _starting_attribute_index_in: i32,
@@ -805,7 +809,20 @@ impl Controller {
                 if uri_ready.is_none() || !uri_ready.unwrap() {
                     return Ok(None);
                 }
-                Util::get_clipboard_content()
+                let Some(content) = self.get_clipboard_content_or_show_error() else {
+                    return Ok(None);
+                };
+                if clipboard::get_content_kind(&content) != ClipboardContentKind::Uri {
+                    let use_anyway = self.ui.ask_yes_no_question(
+                        &format!("This does not look like a URI:\n  {}\n...use it anyway?", content),
+                        "n",
+                        false,
+                    );
+                    if use_anyway != Some(true) {
+                        return Ok(None);
+                    }
+                }
+                content
             };
             let ans2 = self.ui.ask_which(
                 Some(vec![
@@ -845,7 +862,10 @@ impl Controller {
                     if clip.is_none() || !clip.unwrap() {
                         return Ok(None);
                     }
-                    Some(Util::get_clipboard_content())
+                    let Some(content) = self.get_clipboard_content_or_show_error() else {
+                        return Ok(None);
+                    };
+                    Some(clipboard::strip_quotation_marks(&content).to_string())
                 }
             };
             let quote_info = if let Some(ref q) = quote {
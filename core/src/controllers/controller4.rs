@@ -266,7 +266,7 @@ impl Controller {
     //%%?:@tailrec
     //IF ADDING ANY OPTIONAL PARAMETERS, be sure they are also passed along in the recursive
     //call(s) within this method, below!
-    fn attribute_edit_menu(&self, attribute_in: &mut dyn Attribute) -> Result<bool, anyhow::Error> {
+    pub fn attribute_edit_menu(&self, attribute_in: &mut dyn Attribute) -> Result<bool, anyhow::Error> {
         let leading_text = vec![format!(
             "Attribute: {}",
             attribute_in.get_display_string(0, None, None, false)?
@@ -311,6 +311,15 @@ impl Controller {
         {
             first_choices.push("Export the file".to_string());
         }
+        let is_relation = [
+            Util::RELATION_TO_LOCAL_ENTITY_TYPE,
+            Util::RELATION_TO_GROUP_TYPE,
+            Util::RELATION_TO_REMOTE_ENTITY_TYPE,
+        ]
+        .contains(&self.db.borrow().get_attribute_form_name(attribute_in.get_form_id()?)?);
+        if is_relation {
+            first_choices.push("Attributes of this relation itself (like when it began, or a role)...".to_string());
+        }
         let response = self.ui.ask_which(
             Some(leading_text),
             &first_choices,
@@ -658,6 +667,10 @@ impl Controller {
             //      ui.display_text("Failed to export file, due to error: " + msg)
             //  }
             //  attributeEditMenu(attributeIn)
+        } else if answer == 6 && is_relation {
+            let relation_desc = attribute_in.get_display_string(0, None, None, false)?;
+            self.relation_attributes_menu(attribute_in.get_db(), form_id, attribute_in.get_id(), &relation_desc)?;
+            self.attribute_edit_menu(attribute_in)
         } else {
             self.ui.display_text1("invalid response");
             self.attribute_edit_menu(attribute_in)
@@ -13,10 +13,21 @@
 // Controller code is split between controller.rs and controller2.rs - controller6.rs, to make
 // incremental compilation faster when only one has changed (or editing faster w/ rust-analyzer).
 
+use crate::clipboard::{self, ClipboardContentKind};
+use crate::controllers::bookmark_import;
 use crate::controllers::controller::Controller;
+use crate::controllers::csv_export;
+use crate::controllers::csv_import::{self, ColumnTarget, TemplateAttributeForm, TemplateAttributeType};
+use crate::controllers::export_tree::{ExportOptions, ExportTree};
+use crate::controllers::markdown_export;
+use crate::controllers::opml;
+use crate::controllers::rdf_export;
+use crate::controllers::org_mode;
 use crate::controllers::main_menu::MainMenu;
 use crate::model::database::Database;
 use crate::model::entity::Entity;
+use crate::model::entity_query::{parse_query_date, EntityQuery};
+use crate::model::exchange_format::ExchangeData;
 use crate::model::has_id::HasId;
 use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
 use crate::util::Util;
@@ -30,6 +41,7 @@ use crate::controllers::entity_menu::EntityMenu;
 use crate::controllers::group_menu::GroupMenu;
 use crate::controllers::quick_group_menu::QuickGroupMenu;
 use crate::model::attribute::Attribute;
+use crate::model::attribute_with_valid_and_observed_dates::AsOfDate;
 use crate::model::attribute_data_holder::*;
 use crate::model::attribute_data_holder::*;
 use crate::model::boolean_attribute::BooleanAttribute;
@@ -40,12 +52,15 @@ use crate::model::group::Group;
 use crate::model::id_wrapper::IdWrapper;
 use crate::model::om_instance::OmInstance;
 use crate::model::quantity_attribute::QuantityAttribute;
+use crate::model::quantity_series::QuantitySeries;
+use crate::model::unit_conversion::{QuantityAggregate, QuantityScope};
 use crate::model::relation_to_group::RelationToGroup;
 use crate::model::relation_to_local_entity::RelationToLocalEntity;
 use crate::model::relation_to_remote_entity::RelationToRemoteEntity;
 use crate::model::relation_type::RelationType;
 use crate::model::text_attribute::TextAttribute;
 use anyhow::anyhow;
+use chrono::Utc;
 //use std::collections::HashMap;
 //use std::fs::File;
 use std::path::Path;
@@ -516,6 +531,1286 @@ impl Controller {
         return Ok(Some(IdWrapper::new(x.get_id())));
     }
 
+    /// Like ask_for_name_and_search_for_entity, but with a query in the syntax of EntityQuery.
+    pub fn ask_for_query_and_search_for_entity(
+        &self,
+        db_in: Rc<RefCell<dyn Database>>,
+    ) -> Result<Option<IdWrapper>, anyhow::Error> {
+        loop {
+            let Some(answer) = self.ui.ask_for_string1(vec![Util::QUERY_SYNTAX_HELP]) else {
+                return Ok(None);
+            };
+            match EntityQuery::parse(answer.as_str()) {
+                Err(e) => {
+                    self.ui.display_text1(&format!("Unable to use that query: {}", e));
+                    continue;
+                }
+                Ok(query) => return self.find_existing_entity_by_query(db_in, 0, &query),
+            }
+        }
+    }
+
+    /// Shows the entities matching query_in, a page at a time, for the user to pick one.
+    /// (See also find_existing_object_by_text, which this mimics.)
+    pub fn find_existing_entity_by_query(
+        &self,
+        db_in: Rc<RefCell<dyn Database>>,
+        starting_display_row_index_in: u64, /* = 0*/
+        query_in: &EntityQuery,
+    ) -> Result<Option<IdWrapper>, anyhow::Error> {
+        let leading_text = vec![format!("QUERY RESULTS: {}", Util::PICK_FROM_LIST_PROMPT)];
+        let choices = vec![
+            Util::LIST_NEXT_ITEMS_PROMPT.to_string(),
+            "Save this query as a saved search (a group whose entries are computed from the query)...".to_string(),
+        ];
+        let num_displayable_items = self.ui.max_columnar_choices_to_display_after(
+            leading_text.len(),
+            choices.len(),
+            Util::max_name_length(),
+        )?;
+        let mut entities: Vec<Entity> = query_in.get_matching_entities(
+            db_in.clone(),
+            None,
+            starting_display_row_index_in,
+            Some(num_displayable_items),
+        )?;
+        if entities.is_empty() && starting_display_row_index_in == 0 {
+            // (Still allow saving it, since a saved search's entries can change later.)
+            let save = self.ui.ask_yes_no_question(
+                "None found.  Save this query as a saved search anyway?",
+                "n",
+                false,
+            );
+            if save == Some(true) {
+                self.save_query_as_smart_group(db_in, query_in)?;
+            }
+            return Ok(None);
+        } else if entities.is_empty() {
+            self.ui
+                .display_text1("End of list, or none found; starting over from the beginning...");
+            return if starting_display_row_index_in == 0 {
+                Ok(None)
+            } else {
+                self.find_existing_entity_by_query(db_in, 0, query_in)
+            };
+        }
+        let mut names: Vec<String> = Vec::new();
+        for entity in entities.iter_mut() {
+            names.push(format!(
+                "{}{}{}",
+                self.get_entity_content_size_prefix(entity)?,
+                entity.get_archived_status_display_string(None)?,
+                entity.get_name(None)?
+            ));
+        }
+        let Some(answer) = self.ui.ask_which(
+            Some(leading_text),
+            &choices,
+            &names,
+            true,
+            None,
+            None,
+            None,
+            None,
+        ) else {
+            return Ok(None);
+        };
+        if answer == 1 && answer <= choices.len() {
+            let next_starting_index = starting_display_row_index_in + u64::try_from(names.len())?;
+            self.find_existing_entity_by_query(db_in, next_starting_index, query_in)
+        } else if answer == 2 && answer <= choices.len() {
+            self.save_query_as_smart_group(db_in.clone(), query_in)?;
+            self.find_existing_entity_by_query(db_in, starting_display_row_index_in, query_in)
+        } else if answer > choices.len() && answer <= (choices.len() + names.len()) {
+            let entity = &entities[answer - choices.len() - 1];
+            Ok(Some(IdWrapper::new(entity.get_id())))
+        } else {
+            self.ui.display_text1("unknown choice among secondary list");
+            self.find_existing_entity_by_query(db_in, starting_display_row_index_in, query_in)
+        }
+    }
+
+    /// Saves the query as a saved search ("smart group"), attached with a "has" relation to the
+    /// default entity (the one shown at startup) if there is one, so it can be found there.
+    /// (Otherwise it can still be found by searching for groups by name.)
+    fn save_query_as_smart_group(
+        &self,
+        db_in: Rc<RefCell<dyn Database>>,
+        query_in: &EntityQuery,
+    ) -> Result<(), anyhow::Error> {
+        let query_text = query_in.get_text();
+        let Some(name) = self.ui.ask_for_string1(vec!["Enter a name for the saved search:"]) else {
+            return Ok(());
+        };
+        let default_entity_id: Option<i64> = db_in.borrow().get_user_preference_entity_id(
+            None,
+            Util::DEFAULT_ENTITY_PREFERENCE,
+            None,
+        )?;
+        match default_entity_id {
+            Some(entity_id) => {
+                let entity = Entity::new2(db_in.clone(), None, entity_id)?;
+                let has_rel_type_id = db_in
+                    .borrow()
+                    .find_relation_type(None, Util::THE_HAS_RELATION_TYPE_NAME)?;
+                entity.add_smart_group_and_relation_to_group(
+                    None,
+                    has_rel_type_id,
+                    name.as_str(),
+                    query_text,
+                    None,
+                    Utc::now().timestamp_millis(),
+                )?;
+                self.ui.display_text1("Saved, as a group on the default entity.");
+            }
+            None => {
+                Group::create_smart_group(db_in, None, name.as_str(), query_text)?;
+                self.ui.display_text1(
+                    "Saved.  (There is no default entity to attach it to, so find it by searching for groups by name.)",
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows entity_in's attributes as they were valid, or as they were known (observed), on a
+    /// date the user enters.  See Database::get_sorted_attributes_as_of.
+    pub fn show_attributes_as_of(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let name = entity_in.get_name(None)?;
+        let leading_text = vec![format!("View the attributes of \"{}\" as of a date:", name)];
+        let choices = vec![
+            "...as they were valid then (what was true on that date)".to_string(),
+            "...as they were known then (what had been observed/recorded by that date)".to_string(),
+        ];
+        let Some(answer) =
+            self.ui
+                .ask_which(Some(leading_text), &choices, &Vec::new(), true, None, None, None, None)
+        else {
+            return Ok(());
+        };
+        if answer > choices.len() {
+            self.ui.display_text1("invalid response");
+            return self.show_attributes_as_of(entity_in);
+        }
+        let date: i64 = loop {
+            let Some(date_text) = self.ui.ask_for_string1(vec![
+                "Enter the date (YYYY-MM-DD, YYYY-MM-DD HH:MM, today, or -Nd for N days ago; UTC):",
+            ]) else {
+                return Ok(());
+            };
+            match parse_query_date(date_text.trim()) {
+                Err(e) => self.ui.display_text1(&e.to_string()),
+                // A plain day means as of the end of it.
+                Ok((millis, true)) => break millis + (24 * 60 * 60 * 1000) - 1,
+                Ok((millis, false)) => break millis,
+            }
+        };
+        let as_of = if answer == 1 {
+            AsOfDate::ValidOn(date)
+        } else {
+            AsOfDate::ObservedOn(date)
+        };
+        let (attribute_tuples, _) = entity_in.get_sorted_attributes_as_of(None, as_of, 0, 0, false)?;
+        let mut lines: Vec<String> = vec![format!(
+            "Attributes of \"{}\" as {} on {}:",
+            name,
+            if answer == 1 { "valid" } else { "known" },
+            Util::useful_date_format(date)
+        )];
+        if attribute_tuples.is_empty() {
+            lines.push("(none)".to_string());
+        }
+        for (_, attribute) in attribute_tuples {
+            lines.push(format!(
+                "  {}",
+                attribute
+                    .borrow_mut()
+                    .get_display_string(Util::max_name_length().into(), None, None, false)?
+            ));
+        }
+        self.ui.display_text1(&lines.join("\n"));
+        Ok(())
+    }
+
+    /// Shows entity_in's quantity attributes grouped into series by attribute type and unit, with
+    /// statistics and a sparkline for each, and offers to save a series as CSV.
+    pub fn show_quantity_series(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let name = entity_in.get_name(None)?;
+        let leading_text = vec![format!("View quantity series on \"{}\", ordered by:", name)];
+        let choices = vec![
+            "...valid date (when each value was true)".to_string(),
+            "...observation date (when each value was recorded)".to_string(),
+        ];
+        let Some(answer) =
+            self.ui
+                .ask_which(Some(leading_text), &choices, &Vec::new(), true, None, None, None, None)
+        else {
+            return Ok(());
+        };
+        if answer > choices.len() {
+            self.ui.display_text1("invalid response");
+            return self.show_quantity_series(entity_in);
+        }
+        let db = entity_in.get_db();
+        let all_series = QuantitySeries::get_for_entity(db.clone(), None, entity_in.get_id(), answer == 1)?;
+        if all_series.is_empty() {
+            self.ui.display_text1("There are no quantity attributes on this entity.");
+            return Ok(());
+        }
+        let mut descriptions: Vec<String> = Vec::new();
+        for series in &all_series {
+            let type_name = Entity::new2(db.clone(), None, series.attr_type_id)?.get_name(None)?;
+            let unit_name = Entity::new2(db.clone(), None, series.unit_id)?.get_name(None)?;
+            descriptions.push(format!("{} ({}): {}", type_name, unit_name, series.get_summary()));
+        }
+        loop {
+            let leading_text = vec![format!(
+                "Quantity series on \"{}\".  Pick one to save it as a CSV file, or ESC to go back.",
+                name
+            )];
+            let Some(answer) = self.ui.ask_which(
+                Some(leading_text),
+                &Vec::new(),
+                &descriptions,
+                true,
+                None,
+                None,
+                None,
+                None,
+            ) else {
+                return Ok(());
+            };
+            let Some(series) = all_series.get(answer.wrapping_sub(1)) else {
+                self.ui.display_text1("invalid response");
+                continue;
+            };
+            let Some(path) = self.ui.ask_for_string1(vec!["Enter the path of the CSV file to write:"])
+            else {
+                continue;
+            };
+            let path = path.trim();
+            if Path::new(path).exists()
+                && self.ui.ask_yes_no_question("That file exists; overwrite it?", "n", false) != Some(true)
+            {
+                continue;
+            }
+            match std::fs::write(path, series.to_csv()) {
+                Ok(()) => self.ui.display_text1(&format!("Wrote {}.", path)),
+                Err(e) => self.ui.display_text1(&format!("Unable to write {}: {}", path, e)),
+            }
+        }
+    }
+
+    /// Returns None (after showing why) if the clipboard could not be read or is empty.
+    pub fn get_clipboard_content_or_show_error(&self) -> Option<String> {
+        match Util::get_clipboard_content() {
+            Err(e) => {
+                self.ui.display_text1(&e.to_string());
+                None
+            }
+            Ok(content) if content.is_empty() => {
+                self.ui.display_text1("The clipboard is empty.");
+                None
+            }
+            Ok(content) => Some(content),
+        }
+    }
+
+    /// Adds whatever is on the clipboard to entity_in, depending on what it looks like: a URI
+    /// becomes a new URI entity (as with the "external web page" attribute form), a quote
+    /// becomes a "quote" text attribute, and other text becomes the name of a new entity that
+    /// entity_in "has".
+    pub fn add_from_clipboard(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some(content) = self.get_clipboard_content_or_show_error() else {
+            return Ok(());
+        };
+        let db = entity_in.get_db();
+        let is_public = entity_in.get_public(None)?;
+        let now = Utc::now().timestamp_millis();
+        match clipboard::get_content_kind(&content) {
+            ClipboardContentKind::Uri => {
+                let Some(name) = self.ui.ask_for_string3(
+                    vec![
+                        "The clipboard has a URI:",
+                        content.as_str(),
+                        "Enter a name (or description) for this web page or other URI (or ESC to cancel):",
+                    ],
+                    None,
+                    content.clone(),
+                ) else {
+                    return Ok(());
+                };
+                if name.trim().is_empty() {
+                    return Ok(());
+                }
+                entity_in.add_uri_entity_with_uri_attribute(
+                    None,
+                    name.trim().to_string(),
+                    &content,
+                    now,
+                    is_public,
+                    None,
+                )?;
+            }
+            ClipboardContentKind::Quote => {
+                let quote = clipboard::strip_quotation_marks(&content);
+                let prompt = format!("The clipboard has a quote:\n{}\n...add it as a \"quote\" attribute?", quote);
+                if self.ui.ask_yes_no_question(&prompt, "y", false) != Some(true) {
+                    return Ok(());
+                }
+                let (_, quote_template_id) =
+                    db.borrow().get_or_create_class_and_template_entity(None, "quote")?;
+                entity_in.add_text_attribute(None, quote_template_id, quote, None)?;
+            }
+            ClipboardContentKind::PlainText => {
+                let prompt = format!(
+                    "The clipboard has:\n  {}\n...add a new entity with that name, that this one \"has\"?",
+                    content
+                );
+                if self.ui.ask_yes_no_question(&prompt, "y", false) != Some(true) {
+                    return Ok(());
+                }
+                db.borrow().create_entity_and_add_has_local_relation_to_it(
+                    None,
+                    entity_in.get_id(),
+                    &content,
+                    now,
+                    is_public,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Imports to or exports from entity_in, in the formats other programs use.
+    pub fn import_export_menu(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let name = entity_in.get_name(None)?;
+        let leading_text = vec![format!("Import or export, at \"{}\":", name)];
+        let choices = vec![
+            "Import browser bookmarks (an HTML bookmarks file as exported by Firefox, Chromium, etc)".to_string(),
+            "Export to Markdown files".to_string(),
+            "Import an org-mode file (re-importing an exported one updates the same entities)".to_string(),
+            "Export to an org-mode file".to_string(),
+            "Import an OPML outline (as from Workflowy, Dynalist or OmniOutliner)".to_string(),
+            "Export to an OPML file".to_string(),
+            "Export to a OneModel JSON exchange file (lossless; this entity's subtree or the whole database)".to_string(),
+            "Import a OneModel JSON exchange file".to_string(),
+            "Export as RDF linked data (Turtle or JSON-LD)".to_string(),
+            "Import a CSV or tab-separated file as entities of a class".to_string(),
+            "Export one of this entity's groups as a CSV or tab-separated table".to_string(),
+        ];
+        let Some(answer) =
+            self.ui
+                .ask_which(Some(leading_text), &choices, &Vec::new(), true, None, None, None, None)
+        else {
+            return Ok(());
+        };
+        match answer {
+            1 => self.import_bookmarks_file(entity_in),
+            2 => self.export_to_markdown(entity_in),
+            3 => self.import_org_file(entity_in),
+            4 => self.export_to_org(entity_in),
+            5 => self.import_opml_file(entity_in),
+            6 => self.export_to_opml(entity_in),
+            7 => self.export_to_exchange_file(entity_in),
+            8 => self.import_exchange_file(entity_in),
+            9 => self.export_to_rdf(entity_in),
+            10 => self.import_csv_file(entity_in),
+            11 => self.export_group_to_csv(entity_in),
+            _ => {
+                self.ui.display_text1("invalid response");
+                self.import_export_menu(entity_in)
+            }
+        }
+    }
+
+    /// Returns None if the user cancelled.  Keeps asking until the path is of a readable file.
+    fn ask_for_existing_file_contents(&self, prompt_in: &str) -> Option<(String, String)> {
+        loop {
+            let path = self.ui.ask_for_string1(vec![prompt_in])?;
+            let path = path.trim().to_string();
+            if path.is_empty() {
+                return None;
+            }
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => return Some((path, contents)),
+                Err(e) => self.ui.display_text1(&format!("Unable to read {}: {}", path, e)),
+            }
+        }
+    }
+
+    /// See bookmark_import.rs.
+    fn import_bookmarks_file(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some((path, html)) =
+            self.ask_for_existing_file_contents("Enter the path of the exported bookmarks (.html) file:")
+        else {
+            return Ok(());
+        };
+        let root_folder = bookmark_import::parse_bookmarks_html(&html);
+        if root_folder.items.is_empty() {
+            self.ui.display_text1(&format!("Found no bookmarks in {}.", path));
+            return Ok(());
+        }
+        let make_them_public: Option<bool> = self.ui.ask_yes_no_question(
+            "Do you want the entities imported to be marked as public?  Enter y for public, n for \
+            nonpublic, or a space for 'unknown/unspecified', aka decide later.",
+            "",
+            true,
+        );
+        let db = entity_in.get_db();
+        let db_b = db.borrow();
+        let tx = Some(Rc::new(RefCell::new(db_b.begin_trans()?)));
+        // (If this fails, the transaction is rolled back as it goes out of scope.)
+        let counts = bookmark_import::import_bookmarks(
+            db.clone(),
+            tx.clone(),
+            entity_in.get_id(),
+            &root_folder,
+            make_them_public,
+            Utc::now().timestamp_millis(),
+        )?;
+        db_b.commit_local_trans(tx)?;
+        self.ui.display_text1(&format!(
+            "Imported \"{}\" into \"{}\": {} folders, {} new URI entities, and {} bookmarks \
+            linked to URI entities that already existed.",
+            root_folder.name,
+            entity_in.get_name(None)?,
+            counts.folders,
+            counts.created,
+            counts.linked
+        ));
+        Ok(())
+    }
+
+    /// Asks which levels and which public/non-public data to export, as the HTML export does.
+    /// Returns None if the user cancelled.
+    fn ask_for_export_options(&self) -> Option<ExportOptions> {
+        let levels = self.ui.ask_for_string3(
+            vec!["Enter number of levels to export (including this one; 0 = 'all'); ESC to cancel"],
+            Some(Util::is_numeric),
+            "0".to_string(),
+        )?;
+        let levels: usize = levels.trim().parse().unwrap_or(0);
+        let include_public = self.ui.ask_yes_no_question(
+            "Include public data?  (Note: Whether an entity is public, non-public, or unset can be \
+            marked on each entity's menu, and the preference as to whether to display that status on \
+            each entity in a list can be set via the main menu.)",
+            "y",
+            true,
+        )?;
+        let include_non_public = self.ui.ask_yes_no_question("Include data marked non-public?", "n", true)?;
+        let include_unspecified =
+            self.ui
+                .ask_yes_no_question("Include data not specified as public or non-public?", "n", true)?;
+        Some(ExportOptions {
+            levels: if levels == 0 { None } else { Some(levels - 1) },
+            include_public,
+            include_non_public,
+            include_unspecified,
+        })
+    }
+
+    /// See markdown_export.rs.
+    fn export_to_markdown(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some(options) = self.ask_for_export_options() else {
+            return Ok(());
+        };
+        let Some(directory) = self.ui.ask_for_string1(vec![
+            "Enter the directory to write the Markdown files into (it will be created if needed):",
+        ]) else {
+            return Ok(());
+        };
+        let directory = directory.trim().to_string();
+        if directory.is_empty() {
+            return Ok(());
+        }
+        let tree = ExportTree::build(entity_in.get_db(), None, entity_in.get_id(), &options)?;
+        let count = markdown_export::write_markdown_files(&tree, std::path::Path::new(&directory))?;
+        self.ui.display_text1(&format!(
+            "Wrote {} Markdown file(s) to {}, starting with {}.",
+            count,
+            directory,
+            markdown_export::get_file_name(entity_in.get_id())
+        ));
+        Ok(())
+    }
+
+    /// See org_mode.rs.
+    fn import_org_file(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some((path, text)) = self.ask_for_existing_file_contents("Enter the path of the org-mode (.org) file:")
+        else {
+            return Ok(());
+        };
+        let root = org_mode::parse_org(&text);
+        let make_them_public: Option<bool> = self.ui.ask_yes_no_question(
+            "Do you want any new entities imported to be marked as public?  Enter y for public, n for \
+            nonpublic, or a space for 'unknown/unspecified', aka decide later.",
+            "",
+            true,
+        );
+        let db = entity_in.get_db();
+        let db_b = db.borrow();
+        let tx = Some(Rc::new(RefCell::new(db_b.begin_trans()?)));
+        // (If this fails, the transaction is rolled back as it goes out of scope.)
+        let counts = org_mode::import_org(
+            db.clone(),
+            tx.clone(),
+            entity_in.get_id(),
+            &root,
+            make_them_public,
+            Utc::now().timestamp_millis(),
+        )?;
+        db_b.commit_local_trans(tx)?;
+        self.ui.display_text1(&format!(
+            "Imported {}: {} entities created and {} updated.",
+            path, counts.created, counts.updated
+        ));
+        Ok(())
+    }
+
+    /// See org_mode.rs.
+    fn export_to_org(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some(options) = self.ask_for_export_options() else {
+            return Ok(());
+        };
+        let default_path = format!("e{}.org", entity_in.get_id());
+        let Some(path) = self.ui.ask_for_string3(
+            vec!["Enter the path of the org-mode file to write (it will be replaced if it exists):"],
+            None,
+            default_path,
+        ) else {
+            return Ok(());
+        };
+        let path = path.trim().to_string();
+        if path.is_empty() {
+            return Ok(());
+        }
+        let root = org_mode::export_org(entity_in.get_db(), None, entity_in.get_id(), &options)?;
+        std::fs::write(&path, org_mode::to_org_text(&root))?;
+        self.ui.display_text1(&format!("Wrote {}.", path));
+        Ok(())
+    }
+
+    /// See opml.rs.  As with the other imports, it is all in one transaction, so a failure
+    /// partway leaves nothing imported.
+    fn import_opml_file(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some((path, xml)) = self.ask_for_existing_file_contents("Enter the path of the OPML (.opml) file:")
+        else {
+            return Ok(());
+        };
+        let document = match opml::parse_opml(&xml) {
+            Ok(d) => d,
+            Err(e) => {
+                self.ui.display_text1(&format!("Unable to read {}: {}", path, e));
+                return Ok(());
+            }
+        };
+        let default_name = std::path::Path::new(&path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone());
+        let make_them_public: Option<bool> = self.ui.ask_yes_no_question(
+            "Do you want the entities imported to be marked as public?  Enter y for public, n for \
+            nonpublic, or a space for 'unknown/unspecified', aka decide later.",
+            "",
+            true,
+        );
+        let db = entity_in.get_db();
+        let db_b = db.borrow();
+        let tx = Some(Rc::new(RefCell::new(db_b.begin_trans()?)));
+        // (If this fails, the transaction is rolled back as it goes out of scope.)
+        let counts = opml::import_opml(
+            db.clone(),
+            tx.clone(),
+            entity_in.get_id(),
+            &document,
+            &default_name,
+            make_them_public,
+            Utc::now().timestamp_millis(),
+        )?;
+        db_b.commit_local_trans(tx)?;
+        self.ui.display_text1(&format!(
+            "Imported {}: {} entities, {} URI entities, and {} notes.",
+            path, counts.entities, counts.uris, counts.notes
+        ));
+        Ok(())
+    }
+
+    /// See opml.rs.
+    fn export_to_opml(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some(options) = self.ask_for_export_options() else {
+            return Ok(());
+        };
+        let Some(path) = self.ui.ask_for_string3(
+            vec!["Enter the path of the OPML file to write (it will be replaced if it exists):"],
+            None,
+            format!("e{}.opml", entity_in.get_id()),
+        ) else {
+            return Ok(());
+        };
+        let path = path.trim().to_string();
+        if path.is_empty() {
+            return Ok(());
+        }
+        let tree = ExportTree::build(entity_in.get_db(), None, entity_in.get_id(), &options)?;
+        std::fs::write(&path, opml::to_opml(&tree))?;
+        self.ui.display_text1(&format!("Wrote {}.", path));
+        Ok(())
+    }
+
+    fn ask_whether_to_export_whole_database(&self) -> Option<bool> {
+        self.ui.ask_yes_no_question(
+            "Export the whole database?  (If not, exports this entity and everything reachable from it.)",
+            "n",
+            false,
+        )
+    }
+
+    fn read_exchange_data(entity_in: &Entity, whole_database_in: bool) -> Result<ExchangeData, anyhow::Error> {
+        let roots = [entity_in.get_id()];
+        entity_in
+            .get_db()
+            .borrow()
+            .export_exchange_data(None, if whole_database_in { None } else { Some(&roots) })
+    }
+
+    /// See exchange_format.rs.
+    fn export_to_exchange_file(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some(whole_database) = self.ask_whether_to_export_whole_database() else {
+            return Ok(());
+        };
+        let Some(path) = self.ui.ask_for_string3(
+            vec!["Enter the path of the JSON file to write (it will be replaced if it exists):"],
+            None,
+            if whole_database {
+                "onemodel.json".to_string()
+            } else {
+                format!("e{}.json", entity_in.get_id())
+            },
+        ) else {
+            return Ok(());
+        };
+        let path = path.trim().to_string();
+        if path.is_empty() {
+            return Ok(());
+        }
+        let data = Self::read_exchange_data(entity_in, whole_database)?;
+        std::fs::write(&path, data.to_json()?)?;
+        self.ui.display_text1(&format!(
+            "Wrote {}: {} entities, {} attributes and {} groups.",
+            path,
+            data.entities.len(),
+            data.attributes.len(),
+            data.groups.len()
+        ));
+        Ok(())
+    }
+
+    /// See exchange_format.rs.
+    fn import_exchange_file(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some((path, json)) =
+            self.ask_for_existing_file_contents("Enter the path of the OneModel JSON exchange (.json) file:")
+        else {
+            return Ok(());
+        };
+        let data = match ExchangeData::from_json(&json) {
+            Ok(d) => d,
+            Err(e) => {
+                self.ui.display_text1(&format!("Unable to read {}: {}", path, e));
+                return Ok(());
+            }
+        };
+        let db = entity_in.get_db();
+        let db_b = db.borrow();
+        let tx = Some(Rc::new(RefCell::new(db_b.begin_trans()?)));
+        // (If this fails, the transaction is rolled back as it goes out of scope.)
+        let counts =
+            db_b.import_exchange_data(tx.clone(), &data, Some(entity_in.get_id()), Utc::now().timestamp_millis())?;
+        db_b.commit_local_trans(tx)?;
+        self.ui.display_text1(&format!(
+            "Imported {}: created {} entities (using {} existing ones found by name), {} groups and {} attributes.",
+            path, counts.entities_created, counts.entities_matched, counts.groups, counts.attributes
+        ));
+        Ok(())
+    }
+
+    /// See rdf_export.rs.
+    fn export_to_rdf(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let formats = vec!["Turtle (.ttl)".to_string(), "JSON-LD (.jsonld)".to_string()];
+        let Some(format) = self.ui.ask_which(
+            Some(vec!["Which RDF format?".to_string()]),
+            &formats,
+            &Vec::new(),
+            true,
+            None,
+            None,
+            None,
+            None,
+        ) else {
+            return Ok(());
+        };
+        let json_ld = format == 2;
+        let Some(whole_database) = self.ask_whether_to_export_whole_database() else {
+            return Ok(());
+        };
+        let Some(path) = self.ui.ask_for_string3(
+            vec!["Enter the path of the file to write (it will be replaced if it exists):"],
+            None,
+            format!(
+                "{}.{}",
+                if whole_database {
+                    "onemodel".to_string()
+                } else {
+                    format!("e{}", entity_in.get_id())
+                },
+                if json_ld { "jsonld" } else { "ttl" }
+            ),
+        ) else {
+            return Ok(());
+        };
+        let path = path.trim().to_string();
+        if path.is_empty() {
+            return Ok(());
+        }
+        let data = Self::read_exchange_data(entity_in, whole_database)?;
+        let instance_id = entity_in.get_db().borrow().id(None)?;
+        let triples = rdf_export::to_rdf_graph(&data, &instance_id);
+        let content = if json_ld {
+            rdf_export::to_json_ld(&triples)?
+        } else {
+            rdf_export::to_turtle(&triples)
+        };
+        std::fs::write(&path, content)?;
+        self.ui.display_text1(&format!("Wrote {} statements to {}.", triples.len(), path));
+        Ok(())
+    }
+
+    /// See csv_import.rs.  Lets the user adjust which column goes to which of the class's
+    /// template attribute types, and shows a preview with each bad row's problems, before
+    /// importing anything.
+    fn import_csv_file(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some((path, text)) = self.ask_for_existing_file_contents(
+            "Enter the path of the CSV (.csv) or tab-separated (.tsv) file, with a header row:",
+        ) else {
+            return Ok(());
+        };
+        let lower_path = path.to_lowercase();
+        let delimiter = if lower_path.ends_with(".tsv") || lower_path.ends_with(".tab") {
+            '\t'
+        } else {
+            ','
+        };
+        let records = csv_import::parse_delimited(&text, delimiter);
+        if records.len() < 2 {
+            self.ui.display_text1(&format!("Found no rows after the header in {}.", path));
+            return Ok(());
+        }
+        let db = entity_in.get_db();
+        let Some((class_id_wrapper, _, class_name)) = self.choose_or_create_object(
+            db.clone(),
+            Some(vec!["CHOOSE THE CLASS OF THE ENTITIES TO IMPORT (its template entity's attributes are what columns can be imported as):"]),
+            None,
+            None,
+            Util::ENTITY_CLASS_TYPE,
+            0,
+            None,
+            false,
+            None,
+            false,
+            None,
+            false,
+        )?
+        else {
+            return Ok(());
+        };
+        let class_id = class_id_wrapper.get_id();
+        let types = csv_import::read_template_attribute_types(db.clone(), None, class_id)?;
+        if types.is_empty() {
+            self.ui.display_text1(&format!(
+                "(The template entity of class \"{}\" has no quantity, date, boolean or text attributes, \
+                so only the entities' names can be imported.  Attributes added to the template can be \
+                imported too.)",
+                class_name
+            ));
+        }
+        let headers = &records[0];
+        let data_rows = &records[1..];
+        let mut targets = csv_import::guess_column_targets(headers, &types);
+        let planned = loop {
+            let Some(answer) = self.ask_for_csv_column_to_map(headers, &targets, &types) else {
+                return Ok(());
+            };
+            if answer > 0 {
+                if let Some(target) = self.ask_for_csv_column_target(&headers[answer - 1], &types) {
+                    if target == ColumnTarget::Name {
+                        // (Only one column can be the name.)
+                        for t in targets.iter_mut() {
+                            if *t == ColumnTarget::Name {
+                                *t = ColumnTarget::Ignore;
+                            }
+                        }
+                    }
+                    targets[answer - 1] = target;
+                }
+                continue;
+            }
+            if !targets.contains(&ColumnTarget::Name) {
+                self.ui.display_text1("Please choose a column to use as the entities' names.");
+                continue;
+            }
+            let planned = csv_import::plan_import(data_rows, &targets, &types);
+            let good: Vec<&csv_import::PlannedRow> = planned.iter().filter(|r| r.errors.is_empty()).collect();
+            let bad: Vec<&csv_import::PlannedRow> = planned.iter().filter(|r| !r.errors.is_empty()).collect();
+            let mut preview = format!(
+                "PREVIEW (nothing imported yet): {} rows can be imported as \"{}\" entities, and {} have errors.\n",
+                good.len(),
+                class_name,
+                bad.len()
+            );
+            for row in good.iter().take(10) {
+                preview.push_str(&format!("{}\n", csv_import::describe_planned_row(row, &types)));
+            }
+            if good.len() > 10 {
+                preview.push_str(&format!("...and {} more.\n", good.len() - 10));
+            }
+            for row in &bad {
+                preview.push_str(&format!("{}\n", csv_import::describe_planned_row(row, &types)));
+            }
+            self.ui.display_text1(&preview);
+            if good.is_empty() {
+                continue;
+            }
+            let proceed = self.ui.ask_yes_no_question(
+                &format!(
+                    "Import the {} rows without errors?  (If not, you can change the columns' mapping.)",
+                    good.len()
+                ),
+                "n",
+                false,
+            );
+            match proceed {
+                Some(true) => break planned,
+                Some(false) => continue,
+                None => return Ok(()),
+            }
+        };
+        let new_group_name = Path::new(&path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| class_name.clone());
+        let Some(existing_group_id) = self.ask_for_csv_import_group(entity_in, &new_group_name)? else {
+            return Ok(());
+        };
+        let make_them_public: Option<bool> = self.ui.ask_yes_no_question(
+            "Do you want the entities imported to be marked as public?  Enter y for public, n for \
+            nonpublic, or a space for 'unknown/unspecified', aka decide later.",
+            "",
+            true,
+        );
+        let db_b = db.borrow();
+        let tx = Some(Rc::new(RefCell::new(db_b.begin_trans()?)));
+        // (If this fails, the transaction is rolled back as it goes out of scope.)
+        let now = Utc::now().timestamp_millis();
+        let group_id = match existing_group_id {
+            Some(id) => id,
+            None => {
+                let has_relation_type_id =
+                    db_b.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME)?;
+                db_b.create_group_and_relation_to_group(
+                    tx.clone(),
+                    entity_in.get_id(),
+                    has_relation_type_id,
+                    &new_group_name,
+                    false,
+                    None,
+                    now,
+                    None,
+                )?
+                .0
+            }
+        };
+        let counts = csv_import::import_planned_rows(
+            db.clone(),
+            tx.clone(),
+            class_id,
+            group_id,
+            &planned,
+            &types,
+            make_them_public,
+            now,
+        )?;
+        db_b.commit_local_trans(tx)?;
+        self.ui.display_text1(&format!(
+            "Imported {}: {} entities with {} attributes ({} rows with errors were skipped).",
+            path, counts.entities, counts.attributes, counts.skipped_rows
+        ));
+        Ok(())
+    }
+
+    /// Returns 0 to preview the import, or the 1-based column number to change; None if cancelled.
+    fn ask_for_csv_column_to_map(
+        &self,
+        headers_in: &[String],
+        targets_in: &[ColumnTarget],
+        types_in: &[TemplateAttributeType],
+    ) -> Option<usize> {
+        let leading_text = vec![
+            "Each column of the file is imported as shown.  Choose a column to change that, or:".to_string(),
+        ];
+        let choices = vec!["Preview the import with this mapping".to_string()];
+        let columns: Vec<String> = headers_in
+            .iter()
+            .zip(targets_in)
+            .map(|(header, target)| {
+                let target_description = match target {
+                    ColumnTarget::Ignore => "(ignored)".to_string(),
+                    ColumnTarget::Name => "(the entity's name)".to_string(),
+                    ColumnTarget::Attribute(i) => Self::describe_template_attribute_type(&types_in[*i]),
+                };
+                format!("\"{}\" -> {}", header, target_description)
+            })
+            .collect();
+        let answer = self
+            .ui
+            .ask_which(Some(leading_text), &choices, &columns, true, None, None, None, None)?;
+        if answer > choices.len() && answer <= choices.len() + columns.len() {
+            Some(answer - choices.len())
+        } else {
+            Some(0)
+        }
+    }
+
+    fn ask_for_csv_column_target(
+        &self,
+        header_in: &str,
+        types_in: &[TemplateAttributeType],
+    ) -> Option<ColumnTarget> {
+        let leading_text = vec![format!("Import the column \"{}\" as:", header_in)];
+        let choices = vec!["(ignore it)".to_string(), "(the entity's name)".to_string()];
+        let type_descriptions: Vec<String> =
+            types_in.iter().map(Self::describe_template_attribute_type).collect();
+        let answer = self.ui.ask_which(
+            Some(leading_text),
+            &choices,
+            &type_descriptions,
+            true,
+            None,
+            None,
+            None,
+            None,
+        )?;
+        if answer == 1 {
+            Some(ColumnTarget::Ignore)
+        } else if answer == 2 {
+            Some(ColumnTarget::Name)
+        } else if answer > choices.len() && answer <= choices.len() + types_in.len() {
+            Some(ColumnTarget::Attribute(answer - choices.len() - 1))
+        } else {
+            None
+        }
+    }
+
+    fn describe_template_attribute_type(type_in: &TemplateAttributeType) -> String {
+        match &type_in.form {
+            TemplateAttributeForm::Quantity { unit_name, .. } => {
+                format!("{} (quantity, in {})", type_in.name, unit_name)
+            }
+            TemplateAttributeForm::Date => format!("{} (date)", type_in.name),
+            TemplateAttributeForm::Boolean => format!("{} (yes/no)", type_in.name),
+            TemplateAttributeForm::Text => format!("{} (text)", type_in.name),
+        }
+    }
+
+    /// Offers the groups entity_in has, or a new one named new_group_name_in.  Returns
+    /// Some(None) for a new group, and None if the user cancelled.
+    fn ask_for_csv_import_group(
+        &self,
+        entity_in: &Entity,
+        new_group_name_in: &str,
+    ) -> Result<Option<Option<i64>>, anyhow::Error> {
+        let groups = Self::get_entitys_groups(entity_in)?;
+        let leading_text = vec!["Put the imported entities in which group?".to_string()];
+        let choices = vec![format!("A new group here, \"{}\"", new_group_name_in)];
+        let group_names: Vec<String> = groups.iter().map(|(_, name)| name.clone()).collect();
+        let Some(answer) =
+            self.ui
+                .ask_which(Some(leading_text), &choices, &group_names, true, None, None, None, None)
+        else {
+            return Ok(None);
+        };
+        if answer > choices.len() && answer <= choices.len() + groups.len() {
+            Ok(Some(Some(groups[answer - choices.len() - 1].0)))
+        } else {
+            Ok(Some(None))
+        }
+    }
+
+    /// The (id, name) of each group entity_in has a relation to, in its attributes' order.
+    fn get_entitys_groups(entity_in: &Entity) -> Result<Vec<(i64, String)>, anyhow::Error> {
+        let (attribute_tuples, _) = entity_in.get_sorted_attributes(None, 0, 0, false)?;
+        let mut groups: Vec<(i64, String)> = Vec::new();
+        for (_, attribute) in attribute_tuples {
+            let mut attribute = attribute.borrow_mut();
+            if let Some(rtg) = attribute.as_any_mut().downcast_mut::<RelationToGroup>() {
+                let mut group = rtg.get_group(None)?;
+                groups.push((group.get_id(), group.get_name(None)?));
+            }
+        }
+        Ok(groups)
+    }
+
+    /// See csv_export.rs.
+    fn export_group_to_csv(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let groups = Self::get_entitys_groups(entity_in)?;
+        if groups.is_empty() {
+            self.ui.display_text1("This entity has no groups to export.");
+            return Ok(());
+        }
+        let group_names: Vec<String> = groups.iter().map(|(_, name)| name.clone()).collect();
+        let Some(answer) = self.ui.ask_which(
+            Some(vec!["Export which group (its entries must all be of one class)?".to_string()]),
+            &Vec::new(),
+            &group_names,
+            true,
+            None,
+            None,
+            None,
+            None,
+        ) else {
+            return Ok(());
+        };
+        if answer < 1 || answer > groups.len() {
+            return Ok(());
+        }
+        let (group_id, group_name) = &groups[answer - 1];
+        let Some(separator) = self.ui.ask_for_string3(
+            vec!["Enter what to put between the values when an entry has more than one of the same \
+                attribute type (ESC to cancel):"],
+            None,
+            "; ".to_string(),
+        ) else {
+            return Ok(());
+        };
+        let Some(path) = self.ui.ask_for_string3(
+            vec!["Enter the path of the file to write, ending in .tsv for tab-separated or else .csv \
+                (it will be replaced if it exists):"],
+            None,
+            format!("{}.csv", group_name.replace(['/', '\\'], "_")),
+        ) else {
+            return Ok(());
+        };
+        let path = path.trim().to_string();
+        if path.is_empty() {
+            return Ok(());
+        }
+        let lower_path = path.to_lowercase();
+        let delimiter = if lower_path.ends_with(".tsv") || lower_path.ends_with(".tab") {
+            '\t'
+        } else {
+            ','
+        };
+        let table = match csv_export::read_group_table(entity_in.get_db(), None, *group_id, &separator) {
+            Ok(t) => t,
+            Err(e) => {
+                self.ui.display_text1(&e.to_string());
+                return Ok(());
+            }
+        };
+        std::fs::write(&path, csv_export::to_delimited(&table, delimiter))?;
+        self.ui.display_text1(&format!(
+            "Wrote {} rows and {} columns to {}.",
+            table.rows.len(),
+            table.headers.len(),
+            path
+        ));
+        Ok(())
+    }
+
+    /// For relating units to each other (km and m, etc), choosing the unit each quantity type is
+    /// displayed in, and totaling a quantity type across a group or the current entity's subtree.
+    pub fn units_menu(
+        &self,
+        db_in: Rc<RefCell<dyn Database>>,
+        entity_in: &mut Entity,
+    ) -> Result<(), anyhow::Error> {
+        let entity_name = entity_in.get_name(None)?;
+        loop {
+            let choices = vec![
+                "List unit conversions".to_string(),
+                "Add or change a unit conversion (such as: 1 km = 1000 m)".to_string(),
+                "Delete a unit conversion".to_string(),
+                "Set or clear the canonical unit (for display and totals) of a quantity type".to_string(),
+                format!("Total a quantity type under the current entity (\"{}\")", entity_name),
+                "Total a quantity type in a group".to_string(),
+            ];
+            let Some(answer) = self.ui.ask_which(
+                Some(vec!["Units:".to_string()]),
+                &choices,
+                &Vec::new(),
+                true,
+                None,
+                None,
+                None,
+                None,
+            ) else {
+                return Ok(());
+            };
+            match answer {
+                1 => {
+                    let mut lines: Vec<String> = Vec::new();
+                    for (from, to, factor) in db_in.borrow().get_unit_conversions(None)? {
+                        lines.push(format!(
+                            "1 {} = {} {}",
+                            self.get_entity_name_for_display(db_in.clone(), from)?,
+                            factor,
+                            self.get_entity_name_for_display(db_in.clone(), to)?
+                        ));
+                    }
+                    if lines.is_empty() {
+                        lines.push("(There are no unit conversions yet.)".to_string());
+                    }
+                    self.ui.display_text1(&lines.join("\n"));
+                }
+                2 | 3 => {
+                    self.ui.display_text1("First, search for the unit to convert from:");
+                    let Some(from) = self.ask_for_name_and_search_for_entity(db_in.clone())? else {
+                        continue;
+                    };
+                    self.ui.display_text1("Next, search for the unit to convert to:");
+                    let Some(to) = self.ask_for_name_and_search_for_entity(db_in.clone())? else {
+                        continue;
+                    };
+                    if answer == 3 {
+                        let count = db_in.borrow().delete_unit_conversion(None, from.get_id(), to.get_id())?;
+                        self.ui.display_text1(&format!("Deleted {} conversion(s).", count));
+                        continue;
+                    }
+                    let prompt = format!(
+                        "How many \"{}\" make one \"{}\"?",
+                        self.get_entity_name_for_display(db_in.clone(), to.get_id())?,
+                        self.get_entity_name_for_display(db_in.clone(), from.get_id())?
+                    );
+                    let Some(factor) =
+                        self.ui.ask_for_string3(vec![prompt.as_str()], Some(Util::is_numeric), "".to_string())
+                    else {
+                        continue;
+                    };
+                    match db_in.borrow().set_unit_conversion(
+                        None,
+                        from.get_id(),
+                        to.get_id(),
+                        factor.trim().parse::<f64>()?,
+                    ) {
+                        Ok(()) => self.ui.display_text1("Saved."),
+                        Err(e) => self.ui.display_text1(&e.to_string()),
+                    }
+                }
+                4 => {
+                    self.ui.display_text1("Search for the quantity type (the attribute type entity):");
+                    let Some(attr_type) = self.ask_for_name_and_search_for_entity(db_in.clone())? else {
+                        continue;
+                    };
+                    let clear = self.ui.ask_yes_no_question(
+                        "Clear its canonical unit (instead of setting one)?",
+                        "n",
+                        false,
+                    );
+                    match clear {
+                        None => continue,
+                        Some(true) => db_in.borrow().set_canonical_unit(None, attr_type.get_id(), None)?,
+                        Some(false) => {
+                            self.ui.display_text1("Search for the canonical unit:");
+                            let Some(unit) = self.ask_for_name_and_search_for_entity(db_in.clone())? else {
+                                continue;
+                            };
+                            db_in
+                                .borrow()
+                                .set_canonical_unit(None, attr_type.get_id(), Some(unit.get_id()))?;
+                        }
+                    }
+                    self.ui.display_text1("Saved.");
+                }
+                5 | 6 => {
+                    let scope = if answer == 5 {
+                        QuantityScope::Subtree(entity_in.get_id())
+                    } else {
+                        let Some(group_name) = self.ui.ask_for_string1(vec![
+                            Util::entity_or_group_name_sql_search_prompt(Util::GROUP_TYPE).as_str(),
+                        ]) else {
+                            continue;
+                        };
+                        let Some(group) = self.find_existing_object_by_text(
+                            db_in.clone(),
+                            0,
+                            Util::GROUP_TYPE,
+                            None,
+                            &group_name,
+                        )?
+                        else {
+                            continue;
+                        };
+                        QuantityScope::Group(group.get_id())
+                    };
+                    self.ui.display_text1("Search for the quantity type (the attribute type entity) to total:");
+                    let Some(attr_type) = self.ask_for_name_and_search_for_entity(db_in.clone())? else {
+                        continue;
+                    };
+                    let aggregate = QuantityAggregate::compute(db_in.clone(), None, attr_type.get_id(), scope)?;
+                    self.ui.display_text1(&self.get_quantity_aggregate_description(db_in.clone(), &aggregate)?);
+                }
+                _ => self.ui.display_text1("invalid response"),
+            }
+        }
+    }
+
+    fn get_quantity_aggregate_description(
+        &self,
+        db_in: Rc<RefCell<dyn Database>>,
+        aggregate_in: &QuantityAggregate,
+    ) -> Result<String, anyhow::Error> {
+        let type_name = self.get_entity_name_for_display(db_in.clone(), aggregate_in.attr_type_id)?;
+        let Some(unit_id) = aggregate_in.unit_id else {
+            return Ok(format!("No \"{}\" quantities found.", type_name));
+        };
+        let unit_name = self.get_entity_name_for_display(db_in.clone(), unit_id)?;
+        let mut lines = vec![format!(
+            "{}: {} values, sum {} {}, mean {} {}",
+            type_name,
+            aggregate_in.count,
+            aggregate_in.sum,
+            unit_name,
+            aggregate_in.mean().map_or("-".to_string(), |m| m.to_string()),
+            unit_name
+        )];
+        if !aggregate_in.mismatched.is_empty() {
+            lines.push(format!(
+                "NOT INCLUDED, because there is no conversion from their units to \"{}\" \
+                (see \"Add or change a unit conversion\"):",
+                unit_name
+            ));
+            for (entity_id, unit_id, number) in &aggregate_in.mismatched {
+                lines.push(format!(
+                    "  {}: {} {}",
+                    self.get_entity_name_for_display(db_in.clone(), *entity_id)?,
+                    number,
+                    self.get_entity_name_for_display(db_in.clone(), *unit_id)?
+                ));
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn get_entity_name_for_display(
+        &self,
+        db_in: Rc<RefCell<dyn Database>>,
+        entity_id_in: i64,
+    ) -> Result<String, anyhow::Error> {
+        Ok(db_in
+            .borrow()
+            .get_entity_name(None, entity_id_in)?
+            .unwrap_or_else(|| format!("(entity {})", entity_id_in)))
+    }
+
     pub(crate) fn search_by_id(
         &self,
         db_in: Rc<RefCell<dyn Database>>,
@@ -891,20 +2186,60 @@ impl Controller {
         } else {
             None
         };
-        let selection: Option<(IdWrapper, bool, String)> = self.choose_or_create_object(
-            db_in, /*%%%?.into()*/
-            Some(vec!["SELECT OTHER (RELATED) ENTITY FOR THIS RELATION"]),
-            previous_selection_desc, /*%%%.as_deref()*/
-            previous_selection_id,
-            Util::ENTITY_TYPE,
-            0,
-            None,
-            false,
-            None,
-            false,
-            None,
-            false,
-        )?;
+        // If the relation type only allows some classes of entity as its target, only offer those
+        // (asking first which class, if there are several).
+        let allowed_class_ids = db_in
+            .borrow()
+            .get_relation_type_class_constraints(None, rtedh_in.rel_type_id)?
+            .target_class_ids;
+        let class_id: Option<i64> = if allowed_class_ids.len() > 1 {
+            let mut class_names: Vec<String> = Vec::new();
+            for id in &allowed_class_ids {
+                class_names.push(db_in.borrow().get_class_name(None, *id)?.unwrap_or_default());
+            }
+            let leading_text = vec!["The relation type only allows entities of these classes (or their subclasses).  \
+                                    Choose one to pick from:"
+                .to_string()];
+            match self
+                .ui
+                .ask_which(Some(leading_text), &class_names, &Vec::new(), true, None, None, None, None)
+            {
+                Some(answer) if answer <= allowed_class_ids.len() => Some(allowed_class_ids[answer - 1]),
+                _ => return Ok(None),
+            }
+        } else {
+            allowed_class_ids.first().copied()
+        };
+        let selection: Option<(IdWrapper, bool, String)> = loop {
+            let selection = self.choose_or_create_object(
+                db_in.clone(), /*%%%?.into()*/
+                Some(vec!["SELECT OTHER (RELATED) ENTITY FOR THIS RELATION"]),
+                previous_selection_desc.clone(), /*%%%.as_deref()*/
+                previous_selection_id,
+                Util::ENTITY_TYPE,
+                0,
+                class_id,
+                class_id.is_some(),
+                None,
+                false,
+                None,
+                false,
+            )?;
+            // (An entity found some other way, like by searching, could still be of another class.)
+            if let Some((id_wrapper, false, _)) = &selection {
+                let problems = db_in.borrow().get_relation_class_constraint_problems(
+                    None,
+                    rtedh_in.rel_type_id,
+                    None,
+                    Some(id_wrapper.get_id()),
+                )?;
+                if !problems.is_empty() {
+                    self.ui.display_text1(&problems.join("\n"));
+                    continue;
+                }
+            }
+            break selection;
+        };
         if let Some((id_wrapper, is_remote, remote_instance_id)) = selection {
             let rtedh_out = RelationToEntityDH {
                 rel_type_id: rtedh_in.rel_type_id,
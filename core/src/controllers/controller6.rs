@@ -12,6 +12,8 @@
 // Controller code is split between controller.rs and controller2.rs - controller6.rs, to make
 // incremental compilation faster when only one has changed (or editing faster w/ rust-analyzer).
 
+use crate::controllers::class_actions;
+use crate::model::class_schema::{self, AttributeRule};
 use crate::controllers::main_menu::MainMenu;
 use crate::model::database::Database;
 use crate::model::entity::Entity;
@@ -38,13 +40,17 @@ use crate::model::file_attribute::FileAttribute;
 use crate::model::group::Group;
 use crate::model::id_wrapper::IdWrapper;
 use crate::model::om_instance::OmInstance;
+use crate::model::navigation_history::Location;
+use crate::model::path_finder::{self, PathLink, PathOptions};
 use crate::model::quantity_attribute::QuantityAttribute;
 use crate::model::relation_to_group::RelationToGroup;
 use crate::model::relation_to_local_entity::RelationToLocalEntity;
 use crate::model::relation_to_remote_entity::RelationToRemoteEntity;
 use crate::model::relation_type::RelationType;
 use crate::model::text_attribute::TextAttribute;
+use crate::model::action_script::ActionScript;
 use anyhow::anyhow;
+use chrono::Utc;
 //use std::collections::HashMap;
 //use std::fs::File;
 use std::path::Path;
@@ -62,18 +68,15 @@ impl Controller {
             } else {
                 target_entity_in.get_sorted_attributes(None, 0, 0, false)?.0
             };
-            let id: Option<i64> = target_entity_in
-                .get_class_template_entity_id(None)?;
-            let template_entity: Entity = match id {
-                // id.map(|id| Entity::new2(target_entity_in.get_db(), None, id)?);
-                Some(id) => Entity::new2(target_entity_in.get_db(), None, id)?,
-                None => return Err(anyhow!("Unexpected None resulting from entity.get_class_template_entity_id() \
-                            where entity id is {}", target_entity_in.get_id())),
-            };
+            let template_entities: Vec<Entity> = self.get_class_template_entities(target_entity_in)?;
+            if template_entities.is_empty() {
+                return Err(anyhow!("Unexpected: no class template entities found for the entity {}, \
+                            whose class should have one.", target_entity_in.get_id()));
+            }
             //%%next line in scala was:
             //let templateAttributesToCopy: ArrayBuffer[Attribute] = getMissingAttributes(template_entity, attributeTuples);
             let template_attributes_to_copy: Vec<Rc<RefCell<dyn Attribute>>> = self
-                .get_missing_attributes(Some(&template_entity) /*%%?:.as_ref()*/, &attribute_tuples)?;
+                .get_missing_attributes(&template_entities, &attribute_tuples)?;
             self.copy_and_edit_attributes(target_entity_in, template_attributes_to_copy);
         }
         Ok(())
@@ -601,23 +604,46 @@ impl Controller {
         }
     }
 
-    /// This determines which attributes from the template entity (or "pattern" or "class-defining entity")
-    /// are not found on this entity, so they can be added if the user wishes.
+    /// The template entities of entity_in's class and of its ancestors (see
+    /// EntityClass::get_parent_class_id), nearest first; empty if it has no class.
+    fn get_class_template_entities(&self, entity_in: &mut Entity) -> Result<Vec<Entity>, anyhow::Error> {
+        let Some(class_id) = entity_in.get_class_id(None)? else {
+            return Ok(Vec::new());
+        };
+        let db = entity_in.get_db();
+        let lineage = db.borrow().get_class_lineage(None, class_id)?;
+        let mut template_entities = Vec::new();
+        for (_, template_entity_id) in lineage {
+            template_entities.push(Entity::new2(db.clone(), None, template_entity_id)?);
+        }
+        Ok(template_entities)
+    }
+
+    /// This determines which attributes from the template entities (or "pattern" or "class-defining entity")
+    /// are not found on this entity, so they can be added if the user wishes.  The templates are
+    /// those of the entity's class and then of its ancestors; an attribute type on a nearer
+    /// template takes the place of the same type on those after it.
     fn get_missing_attributes(
         &self,
-        class_template_entity_in: Option<&Entity>,
+        class_template_entities_in: &[Entity],
         // existing_attribute_tuples_in: Vec<(i64, Box<dyn Attribute>)>,
         existing_attribute_tuples_in: &Vec<(i64, Rc<RefCell<dyn Attribute>>)>,
     ) -> Result<Vec<Rc<RefCell<dyn Attribute>>>, anyhow::Error> {
         let mut attributes_to_suggest_copying = Vec::<Rc<RefCell<dyn Attribute>>>::new();
-        if let Some(template_entity) = class_template_entity_in {
+        let mut type_ids_on_nearer_templates: Vec<i64> = Vec::new();
+        for template_entity in class_template_entities_in {
             let (cde_attribute_tuples, _) =
                 template_entity.get_sorted_attributes(None, 0, 0, false)?;
+            let mut type_ids_on_this_template: Vec<i64> = Vec::new();
             for (_, cde_attribute) in cde_attribute_tuples {
                 let mut attribute_type_found_on_entity = false;
                 //%%% next line in scala was:
                 //let cde_attribute = cde_attributeTuple._2;
                 let cde_type_id = cde_attribute.borrow_mut().get_attr_type_id(None)?;
+                type_ids_on_this_template.push(cde_type_id);
+                if type_ids_on_nearer_templates.contains(&cde_type_id) {
+                    continue;
+                }
                 //%%%in scala: for (attributeTuple <- existingAttributeTuplesIn) {
                 for (_, attribute) in existing_attribute_tuples_in {
                     if !attribute_type_found_on_entity {
@@ -643,6 +669,7 @@ impl Controller {
                     attributes_to_suggest_copying.push(cde_attribute /*%%%?: .clone()*/);
                 }
             }
+            type_ids_on_nearer_templates.extend(type_ids_on_this_template);
         }
         Ok(attributes_to_suggest_copying)
     }
@@ -659,10 +686,12 @@ impl Controller {
         let create_attributes: Option<bool> = entity_class.get_create_default_attributes(None)?;
         if let Some(create) = create_attributes {
             Ok(create)
-        } else if let Some(template_id) = entity_in.get_class_template_entity_id(None)? {
-            let template_entity: Entity = Entity::new2(entity_in.get_db(), None, template_id)?;
-            let attr_count =
-                template_entity.get_attribute_count(None, self.db.borrow().include_archived_entities())?;
+        } else if entity_in.get_class_template_entity_id(None)?.is_some() {
+            let mut attr_count = 0;
+            for template_entity in self.get_class_template_entities(entity_in)? {
+                attr_count +=
+                    template_entity.get_attribute_count(None, self.db.borrow().include_archived_entities())?;
+            }
             if attr_count == 0 {
                 Ok(false)
             } else {
@@ -677,4 +706,1485 @@ impl Controller {
             Ok(false)
         }
     }
+
+    /// Runs one of the actions of entity_in's class on it, or defines, edits or deletes the
+    /// class's actions.  (See class_actions.rs and action_script.rs.)
+    pub fn class_actions_menu(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let Some(class_id) = entity_in.get_class_id(None)? else {
+            self.ui.display_text1(
+                "This entity has no class, so has no class actions.  (Actions are defined for a class, \
+                and can be run on any entity of it.)",
+            );
+            return Ok(());
+        };
+        self.class_actions_menu_for_class(entity_in.get_db(), class_id, Some(entity_in))
+    }
+
+    /// Like class_actions_menu, but for class_id_in; with no entity_in, choosing an action edits
+    /// it instead of running it.
+    pub fn class_actions_menu_for_class(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        class_id_in: i64,
+        mut entity_in: Option<&mut Entity>,
+    ) -> Result<(), anyhow::Error> {
+        let class_name = EntityClass::new2(db.clone(), None, class_id_in)?.get_name(None)?;
+        loop {
+            let actions = db.borrow().get_actions(None, class_id_in)?;
+            let (leading_text, listed) = match entity_in.as_deref_mut() {
+                Some(entity) => (
+                    format!(
+                        "Actions of the class \"{}\" (and those it inherits), for \"{}\": choose one to run it, or:",
+                        class_name,
+                        entity.get_name(None)?
+                    ),
+                    self.get_runnable_class_actions(entity)?,
+                ),
+                None => (
+                    format!(
+                        "Actions of the class \"{}\": choose one to edit, rename or delete it, or:",
+                        class_name
+                    ),
+                    actions.clone(),
+                ),
+            };
+            let choices = vec![
+                "Define a new action for this class".to_string(),
+                "Edit, rename or delete an action".to_string(),
+                "How to write actions".to_string(),
+            ];
+            let action_names: Vec<String> = actions.iter().map(|(_, name, _)| name.clone()).collect();
+            let listed_names: Vec<String> = listed.iter().map(|(_, name, _)| name.clone()).collect();
+            let Some(answer) =
+                self.ui
+                    .ask_which(Some(vec![leading_text]), &choices, &listed_names, true, None, None, None, None)
+            else {
+                return Ok(());
+            };
+            if answer == 1 {
+                self.define_class_action(db.clone(), class_id_in, None)?;
+            } else if answer == 2 {
+                let Some(which) = self.ui.ask_which(
+                    Some(vec!["Change which action?".to_string()]),
+                    &Vec::new(),
+                    &action_names,
+                    true,
+                    None,
+                    None,
+                    None,
+                    None,
+                ) else {
+                    continue;
+                };
+                if which >= 1 && which <= actions.len() {
+                    self.define_class_action(db.clone(), class_id_in, Some(&actions[which - 1]))?;
+                }
+            } else if answer == 3 {
+                self.ui.display_text1(CLASS_ACTION_HELP);
+            } else if answer > choices.len() && answer <= choices.len() + listed.len() {
+                let chosen = &listed[answer - choices.len() - 1];
+                match entity_in.as_deref_mut() {
+                    Some(entity) => {
+                        self.run_class_action(entity, &chosen.1, &chosen.2)?;
+                        return Ok(());
+                    }
+                    None => self.define_class_action(db.clone(), class_id_in, Some(chosen))?,
+                }
+            } else {
+                self.ui.display_text1("invalid response");
+            }
+        }
+    }
+
+    /// The actions that can be run on entity_in: those of its class, then those of its
+    /// ancestors (except any named like a nearer one, which overrides it).
+    pub fn get_runnable_class_actions(&self, entity_in: &mut Entity) -> Result<Vec<(i64, String, String)>, anyhow::Error> {
+        let Some(class_id) = entity_in.get_class_id(None)? else {
+            return Ok(Vec::new());
+        };
+        let db = entity_in.get_db();
+        let mut actions: Vec<(i64, String, String)> = Vec::new();
+        for (lineage_class_id, _) in db.borrow().get_class_lineage(None, class_id)? {
+            for action in db.borrow().get_actions(None, lineage_class_id)? {
+                if !actions.iter().any(|(_, name, _)| *name == action.1) {
+                    actions.push(action);
+                }
+            }
+        }
+        Ok(actions)
+    }
+
+    /// Runs the action in its own transaction, so if the script fails partway, none of its
+    /// changes are kept.
+    pub fn run_class_action(&self, entity_in: &mut Entity, name_in: &str, action_in: &str) -> Result<(), anyhow::Error> {
+        let db = entity_in.get_db();
+        let db_b = db.borrow();
+        let tx = Some(Rc::new(RefCell::new(db_b.begin_trans()?)));
+        match class_actions::run_action(db.clone(), tx.clone(), entity_in.get_id(), action_in, Utc::now().timestamp_millis()) {
+            Ok((said, changes)) => {
+                db_b.commit_local_trans(tx)?;
+                let mut message = said.join("\n");
+                if !message.is_empty() {
+                    message.push('\n');
+                }
+                message.push_str(&format!("(Ran \"{}\": {} attributes changed.)", name_in, changes));
+                self.ui.display_text1(&message);
+            }
+            Err(e) => {
+                // (The transaction is rolled back as it goes out of scope.)
+                self.ui
+                    .display_text1(&format!("The action \"{}\" stopped, and changed nothing: {}", name_in, e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates an action (if existing_in is None), or edits, renames or deletes existing_in, which
+    /// is (id, name, action) as from Database::get_actions.  Scripts are checked before saving.
+    fn define_class_action(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        class_id_in: i64,
+        existing_in: Option<&(i64, String, String)>,
+    ) -> Result<(), anyhow::Error> {
+        if let Some((id, name, _)) = existing_in {
+            let delete = self.ui.ask_yes_no_question(&format!("Delete the action \"{}\"?", name), "n", false);
+            match delete {
+                None => return Ok(()),
+                Some(true) => {
+                    db.borrow().delete_action(None, *id)?;
+                    return Ok(());
+                }
+                Some(false) => {}
+            }
+        }
+        let Some(name) = self.ui.ask_for_string3(
+            vec!["Enter the action's name, as it will show in the menu (like \"postpone a week\"):"],
+            None,
+            existing_in.map(|(_, name, _)| name.clone()).unwrap_or_default(),
+        ) else {
+            return Ok(());
+        };
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Ok(());
+        }
+        let mut script = existing_in
+            .map(|(_, _, action)| action.clone())
+            .unwrap_or_else(|| "# See \"How to write actions\" on the class actions menu.  For example:\n\
+                # set \"due\" = get(\"due\") + 1 week\n"
+                .to_string());
+        loop {
+            script = Util::edit_multiline_text(&script, &self.ui)?;
+            match ActionScript::parse(&script) {
+                Ok(_) => break,
+                Err(e) => {
+                    let again = self.ui.ask_yes_no_question(
+                        &format!("The action has an error: {}.  Edit it again (else it is not saved)?", e),
+                        "y",
+                        false,
+                    );
+                    if again != Some(true) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        match existing_in {
+            Some((id, _, _)) => {
+                db.borrow().update_action(None, *id, &name, &script)?;
+            }
+            None => {
+                db.borrow().create_action(None, class_id_in, &name, &script)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows the rules of class_id_in's schema (see class_schema.rs) to add, change or delete
+    /// them, and can list the entities of the class that break them.
+    pub fn class_schema_menu(&self, db: Rc<RefCell<dyn Database>>, class_id_in: i64) -> Result<(), anyhow::Error> {
+        let class_name = EntityClass::new2(db.clone(), None, class_id_in)?.get_name(None)?;
+        let name_of = |id: i64| -> String {
+            match db.borrow().get_entity_name(None, id) {
+                Ok(Some(name)) => name,
+                _ => format!("#{}", id),
+            }
+        };
+        loop {
+            let rules = db.borrow().get_class_attribute_rules(None, class_id_in)?;
+            let leading_text = vec![format!(
+                "Rules for the attributes of entities in the class \"{}\" (checked whenever one is added or \
+                changed, except on the class's template entity).  Choose a rule to change or delete it, or:",
+                class_name
+            )];
+            let parent_class_id = EntityClass::new2(db.clone(), None, class_id_in)?.get_parent_class_id(None)?;
+            let parent_desc = match parent_class_id {
+                Some(id) => EntityClass::new2(db.clone(), None, id)?.get_name(None)?,
+                None => "(none)".to_string(),
+            };
+            let choices = vec![
+                "Add a rule, for an attribute type".to_string(),
+                "Validate all members of this class (list those that break the rules)".to_string(),
+                format!(
+                    "Set or clear the parent class (members of this class count as its members too, and \
+                    inherit its template attributes and rules; currently: {})",
+                    parent_desc
+                ),
+                "Actions: define, edit or delete this class's actions (scripts to run on its entities)...".to_string(),
+            ];
+            let rule_descriptions: Vec<String> = rules
+                .iter()
+                .map(|r| format!("{}: {}", name_of(r.attr_type_id), r.describe(&name_of)))
+                .collect();
+            let Some(answer) =
+                self.ui
+                    .ask_which(Some(leading_text), &choices, &rule_descriptions, true, None, None, None, None)
+            else {
+                return Ok(());
+            };
+            if answer == 1 {
+                self.define_class_attribute_rule(db.clone(), class_id_in, None)?;
+            } else if answer == 2 {
+                self.show_class_validation_report(db.clone(), class_id_in, &class_name)?;
+            } else if answer == 3 {
+                self.ask_for_parent_class(db.clone(), class_id_in, parent_class_id.is_some())?;
+            } else if answer == 4 {
+                self.class_actions_menu_for_class(db.clone(), class_id_in, None)?;
+            } else if answer > choices.len() && answer <= choices.len() + rules.len() {
+                let rule = &rules[answer - choices.len() - 1];
+                let delete = self.ui.ask_yes_no_question(
+                    &format!("Delete the rule for \"{}\" (else change it)?", name_of(rule.attr_type_id)),
+                    "n",
+                    false,
+                );
+                match delete {
+                    None => {}
+                    Some(true) => {
+                        db.borrow().delete_class_attribute_rule(None, class_id_in, rule.attr_type_id)?;
+                    }
+                    Some(false) => self.define_class_attribute_rule(db.clone(), class_id_in, Some(rule))?,
+                }
+            } else {
+                self.ui.display_text1("invalid response");
+            }
+        }
+    }
+
+    /// Lets the user pick a new parent for the class, or clear the current one.  A choice that
+    /// would make the class its own ancestor is refused with a message.
+    fn ask_for_parent_class(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        class_id_in: i64,
+        has_parent_in: bool,
+    ) -> Result<(), anyhow::Error> {
+        if has_parent_in {
+            let clear = self.ui.ask_yes_no_question("Clear the parent class (else choose another)?", "n", true);
+            match clear {
+                None => return Ok(()),
+                Some(true) => return db.borrow().update_class_parent(None, class_id_in, None),
+                Some(false) => {}
+            }
+        }
+        let Some((id_wrapper, _, _)) = self.choose_or_create_object(
+            db.clone(),
+            Some(vec!["CHOOSE THE PARENT CLASS:"]),
+            None,
+            None,
+            Util::ENTITY_CLASS_TYPE,
+            0,
+            None,
+            false,
+            None,
+            false,
+            None,
+            false,
+        )?
+        else {
+            return Ok(());
+        };
+        if let Err(e) = db.borrow().update_class_parent(None, class_id_in, Some(id_wrapper.get_id())) {
+            self.ui.display_text1(&e.to_string());
+        }
+        Ok(())
+    }
+
+    /// Asks for a rule's details and saves it.  Changing existing_in keeps its attribute type and
+    /// form.
+    fn define_class_attribute_rule(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        class_id_in: i64,
+        existing_in: Option<&AttributeRule>,
+    ) -> Result<(), anyhow::Error> {
+        let (attr_type_id, form): (i64, &str) = match existing_in {
+            Some(rule) => (rule.attr_type_id, rule.form.as_str()),
+            None => {
+                let forms: Vec<String> = class_schema::FORMS
+                    .iter()
+                    .map(|f| class_schema::describe_form(f).to_string())
+                    .collect();
+                let Some(which) = self.ui.ask_which(
+                    Some(vec!["Which form must attributes of the type take?".to_string()]),
+                    &forms,
+                    &Vec::new(),
+                    true,
+                    None,
+                    None,
+                    None,
+                    None,
+                ) else {
+                    return Ok(());
+                };
+                if which < 1 || which > forms.len() {
+                    return Ok(());
+                }
+                let form = class_schema::FORMS[which - 1];
+                let prompt = format!(
+                    "SELECT THE {} ATTRIBUTE TYPE THAT THE RULE IS FOR:",
+                    class_schema::describe_form(form).to_uppercase()
+                );
+                let Some((type_id, _, _)) = self.choose_or_create_object(
+                    db.clone(),
+                    Some(vec![prompt.as_str()]),
+                    None,
+                    None,
+                    form,
+                    0,
+                    None,
+                    false,
+                    None,
+                    false,
+                    None,
+                    false,
+                )?
+                else {
+                    return Ok(());
+                };
+                (type_id.get_id(), form)
+            }
+        };
+        let Some(Some(min_count)) = self.ask_for_rule_number(
+            "How many attributes of this type must each entity of the class have, at least?  (0 if they are \
+            optional, 1 if one is required)",
+            Some(existing_in.map_or(0.0, |r| r.min_count as f64)),
+        ) else {
+            return Ok(());
+        };
+        let Some(max_count) = self.ask_for_rule_number(
+            "And at most how many?  (Blank for no limit.)",
+            existing_in.and_then(|r| r.max_count).map(|x| x as f64),
+        ) else {
+            return Ok(());
+        };
+        let mut allowed_unit_ids: Vec<i64> = existing_in.map(|r| r.allowed_unit_ids.clone()).unwrap_or_default();
+        let (mut min_value, mut max_value) = (None, None);
+        if form == Util::QUANTITY_TYPE {
+            let limit_units = self.ui.ask_yes_no_question(
+                "Allow only certain units? (If so, you choose them one at a time next.)",
+                if allowed_unit_ids.is_empty() { "n" } else { "y" },
+                false,
+            );
+            match limit_units {
+                None => return Ok(()),
+                Some(false) => allowed_unit_ids.clear(),
+                Some(true) => {
+                    allowed_unit_ids.clear();
+                    loop {
+                        let Some((unit_id, _, _)) = self.choose_or_create_object(
+                            db.clone(),
+                            Some(vec!["SELECT AN ALLOWED UNIT (ESC when done):"]),
+                            None,
+                            None,
+                            Util::QUANTITY_TYPE,
+                            0,
+                            None,
+                            false,
+                            None,
+                            false,
+                            None,
+                            true,
+                        )?
+                        else {
+                            break;
+                        };
+                        allowed_unit_ids.push(unit_id.get_id());
+                    }
+                }
+            }
+        }
+        if form == Util::DATE_TYPE {
+            for (prompt, value) in [("earliest", &mut min_value), ("latest", &mut max_value)] {
+                let ask = self.ui.ask_yes_no_question(&format!("Set an {} date allowed?", prompt), "n", false);
+                match ask {
+                    None => return Ok(()),
+                    Some(false) => {}
+                    Some(true) => match Util::ask_for_date_generic(None, None, &self.ui) {
+                        None => return Ok(()),
+                        Some(date) => *value = Some(date as f64),
+                    },
+                }
+            }
+        } else if form == Util::QUANTITY_TYPE || form == Util::TEXT_TYPE {
+            let what = if form == Util::TEXT_TYPE { "length of the text" } else { "number" };
+            let Some(min) = self.ask_for_rule_number(
+                &format!("The smallest {} allowed?  (Blank for none.)", what),
+                existing_in.and_then(|r| r.min_value),
+            ) else {
+                return Ok(());
+            };
+            let Some(max) = self.ask_for_rule_number(
+                &format!("The largest {} allowed?  (Blank for none.)", what),
+                existing_in.and_then(|r| r.max_value),
+            ) else {
+                return Ok(());
+            };
+            (min_value, max_value) = (min, max);
+        }
+        let rule = AttributeRule {
+            attr_type_id,
+            form: form.to_string(),
+            min_count: min_count as i32,
+            max_count: max_count.map(|x| x as i32),
+            min_value,
+            max_value,
+            allowed_unit_ids,
+        };
+        if let Err(e) = db.borrow().set_class_attribute_rule(None, class_id_in, &rule) {
+            self.ui.display_text1(&format!("The rule was not saved: {}", e));
+            return Ok(());
+        }
+        let breaking = db.borrow().validate_class_members(None, class_id_in)?.len();
+        if breaking > 0 {
+            self.ui.display_text1(&format!(
+                "Saved.  {} entities of the class already there break the class's rules; to see them, \
+                choose \"Validate all members of this class\".",
+                breaking
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns None if the user cancelled (ESC), Some(None) for a blank answer, or the number.
+    fn ask_for_rule_number(&self, prompt_in: &str, default_in: Option<f64>) -> Option<Option<f64>> {
+        fn is_blank_or_numeric(input: &str) -> Result<(), anyhow::Error> {
+            if input.trim().is_empty() {
+                Ok(())
+            } else {
+                Util::is_numeric(input.trim())
+            }
+        }
+        let answer = self.ui.ask_for_string3(
+            vec![prompt_in],
+            Some(is_blank_or_numeric),
+            default_in.map(|x| x.to_string()).unwrap_or_default(),
+        )?;
+        Some(answer.trim().parse::<f64>().ok())
+    }
+
+    fn show_class_validation_report(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        class_id_in: i64,
+        class_name_in: &str,
+    ) -> Result<(), anyhow::Error> {
+        let results = db.borrow().validate_class_members(None, class_id_in)?;
+        if results.is_empty() {
+            self.ui.display_text1(&format!(
+                "All entities of the class \"{}\" follow its rules.",
+                class_name_in
+            ));
+            return Ok(());
+        }
+        let mut report = format!(
+            "{} entities of the class \"{}\" break its rules:\n",
+            results.len(),
+            class_name_in
+        );
+        for (id, name, problems) in results {
+            report.push_str(&format!("{} (id {}):\n", name, id));
+            for problem in problems {
+                report.push_str(&format!("    {}\n", problem));
+            }
+        }
+        self.ui.display_text1(&report);
+        Ok(())
+    }
+
+    /// Lines for the entity view, for the relations implied by entity_id_in's (see
+    /// relation_inference.rs).  Each is marked "(inferred)" and says why, so it can't be taken
+    /// for one that was entered.
+    pub fn get_inferred_relation_display_strings(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let inferred = db.borrow().get_inferred_relations(None, entity_id_in)?;
+        let name_of = |id: i64| -> String {
+            match db.borrow().get_entity_name(None, id) {
+                Ok(Some(name)) => name,
+                _ => format!("#{}", id),
+            }
+        };
+        Ok(inferred
+            .iter()
+            .map(|r| {
+                format!(
+                    "(inferred) {}: {}  [{}]",
+                    name_of(r.rel_type_id),
+                    name_of(r.to_entity_id),
+                    r.reason.describe(&name_of)
+                )
+            })
+            .collect())
+    }
+
+    /// Shows the relations implied by entity_in's (see get_inferred_relation_display_strings).
+    pub fn show_inferred_relations(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let lines = self.get_inferred_relation_display_strings(entity_in.get_db(), entity_in.get_id())?;
+        let name = entity_in.get_name(None)?;
+        if lines.is_empty() {
+            self.ui.display_text1(&format!(
+                "No relations are implied by those of \"{}\".  (They come from relation types marked \
+                transitive or symmetric, or with an inverse: see the main menu choice for relation type semantics.)",
+                name
+            ));
+        } else {
+            self.ui.display_text1(&format!(
+                "Relations implied by those of \"{}\":\n{}",
+                name,
+                lines.join("\n")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Lets the user pick a relation type, then edit_relation_type_semantics on it (which is also
+    /// where the classes it allows, at either end, are set: see edit_relation_type_allowed_classes).
+    pub fn choose_and_edit_relation_type_semantics(&self, db: Rc<RefCell<dyn Database>>) -> Result<(), anyhow::Error> {
+        let Some((id_wrapper, _, _)) = self.choose_or_create_object(
+            db.clone(),
+            Some(vec!["CHOOSE THE RELATION TYPE TO DESCRIBE:"]),
+            None,
+            None,
+            Util::RELATION_TYPE_TYPE,
+            0,
+            None,
+            false,
+            None,
+            false,
+            None,
+            false,
+        )?
+        else {
+            return Ok(());
+        };
+        self.edit_relation_type_semantics(db, id_wrapper.get_id())
+    }
+
+    /// Lets the user say whether rel_type_id_in is transitive or symmetric, and which relation
+    /// type (if any) is its inverse, so the entity view can show the relations that implies.
+    pub fn edit_relation_type_semantics(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        rel_type_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        let mut rel_type = RelationType::new2(db.clone(), None, rel_type_id_in)?;
+        let name = rel_type.get_name(None)?;
+        let yes_no = |x: bool| if x { "yes" } else { "no" };
+        loop {
+            let mut semantics = rel_type.get_semantics(None)?;
+            let inverse_desc = match semantics.inverse_of_id {
+                Some(id) => db.borrow().get_entity_name(None, id)?.unwrap_or_else(|| format!("#{}", id)),
+                None => "(none)".to_string(),
+            };
+            let constraints = rel_type.get_class_constraints(None)?;
+            let mut class_descs: Vec<String> = Vec::new();
+            for class_ids in [&constraints.source_class_ids, &constraints.target_class_ids] {
+                let mut names: Vec<String> = Vec::new();
+                for id in class_ids {
+                    names.push(format!("\"{}\"", db.borrow().get_class_name(None, *id)?.unwrap_or_default()));
+                }
+                class_descs.push(if names.is_empty() {
+                    "(any)".to_string()
+                } else {
+                    names.join(", ")
+                });
+            }
+            let leading_text = vec![format!(
+                "What relations of the type \"{}\" imply (shown as inferred, in the entity view), and what they can \
+                connect.  Choose one to change it:",
+                name
+            )];
+            let choices = vec![
+                format!(
+                    "Transitive: a chain of them implies one from its start to its end, as \"part of\" does \
+                    (currently: {})",
+                    yes_no(semantics.transitive)
+                ),
+                format!(
+                    "Symmetric: each implies the same relation the other way, as \"sibling of\" does (currently: {})",
+                    yes_no(semantics.symmetric)
+                ),
+                format!(
+                    "Inverse: the relation type that each implies the other way, as \"contains\" is for \"part of\" \
+                    (currently: {})",
+                    inverse_desc
+                ),
+                format!(
+                    "Allowed classes for the entity each is from (domain; subclasses count too) (currently: {})",
+                    class_descs[0]
+                ),
+                format!(
+                    "Allowed classes for the entity each is to (range; subclasses count too) (currently: {})",
+                    class_descs[1]
+                ),
+            ];
+            let Some(answer) =
+                self.ui
+                    .ask_which(Some(leading_text), &choices, &Vec::new(), true, None, None, None, None)
+            else {
+                return Ok(());
+            };
+            if answer == 1 {
+                semantics.transitive = !semantics.transitive;
+            } else if answer == 2 {
+                semantics.symmetric = !semantics.symmetric;
+            } else if answer == 3 {
+                let clear = semantics.inverse_of_id.is_some()
+                    && self.ui.ask_yes_no_question("Clear the inverse (else choose another)?", "y", false)
+                        == Some(true);
+                if clear {
+                    semantics.inverse_of_id = None;
+                } else {
+                    let Some((id_wrapper, _, _)) = self.choose_or_create_object(
+                        db.clone(),
+                        Some(vec!["CHOOSE THE INVERSE RELATION TYPE:"]),
+                        None,
+                        None,
+                        Util::RELATION_TYPE_TYPE,
+                        0,
+                        None,
+                        false,
+                        None,
+                        false,
+                        None,
+                        false,
+                    )?
+                    else {
+                        continue;
+                    };
+                    semantics.inverse_of_id = Some(id_wrapper.get_id());
+                }
+            } else if answer == 4 || answer == 5 {
+                self.edit_relation_type_allowed_classes(db.clone(), &rel_type, answer == 5)?;
+                continue;
+            } else {
+                self.ui.display_text1("invalid response");
+                continue;
+            }
+            if let Err(e) = rel_type.update_semantics(None, &semantics) {
+                self.ui.display_text1(&e.to_string());
+            }
+        }
+    }
+
+    /// Lists entity_in's relations, to choose one and see or edit its own attributes (with
+    /// relation_attributes_menu), or sorts them by one of those.
+    pub fn relation_attributes_of_entity_menu(&self, entity_in: &mut Entity) -> Result<(), anyhow::Error> {
+        let db = entity_in.get_db();
+        let relation_form_ids = [
+            Util::RELATION_TO_LOCAL_ENTITY_TYPE,
+            Util::RELATION_TO_REMOTE_ENTITY_TYPE,
+            Util::RELATION_TO_GROUP_TYPE,
+        ]
+        .iter()
+        .map(|form| db.borrow().get_attribute_form_id(form))
+        .collect::<Result<Vec<i32>, anyhow::Error>>()?;
+        loop {
+            let mut relations: Vec<(i32, i64, String)> = Vec::new();
+            for (_, attribute) in entity_in.get_sorted_attributes(None, 0, 0, false)?.0 {
+                let mut attribute = attribute.borrow_mut();
+                let form_id = attribute.get_form_id()?;
+                if relation_form_ids.contains(&form_id) {
+                    let desc = attribute.get_display_string(0, None, None, false)?;
+                    relations.push((form_id, attribute.get_id(), desc));
+                }
+            }
+            if relations.is_empty() {
+                self.ui.display_text1("The current entity has no relations, so none with attributes of their own.");
+                return Ok(());
+            }
+            let leading_text = vec![format!(
+                "Relations of \"{}\": choose one to see or edit its own attributes (like when it began), or:",
+                entity_in.get_name(None)?
+            )];
+            let choices = vec!["Sort these relations by one of their own attributes...".to_string()];
+            let descs: Vec<String> = relations.iter().map(|(_, _, desc)| desc.clone()).collect();
+            let Some(answer) = self.ui.ask_which(Some(leading_text), &choices, &descs, true, None, None, None, None)
+            else {
+                return Ok(());
+            };
+            if answer == 1 {
+                self.sort_relations_by_relation_attribute(db.clone(), entity_in.get_id())?;
+            } else if answer > choices.len() && answer <= choices.len() + relations.len() {
+                let (form_id, relation_id, desc) = &relations[answer - choices.len() - 1];
+                self.relation_attributes_menu(db.clone(), *form_id, *relation_id, desc)?;
+            } else {
+                self.ui.display_text1("invalid response");
+            }
+        }
+    }
+
+    fn sort_relations_by_relation_attribute(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        entity_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        let types = db.borrow().get_relation_attribute_types(None, entity_id_in)?;
+        if types.is_empty() {
+            self.ui.display_text1("None of these relations has attributes of its own yet, to sort them by.");
+            return Ok(());
+        }
+        let mut type_descs: Vec<String> = Vec::new();
+        for (form, attr_type_id) in &types {
+            let name = db.borrow().get_entity_name(None, *attr_type_id)?.unwrap_or_default();
+            type_descs.push(format!("{} ({})", name, form));
+        }
+        let Some(which) = self.ui.ask_which(
+            Some(vec!["Sort the relations by which attribute?".to_string()]),
+            &Vec::new(),
+            &type_descs,
+            true,
+            None,
+            None,
+            None,
+            None,
+        ) else {
+            return Ok(());
+        };
+        let Some((form, attr_type_id)) = types.get(which - 1) else {
+            self.ui.display_text1("invalid response");
+            return Ok(());
+        };
+        let Some(descending) =
+            self.ui.ask_yes_no_question("Highest (or latest, or last alphabetically) first?", "n", false)
+        else {
+            return Ok(());
+        };
+        db.borrow()
+            .sort_relations_by_relation_attribute(None, entity_id_in, form, *attr_type_id, descending)?;
+        Ok(())
+    }
+
+    /// Lets the user add, edit or delete a relation's own attributes (like the start date, role
+    /// or salary of a "worked at" relation), which are held by an entity made for them (see
+    /// Database::get_relation_attribute_holder_id).  relation_form_id_in is the relation's
+    /// attribute form id.
+    pub fn relation_attributes_menu(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+        relation_desc_in: &str,
+    ) -> Result<(), anyhow::Error> {
+        let forms = [Util::QUANTITY_TYPE, Util::DATE_TYPE, Util::BOOLEAN_TYPE, Util::TEXT_TYPE];
+        loop {
+            let holder_id = db
+                .borrow()
+                .get_relation_attribute_holder_id(None, relation_form_id_in, relation_id_in)?;
+            let attributes: Vec<(i64, Rc<RefCell<dyn Attribute>>)> = match holder_id {
+                Some(id) => Entity::new2(db.clone(), None, id)?.get_sorted_attributes(None, 0, 0, false)?.0,
+                None => Vec::new(),
+            };
+            let mut attribute_descs: Vec<String> = Vec::new();
+            for (_, attribute) in &attributes {
+                attribute_descs.push(attribute.borrow_mut().get_display_string(0, None, None, false)?);
+            }
+            let leading_text = vec![
+                format!("Attributes of the relation: {}", relation_desc_in),
+                "(Add one, or choose one listed to edit or delete it.)".to_string(),
+            ];
+            let choices: Vec<String> = vec![
+                "Add a quantity attribute (like a salary)".to_string(),
+                "Add a date attribute (like when it began)".to_string(),
+                "Add a true/false attribute".to_string(),
+                "Add a text attribute (like a role)".to_string(),
+            ];
+            let Some(answer) = self.ui.ask_which(
+                Some(leading_text),
+                &choices,
+                &attribute_descs,
+                true,
+                None,
+                None,
+                None,
+                None,
+            ) else {
+                return Ok(());
+            };
+            if answer <= choices.len() {
+                let holder_id =
+                    db.borrow()
+                        .get_or_create_relation_attribute_holder(None, relation_form_id_in, relation_id_in)?;
+                let mut holder = Entity::new2(db.clone(), None, holder_id)?;
+                let form_id = db.borrow().get_attribute_form_id(forms[answer - 1])?;
+                self.add_attribute(&mut holder, 0, form_id, None)?;
+            } else if answer <= choices.len() + attributes.len() {
+                let attribute = attributes[answer - choices.len() - 1].1.clone();
+                self.attribute_edit_menu(&mut *attribute.borrow_mut())?;
+            } else {
+                self.ui.display_text1("invalid response");
+            }
+        }
+    }
+
+    /// Lets the user add a class to, or remove one from, the classes allowed at one end (the
+    /// target if for_target_in, else the source) of relations of rel_type_in.  New relations to
+    /// local entities that break these are refused.
+    fn edit_relation_type_allowed_classes(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        rel_type_in: &RelationType,
+        for_target_in: bool,
+    ) -> Result<(), anyhow::Error> {
+        let mut constraints = rel_type_in.get_class_constraints(None)?;
+        let class_ids = if for_target_in {
+            &mut constraints.target_class_ids
+        } else {
+            &mut constraints.source_class_ids
+        };
+        let mut choices = vec!["Add a class...".to_string()];
+        for id in class_ids.iter() {
+            choices.push(format!(
+                "Remove \"{}\"",
+                db.borrow().get_class_name(None, *id)?.unwrap_or_default()
+            ));
+        }
+        let leading_text = vec![format!(
+            "Relations of this type can only be {} entities in these classes (or any entity, if there are none):",
+            if for_target_in { "to" } else { "from" }
+        )];
+        let Some(answer) = self
+            .ui
+            .ask_which(Some(leading_text), &choices, &Vec::new(), true, None, None, None, None)
+        else {
+            return Ok(());
+        };
+        if answer == 1 {
+            let Some((id_wrapper, _, _)) = self.choose_or_create_object(
+                db.clone(),
+                Some(vec!["CHOOSE A CLASS TO ALLOW:"]),
+                None,
+                None,
+                Util::ENTITY_CLASS_TYPE,
+                0,
+                None,
+                false,
+                None,
+                false,
+                None,
+                false,
+            )?
+            else {
+                return Ok(());
+            };
+            class_ids.push(id_wrapper.get_id());
+        } else if answer <= choices.len() {
+            class_ids.remove(answer - 2);
+        } else {
+            self.ui.display_text1("invalid response");
+            return Ok(());
+        }
+        if let Err(e) = rel_type_in.update_class_constraints(None, &constraints) {
+            self.ui.display_text1(&e.to_string());
+        }
+        Ok(())
+    }
+
+    /// Shows every place entity_in is referred to (see backlinks.rs), with counts, and lets the
+    /// user pick a referring entity to go to, which is returned.  (For a group, that is an
+    /// entity containing the group.)
+    pub fn backlinks_menu(&self, entity_in: &mut Entity) -> Result<Option<Entity>, anyhow::Error> {
+        let db = entity_in.get_db();
+        let name = entity_in.get_name(None)?;
+        let backlinks = db.borrow().get_backlinks(None, entity_in.get_id())?;
+        if backlinks.total_count() == 0 {
+            self.ui.display_text1(&format!("Nothing refers to \"{}\".", name));
+            return Ok(None);
+        }
+        let name_of = |id: i64| -> String {
+            match db.borrow().get_entity_name(None, id) {
+                Ok(Some(name)) => name,
+                _ => format!("#{}", id),
+            }
+        };
+        // Each kind of reference's description, and its entities (or groups, for groups_section):
+        let mut sections: Vec<(String, Vec<i64>)> = Vec::new();
+        for (rel_type_id, ids) in &backlinks.containing_entities {
+            sections.push((
+                format!("Entities with a \"{}\" relation to it ({})", name_of(*rel_type_id), ids.len()),
+                ids.clone(),
+            ));
+        }
+        let groups_section = if backlinks.containing_group_ids.is_empty() {
+            None
+        } else {
+            sections.push((
+                format!("Groups it is in ({})", backlinks.containing_group_ids.len()),
+                backlinks.containing_group_ids.clone(),
+            ));
+            Some(sections.len() - 1)
+        };
+        for (form, ids) in &backlinks.attribute_type_users {
+            let form_desc = if Util::RELATION_ATTR_TYPE_NAMES.contains(&form.as_str()) {
+                "relation"
+            } else {
+                class_schema::describe_form(form)
+            };
+            sections.push((
+                format!("Entities using it as the type of a {} attribute ({})", form_desc, ids.len()),
+                ids.clone(),
+            ));
+        }
+        if !backlinks.unit_users.is_empty() {
+            sections.push((
+                format!("Entities with quantities in it as a unit ({})", backlinks.unit_users.len()),
+                backlinks.unit_users.clone(),
+            ));
+        }
+        if !backlinks.remote_referrers.is_empty() {
+            sections.push((
+                format!(
+                    "Entities with relations to it as a remote entity, via this instance's id ({})",
+                    backlinks.remote_referrers.len()
+                ),
+                backlinks.remote_referrers.clone(),
+            ));
+        }
+        let choices: Vec<String> = sections.iter().map(|(desc, _)| desc.clone()).collect();
+        loop {
+            let leading_text = vec![format!(
+                "Backlinks: the {} references to \"{}\".  Choose a kind to see them.",
+                backlinks.total_count(),
+                name
+            )];
+            let Some(answer) = self.ui.ask_which(Some(leading_text), &choices, &Vec::new(), true, None, None, None, None)
+            else {
+                return Ok(None);
+            };
+            let Some((desc, ids)) = sections.get(answer.wrapping_sub(1)) else {
+                self.ui.display_text1("invalid response");
+                continue;
+            };
+            let chosen_id = if groups_section == Some(answer - 1) {
+                let mut group_names: Vec<String> = Vec::new();
+                for group_id in ids {
+                    group_names.push(Group::new2(db.clone(), None, *group_id)?.get_name(None)?);
+                }
+                let Some(group_id) = self.choose_backlink(desc, ids, &group_names) else {
+                    continue;
+                };
+                let containing_ids: Vec<i64> = db
+                    .borrow()
+                    .get_entities_containing_group(None, group_id, 0, None)?
+                    .into_iter()
+                    .map(|(_, id)| id)
+                    .collect();
+                match containing_ids.len() {
+                    0 => {
+                        self.ui.display_text1(Util::ORPHANED_GROUP_MESSAGE);
+                        continue;
+                    }
+                    1 => Some(containing_ids[0]),
+                    _ => {
+                        let names: Vec<String> = containing_ids.iter().map(|id| name_of(*id)).collect();
+                        self.choose_backlink("Entities containing the group", &containing_ids, &names)
+                    }
+                }
+            } else {
+                let names: Vec<String> = ids.iter().map(|id| name_of(*id)).collect();
+                self.choose_backlink(desc, ids, &names)
+            };
+            if let Some(id) = chosen_id {
+                return Ok(Some(Entity::new2(db.clone(), None, id)?));
+            }
+        }
+    }
+
+    /// Returns the one of ids_in that the user picks by its name in names_in, if any.
+    fn choose_backlink(&self, desc_in: &str, ids_in: &[i64], names_in: &[String]) -> Option<i64> {
+        let leading_text = vec![format!("{}: pick one to go to it", desc_in)];
+        let answer = self
+            .ui
+            .ask_which(Some(leading_text), &Vec::new(), &names_in.to_vec(), true, None, None, None, None);
+        answer.and_then(|a| ids_in.get(a.wrapping_sub(1)).copied())
+    }
+
+    /// Asks for another entity and the limits of the search, then shows the shortest paths from
+    /// entity_in to it (see path_finder.rs).  The user can pick an entity along a path to go to
+    /// it, which is returned.
+    pub fn path_finder_menu(&self, entity_in: &mut Entity) -> Result<Option<Entity>, anyhow::Error> {
+        let db = entity_in.get_db();
+        let Some((target, _, target_name)) = self.choose_or_create_object(
+            db.clone(),
+            Some(vec!["CHOOSE THE ENTITY TO FIND HOW THE CURRENT ONE IS CONNECTED TO:"]),
+            None,
+            None,
+            Util::ENTITY_TYPE,
+            0,
+            None,
+            false,
+            None,
+            false,
+            None,
+            false,
+        )?
+        else {
+            return Ok(None);
+        };
+        let name_of = |id: i64| -> String {
+            match db.borrow().get_entity_name(None, id) {
+                Ok(Some(name)) => name,
+                _ => format!("#{}", id),
+            }
+        };
+        let mut options = PathOptions::default();
+        loop {
+            let rel_types_desc = if options.rel_type_ids.is_empty() {
+                "all".to_string()
+            } else {
+                options
+                    .rel_type_ids
+                    .iter()
+                    .map(|id| format!("\"{}\"", name_of(*id)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+            let leading_text = vec![format!(
+                "Find how \"{}\" is connected to \"{}\":",
+                entity_in.get_name(None)?,
+                target_name
+            )];
+            let choices: Vec<String> = vec![
+                "Find the shortest paths".to_string(),
+                format!("Change the most steps in a path (now {})", options.max_depth),
+                format!("Follow only certain relation types: add one (now following {})", rel_types_desc),
+                "Follow all relation types".to_string(),
+                format!(
+                    "{} links from entities to the types of their attributes",
+                    if options.include_attribute_types {
+                        "Stop following"
+                    } else {
+                        "Also follow"
+                    }
+                ),
+            ];
+            let Some(answer) = self.ui.ask_which(Some(leading_text), &choices, &Vec::new(), true, None, None, None, None)
+            else {
+                return Ok(None);
+            };
+            match answer {
+                1 => {
+                    let paths = db
+                        .borrow()
+                        .find_paths_between_entities(None, entity_in.get_id(), target.get_id(), &options)?;
+                    if paths.is_empty() {
+                        self.ui.display_text1(&format!(
+                            "No path of up to {} steps was found, with those limits.",
+                            options.max_depth
+                        ));
+                    } else if let Some(id) = self.choose_entity_on_paths(&paths, &name_of) {
+                        return Ok(Some(Entity::new2(db.clone(), None, id)?));
+                    }
+                }
+                2 => {
+                    let Some(answer) = self.ui.ask_for_string3(
+                        vec!["Enter the most steps a path can have (more takes longer on a large database):"],
+                        Some(|s: &str| match s.trim().parse::<usize>() {
+                            Ok(n) if n > 0 => Ok(()),
+                            _ => Err(anyhow!("Enter a whole number above 0.")),
+                        }),
+                        options.max_depth.to_string(),
+                    ) else {
+                        continue;
+                    };
+                    options.max_depth = answer.trim().parse::<usize>().unwrap_or(options.max_depth);
+                }
+                3 => {
+                    if let Some((rel_type, _, _)) = self.choose_or_create_object(
+                        db.clone(),
+                        Some(vec!["CHOOSE A RELATION TYPE TO FOLLOW:"]),
+                        None,
+                        None,
+                        Util::RELATION_TYPE_TYPE,
+                        0,
+                        None,
+                        false,
+                        None,
+                        false,
+                        None,
+                        false,
+                    )? {
+                        if !options.rel_type_ids.contains(&rel_type.get_id()) {
+                            options.rel_type_ids.push(rel_type.get_id());
+                        }
+                    }
+                }
+                4 => options.rel_type_ids.clear(),
+                5 => options.include_attribute_types = !options.include_attribute_types,
+                _ => self.ui.display_text1("invalid response"),
+            }
+        }
+    }
+
+    /// Shows paths_in (picking one first if there are several) as a chain of entities, and
+    /// returns the id of the one the user picks, if any.
+    fn choose_entity_on_paths(&self, paths_in: &[Vec<PathLink>], name_of: &dyn Fn(i64) -> String) -> Option<i64> {
+        let path = if paths_in.len() == 1 {
+            &paths_in[0]
+        } else {
+            let descs: Vec<String> = paths_in.iter().map(|p| path_finder::describe_path(p, name_of)).collect();
+            let leading_text = vec![format!(
+                "{} shortest paths were found ({} steps each).  Pick one to see it:",
+                paths_in.len(),
+                paths_in[0].len()
+            )];
+            let answer = self.ui.ask_which(Some(leading_text), &Vec::new(), &descs, true, None, None, None, None)?;
+            paths_in.get(answer.wrapping_sub(1))?
+        };
+        let mut ids: Vec<i64> = path.first().map(|l| l.from_entity_id).into_iter().collect();
+        let mut steps: Vec<String> = ids.iter().map(|id| name_of(*id)).collect();
+        for link in path {
+            ids.push(link.to_entity_id);
+            steps.push(format!("...{} {}", link.describe(name_of), name_of(link.to_entity_id)));
+        }
+        let leading_text = vec![format!(
+            "The path ({} steps).  Pick an entity on it to go to it:",
+            path.len()
+        )];
+        let answer = self.ui.ask_which(Some(leading_text), &Vec::new(), &steps, true, None, None, None, None)?;
+        ids.get(answer.wrapping_sub(1)).copied()
+    }
+
+    /// Notes that the user is at location_in (see navigation_history.rs), and saves the history
+    /// if that changed it, so OM can reopen there next time.  Arriving at an entity also counts
+    /// as a view of it, for the recently and frequently viewed lists.  (The main menu records
+    /// entities, and group_entries_menu groups.)
+    pub fn record_visit(&self, location_in: Location) -> Result<(), anyhow::Error> {
+        if self.navigation.borrow_mut().visit(location_in) {
+            self.db.borrow().save_navigation_history(None, &self.navigation.borrow())?;
+            if let Location::Entity(id) = location_in {
+                self.db.borrow().record_entity_view(None, id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like record_visit, for the main menu showing entity_in.
+    pub fn record_entity_visit(&self, entity_in: &Entity) -> Result<(), anyhow::Error> {
+        self.record_visit(Location::Entity(entity_in.get_id()))
+    }
+
+    /// Lists the entries of the group group_id_in, to pick one to go to (which is returned).  It
+    /// is a simple stand-in for the group menus until they are ported from Scala, and like them
+    /// records the group as a place visited, so it is in the history and breadcrumbs.
+    pub fn group_entries_menu(&self, group_id_in: i64) -> Result<Option<Entity>, anyhow::Error> {
+        self.record_visit(Location::Group(group_id_in))?;
+        let name = Group::new2(self.db.clone(), None, group_id_in)?.get_name(None)?;
+        let mut starting_index: i64 = 0;
+        loop {
+            let mut leading_text = vec![format!("Group \"{}\": pick an entry to go to it.", name)];
+            if let Some(breadcrumbs) = self.get_breadcrumbs_text() {
+                leading_text.push(breadcrumbs);
+            }
+            let choices = vec![Util::LIST_NEXT_ITEMS_PROMPT.to_string()];
+            let max_entries = self.ui.max_columnar_choices_to_display_after(
+                leading_text.len(),
+                choices.len(),
+                Util::max_name_length(),
+            )?;
+            let ids = self
+                .db
+                .borrow()
+                .get_group_entry_ids(None, group_id_in, starting_index, Some(max_entries))?;
+            if ids.is_empty() && starting_index == 0 {
+                self.ui.display_text1(&format!("The group \"{}\" is empty.", name));
+                return Ok(None);
+            }
+            let names: Vec<String> = ids.iter().map(|id| self.describe_location(Location::Entity(*id))).collect();
+            let Some(answer) = self.ui.ask_which(Some(leading_text), &choices, &names, true, None, None, None, None)
+            else {
+                return Ok(None);
+            };
+            if answer == 1 {
+                // (After the last page, back to the first.)
+                starting_index = if ids.len() as u64 == max_entries {
+                    starting_index + ids.len() as i64
+                } else {
+                    0
+                };
+            } else if answer > choices.len() && answer <= choices.len() + ids.len() {
+                return Entity::get_entity(self.db.clone(), None, ids[answer - choices.len() - 1]);
+            } else {
+                self.ui.display_text1("invalid response");
+            }
+        }
+    }
+
+    fn describe_location(&self, location_in: Location) -> String {
+        match location_in {
+            Location::Entity(id) => match self.db.borrow().get_entity_name(None, id) {
+                Ok(Some(name)) => name,
+                _ => format!("(missing entity #{})", id),
+            },
+            Location::Group(id) => match Group::new2(self.db.clone(), None, id).and_then(|mut g| g.get_name(None)) {
+                Ok(name) => format!("group \"{}\"", name),
+                _ => format!("(missing group #{})", id),
+            },
+        }
+    }
+
+    /// Like "You are here: A > B > C", for the last few places up to the current one, or None
+    /// before there is any trail (fewer than two places).
+    pub fn get_breadcrumbs_text(&self) -> Option<String> {
+        let history = self.navigation.borrow();
+        let crumbs = history.breadcrumbs(BREADCRUMB_COUNT);
+        if crumbs.len() < 2 {
+            return None;
+        }
+        let names: Vec<String> = crumbs.iter().map(|l| self.describe_location(*l)).collect();
+        let more = if history.current_index() >= Some(BREADCRUMB_COUNT) {
+            "... > "
+        } else {
+            ""
+        };
+        Some(format!("You are here: {}{}", more, names.join(" > ")))
+    }
+
+    /// For a menu choice: like "Go back (to X)", or with "(nowhere to go back to)".
+    pub fn get_back_or_forward_choice_text(&self, forward_in: bool) -> String {
+        let history = self.navigation.borrow();
+        let (step, to) = if forward_in {
+            ("forward", history.peek_forward())
+        } else {
+            ("back", history.peek_back())
+        };
+        match to {
+            Some(location) => format!("Go {} (to {})", step, self.describe_location(location)),
+            None => format!("Go {} (nowhere to go {} to)", step, step),
+        }
+    }
+
+    /// Moves back (or forward) one place in the navigation history, and returns the entity to
+    /// show for it (see entity_for_location), or None if there was nowhere to go.
+    pub fn go_back_or_forward(&self, forward_in: bool) -> Result<Option<Entity>, anyhow::Error> {
+        let moved_to = {
+            let mut history = self.navigation.borrow_mut();
+            if forward_in {
+                history.forward()
+            } else {
+                history.back()
+            }
+        };
+        match moved_to {
+            None => {
+                self.ui.display_text1(if forward_in {
+                    "There is nowhere to go forward to."
+                } else {
+                    "There is nowhere to go back to."
+                });
+                Ok(None)
+            }
+            Some(location) => {
+                self.db.borrow().save_navigation_history(None, &self.navigation.borrow())?;
+                self.entity_for_location(location)
+            }
+        }
+    }
+
+    /// Lists the navigation history (newest first, marking where the user is), and goes to the
+    /// place picked, keeping those after it for going forward again.  Returns the entity to show
+    /// for it, if any.
+    pub fn navigation_history_menu(&self) -> Result<Option<Entity>, anyhow::Error> {
+        let (locations, current) = {
+            let history = self.navigation.borrow();
+            (history.locations().to_vec(), history.current_index())
+        };
+        if locations.is_empty() {
+            self.ui.display_text1("There is no history yet.");
+            return Ok(None);
+        }
+        let names: Vec<String> = locations
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, l)| {
+                let here = if Some(i) == current { " (you are here)" } else { "" };
+                format!("{}{}", self.describe_location(*l), here)
+            })
+            .collect();
+        let leading_text = vec!["Places visited, most recent first.  Pick one to go back to it:".to_string()];
+        let Some(answer) = self.ui.ask_which(Some(leading_text), &Vec::new(), &names, true, None, None, None, None)
+        else {
+            return Ok(None);
+        };
+        let Some(index) = locations.len().checked_sub(answer) else {
+            self.ui.display_text1("invalid response");
+            return Ok(None);
+        };
+        let Some(location) = self.navigation.borrow_mut().go_to(index) else {
+            return Ok(None);
+        };
+        self.db.borrow().save_navigation_history(None, &self.navigation.borrow())?;
+        self.entity_for_location(location)
+    }
+
+    /// The entity to show for location_in: the entity itself, or for a group, the entry picked
+    /// from it (see group_entries_menu), else an entity containing it (asking which, if several).
+    /// None if it no longer exists.
+    pub fn entity_for_location(&self, location_in: Location) -> Result<Option<Entity>, anyhow::Error> {
+        match location_in {
+            Location::Entity(id) => Entity::get_entity(self.db.clone(), None, id),
+            Location::Group(group_id) => {
+                if !self.db.borrow().group_key_exists(None, group_id)? {
+                    self.ui.display_text1("That group no longer exists.");
+                    return Ok(None);
+                }
+                if let Some(entry) = self.group_entries_menu(group_id)? {
+                    return Ok(Some(entry));
+                }
+                let containing_ids: Vec<i64> = self
+                    .db
+                    .borrow()
+                    .get_entities_containing_group(None, group_id, 0, None)?
+                    .into_iter()
+                    .map(|(_, id)| id)
+                    .collect();
+                let chosen_id = match containing_ids.len() {
+                    0 => {
+                        self.ui.display_text1(Util::ORPHANED_GROUP_MESSAGE);
+                        None
+                    }
+                    1 => Some(containing_ids[0]),
+                    _ => {
+                        let names: Vec<String> =
+                            containing_ids.iter().map(|id| self.describe_location(Location::Entity(*id))).collect();
+                        self.choose_backlink("Entities containing the group", &containing_ids, &names)
+                    }
+                };
+                match chosen_id {
+                    Some(id) => Entity::get_entity(self.db.clone(), None, id),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    /// Lists the bookmarks (see postgresql_bookmarks.rs) to go to one, which is returned, and
+    /// lets the user bookmark entity_in or remove its bookmark.
+    pub fn bookmarks_menu(&self, entity_in: &mut Entity) -> Result<Option<Entity>, anyhow::Error> {
+        let db = entity_in.get_db();
+        loop {
+            let bookmarks = db.borrow().get_bookmarked_entity_ids(None)?;
+            let is_bookmarked = bookmarks.contains(&entity_in.get_id());
+            let name = entity_in.get_name(None)?;
+            let toggle = if is_bookmarked {
+                format!("Remove the bookmark to the current entity (\"{}\")", name)
+            } else {
+                format!("Bookmark the current entity (\"{}\")", name)
+            };
+            let names: Vec<String> = bookmarks
+                .iter()
+                .map(|id| self.describe_location(Location::Entity(*id)))
+                .collect();
+            let leading_text = vec![if bookmarks.is_empty() {
+                "Bookmarks: there are none yet.".to_string()
+            } else {
+                format!(
+                    "Bookmarks: pick one to go to it.  (They are kept under \"{}\" in \"{}\", where they can be reordered.)",
+                    Util::BOOKMARKS_PREFERENCE,
+                    Util::USER_PREFERENCES
+                )
+            }];
+            let Some(answer) = self.ui.ask_which(Some(leading_text), &vec![toggle], &names, true, None, None, None, None)
+            else {
+                return Ok(None);
+            };
+            if answer == 1 {
+                if is_bookmarked {
+                    db.borrow().remove_bookmark(None, entity_in.get_id())?;
+                } else {
+                    db.borrow().add_bookmark(None, entity_in.get_id())?;
+                }
+            } else if let Some(id) = bookmarks.get(answer.wrapping_sub(2)) {
+                return Entity::get_entity(db.clone(), None, *id);
+            } else {
+                self.ui.display_text1("invalid response");
+            }
+        }
+    }
+
+    /// Lists the most recently viewed entities, or the most often viewed, to go to one, which is
+    /// returned.
+    pub fn viewed_entities_menu(&self) -> Result<Option<Entity>, anyhow::Error> {
+        let mut by_count = false;
+        loop {
+            let viewed = self.db.borrow().get_viewed_entities(None, by_count, VIEWED_ENTITIES_COUNT)?;
+            if viewed.is_empty() {
+                self.ui.display_text1("No entities have been viewed yet.");
+                return Ok(None);
+            }
+            let names: Vec<String> = viewed
+                .iter()
+                .map(|(id, count)| {
+                    let times = if *count == 1 { "time" } else { "times" };
+                    format!("{} (viewed {} {})", self.describe_location(Location::Entity(*id)), count, times)
+                })
+                .collect();
+            let (leading_text, switch) = if by_count {
+                ("Most often viewed entities.  Pick one to go to it:", "Show the most recently viewed instead")
+            } else {
+                ("Most recently viewed entities.  Pick one to go to it:", "Show the most often viewed instead")
+            };
+            let Some(answer) = self.ui.ask_which(
+                Some(vec![leading_text.to_string()]),
+                &vec![switch.to_string()],
+                &names,
+                true,
+                None,
+                None,
+                None,
+                None,
+            ) else {
+                return Ok(None);
+            };
+            if answer == 1 {
+                by_count = !by_count;
+            } else if let Some((id, _)) = viewed.get(answer.wrapping_sub(2)) {
+                return Entity::get_entity(self.db.clone(), None, *id);
+            } else {
+                self.ui.display_text1("invalid response");
+            }
+        }
+    }
 }
+
+/// How many places the breadcrumbs show (see get_breadcrumbs_text).
+const BREADCRUMB_COUNT: usize = 5;
+/// How many entities the recently and frequently viewed lists show.
+const VIEWED_ENTITIES_COUNT: usize = 20;
+
+const CLASS_ACTION_HELP: &str = "A class action is a short script, one statement per line (# starts a comment):\n\
+    \x20 set \"<attribute type name>\" = <expression>   (updates that attribute; or adds it, like the class's template has it)\n\
+    \x20 if <expression> then <statement>\n\
+    \x20 say <expression>   (shown after the action runs)\n\
+    \x20 stop\n\
+    Expressions can use numbers, \"text\", true, false, today, now, name (the entity's), durations like 3 days \
+    (or minutes, hours, weeks), get(\"<type name>\"), has(\"<type name>\"), date(\"2024-05-31\"), \
+    + - * /, = != < <= > >=, and, or, not, and parentheses.  For example:\n\
+    \x20 set \"due\" = get(\"due\") + 1 week\n\
+    \x20 if not get(\"done\") then set \"done\" = true\n\
+    Actions can only change the attributes of the entity they are run on, and if one fails partway, \
+    nothing is changed.";
@@ -0,0 +1,202 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Writes a group whose entries all have one class (i.e., mixed classes are not allowed in
+//! it) as a table for a spreadsheet or pandas: a row per entry, and after the name a column
+//! per quantity, date, boolean or text attribute type on the class's template entity (the
+//! same columns csv_import.rs reads, so the file can be imported again).  Quantities are
+//! written with their units, as "70 kg", and dates in ISO 8601.  An entry with more than
+//! one attribute of a column's type gets all of them, joined by a separator the user chooses.
+use crate::controllers::csv_import::{self, TemplateAttributeForm, TemplateAttributeType};
+use crate::model::boolean_attribute::BooleanAttribute;
+use crate::model::database::Database;
+use crate::model::date_attribute::DateAttribute;
+use crate::model::entity::Entity;
+use crate::model::group::Group;
+use crate::model::quantity_attribute::QuantityAttribute;
+use crate::model::text_attribute::TextAttribute;
+use anyhow::anyhow;
+use chrono::{TimeZone, Timelike, Utc};
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Reads the entries of group_id_in, in the group's order.  It is an error if the group
+/// allows mixed classes.  If its entries have no class, the table has only their names.
+pub fn read_group_table(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    group_id_in: i64,
+    multi_value_separator_in: &str,
+) -> Result<GroupTable, anyhow::Error> {
+    let mut group = Group::new2(db.clone(), transaction.clone(), group_id_in)?;
+    if group.get_mixed_classes_allowed(transaction.clone())? {
+        return Err(anyhow!(
+            "The group \"{}\" allows entries of mixed classes, so it has no one set of columns to export.",
+            group.get_name(transaction.clone())?
+        ));
+    }
+    let types: Vec<TemplateAttributeType> = match group.get_class_id(transaction.clone())? {
+        Some(class_id) => csv_import::read_template_attribute_types(db.clone(), transaction.clone(), class_id)?,
+        None => Vec::new(),
+    };
+    let mut headers = vec!["name".to_string()];
+    headers.extend(types.iter().map(|t| t.name.clone()));
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let entry_ids = db.borrow().get_group_entry_ids(transaction.clone(), group_id_in, 0, None)?;
+    for entry_id in entry_ids {
+        let mut entity = Entity::new2(db.clone(), transaction.clone(), entry_id)?;
+        let mut cells: Vec<Vec<String>> = vec![Vec::new(); types.len()];
+        let (attribute_tuples, _) = entity.get_sorted_attributes(transaction.clone(), 0, 0, false)?;
+        for (_, attribute) in attribute_tuples {
+            let mut attribute = attribute.borrow_mut();
+            let attr_type_id = attribute.get_attr_type_id(transaction.clone())?;
+            let any = attribute.as_any_mut();
+            let (is_form, value): (fn(&TemplateAttributeForm) -> bool, String) =
+                if let Some(quantity) = any.downcast_mut::<QuantityAttribute>() {
+                    let unit_id = quantity.get_unit_id(transaction.clone())?;
+                    let unit_name =
+                        Entity::new2(db.clone(), transaction.clone(), unit_id)?.get_name(transaction.clone())?;
+                    (
+                        |f| matches!(f, TemplateAttributeForm::Quantity { .. }),
+                        format!("{} {}", quantity.get_number(transaction.clone())?, unit_name),
+                    )
+                } else if let Some(date) = any.downcast_mut::<DateAttribute>() {
+                    (
+                        |f| *f == TemplateAttributeForm::Date,
+                        format_iso_date(date.get_date(transaction.clone())?),
+                    )
+                } else if let Some(boolean) = any.downcast_mut::<BooleanAttribute>() {
+                    (
+                        |f| *f == TemplateAttributeForm::Boolean,
+                        boolean.get_boolean(transaction.clone())?.to_string(),
+                    )
+                } else if let Some(text) = any.downcast_mut::<TextAttribute>() {
+                    (
+                        |f| *f == TemplateAttributeForm::Text,
+                        text.get_text(transaction.clone())?,
+                    )
+                } else {
+                    continue;
+                };
+            if let Some(i) = types
+                .iter()
+                .position(|t| t.attr_type_id == attr_type_id && is_form(&t.form))
+            {
+                cells[i].push(value);
+            }
+        }
+        let mut row = vec![entity.get_name(transaction.clone())?];
+        row.extend(cells.iter().map(|values| values.join(multi_value_separator_in)));
+        rows.push(row);
+    }
+    Ok(GroupTable { headers, rows })
+}
+
+/// Like "2024-05-31", or "2024-05-31T14:30:00" if not at midnight (UTC, as dates are stored).
+pub fn format_iso_date(millis_in: i64) -> String {
+    match Utc.timestamp_millis_opt(millis_in).single() {
+        Some(date_time) if date_time.num_seconds_from_midnight() == 0 => date_time.format("%Y-%m-%d").to_string(),
+        Some(date_time) => date_time.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        None => millis_in.to_string(),
+    }
+}
+
+/// Quotes field_in if needed, as csv_import::parse_delimited expects (per RFC 4180).
+pub fn quote_field(field_in: &str, delimiter_in: char) -> String {
+    if field_in.contains(delimiter_in) || field_in.contains('"') || field_in.contains('\n') || field_in.contains('\r') {
+        format!("\"{}\"", field_in.replace('"', "\"\""))
+    } else {
+        field_in.to_string()
+    }
+}
+
+pub fn to_delimited(table_in: &GroupTable, delimiter_in: char) -> String {
+    let mut out = String::new();
+    for record in std::iter::once(&table_in.headers).chain(table_in.rows.iter()) {
+        let fields: Vec<String> = record.iter().map(|f| quote_field(f, delimiter_in)).collect();
+        out.push_str(&fields.join(&delimiter_in.to_string()));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::controllers::csv_import::parse_delimited;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+    use crate::util::Util;
+
+    #[test]
+    fn test_to_delimited() {
+        let table = GroupTable {
+            headers: vec!["name".to_string(), "notes".to_string()],
+            rows: vec![vec!["Ann".to_string(), "says \"hi\", twice".to_string()]],
+        };
+        let csv = to_delimited(&table, ',');
+        assert_eq!(csv, "name,notes\nAnn,\"says \"\"hi\"\", twice\"\n");
+        assert_eq!(parse_delimited(&csv, ','), vec![table.headers.clone(), table.rows[0].clone()]);
+        assert_eq!(to_delimited(&table, '\t'), "name\tnotes\nAnn\t\"says \"\"hi\"\", twice\"\n");
+        assert_eq!(format_iso_date(981158400000), "2001-02-03");
+        assert_eq!(format_iso_date(981158400000 + 3_600_000), "2001-02-03T01:00:00");
+    }
+
+    #[test]
+    fn test_read_group_table() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let (class_id, template_id) = db_b.create_class_and_its_template_entity(tx.clone(), "csv export book").unwrap();
+        let pages_type = db_b.create_entity(tx.clone(), "pages", None, None).unwrap();
+        let page_unit = db_b.create_entity(tx.clone(), "page", None, None).unwrap();
+        let author_type = db_b.create_entity(tx.clone(), "author", None, None).unwrap();
+        let read_type = db_b.create_entity(tx.clone(), "read on", None, None).unwrap();
+        db_b.create_quantity_attribute(tx.clone(), template_id, pages_type, page_unit, 0.0, None, 0, None)
+            .unwrap();
+        db_b.create_text_attribute(tx.clone(), template_id, author_type, "", None, 0, None)
+            .unwrap();
+        db_b.create_date_attribute(tx.clone(), template_id, read_type, 0, None).unwrap();
+        let group_id = db_b.create_group(tx.clone(), "csv export books", false).unwrap();
+        let book = db_b.create_entity(tx.clone(), "Good Omens", Some(class_id), None).unwrap();
+        db_b.create_quantity_attribute(tx.clone(), book, pages_type, page_unit, 412.0, None, 0, None)
+            .unwrap();
+        db_b.create_text_attribute(tx.clone(), book, author_type, "Pratchett", None, 0, None)
+            .unwrap();
+        db_b.create_text_attribute(tx.clone(), book, author_type, "Gaiman", None, 0, None)
+            .unwrap();
+        db_b.create_date_attribute(tx.clone(), book, read_type, 981158400000, None).unwrap();
+        let other_book = db_b.create_entity(tx.clone(), "Dune", Some(class_id), None).unwrap();
+        db_b.add_entity_to_group(tx.clone(), group_id, book, None).unwrap();
+        db_b.add_entity_to_group(tx.clone(), group_id, other_book, None).unwrap();
+
+        let table = read_group_table(db_dyn.clone(), tx.clone(), group_id, " | ").unwrap();
+        assert_eq!(table.headers, vec!["name", "pages", "author", "read on"]);
+        assert_eq!(table.rows.len(), 2);
+        let good_omens = table.rows.iter().find(|r| r[0] == "Good Omens").unwrap();
+        assert_eq!(good_omens[1], "412 page");
+        assert!(good_omens[2] == "Pratchett | Gaiman" || good_omens[2] == "Gaiman | Pratchett");
+        assert_eq!(good_omens[3], "2001-02-03");
+        let dune = table.rows.iter().find(|r| r[0] == "Dune").unwrap();
+        assert_eq!(dune[1..], ["", "", ""]);
+
+        let mixed_group_id = db_b.create_group(tx.clone(), "csv export mixed", true).unwrap();
+        assert!(read_group_table(db_dyn, tx.clone(), mixed_group_id, ";").is_err());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
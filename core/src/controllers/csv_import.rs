@@ -0,0 +1,522 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Imports the rows of a CSV (or tab-separated) file as entities of one class, put into a group.
+//! The columns are mapped to the attribute types on the class's template entity (the same
+//! ones Controller::default_attribute_copying offers to copy), and the values parsed according
+//! to the form of the template's attribute: quantity (in the template's unit), date, boolean
+//! or text.  One column gives each entity its name.
+//!
+//! plan_import does all the parsing without changing anything, so its result can be shown as a
+//! preview (with each bad row's problems) before import_planned_rows writes the good rows.
+use crate::model::boolean_attribute::BooleanAttribute;
+use crate::model::database::Database;
+use crate::model::date_attribute::DateAttribute;
+use crate::model::entity::Entity;
+use crate::model::entity_query::parse_query_date;
+use crate::model::quantity_attribute::QuantityAttribute;
+use crate::model::text_attribute::TextAttribute;
+use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Splits text_in into records of fields, as RFC 4180 describes: fields containing the
+/// delimiter, quotes or line breaks are quoted, with quotes inside doubled.
+pub fn parse_delimited(text_in: &str, delimiter_in: char) -> Vec<Vec<String>> {
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text_in.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == '"' && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter_in {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            record.push(std::mem::take(&mut field));
+            records.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateAttributeForm {
+    Quantity { unit_id: i64, unit_name: String },
+    Date,
+    Boolean,
+    Text,
+}
+
+/// An attribute type found on a class's template entity, which a column can be mapped to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateAttributeType {
+    pub attr_type_id: i64,
+    pub name: String,
+    pub form: TemplateAttributeForm,
+}
+
+/// The quantity, date, boolean and text attribute types on the template entity of
+/// class_id_in, in the template's order, then those on its ancestors' templates that it doesn't
+/// already have.  (Relations on the template are left out, since a cell of text can't say
+/// which entity to relate to.)
+pub fn read_template_attribute_types(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    class_id_in: i64,
+) -> Result<Vec<TemplateAttributeType>, anyhow::Error> {
+    let lineage = db.borrow().get_class_lineage(transaction.clone(), class_id_in)?;
+    let mut types: Vec<TemplateAttributeType> = Vec::new();
+    for (_, template_id) in lineage {
+        let count_from_nearer_templates = types.len();
+        let template = Entity::new2(db.clone(), transaction.clone(), template_id)?;
+        let (attribute_tuples, _) = template.get_sorted_attributes(transaction.clone(), 0, 0, false)?;
+        for (_, attribute) in attribute_tuples {
+            let mut attribute = attribute.borrow_mut();
+            let attr_type_id = attribute.get_attr_type_id(transaction.clone())?;
+            if types[..count_from_nearer_templates].iter().any(|t| t.attr_type_id == attr_type_id) {
+                continue;
+            }
+            let any = attribute.as_any_mut();
+            let form = if let Some(quantity) = any.downcast_mut::<QuantityAttribute>() {
+                let unit_id = quantity.get_unit_id(transaction.clone())?;
+                let unit_name = Entity::new2(db.clone(), transaction.clone(), unit_id)?.get_name(transaction.clone())?;
+                TemplateAttributeForm::Quantity { unit_id, unit_name }
+            } else if any.downcast_mut::<DateAttribute>().is_some() {
+                TemplateAttributeForm::Date
+            } else if any.downcast_mut::<BooleanAttribute>().is_some() {
+                TemplateAttributeForm::Boolean
+            } else if any.downcast_mut::<TextAttribute>().is_some() {
+                TemplateAttributeForm::Text
+            } else {
+                continue;
+            };
+            if types.iter().any(|t| t.attr_type_id == attr_type_id && t.form == form) {
+                continue;
+            }
+            let name = Entity::new2(db.clone(), transaction.clone(), attr_type_id)?.get_name(transaction.clone())?;
+            types.push(TemplateAttributeType {
+                attr_type_id,
+                name,
+                form,
+            });
+        }
+    }
+    Ok(types)
+}
+
+/// What a column is imported as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnTarget {
+    Ignore,
+    Name,
+    /// An index into the template attribute types.
+    Attribute(usize),
+}
+
+/// Maps a "name" column to the entity name and others to the template attribute types of the
+/// same name (ignoring case); if no column is called "name", the first unmapped one is used.
+pub fn guess_column_targets(headers_in: &[String], types_in: &[TemplateAttributeType]) -> Vec<ColumnTarget> {
+    let mut targets: Vec<ColumnTarget> = headers_in
+        .iter()
+        .map(|header| {
+            let header = header.trim();
+            match types_in.iter().position(|t| t.name.eq_ignore_ascii_case(header)) {
+                Some(i) => ColumnTarget::Attribute(i),
+                None => ColumnTarget::Ignore,
+            }
+        })
+        .collect();
+    let name_column = headers_in
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case("name"))
+        .or_else(|| targets.iter().position(|t| *t == ColumnTarget::Ignore));
+    if let Some(i) = name_column {
+        targets[i] = ColumnTarget::Name;
+    }
+    targets
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvValue {
+    Quantity(f64),
+    /// Milliseconds, UTC.
+    Date(i64),
+    Boolean(bool),
+    Text(String),
+}
+
+/// Accepts what parse_query_date does (like "2024-05-31" or "2024-05-31 14:30"), and also
+/// "2024/05/31" and "05/31/2024" as spreadsheets often write them.
+pub fn parse_csv_date(text_in: &str) -> Result<i64, anyhow::Error> {
+    if let Ok((millis, _)) = parse_query_date(text_in) {
+        return Ok(millis);
+    }
+    for format in ["%Y/%m/%d", "%m/%d/%Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(text_in, format) {
+            if let Some(date_time) = date.and_hms_opt(0, 0, 0) {
+                return Ok(date_time.timestamp_millis());
+            }
+        }
+    }
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(text_in, "%Y-%m-%dT%H:%M:%S") {
+        return Ok(date_time.timestamp_millis());
+    }
+    Err(anyhow!(
+        "\"{}\" is not a date (expected like 2024-05-31, 2024-05-31 14:30, 2024/05/31 or 05/31/2024)",
+        text_in
+    ))
+}
+
+/// Returns None for a blank cell, which adds no attribute.
+pub fn parse_value(form_in: &TemplateAttributeForm, text_in: &str) -> Result<Option<CsvValue>, anyhow::Error> {
+    let text = text_in.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let value = match form_in {
+        TemplateAttributeForm::Quantity { unit_name, .. } => {
+            // (Allowing the unit to be repeated, as in "70 kg".)
+            let number = text.strip_suffix(unit_name.as_str()).unwrap_or(text).trim();
+            match number.parse::<f64>() {
+                Ok(n) if n.is_finite() => CsvValue::Quantity(n),
+                _ => return Err(anyhow!("\"{}\" is not a number (of {})", text, unit_name)),
+            }
+        }
+        TemplateAttributeForm::Date => CsvValue::Date(parse_csv_date(text)?),
+        TemplateAttributeForm::Boolean => match text.to_lowercase().as_str() {
+            "y" | "yes" | "true" | "t" | "1" => CsvValue::Boolean(true),
+            "n" | "no" | "false" | "f" | "0" => CsvValue::Boolean(false),
+            _ => return Err(anyhow!("\"{}\" is not yes/no (or true/false, 1/0)", text)),
+        },
+        TemplateAttributeForm::Text => CsvValue::Text(text_in.to_string()),
+    };
+    Ok(Some(value))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedRow {
+    /// Counting the header as record 1, as a spreadsheet numbers its rows.
+    pub record_number: usize,
+    pub name: String,
+    /// (index into the template attribute types, value)
+    pub values: Vec<(usize, CsvValue)>,
+    /// If any, the row is not imported.
+    pub errors: Vec<String>,
+}
+
+/// Parses data_rows_in (the records after the header) without changing anything.  Blank rows
+/// are left out.
+pub fn plan_import(
+    data_rows_in: &[Vec<String>],
+    targets_in: &[ColumnTarget],
+    types_in: &[TemplateAttributeType],
+) -> Vec<PlannedRow> {
+    let mut planned: Vec<PlannedRow> = Vec::new();
+    for (i, row) in data_rows_in.iter().enumerate() {
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let mut planned_row = PlannedRow {
+            record_number: i + 2,
+            name: String::new(),
+            values: Vec::new(),
+            errors: Vec::new(),
+        };
+        if row.len() > targets_in.len() {
+            planned_row.errors.push(format!(
+                "has {} fields but the header has {}",
+                row.len(),
+                targets_in.len()
+            ));
+        }
+        for (field, target) in row.iter().zip(targets_in) {
+            match target {
+                ColumnTarget::Ignore => {}
+                ColumnTarget::Name => planned_row.name = field.trim().to_string(),
+                ColumnTarget::Attribute(type_index) => match parse_value(&types_in[*type_index].form, field) {
+                    Ok(Some(value)) => planned_row.values.push((*type_index, value)),
+                    Ok(None) => {}
+                    Err(e) => planned_row.errors.push(format!("{}: {}", types_in[*type_index].name, e)),
+                },
+            }
+        }
+        if planned_row.name.is_empty() {
+            planned_row.errors.push("has no name".to_string());
+        }
+        planned.push(planned_row);
+    }
+    planned
+}
+
+/// One line for the preview.
+pub fn describe_planned_row(row_in: &PlannedRow, types_in: &[TemplateAttributeType]) -> String {
+    if !row_in.errors.is_empty() {
+        return format!("Row {}: NOT IMPORTED: {}", row_in.record_number, row_in.errors.join("; "));
+    }
+    let values: Vec<String> = row_in
+        .values
+        .iter()
+        .map(|(type_index, value)| {
+            let attribute_type = &types_in[*type_index];
+            let shown = match (value, &attribute_type.form) {
+                (CsvValue::Quantity(n), TemplateAttributeForm::Quantity { unit_name, .. }) => {
+                    format!("{} {}", n, unit_name)
+                }
+                (CsvValue::Date(millis), _) => Utc
+                    .timestamp_millis_opt(*millis)
+                    .single()
+                    .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| millis.to_string()),
+                (CsvValue::Boolean(b), _) => b.to_string(),
+                (CsvValue::Text(t), _) => t.clone(),
+                (CsvValue::Quantity(n), _) => n.to_string(),
+            };
+            format!("{}: {}", attribute_type.name, shown)
+        })
+        .collect();
+    format!("Row {}: \"{}\" {}", row_in.record_number, row_in.name, values.join(", "))
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CsvImportCounts {
+    pub entities: usize,
+    pub attributes: usize,
+    pub skipped_rows: usize,
+}
+
+/// Creates an entity of class_id_in in group_id_in for each of rows_in without errors, with
+/// its values as attributes.
+#[allow(clippy::too_many_arguments)]
+pub fn import_planned_rows(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    class_id_in: i64,
+    group_id_in: i64,
+    rows_in: &[PlannedRow],
+    types_in: &[TemplateAttributeType],
+    make_them_public_in: Option<bool>,
+    observation_date_in: i64,
+) -> Result<CsvImportCounts, anyhow::Error> {
+    let mut counts = CsvImportCounts::default();
+    let db = db.borrow();
+    for row in rows_in {
+        if !row.errors.is_empty() {
+            counts.skipped_rows += 1;
+            continue;
+        }
+        let entity_id = db.create_entity(transaction.clone(), &row.name, Some(class_id_in), make_them_public_in)?;
+        db.add_entity_to_group(transaction.clone(), group_id_in, entity_id, None)?;
+        counts.entities += 1;
+        for (type_index, value) in &row.values {
+            let attribute_type = &types_in[*type_index];
+            let attr_type_id = attribute_type.attr_type_id;
+            match (value, &attribute_type.form) {
+                (CsvValue::Quantity(n), TemplateAttributeForm::Quantity { unit_id, .. }) => {
+                    db.create_quantity_attribute(
+                        transaction.clone(),
+                        entity_id,
+                        attr_type_id,
+                        *unit_id,
+                        *n,
+                        None,
+                        observation_date_in,
+                        None,
+                    )?;
+                }
+                (CsvValue::Date(date), _) => {
+                    db.create_date_attribute(transaction.clone(), entity_id, attr_type_id, *date, None)?;
+                }
+                (CsvValue::Boolean(b), _) => {
+                    db.create_boolean_attribute(
+                        transaction.clone(),
+                        entity_id,
+                        attr_type_id,
+                        *b,
+                        None,
+                        observation_date_in,
+                        None,
+                    )?;
+                }
+                (CsvValue::Text(t), _) => {
+                    db.create_text_attribute(
+                        transaction.clone(),
+                        entity_id,
+                        attr_type_id,
+                        t,
+                        None,
+                        observation_date_in,
+                        None,
+                    )?;
+                }
+                (CsvValue::Quantity(_), _) => {
+                    return Err(anyhow!("A quantity was planned for non-quantity type {}.", attribute_type.name))
+                }
+            }
+            counts.attributes += 1;
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+    use crate::util::Util;
+
+    #[test]
+    fn test_parse_delimited() {
+        let text = "name,notes\r\nAnn,\"says \"\"hi\"\", twice\"\n\"Bo\nb\",\n";
+        assert_eq!(
+            parse_delimited(text, ','),
+            vec![
+                vec!["name".to_string(), "notes".to_string()],
+                vec!["Ann".to_string(), "says \"hi\", twice".to_string()],
+                vec!["Bo\nb".to_string(), "".to_string()],
+            ]
+        );
+        assert_eq!(parse_delimited("a\tb", '\t'), vec![vec!["a".to_string(), "b".to_string()]]);
+    }
+
+    #[test]
+    fn test_plan_import() {
+        let types = vec![
+            TemplateAttributeType {
+                attr_type_id: 1,
+                name: "Weight".to_string(),
+                form: TemplateAttributeForm::Quantity {
+                    unit_id: 9,
+                    unit_name: "kg".to_string(),
+                },
+            },
+            TemplateAttributeType {
+                attr_type_id: 2,
+                name: "born".to_string(),
+                form: TemplateAttributeForm::Date,
+            },
+            TemplateAttributeType {
+                attr_type_id: 3,
+                name: "active".to_string(),
+                form: TemplateAttributeForm::Boolean,
+            },
+        ];
+        let records = parse_delimited("Name,weight,Born,active,other\nAnn,70 kg,2001-02-03,yes,x\n,,,,\nBo,heavy,3/4/2001,maybe\n,1\n", ',');
+        let targets = guess_column_targets(&records[0], &types);
+        assert_eq!(
+            targets,
+            vec![
+                ColumnTarget::Name,
+                ColumnTarget::Attribute(0),
+                ColumnTarget::Attribute(1),
+                ColumnTarget::Attribute(2),
+                ColumnTarget::Ignore
+            ]
+        );
+        let planned = plan_import(&records[1..], &targets, &types);
+        assert_eq!(planned.len(), 3);
+        assert_eq!(planned[0].record_number, 2);
+        assert_eq!(planned[0].name, "Ann");
+        assert_eq!(
+            planned[0].values,
+            vec![
+                (0, CsvValue::Quantity(70.0)),
+                (1, CsvValue::Date(981158400000)),
+                (2, CsvValue::Boolean(true))
+            ]
+        );
+        assert!(planned[0].errors.is_empty());
+        assert_eq!(planned[1].record_number, 4);
+        assert_eq!(planned[1].errors.len(), 2);
+        assert!(planned[1].errors[0].starts_with("Weight: \"heavy\" is not a number"));
+        assert_eq!(planned[1].values, vec![(1, CsvValue::Date(983664000000))]);
+        assert_eq!(planned[2].errors, vec!["has no name".to_string()]);
+        assert_eq!(
+            describe_planned_row(&planned[0], &types),
+            "Row 2: \"Ann\" Weight: 70 kg, born: 2001-02-03 00:00, active: true"
+        );
+        assert!(describe_planned_row(&planned[2], &types).starts_with("Row 5: NOT IMPORTED"));
+    }
+
+    #[test]
+    fn test_import_planned_rows() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let (class_id, template_id) = db_b.create_class_and_its_template_entity(tx.clone(), "csv book").unwrap();
+        let pages_type = db_b.create_entity(tx.clone(), "pages", None, None).unwrap();
+        let page_unit = db_b.create_entity(tx.clone(), "page", None, None).unwrap();
+        let author_type = db_b.create_entity(tx.clone(), "author", None, None).unwrap();
+        db_b.create_quantity_attribute(tx.clone(), template_id, pages_type, page_unit, 0.0, None, 0, None)
+            .unwrap();
+        db_b.create_text_attribute(tx.clone(), template_id, author_type, "", None, 0, None)
+            .unwrap();
+        let types = read_template_attribute_types(db_dyn.clone(), tx.clone(), class_id).unwrap();
+        assert_eq!(types.len(), 2);
+        assert_eq!(types[0].name, "pages");
+        assert_eq!(
+            types[0].form,
+            TemplateAttributeForm::Quantity {
+                unit_id: page_unit,
+                unit_name: "page".to_string()
+            }
+        );
+        assert_eq!(types[1].form, TemplateAttributeForm::Text);
+
+        let records = parse_delimited("title\tauthor\tpages\nDune\tHerbert\t412\nBad\tX\tmany\n", '\t');
+        let mut targets = guess_column_targets(&records[0], &types);
+        assert_eq!(targets[0], ColumnTarget::Name);
+        targets[2] = ColumnTarget::Attribute(0);
+        let planned = plan_import(&records[1..], &targets, &types);
+        let group_id = db_b.create_group(tx.clone(), "csv books", false).unwrap();
+        let counts =
+            import_planned_rows(db_dyn.clone(), tx.clone(), class_id, group_id, &planned, &types, None, 5).unwrap();
+        assert_eq!(
+            counts,
+            CsvImportCounts {
+                entities: 1,
+                attributes: 2,
+                skipped_rows: 1
+            }
+        );
+        let entry_ids = db_b.get_group_entry_ids(tx.clone(), group_id, 0, None).unwrap();
+        assert_eq!(entry_ids.len(), 1);
+        let mut dune = Entity::new2(db_dyn, tx.clone(), entry_ids[0]).unwrap();
+        assert_eq!(dune.get_name(tx.clone()).unwrap(), "Dune");
+        assert_eq!(dune.get_class_id(tx.clone()).unwrap(), Some(class_id));
+        assert_eq!(dune.get_attribute_count(tx.clone(), false).unwrap(), 2);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
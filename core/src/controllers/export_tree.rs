@@ -0,0 +1,356 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! The part of exporting that reads what is to be exported: an entity and what it contains,
+//! walked (as the HTML export in import_export.rs does) through get_sorted_attributes,
+//! RelationToLocalEntity targets and RelationToGroup contents, within a number of levels and
+//! with public/non-public filtering.  The exporters for each format then only write it out.
+//! A relation's own attributes (see Database::get_relation_attribute_holder_id) go with the
+//! item for the relation, as displayed, since the formats have no place to sort or compute on
+//! them.
+use crate::model::attribute::Attribute;
+use crate::model::database::Database;
+use crate::model::entity::Entity;
+use crate::model::group::Group;
+use crate::model::relation_to_entity::RelationToEntity;
+use crate::model::relation_to_group::RelationToGroup;
+use crate::model::relation_to_local_entity::RelationToLocalEntity;
+use crate::model::text_attribute::TextAttribute;
+use crate::util::Util;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::Rc;
+
+/// Which entities to export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    /// How many levels below the starting entity to include; None for all.
+    pub levels: Option<usize>,
+    pub include_public: bool,
+    pub include_non_public: bool,
+    /// For entities whose public status was never set.
+    pub include_unspecified: bool,
+}
+
+impl ExportOptions {
+    pub fn allows(&self, public_in: Option<bool>) -> bool {
+        match public_in {
+            Some(true) => self.include_public,
+            Some(false) => self.include_non_public,
+            None => self.include_unspecified,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportItem {
+    /// Any attribute not covered below, as displayed (like "weight: 70 kg").
+    Attribute(String),
+    /// The text of a text attribute of type Util::DESCRIPTION_TYPE_NAME, which formats with a
+    /// place for notes on an item (like OPML's _note) can put there.
+    Note(String),
+    /// An entity of the "URI" class (see Database::add_uri_entity_with_uri_attribute), which
+    /// is exported as a link instead of being walked into.
+    Uri {
+        name: String,
+        uri: String,
+        quote: Option<String>,
+    },
+    /// A related entity, exported in ExportTree::entities.  relation is None for "has", and
+    /// relation_attributes are the relation's own (like "role: editor"), as displayed.
+    Entity {
+        relation: Option<String>,
+        relation_attributes: Vec<String>,
+        entity_id: i64,
+    },
+    Group {
+        relation: Option<String>,
+        relation_attributes: Vec<String>,
+        name: String,
+        entries: Vec<ExportItem>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedEntity {
+    pub id: i64,
+    pub name: String,
+    /// The fewest levels below the starting entity at which it was found.
+    pub depth: usize,
+    pub items: Vec<ExportItem>,
+    /// How many ExportItem::Entity (or group entries) refer to it.
+    pub reference_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportTree {
+    pub root_id: i64,
+    pub entities: BTreeMap<i64, ExportedEntity>,
+}
+
+impl ExportTree {
+    /// Reads root_id_in and what it contains, as allowed by options_in.  Each entity is read
+    /// once however many ways it is reachable (so cycles end), with its items listing what it
+    /// refers to.
+    pub fn build(
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        root_id_in: i64,
+        options_in: &ExportOptions,
+    ) -> Result<ExportTree, anyhow::Error> {
+        // (Only looked up, since exporting shouldn't write anything: with no such classes, there
+        // are just no URI entities.)
+        let uri_class = db.borrow().find_class_and_template_entity(transaction.clone(), "URI")?;
+        let quote_template_id = db
+            .borrow()
+            .find_class_and_template_entity(transaction.clone(), "quote")?
+            .map(|(_, template_id)| template_id);
+        let description_type_id =
+            Util::find_entity_id_by_name(&*db.borrow(), transaction.clone(), Util::DESCRIPTION_TYPE_NAME)?;
+        let mut builder = ExportTreeBuilder {
+            db: db.clone(),
+            transaction,
+            options: options_in,
+            uri_class,
+            quote_template_id,
+            description_type_id,
+            entities: BTreeMap::new(),
+            queue: VecDeque::new(),
+        };
+        let mut root = Entity::new2(db, builder.transaction.clone(), root_id_in)?;
+        let root_name = root.get_name(builder.transaction.clone())?;
+        builder.entities.insert(
+            root_id_in,
+            ExportedEntity {
+                id: root_id_in,
+                name: root_name,
+                depth: 0,
+                items: Vec::new(),
+                reference_count: 0,
+            },
+        );
+        builder.queue.push_back(root_id_in);
+        // (Breadth-first, so each entity's depth is the least it can have.)
+        while let Some(entity_id) = builder.queue.pop_front() {
+            let items = builder.read_items(entity_id)?;
+            builder.entities.get_mut(&entity_id).unwrap().items = items;
+        }
+        Ok(ExportTree {
+            root_id: root_id_in,
+            entities: builder.entities,
+        })
+    }
+
+    /// Whether the entity should be exported on its own (as a separate file or section),
+    /// rather than within the one place that refers to it: true for the starting entity and
+    /// for any reachable more than one way.  Since entering a cycle makes a second way, this
+    /// also means nothing is written twice and writing never loops.
+    pub fn is_shared(&self, entity_id_in: i64) -> bool {
+        entity_id_in == self.root_id
+            || self
+                .entities
+                .get(&entity_id_in)
+                .is_some_and(|e| e.reference_count > 1)
+    }
+
+    pub fn get(&self, entity_id_in: i64) -> Option<&ExportedEntity> {
+        self.entities.get(&entity_id_in)
+    }
+}
+
+/// The display strings of the attributes of a relation (of the given form, like
+/// RelationToLocalEntity's), if it has any.
+pub fn get_relation_attribute_descriptions(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    relation_form_id_in: i32,
+    relation_id_in: i64,
+) -> Result<Vec<String>, anyhow::Error> {
+    let holder_id = db
+        .borrow()
+        .get_relation_attribute_holder_id(transaction.clone(), relation_form_id_in, relation_id_in)?;
+    let Some(holder_id) = holder_id else {
+        return Ok(Vec::new());
+    };
+    let holder = Entity::new2(db, transaction.clone(), holder_id)?;
+    let (attribute_tuples, _) = holder.get_sorted_attributes(transaction, 0, 0, false)?;
+    let mut descriptions: Vec<String> = Vec::new();
+    for (_, attribute) in attribute_tuples {
+        descriptions.push(attribute.borrow_mut().get_display_string(0, None, None, true)?);
+    }
+    Ok(descriptions)
+}
+
+struct ExportTreeBuilder<'a, 't> {
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<'t, Postgres>>>>,
+    options: &'a ExportOptions,
+    /// The URI class and its template entity (the type of a URI entity's uri attribute), if any.
+    uri_class: Option<(i64, i64)>,
+    quote_template_id: Option<i64>,
+    description_type_id: Option<i64>,
+    entities: BTreeMap<i64, ExportedEntity>,
+    queue: VecDeque<i64>,
+}
+
+impl ExportTreeBuilder<'_, '_> {
+    fn read_items(&mut self, entity_id_in: i64) -> Result<Vec<ExportItem>, anyhow::Error> {
+        let depth = self.entities[&entity_id_in].depth;
+        let children_allowed = self.options.levels.is_none_or(|levels| depth < levels);
+        let entity = Entity::new2(self.db.clone(), self.transaction.clone(), entity_id_in)?;
+        let (attribute_tuples, _) = entity.get_sorted_attributes(self.transaction.clone(), 0, 0, false)?;
+        let mut items: Vec<ExportItem> = Vec::new();
+        for (_, attribute) in attribute_tuples {
+            let mut attribute = attribute.borrow_mut();
+            let is_relation = attribute.as_any_mut().is::<RelationToLocalEntity>()
+                || attribute.as_any_mut().is::<RelationToGroup>();
+            let relation_attributes = if is_relation && children_allowed {
+                get_relation_attribute_descriptions(
+                    self.db.clone(),
+                    self.transaction.clone(),
+                    attribute.get_form_id()?,
+                    attribute.get_id(),
+                )?
+            } else {
+                Vec::new()
+            };
+            if let Some(rtle) = attribute.as_any_mut().downcast_mut::<RelationToLocalEntity>() {
+                if !children_allowed {
+                    continue;
+                }
+                let relation = self.get_relation_name(rtle.get_attr_type_id(self.transaction.clone())?)?;
+                if let Some(item) =
+                    self.read_related_entity(rtle.get_related_id2(), relation, relation_attributes, depth + 1)?
+                {
+                    items.push(item);
+                }
+            } else if let Some(rtg) = attribute.as_any_mut().downcast_mut::<RelationToGroup>() {
+                if !children_allowed {
+                    continue;
+                }
+                let relation = self.get_relation_name(rtg.get_attr_type_id(self.transaction.clone())?)?;
+                let group_id = rtg.get_group_id(self.transaction.clone())?;
+                let name = Group::new2(self.db.clone(), self.transaction.clone(), group_id)?
+                    .get_name(self.transaction.clone())?;
+                let entry_ids = self.db.borrow().get_group_entry_ids(self.transaction.clone(), group_id, 0, None)?;
+                let mut entries: Vec<ExportItem> = Vec::new();
+                for entry_id in entry_ids {
+                    if let Some(item) = self.read_related_entity(entry_id, None, Vec::new(), depth + 1)? {
+                        entries.push(item);
+                    }
+                }
+                items.push(ExportItem::Group {
+                    relation,
+                    relation_attributes,
+                    name,
+                    entries,
+                });
+            } else if let Some(text) = self.get_description(&mut *attribute)? {
+                items.push(ExportItem::Note(text));
+            } else {
+                items.push(ExportItem::Attribute(attribute.get_display_string(0, None, None, true)?));
+            }
+        }
+        Ok(items)
+    }
+
+    fn get_description(&self, attribute_in: &mut dyn Attribute) -> Result<Option<String>, anyhow::Error> {
+        if self.description_type_id.is_none()
+            || Some(attribute_in.get_attr_type_id(self.transaction.clone())?) != self.description_type_id
+        {
+            return Ok(None);
+        }
+        match attribute_in.as_any_mut().downcast_mut::<TextAttribute>() {
+            Some(text_attribute) => Ok(Some(text_attribute.get_text(self.transaction.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// None for "has", which is what relations usually are, so it need not be written out.
+    fn get_relation_name(&self, relation_type_id_in: i64) -> Result<Option<String>, anyhow::Error> {
+        let name = Entity::new2(self.db.clone(), self.transaction.clone(), relation_type_id_in)?
+            .get_name(self.transaction.clone())?;
+        if name == Util::THE_HAS_RELATION_TYPE_NAME {
+            Ok(None)
+        } else {
+            Ok(Some(name))
+        }
+    }
+
+    /// Returns None if the entity is not to be exported (as for a non-public one, whose name
+    /// should not be disclosed even in a link).
+    fn read_related_entity(
+        &mut self,
+        entity_id_in: i64,
+        relation_in: Option<String>,
+        relation_attributes_in: Vec<String>,
+        depth_in: usize,
+    ) -> Result<Option<ExportItem>, anyhow::Error> {
+        if let Some(existing) = self.entities.get_mut(&entity_id_in) {
+            existing.reference_count += 1;
+            return Ok(Some(ExportItem::Entity {
+                relation: relation_in,
+                relation_attributes: relation_attributes_in,
+                entity_id: entity_id_in,
+            }));
+        }
+        let mut entity = Entity::new2(self.db.clone(), self.transaction.clone(), entity_id_in)?;
+        if !self.options.allows(entity.get_public(self.transaction.clone())?) {
+            return Ok(None);
+        }
+        let name = entity.get_name(self.transaction.clone())?;
+        if let Some((uri_class_id, uri_template_id)) = self.uri_class {
+            if entity.get_class_id(self.transaction.clone())? == Some(uri_class_id) {
+                let (uri, quote) = self.get_uri_and_quote(&entity, uri_template_id)?;
+                if let Some(uri) = uri {
+                    return Ok(Some(ExportItem::Uri { name, uri, quote }));
+                }
+            }
+        }
+        self.entities.insert(
+            entity_id_in,
+            ExportedEntity {
+                id: entity_id_in,
+                name,
+                depth: depth_in,
+                items: Vec::new(),
+                reference_count: 1,
+            },
+        );
+        self.queue.push_back(entity_id_in);
+        Ok(Some(ExportItem::Entity {
+            relation: relation_in,
+            relation_attributes: relation_attributes_in,
+            entity_id: entity_id_in,
+        }))
+    }
+
+    fn get_uri_and_quote(
+        &self,
+        entity_in: &Entity,
+        uri_template_id_in: i64,
+    ) -> Result<(Option<String>, Option<String>), anyhow::Error> {
+        let (attribute_tuples, _) = entity_in.get_sorted_attributes(self.transaction.clone(), 0, 0, false)?;
+        let mut uri: Option<String> = None;
+        let mut quote: Option<String> = None;
+        for (_, attribute) in attribute_tuples {
+            let mut attribute = attribute.borrow_mut();
+            let attr_type_id = attribute.get_attr_type_id(self.transaction.clone())?;
+            if let Some(text_attribute) = attribute.as_any_mut().downcast_mut::<TextAttribute>() {
+                if attr_type_id == uri_template_id_in && uri.is_none() {
+                    uri = Some(text_attribute.get_text(self.transaction.clone())?);
+                } else if Some(attr_type_id) == self.quote_template_id && quote.is_none() {
+                    quote = Some(text_attribute.get_text(self.transaction.clone())?);
+                }
+            }
+        }
+        Ok((uri, quote))
+    }
+}
@@ -22,6 +22,12 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use tracing::*;
 
+/// The main menu's class actions choice, which is only shown when the current entity has any
+/// (so the choices after it then move up by one).
+const CLASS_ACTIONS_CHOICE: usize = 12;
+/// How many of the current entity's inferred relations the main menu lists.
+const MAX_INFERRED_RELATIONS_SHOWN: usize = 10;
+
 pub struct MainMenu {
     ui: Rc<TextUI>,
     db: Rc<RefCell<dyn Database>>,
@@ -173,14 +179,30 @@ impl MainMenu {
             // or passed from the calling menuLoop (by this or another process):
             let mut entity =
                 Entity::new2(self.db.clone(), None, entity_in.clone().unwrap().get_id())?;
-            let leading_text = "Main OM menu:";
+            self.controller.record_entity_visit(&entity)?;
+            let mut leading_text = vec!["Main OM menu:".to_string()];
+            if let Some(breadcrumbs) = self.controller.get_breadcrumbs_text() {
+                leading_text.push(breadcrumbs);
+            }
+            // The current entity's inferred relations, since they are part of what it is related to
+            // (though not stored): all of them are under the choice to show them, below.
+            let inferred = self.controller.get_inferred_relation_display_strings(entity.get_db(), entity.get_id())?;
+            leading_text.extend(inferred.iter().take(MAX_INFERRED_RELATIONS_SHOWN).cloned());
+            if inferred.len() > MAX_INFERRED_RELATIONS_SHOWN {
+                leading_text.push(format!(
+                    "(inferred) ...and {} more",
+                    inferred.len() - MAX_INFERRED_RELATIONS_SHOWN
+                ));
+            }
             let menutext_create_relation_type = Util::menutext_create_relation_type();
             let entity_descr = entity.get_display_string(None, false)?;
             let go_to_current_entity = format!(
                 "Go to current entity ({}; or its sole subgroup, if present)",
                 entity_descr
             );
-            let choices = vec![
+            // (Defining a class's first action is from its class schema menu, choice 13.)
+            let class_actions = self.controller.get_runnable_class_actions(&mut entity)?;
+            let mut choices = vec![
                 Util::MENUTEXT_CREATE_ENTITY_OR_ATTR_TYPE.to_string(),
                 menutext_create_relation_type.as_str().to_string(),
                 Util::MENUTEXT_VIEW_PREFERENCES.to_string(),
@@ -189,12 +211,38 @@ impl MainMenu {
                 Util::MAIN_SEARCH_PROMPT.to_string(),
                 "List existing classes".to_string(),
                 "List OneModel (OM) instances (local & remote)".to_string(),
+                "Units: conversions, canonical units, and totals...".to_string(),
+                "Paste from the clipboard into the current entity (a URI, quote, or new entry)".to_string(),
+                "Import or export (bookmarks, etc)...".to_string(),
+                "Class actions: run one on the current entity, or define them for its class...".to_string(),
+                "Class schema: required and typed attributes, and parent class, of the current entity's class...".to_string(),
+                "Backlinks: everything that refers to the current entity, to go to one...".to_string(),
+                "Find how the current entity is connected to another (the shortest paths between them)...".to_string(),
+                self.controller.get_back_or_forward_choice_text(false),
+                self.controller.get_back_or_forward_choice_text(true),
+                "History: pick any earlier place to go back to...".to_string(),
+                "Bookmarks: go to one, or bookmark the current entity...".to_string(),
+                "Recently or frequently viewed entities, to go to one...".to_string(),
+                "View the current entity's attributes as of a date (as valid, or as known, then)...".to_string(),
+                "Quantity series: the current entity's quantities over time, with statistics...".to_string(),
+                "Relation type semantics: whether one is transitive or symmetric, has an inverse, or allows only \
+                certain classes...".to_string(),
+                "Show the relations implied by the current entity's (inferred from relation type semantics)".to_string(),
+                "Relation attributes: those of the current entity's relations (like when one began), or sort \
+                its relations by them...".to_string(),
             ];
+            if class_actions.is_empty() {
+                choices.remove(CLASS_ACTIONS_CHOICE - 1);
+            }
+            let action_choices: Vec<String> = class_actions
+                .iter()
+                .map(|(_, name, _)| format!("Run the class action \"{}\"", name))
+                .collect();
             let response = if go_directly_to_choice.is_none() {
                 let ans = self.ui.ask_which(
-                    Some(vec![leading_text.to_string()]),
+                    Some(leading_text),
                     &choices,
-                    &Vec::new(),
+                    &action_choices,
                     true,
                     Some(format!("{} to quit (anytime)", self.ui.how_quit()).as_str()),
                     None,
@@ -205,8 +253,18 @@ impl MainMenu {
             } else {
                 go_directly_to_choice
             };
-            if response.is_some() && response.unwrap() != 0 {
-                let answer = response.unwrap();
+            if let Some(answer) = response.filter(|a| go_directly_to_choice.is_none() && *a > choices.len()) {
+                if let Some((_, name, action)) = class_actions.get(answer - choices.len() - 1) {
+                    self.controller.run_class_action(&mut entity, name, action)?;
+                }
+                self.main_menu(Some(entity), None);
+                Ok(())
+            } else if response.is_some() && response.unwrap() != 0 {
+                let mut answer = response.unwrap();
+                // (The numbers below are as if the class actions choice were always there.)
+                if go_directly_to_choice.is_none() && class_actions.is_empty() && answer >= CLASS_ACTIONS_CHOICE {
+                    answer += 1;
+                }
                 match answer {
                     1 => {
                         println!(
@@ -370,6 +428,70 @@ impl MainMenu {
                         //    self.main_menu(Some(entity), None);
                         //}
                     }
+                    9 => {
+                        self.controller.units_menu(self.db.clone(), &mut entity)?;
+                    }
+                    10 => {
+                        self.controller.add_from_clipboard(&mut entity)?;
+                    }
+                    11 => {
+                        self.controller.import_export_menu(&mut entity)?;
+                    }
+                    CLASS_ACTIONS_CHOICE => {
+                        self.controller.class_actions_menu(&mut entity)?;
+                    }
+                    13 => match entity.get_class_id(None)? {
+                        Some(class_id) => self.controller.class_schema_menu(entity.get_db(), class_id)?,
+                        None => self.ui.display_text1(
+                            "This entity has no class, so there is no class schema for it.  (A schema is \
+                            defined for a class, and applies to every entity of it.)",
+                        ),
+                    },
+                    14 => {
+                        if let Some(referrer) = self.controller.backlinks_menu(&mut entity)? {
+                            entity = referrer;
+                        }
+                    }
+                    15 => {
+                        if let Some(on_path) = self.controller.path_finder_menu(&mut entity)? {
+                            entity = on_path;
+                        }
+                    }
+                    16 | 17 => {
+                        if let Some(there) = self.controller.go_back_or_forward(answer == 17)? {
+                            entity = there;
+                        }
+                    }
+                    18 => {
+                        if let Some(earlier) = self.controller.navigation_history_menu()? {
+                            entity = earlier;
+                        }
+                    }
+                    19 => {
+                        if let Some(bookmarked) = self.controller.bookmarks_menu(&mut entity)? {
+                            entity = bookmarked;
+                        }
+                    }
+                    20 => {
+                        if let Some(viewed) = self.controller.viewed_entities_menu()? {
+                            entity = viewed;
+                        }
+                    }
+                    21 => {
+                        self.controller.show_attributes_as_of(&mut entity)?;
+                    }
+                    22 => {
+                        self.controller.show_quantity_series(&mut entity)?;
+                    }
+                    23 => {
+                        self.controller.choose_and_edit_relation_type_semantics(entity.get_db())?;
+                    }
+                    24 => {
+                        self.controller.show_inferred_relations(&mut entity)?;
+                    }
+                    25 => {
+                        self.controller.relation_attributes_of_entity_menu(&mut entity)?;
+                    }
                     _ => {
                         self.ui.display_text1(&format!("unexpected: {}", answer));
                     }
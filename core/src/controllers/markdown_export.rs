@@ -0,0 +1,270 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Writes an ExportTree as Markdown: one file for the starting entity and one for each entity
+//! reachable more than one way (see ExportTree::is_shared), each a heading then a nested list.
+//! Entities reachable only one way are written within the list where they are found, and the
+//! others are links to their files, like the HTML export's "e<id>.html" files.
+use crate::controllers::export_tree::{ExportItem, ExportTree};
+use std::path::Path;
+
+pub fn get_file_name(entity_id_in: i64) -> String {
+    format!("e{}.md", entity_id_in)
+}
+
+/// Returns the file names and contents, the starting entity's first.
+pub fn to_markdown_files(tree: &ExportTree) -> Vec<(String, String)> {
+    let mut shared_ids: Vec<i64> = vec![tree.root_id];
+    shared_ids.extend(tree.entities.keys().filter(|id| **id != tree.root_id && tree.is_shared(**id)));
+    let mut files: Vec<(String, String)> = Vec::new();
+    for id in shared_ids {
+        let entity = &tree.entities[&id];
+        let mut content = format!("# {}\n\n", escape_markdown(&entity.name));
+        write_items(tree, &entity.items, 0, &mut content);
+        files.push((get_file_name(id), content));
+    }
+    files
+}
+
+/// Writes the files into directory_in (creating it if needed), returning how many there were.
+pub fn write_markdown_files(tree: &ExportTree, directory_in: &Path) -> Result<usize, anyhow::Error> {
+    std::fs::create_dir_all(directory_in)?;
+    let files = to_markdown_files(tree);
+    for (name, content) in &files {
+        std::fs::write(directory_in.join(name), content)?;
+    }
+    Ok(files.len())
+}
+
+fn write_items(tree: &ExportTree, items: &[ExportItem], indent: usize, out: &mut String) {
+    let prefix = "  ".repeat(indent);
+    for item in items {
+        match item {
+            ExportItem::Attribute(text) | ExportItem::Note(text) => {
+                out.push_str(&format!("{}- {}\n", prefix, escape_markdown(text)));
+            }
+            ExportItem::Uri { name, uri, quote } => {
+                out.push_str(&format!("{}- [{}](<{}>)\n", prefix, escape_markdown(name), uri));
+                if let Some(quote) = quote {
+                    for line in quote.lines() {
+                        out.push_str(&format!("{}  > {}\n", prefix, line));
+                    }
+                }
+            }
+            ExportItem::Entity {
+                relation,
+                relation_attributes,
+                entity_id,
+            } => {
+                let entity = &tree.entities[entity_id];
+                let relation = get_relation_label(relation, relation_attributes);
+                if tree.is_shared(*entity_id) {
+                    out.push_str(&format!(
+                        "{}- {}[{}]({})\n",
+                        prefix,
+                        relation,
+                        escape_markdown(&entity.name),
+                        get_file_name(*entity_id)
+                    ));
+                } else {
+                    out.push_str(&format!("{}- {}{}\n", prefix, relation, escape_markdown(&entity.name)));
+                    write_items(tree, &entity.items, indent + 1, out);
+                }
+            }
+            ExportItem::Group {
+                relation,
+                relation_attributes,
+                name,
+                entries,
+            } => {
+                let relation = get_relation_label(relation, relation_attributes);
+                out.push_str(&format!("{}- {}**{}**\n", prefix, relation, escape_markdown(name)));
+                write_items(tree, entries, indent + 1, out);
+            }
+        }
+    }
+}
+
+/// Like "worked at (role: editor; began: 2020-01-01): ", or "" for a plain "has" relation.
+fn get_relation_label(relation_in: &Option<String>, relation_attributes_in: &[String]) -> String {
+    let mut label = relation_in.as_deref().map(escape_markdown).unwrap_or_default();
+    if !relation_attributes_in.is_empty() {
+        if !label.is_empty() {
+            label.push(' ');
+        }
+        let attributes: Vec<String> = relation_attributes_in.iter().map(|a| escape_markdown(a)).collect();
+        label.push_str(&format!("({})", attributes.join("; ")));
+    }
+    if label.is_empty() {
+        label
+    } else {
+        format!("{}: ", label)
+    }
+}
+
+/// Escapes characters that Markdown would otherwise take as formatting, and puts any line
+/// breaks on one line so list items stay whole.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push(' '),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::controllers::export_tree::{ExportOptions, ExportedEntity};
+    use crate::model::database::Database;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+    use crate::util::Util;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+
+    fn exported(id: i64, name: &str, reference_count: usize, items: Vec<ExportItem>) -> ExportedEntity {
+        ExportedEntity {
+            id,
+            name: name.to_string(),
+            depth: 0,
+            items,
+            reference_count,
+        }
+    }
+
+    #[test]
+    fn test_to_markdown_files() {
+        let entity = |id: i64| ExportItem::Entity {
+            relation: None,
+            relation_attributes: Vec::new(),
+            entity_id: id,
+        };
+        // 1 has 2 and a group holding 3 and 4; 3 refers back to 1, and 2 also has 4.
+        let mut entities = BTreeMap::new();
+        entities.insert(
+            1,
+            exported(
+                1,
+                "top",
+                1,
+                vec![
+                    ExportItem::Attribute("weight: 70 kg".to_string()),
+                    entity(2),
+                    ExportItem::Group {
+                        relation: Some("lists".to_string()),
+                        relation_attributes: Vec::new(),
+                        name: "todo_list".to_string(),
+                        entries: vec![entity(3), entity(4)],
+                    },
+                ],
+            ),
+        );
+        entities.insert(2, exported(2, "two", 1, vec![entity(4)]));
+        entities.insert(
+            3,
+            exported(
+                3,
+                "three",
+                1,
+                vec![
+                    ExportItem::Entity {
+                        relation: Some("is part of".to_string()),
+                        relation_attributes: vec!["since: 2020".to_string()],
+                        entity_id: 1,
+                    },
+                    ExportItem::Uri {
+                        name: "a site".to_string(),
+                        uri: "https://example.com/a".to_string(),
+                        quote: Some("said it".to_string()),
+                    },
+                ],
+            ),
+        );
+        entities.insert(4, exported(4, "four", 2, vec![ExportItem::Attribute("x".to_string())]));
+        let tree = ExportTree { root_id: 1, entities };
+        let files = to_markdown_files(&tree);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].0, "e1.md");
+        assert_eq!(
+            files[0].1,
+            "# top\n\n\
+            - weight: 70 kg\n\
+            - two\n  \
+              - [four](e4.md)\n\
+            - lists: **todo\\_list**\n  \
+              - three\n    \
+                - is part of (since: 2020): [top](e1.md)\n    \
+                - [a site](<https://example.com/a>)\n      \
+                  > said it\n  \
+              - [four](e4.md)\n"
+        );
+        assert_eq!(files[1], ("e4.md".to_string(), "# four\n\n- x\n".to_string()));
+    }
+
+    #[test]
+    fn test_build_export_tree() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let top = db_b.create_entity(tx.clone(), "export top", None, Some(true)).unwrap();
+        let (child, _, _) = db_b
+            .create_entity_and_add_has_local_relation_to_it(tx.clone(), top, "export child", 0, Some(true))
+            .unwrap();
+        let (hidden, _, _) = db_b
+            .create_entity_and_add_has_local_relation_to_it(tx.clone(), top, "export hidden", 0, Some(false))
+            .unwrap();
+        let (grandchild, _, _) = db_b
+            .create_entity_and_add_has_local_relation_to_it(tx.clone(), child, "export grandchild", 0, Some(true))
+            .unwrap();
+        // A cycle back to the top:
+        let rel_type_id = db_b.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME).unwrap();
+        db_b.create_relation_to_local_entity(tx.clone(), rel_type_id, grandchild, top, None, 0, None)
+            .unwrap();
+
+        let public_only = ExportOptions {
+            levels: None,
+            include_public: true,
+            include_non_public: false,
+            include_unspecified: false,
+        };
+        let tree = ExportTree::build(db_dyn.clone(), tx.clone(), top, &public_only).unwrap();
+        assert_eq!(tree.entities.len(), 3);
+        assert!(tree.get(hidden).is_none());
+        assert!(tree.is_shared(top));
+        assert!(!tree.is_shared(child));
+        assert_eq!(tree.get(grandchild).unwrap().depth, 2);
+        assert_eq!(tree.get(top).unwrap().reference_count, 1);
+        let files = to_markdown_files(&tree);
+        assert_eq!(files.len(), 1);
+        assert!(files[0].1.contains("    - [export top](e"));
+
+        let one_level = ExportOptions {
+            levels: Some(1),
+            include_non_public: true,
+            ..public_only
+        };
+        let tree = ExportTree::build(db_dyn, tx.clone(), top, &one_level).unwrap();
+        assert!(tree.get(hidden).is_some());
+        assert!(tree.get(grandchild).is_none());
+        assert!(tree.get(child).unwrap().items.is_empty());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
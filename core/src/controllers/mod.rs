@@ -8,9 +8,11 @@
     You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
 */
 
+pub mod bookmark_import;
 pub mod class_menu;
 // There are several "controller" files for one impl, just because rust-analyzer was slow analyzing
 // fewer big files:
+pub mod class_actions;
 pub mod controller;
 pub mod controller2;
 pub mod controller3;
@@ -18,11 +20,18 @@ pub mod controller4;
 pub mod controller5;
 pub mod controller6;
 pub mod controller_test;
+pub mod csv_export;
+pub mod csv_import;
 pub mod entity_menu;
+pub mod export_tree;
 pub mod group_menu;
 pub mod import_export;
 pub mod main_menu;
+pub mod markdown_export;
 pub mod om_instance_menu;
+pub mod opml;
+pub mod org_mode;
 pub mod other_entity_menu;
 pub mod quick_group_menu;
+pub mod rdf_export;
 pub mod sortable_entries_menu;
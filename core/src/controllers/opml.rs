@@ -0,0 +1,553 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! OPML, the outline format of outliners like Workflowy, Dynalist and OmniOutliner.  Importing
+//! makes each outline with sub-outlines an entity with a group of them (as the text-outline
+//! import in import_export.rs does with indentation), an outline's _note a text attribute of
+//! type Util::DESCRIPTION_TYPE_NAME, and an outline with a url (or htmlUrl) a URI entity.
+//! Exporting writes an ExportTree the same way.
+use crate::controllers::bookmark_import::decode_html_entities;
+use crate::controllers::export_tree::{ExportItem, ExportTree};
+use crate::model::database::Database;
+use crate::util::Util;
+use anyhow::anyhow;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OpmlOutline {
+    pub text: String,
+    pub note: Option<String>,
+    pub url: Option<String>,
+    pub children: Vec<OpmlOutline>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OpmlDocument {
+    pub title: Option<String>,
+    pub outlines: Vec<OpmlOutline>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct OpmlImportCounts {
+    pub entities: usize,
+    pub uris: usize,
+    pub notes: usize,
+}
+
+/// Reads the title and outlines, ignoring the rest.  Fails on XML too broken to tell what the
+/// outlines are, so that nothing is imported from it.
+pub fn parse_opml(xml: &str) -> Result<OpmlDocument, anyhow::Error> {
+    let mut document = OpmlDocument::default();
+    let mut stack: Vec<OpmlOutline> = Vec::new();
+    let mut found_opml = false;
+    let mut title: Option<String> = None;
+    let mut rest = xml;
+    while let Some(tag_start) = rest.find('<') {
+        if let Some(t) = title.as_mut() {
+            t.push_str(&rest[..tag_start]);
+        }
+        rest = &rest[tag_start..];
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").ok_or_else(|| anyhow!("Unterminated comment in OPML."))?;
+            rest = &after[end + 3..];
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").ok_or_else(|| anyhow!("Unterminated CDATA section in OPML."))?;
+            if let Some(t) = title.as_mut() {
+                // (Escaped so it is not decoded again below.)
+                t.push_str(&escape_xml(&after[..end]));
+            }
+            rest = &after[end + 3..];
+            continue;
+        }
+        let tag_length = find_tag_end(rest).ok_or_else(|| anyhow!("Unterminated tag in OPML."))?;
+        let tag = &rest[1..tag_length];
+        rest = &rest[tag_length + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            match name.trim() {
+                "outline" => {
+                    let outline = stack.pop().ok_or_else(|| anyhow!("Unexpected </outline> in OPML."))?;
+                    add_outline(&mut document, &mut stack, outline);
+                }
+                "title" => {
+                    if let Some(t) = title.take() {
+                        document.title = Some(decode_html_entities(t.trim())).filter(|t| !t.is_empty());
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        match name {
+            "opml" => found_opml = true,
+            "title" if !self_closing && stack.is_empty() => title = Some(String::new()),
+            "outline" => {
+                let attributes = parse_attributes(attributes)?;
+                let get = |key: &str| attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+                let outline = OpmlOutline {
+                    text: get("text").or_else(|| get("title")).unwrap_or_default(),
+                    note: get("_note").filter(|n| !n.trim().is_empty()),
+                    url: get("url").or_else(|| get("htmlUrl")).filter(|u| !u.trim().is_empty()),
+                    children: Vec::new(),
+                };
+                if self_closing {
+                    add_outline(&mut document, &mut stack, outline);
+                } else {
+                    stack.push(outline);
+                }
+            }
+            _ => {}
+        }
+    }
+    if !found_opml {
+        return Err(anyhow!("This is not an OPML file (it has no <opml> element)."));
+    }
+    if !stack.is_empty() {
+        return Err(anyhow!("An <outline> element in the OPML is not closed."));
+    }
+    Ok(document)
+}
+
+fn add_outline(document: &mut OpmlDocument, stack: &mut [OpmlOutline], outline: OpmlOutline) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(outline),
+        None => document.outlines.push(outline),
+    }
+}
+
+/// The index of the ">" ending the tag at the start of text, skipping any in quoted values.
+fn find_tag_end(text: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses name="value" (or name='value') pairs, decoding the values.
+fn parse_attributes(text: &str) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let equals = rest
+            .find('=')
+            .ok_or_else(|| anyhow!("Expected name=\"value\" in OPML tag at: {}", rest))?;
+        let name = rest[..equals].trim();
+        let after = rest[equals + 1..].trim_start();
+        let quote = after
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| anyhow!("Expected a quoted value for \"{}\" in OPML tag.", name))?;
+        let value_length = after[1..]
+            .find(quote)
+            .ok_or_else(|| anyhow!("Unterminated value for \"{}\" in OPML tag.", name))?;
+        attributes.push((name.to_string(), decode_html_entities(&after[1..1 + value_length])));
+        rest = after[value_length + 2..].trim_start();
+    }
+    Ok(attributes)
+}
+
+/// For text in attribute values or elements, including line breaks (which attribute values
+/// would otherwise lose).
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// An entity's outline has its notes as _note, and its items as sub-outlines.  A group named
+/// like its entity (as the importers make them) is not an outline of its own: its entries are
+/// the entity's sub-outlines.  An entity reachable more than one way is written in full only the
+/// first time, then as just its name, so nothing is duplicated and cycles end.
+pub fn to_opml(tree: &ExportTree) -> String {
+    let root = &tree.entities[&tree.root_id];
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n");
+    out.push_str(&format!("  <head>\n    <title>{}</title>\n  </head>\n  <body>\n", escape_xml(&root.name)));
+    let mut written: HashSet<i64> = HashSet::new();
+    written.insert(tree.root_id);
+    // (The file has no place for the starting entity's notes, so they are outlines, as
+    // write_item makes them.)
+    for item in &root.items {
+        write_item(tree, item, &root.name, 2, &mut written, &mut out);
+    }
+    out.push_str("  </body>\n</opml>\n");
+    out
+}
+
+fn write_item(
+    tree: &ExportTree,
+    item: &ExportItem,
+    parent_name: &str,
+    indent: usize,
+    written: &mut HashSet<i64>,
+    out: &mut String,
+) {
+    match item {
+        ExportItem::Attribute(text) | ExportItem::Note(text) => {
+            write_outline_start(out, indent, text, &[], None, false);
+        }
+        ExportItem::Uri { name, uri, quote } => {
+            write_outline_start(out, indent, name, &[("type", "link"), ("url", uri)], quote.as_deref(), false);
+        }
+        ExportItem::Entity {
+            relation,
+            relation_attributes,
+            entity_id,
+        } => {
+            let entity = &tree.entities[entity_id];
+            let relation_attributes = relation_attributes.join("; ");
+            let relation = get_relation_outline_attributes(relation, &relation_attributes);
+            if !written.insert(*entity_id) {
+                write_outline_start(out, indent, &entity.name, &relation, None, false);
+                return;
+            }
+            let notes: Vec<&str> = entity
+                .items
+                .iter()
+                .filter_map(|i| match i {
+                    ExportItem::Note(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect();
+            let note = notes.join("\n\n");
+            let note = Some(note.as_str()).filter(|n| !n.is_empty());
+            let children: Vec<&ExportItem> =
+                entity.items.iter().filter(|i| !matches!(i, ExportItem::Note(_))).collect();
+            write_outline_start(out, indent, &entity.name, &relation, note, !children.is_empty());
+            if !children.is_empty() {
+                for child in children {
+                    write_item(tree, child, &entity.name, indent + 1, written, out);
+                }
+                write_outline_end(out, indent);
+            }
+        }
+        ExportItem::Group {
+            relation,
+            relation_attributes,
+            name,
+            entries,
+        } => {
+            if relation.is_none() && relation_attributes.is_empty() && name == parent_name {
+                for entry in entries {
+                    write_item(tree, entry, name, indent, written, out);
+                }
+            } else {
+                let relation_attributes = relation_attributes.join("; ");
+                let relation = get_relation_outline_attributes(relation, &relation_attributes);
+                write_outline_start(out, indent, name, &relation, None, !entries.is_empty());
+                if !entries.is_empty() {
+                    for entry in entries {
+                        write_item(tree, entry, name, indent + 1, written, out);
+                    }
+                    write_outline_end(out, indent);
+                }
+            }
+        }
+    }
+}
+
+/// The (non-standard) outline attributes for a relation other than a plain "has": its type,
+/// and its own attributes (joined with "; ").
+fn get_relation_outline_attributes<'a>(
+    relation_in: &'a Option<String>,
+    relation_attributes_in: &'a str,
+) -> Vec<(&'static str, &'a str)> {
+    let mut attributes: Vec<(&str, &str)> = relation_in.iter().map(|r| ("relation", r.as_str())).collect();
+    if !relation_attributes_in.is_empty() {
+        attributes.push(("relationAttributes", relation_attributes_in));
+    }
+    attributes
+}
+
+fn write_outline_start(
+    out: &mut String,
+    indent: usize,
+    text: &str,
+    attributes: &[(&str, &str)],
+    note: Option<&str>,
+    has_children: bool,
+) {
+    out.push_str(&format!("{}<outline text=\"{}\"", "  ".repeat(indent), escape_xml(text)));
+    for (name, value) in attributes {
+        out.push_str(&format!(" {}=\"{}\"", name, escape_xml(value)));
+    }
+    if let Some(note) = note {
+        out.push_str(&format!(" _note=\"{}\"", escape_xml(note)));
+    }
+    out.push_str(if has_children { ">\n" } else { "/>\n" });
+}
+
+fn write_outline_end(out: &mut String, indent: usize) {
+    out.push_str(&format!("{}</outline>\n", "  ".repeat(indent)));
+}
+
+/// Adds the document to the entity containing_entity_id_in as a new entity (named by its
+/// title, else default_name_in), with a group of its outlines, and so on.
+pub fn import_opml(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    containing_entity_id_in: i64,
+    document_in: &OpmlDocument,
+    default_name_in: &str,
+    make_them_public_in: Option<bool>,
+    observation_date_in: i64,
+) -> Result<OpmlImportCounts, anyhow::Error> {
+    let db = db.borrow();
+    let has_relation_type_id = db.find_relation_type(transaction.clone(), Util::THE_HAS_RELATION_TYPE_NAME)?;
+    let name = document_in.title.as_deref().unwrap_or(default_name_in);
+    let (entity_id, _, _) = db.create_entity_and_add_has_local_relation_to_it(
+        transaction.clone(),
+        containing_entity_id_in,
+        name,
+        observation_date_in,
+        make_them_public_in,
+    )?;
+    let mut importer = OpmlImporter {
+        db: &*db,
+        transaction,
+        has_relation_type_id,
+        description_type_id: None,
+        make_them_public: make_them_public_in,
+        observation_date: observation_date_in,
+        counts: OpmlImportCounts::default(),
+    };
+    importer.import_outlines(entity_id, name, &document_in.outlines)?;
+    Ok(importer.counts)
+}
+
+struct OpmlImporter<'a, 't> {
+    db: &'a dyn Database,
+    transaction: Option<Rc<RefCell<Transaction<'t, Postgres>>>>,
+    has_relation_type_id: i64,
+    description_type_id: Option<i64>,
+    make_them_public: Option<bool>,
+    observation_date: i64,
+    counts: OpmlImportCounts,
+}
+
+impl OpmlImporter<'_, '_> {
+    /// Creates the group on entity_id_in, and fills it.
+    fn import_outlines(&mut self, entity_id_in: i64, name_in: &str, outlines_in: &[OpmlOutline]) -> Result<(), anyhow::Error> {
+        if outlines_in.is_empty() {
+            return Ok(());
+        }
+        let tx = self.transaction.clone();
+        let (group_id, _) = self.db.create_group_and_relation_to_group(
+            tx.clone(),
+            entity_id_in,
+            self.has_relation_type_id,
+            name_in,
+            true,
+            None,
+            self.observation_date,
+            None,
+        )?;
+        for outline in outlines_in {
+            let name = match outline.text.trim() {
+                "" => outline.url.as_deref().unwrap_or("(untitled)").trim(),
+                text => text,
+            };
+            let id = match &outline.url {
+                Some(url) => {
+                    self.counts.uris += 1;
+                    self.db
+                        .create_uri_entity(tx.clone(), name, url.trim(), self.observation_date, self.make_them_public, None)?
+                }
+                None => {
+                    self.counts.entities += 1;
+                    self.db.create_entity(tx.clone(), name, None, self.make_them_public)?
+                }
+            };
+            self.db.add_entity_to_group(tx.clone(), group_id, id, None)?;
+            if let Some(note) = &outline.note {
+                let type_id = self.get_description_type_id()?;
+                self.db
+                    .create_text_attribute(tx.clone(), id, type_id, note, None, self.observation_date, None)?;
+                self.counts.notes += 1;
+            }
+            self.import_outlines(id, name, &outline.children)?;
+        }
+        Ok(())
+    }
+
+    fn get_description_type_id(&mut self) -> Result<i64, anyhow::Error> {
+        if let Some(id) = self.description_type_id {
+            return Ok(id);
+        }
+        let id = match Util::find_entity_id_by_name(self.db, self.transaction.clone(), Util::DESCRIPTION_TYPE_NAME)? {
+            Some(id) => id,
+            None => self
+                .db
+                .create_entity(self.transaction.clone(), Util::DESCRIPTION_TYPE_NAME, None, None)?,
+        };
+        self.description_type_id = Some(id);
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::controllers::export_tree::{ExportOptions, ExportedEntity};
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+    use std::collections::BTreeMap;
+
+    const SAMPLE: &str = "<?xml version=\"1.0\"?>\n\
+        <!-- exported -->\n\
+        <opml version=\"2.0\">\n\
+          <head><title>My &amp; outline</title></head>\n\
+          <body>\n\
+            <outline text=\"Groceries\" _note=\"for the week&#10;(Sat)\">\n\
+              <outline text='Milk'/>\n\
+              <outline text=\"Eggs &gt; 6\" />\n\
+            </outline>\n\
+            <outline text=\"Reading\">\n\
+              <outline text=\"OPML spec\" type=\"link\" htmlUrl=\"http://opml.org/spec2.opml\"/>\n\
+            </outline>\n\
+          </body>\n\
+        </opml>\n";
+
+    #[test]
+    fn test_parse_opml() {
+        let document = parse_opml(SAMPLE).unwrap();
+        assert_eq!(document.title.as_deref(), Some("My & outline"));
+        assert_eq!(document.outlines.len(), 2);
+        let groceries = &document.outlines[0];
+        assert_eq!(groceries.note.as_deref(), Some("for the week\n(Sat)"));
+        assert_eq!(groceries.children[0].text, "Milk");
+        assert_eq!(groceries.children[1].text, "Eggs > 6");
+        let link = &document.outlines[1].children[0];
+        assert_eq!(link.url.as_deref(), Some("http://opml.org/spec2.opml"));
+        assert!(link.children.is_empty());
+
+        assert!(parse_opml("<html><body/></html>").is_err());
+        assert!(parse_opml("<opml><body><outline text=\"a\"></body></opml>").is_err());
+        assert!(parse_opml("<opml><body><outline text=\"a></body></opml>").is_err());
+    }
+
+    #[test]
+    fn test_to_opml() {
+        let mut entities = BTreeMap::new();
+        let entity = |id: i64| ExportItem::Entity {
+            relation: None,
+            relation_attributes: Vec::new(),
+            entity_id: id,
+        };
+        let exported = |id: i64, name: &str, items: Vec<ExportItem>| ExportedEntity {
+            id,
+            name: name.to_string(),
+            depth: 0,
+            items,
+            reference_count: 1,
+        };
+        entities.insert(
+            1,
+            exported(
+                1,
+                "top",
+                vec![ExportItem::Group {
+                    relation: None,
+                    relation_attributes: Vec::new(),
+                    name: "top".to_string(),
+                    entries: vec![
+                        entity(2),
+                        ExportItem::Entity {
+                            relation: Some("reviews".to_string()),
+                            relation_attributes: vec!["since: 2020".to_string()],
+                            entity_id: 1,
+                        },
+                    ],
+                }],
+            ),
+        );
+        entities.insert(
+            2,
+            exported(
+                2,
+                "a \"b\"",
+                vec![
+                    ExportItem::Note("line 1\nline 2".to_string()),
+                    ExportItem::Uri {
+                        name: "site".to_string(),
+                        uri: "https://example.com/?a=1&b=2".to_string(),
+                        quote: None,
+                    },
+                ],
+            ),
+        );
+        let tree = ExportTree { root_id: 1, entities };
+        let opml = to_opml(&tree);
+        assert!(opml.contains("<title>top</title>"));
+        assert!(opml.contains(
+            "    <outline text=\"a &quot;b&quot;\" _note=\"line 1&#10;line 2\">\n      \
+            <outline text=\"site\" type=\"link\" url=\"https://example.com/?a=1&amp;b=2\"/>\n    \
+            </outline>\n    \
+            <outline text=\"top\" relation=\"reviews\" relationAttributes=\"since: 2020\"/>\n"
+        ));
+        let document = parse_opml(&opml).unwrap();
+        assert_eq!(document.outlines[0].note.as_deref(), Some("line 1\nline 2"));
+        assert_eq!(document.outlines[0].children[0].url.as_deref(), Some("https://example.com/?a=1&b=2"));
+    }
+
+    #[test]
+    fn test_import_opml() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let top = db_b.create_entity(tx.clone(), "opml import top", None, None).unwrap();
+        let document = parse_opml(SAMPLE).unwrap();
+        let counts = import_opml(db_dyn.clone(), tx.clone(), top, &document, "unused", None, 0).unwrap();
+        assert_eq!(counts, OpmlImportCounts { entities: 4, uris: 1, notes: 1 });
+
+        let options = ExportOptions {
+            levels: None,
+            include_public: true,
+            include_non_public: true,
+            include_unspecified: true,
+        };
+        let tree = ExportTree::build(db_dyn, tx.clone(), top, &options).unwrap();
+        let opml = to_opml(&tree);
+        let exported = parse_opml(&opml).unwrap();
+        assert_eq!(exported.title.as_deref(), Some("opml import top"));
+        let imported = &exported.outlines[0];
+        assert_eq!(imported.text, "My & outline");
+        // (The groups named like their entities do not add levels, so it is as imported.)
+        assert_eq!(imported.children, document.outlines);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
@@ -0,0 +1,949 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Exporting an entity to an emacs org-mode file, and importing such a file (or any org file).
+//! Headings are entities, with sub-headings the entities in their groups (or related to them).
+//! Text attributes are the body text (those of type BODY_TYPE_NAME) or properties, date
+//! attributes of types "scheduled" and "deadline" are the SCHEDULED and DEADLINE dates, and a
+//! boolean attribute of type "done" is the TODO/DONE keyword.  Other date and boolean attributes
+//! are properties too (as org timestamps, and "t" or "nil"); other kinds of attributes (like
+//! quantities and files) are not exported.
+//! The attributes of a relation to a heading's entity (see Database::get_relation_attribute_holder_id)
+//! are its OM_RELATION_ATTRIBUTE properties, as displayed; since they are descriptions, not
+//! values, importing does not read them back.  (Those of a relation to a group are not
+//! exported, since the group is not a heading.)
+//! Each heading's OM_ID property is its entity's id, qualified by the id of the OM instance it
+//! came from (like "<instance id>:<entity id>", as in the IRIs of rdf_export.rs), so that
+//! re-importing an exported file into the same instance updates those entities instead of
+//! creating new ones.  An OM_ID from another instance (or with no instance) is not used to find
+//! an entity, since the same number here would be some unrelated one.  Importing only adds or
+//! changes: it does not delete attributes that are missing from the file, nor move entities
+//! whose headings moved.  A new property is imported as a date or boolean attribute only if its
+//! attribute type is already used for that form (and not for text), so that text which happens
+//! to look like a timestamp, or "t" or "nil", stays text.
+use crate::controllers::export_tree::{get_relation_attribute_descriptions, ExportOptions};
+use crate::model::attribute::Attribute;
+use crate::model::boolean_attribute::BooleanAttribute;
+use crate::model::database::Database;
+use crate::model::date_attribute::DateAttribute;
+use crate::model::entity::Entity;
+use crate::model::relation_to_entity::RelationToEntity;
+use crate::model::relation_to_group::RelationToGroup;
+use crate::model::relation_to_local_entity::RelationToLocalEntity;
+use crate::model::text_attribute::TextAttribute;
+use crate::util::Util;
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone, Timelike};
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+pub const ID_PROPERTY: &str = "OM_ID";
+pub const RELATION_ATTRIBUTE_PROPERTY: &str = "OM_RELATION_ATTRIBUTE";
+pub const BODY_TYPE_NAME: &str = Util::DESCRIPTION_TYPE_NAME;
+pub const SCHEDULED_TYPE_NAME: &str = "scheduled";
+pub const DEADLINE_TYPE_NAME: &str = "deadline";
+pub const DONE_TYPE_NAME: &str = "done";
+
+/// An org heading and what is under it.  The file itself is the heading at level 0, whose
+/// title is the #+TITLE.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OrgHeading {
+    pub level: usize,
+    /// Some(false) for TODO, Some(true) for DONE.
+    pub done: Option<bool>,
+    pub title: String,
+    /// From the OM_ID property: the entity id.
+    pub om_id: Option<i64>,
+    /// From the OM_ID property: the id of the instance the entity is in, if given.
+    pub om_instance_id: Option<String>,
+    /// From the OM_RELATION_ATTRIBUTE properties: the attributes of the relation from the
+    /// parent heading's entity to this one, as displayed.
+    pub relation_attributes: Vec<String>,
+    /// The other properties, in order, their values unescaped (see escape_property_value).
+    pub properties: Vec<(String, String)>,
+    pub scheduled: Option<i64>,
+    pub deadline: Option<i64>,
+    pub body: String,
+    pub children: Vec<OrgHeading>,
+}
+
+impl OrgHeading {
+    pub fn new(level_in: usize, title_in: String) -> OrgHeading {
+        OrgHeading {
+            level: level_in,
+            title: title_in,
+            ..Default::default()
+        }
+    }
+}
+
+pub fn parse_org(text: &str) -> OrgHeading {
+    let mut stack: Vec<OrgHeading> = vec![OrgHeading::new(0, String::new())];
+    let mut in_property_drawer = false;
+    for line in text.lines() {
+        let stars = line.chars().take_while(|c| *c == '*').count();
+        if stars > 0 && line[stars..].starts_with(' ') {
+            while stack.last().unwrap().level >= stars {
+                close_last_heading(&mut stack);
+            }
+            let mut heading = OrgHeading::new(stars, String::new());
+            let title = line[stars..].trim();
+            let (done, title) = match title.split_once(' ').unwrap_or((title, "")) {
+                ("TODO", rest) => (Some(false), rest.trim()),
+                ("DONE", rest) => (Some(true), rest.trim()),
+                _ => (None, title),
+            };
+            heading.done = done;
+            heading.title = title.to_string();
+            stack.push(heading);
+            in_property_drawer = false;
+            continue;
+        }
+        let heading = stack.last_mut().unwrap();
+        let trimmed = line.trim();
+        if in_property_drawer {
+            if trimmed.eq_ignore_ascii_case(":END:") {
+                in_property_drawer = false;
+            } else if let Some((key, value)) = trimmed.strip_prefix(':').and_then(|s| s.split_once(':')) {
+                let value = value.trim();
+                if key == ID_PROPERTY {
+                    let (instance_id, entity_id) = match value.rsplit_once(':') {
+                        Some((instance_id, entity_id)) => (Some(instance_id.to_string()), entity_id),
+                        None => (None, value),
+                    };
+                    heading.om_id = entity_id.trim().parse().ok();
+                    heading.om_instance_id = instance_id.filter(|_| heading.om_id.is_some());
+                } else if key == RELATION_ATTRIBUTE_PROPERTY {
+                    heading.relation_attributes.push(unescape_property_value(value));
+                } else {
+                    heading.properties.push((key.to_string(), unescape_property_value(value)));
+                }
+            }
+            continue;
+        }
+        // (Planning lines and the property drawer come right after the heading, before any text.)
+        let before_body = heading.body.trim().is_empty();
+        if before_body && trimmed.eq_ignore_ascii_case(":PROPERTIES:") {
+            in_property_drawer = true;
+        } else if before_body && heading.level > 0 && is_planning_line(trimmed) {
+            heading.scheduled = heading.scheduled.or(find_planning_date(trimmed, "SCHEDULED:"));
+            heading.deadline = heading.deadline.or(find_planning_date(trimmed, "DEADLINE:"));
+        } else if heading.level == 0 && trimmed.starts_with("#+") {
+            if let Some((keyword, value)) = trimmed[2..].split_once(':') {
+                if keyword.eq_ignore_ascii_case("TITLE") {
+                    heading.title = value.trim().to_string();
+                }
+            }
+            // (Other file settings are ignored.)
+        } else {
+            heading.body.push_str(&unescape_body_line(line));
+            heading.body.push('\n');
+        }
+    }
+    while stack.len() > 1 {
+        close_last_heading(&mut stack);
+    }
+    let mut root = stack.pop().unwrap();
+    root.body = trim_blank_lines(&root.body);
+    root
+}
+
+fn close_last_heading(stack: &mut Vec<OrgHeading>) {
+    let mut heading = stack.pop().unwrap();
+    heading.body = trim_blank_lines(&heading.body);
+    stack.last_mut().unwrap().children.push(heading);
+}
+
+fn trim_blank_lines(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().map(|l| l.trim_end()).collect();
+    let Some(first) = lines.iter().position(|l| !l.is_empty()) else {
+        return String::new();
+    };
+    let last = lines.iter().rposition(|l| !l.is_empty()).unwrap();
+    lines[first..=last].join("\n")
+}
+
+fn is_planning_line(line: &str) -> bool {
+    ["SCHEDULED:", "DEADLINE:", "CLOSED:"].iter().any(|k| line.starts_with(k))
+}
+
+fn find_planning_date(line: &str, keyword: &str) -> Option<i64> {
+    let after = &line[line.find(keyword)? + keyword.len()..];
+    let start = after.find(['<', '['])?;
+    let end = after[start..].find(['>', ']'])? + start;
+    parse_org_timestamp(&after[start..=end])
+}
+
+/// Like "<2025-01-15 Wed>" or "<2025-01-15 Wed 10:30>", in local time.
+pub fn format_org_timestamp(millis_in: i64) -> String {
+    match Local.timestamp_millis_opt(millis_in).single() {
+        Some(dt) if dt.hour() == 0 && dt.minute() == 0 => dt.format("<%Y-%m-%d %a>").to_string(),
+        Some(dt) => dt.format("<%Y-%m-%d %a %H:%M>").to_string(),
+        None => format!("<{}>", millis_in),
+    }
+}
+
+/// Reads an active or inactive org timestamp, ignoring any day name, repeater or end time.
+pub fn parse_org_timestamp(text_in: &str) -> Option<i64> {
+    let text = text_in.trim();
+    let inner = text
+        .strip_prefix('<')
+        .and_then(|t| t.strip_suffix('>'))
+        .or_else(|| text.strip_prefix('[').and_then(|t| t.strip_suffix(']')))?;
+    let mut parts = inner.split_whitespace();
+    let date = NaiveDate::parse_from_str(parts.next()?, "%Y-%m-%d").ok()?;
+    let time = parts
+        .find_map(|p| NaiveTime::parse_from_str(p.split('-').next().unwrap_or(p), "%H:%M").ok())
+        .unwrap_or_default();
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|dt| dt.timestamp_millis())
+}
+
+/// Property values are one line, so line breaks (and so backslashes) are escaped.
+fn escape_property_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_property_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                result.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                result.push('\\');
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// As org does in source blocks: a comma before lines that would otherwise be read as headings
+/// or settings (and before those already starting with commas, so it can be undone).
+fn escape_body_line(line: &str) -> String {
+    let rest = line.trim_start_matches(',');
+    if rest.starts_with('*') || rest.starts_with("#+") {
+        format!(",{}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+fn unescape_body_line(line: &str) -> String {
+    let rest = line.trim_start_matches(',');
+    if line.starts_with(',') && (rest.starts_with('*') || rest.starts_with("#+")) {
+        line[1..].to_string()
+    } else {
+        line.to_string()
+    }
+}
+
+/// Property keys cannot contain spaces (nor colons).
+fn to_property_key(type_name: &str) -> String {
+    type_name
+        .chars()
+        .map(|c| if c.is_whitespace() || c == ':' { '_' } else { c })
+        .collect()
+}
+
+pub fn to_org_text(root: &OrgHeading) -> String {
+    let mut out = String::new();
+    write_heading(root, &mut out);
+    out
+}
+
+fn write_heading(heading: &OrgHeading, out: &mut String) {
+    if heading.level == 0 {
+        out.push_str(&format!("#+TITLE: {}\n", heading.title));
+    } else {
+        let keyword = match heading.done {
+            Some(false) => "TODO ",
+            Some(true) => "DONE ",
+            None => "",
+        };
+        out.push_str(&format!("{} {}{}\n", "*".repeat(heading.level), keyword, heading.title));
+        let mut planning: Vec<String> = Vec::new();
+        if let Some(date) = heading.scheduled {
+            planning.push(format!("SCHEDULED: {}", format_org_timestamp(date)));
+        }
+        if let Some(date) = heading.deadline {
+            planning.push(format!("DEADLINE: {}", format_org_timestamp(date)));
+        }
+        if !planning.is_empty() {
+            out.push_str(&format!("{}\n", planning.join(" ")));
+        }
+    }
+    if heading.om_id.is_some() || !heading.relation_attributes.is_empty() || !heading.properties.is_empty() {
+        out.push_str(":PROPERTIES:\n");
+        if let Some(id) = heading.om_id {
+            match &heading.om_instance_id {
+                Some(instance_id) => out.push_str(&format!(":{}: {}:{}\n", ID_PROPERTY, instance_id, id)),
+                None => out.push_str(&format!(":{}: {}\n", ID_PROPERTY, id)),
+            }
+        }
+        for value in &heading.relation_attributes {
+            out.push_str(&format!(":{}: {}\n", RELATION_ATTRIBUTE_PROPERTY, escape_property_value(value)));
+        }
+        for (key, value) in &heading.properties {
+            out.push_str(&format!(":{}: {}\n", key, escape_property_value(value)));
+        }
+        out.push_str(":END:\n");
+    }
+    if !heading.body.is_empty() {
+        for line in heading.body.lines() {
+            out.push_str(&escape_body_line(line));
+            out.push('\n');
+        }
+    }
+    for child in &heading.children {
+        write_heading(child, out);
+    }
+}
+
+/// Reads the entity and what it contains (as allowed by options_in) into headings.  An entity
+/// reachable more than one way is written in full only the first time, then as just its title
+/// and OM_ID, so nothing is duplicated and cycles end.
+pub fn export_org(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    entity_id_in: i64,
+    options_in: &ExportOptions,
+) -> Result<OrgHeading, anyhow::Error> {
+    let instance_id = db.borrow().id(transaction.clone())?;
+    let mut exporter = OrgExporter {
+        db,
+        transaction,
+        instance_id,
+        options: options_in,
+        type_names: HashMap::new(),
+        written: HashSet::new(),
+    };
+    exporter.read_heading(entity_id_in, 0)
+}
+
+struct OrgExporter<'a, 't> {
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<'t, Postgres>>>>,
+    /// This (local) instance's id, for the OM_IDs.
+    instance_id: String,
+    options: &'a ExportOptions,
+    type_names: HashMap<i64, String>,
+    written: HashSet<i64>,
+}
+
+impl OrgExporter<'_, '_> {
+    fn get_type_name(&mut self, type_id_in: i64) -> Result<String, anyhow::Error> {
+        if let Some(name) = self.type_names.get(&type_id_in) {
+            return Ok(name.clone());
+        }
+        let name = Entity::new2(self.db.clone(), self.transaction.clone(), type_id_in)?
+            .get_name(self.transaction.clone())?;
+        self.type_names.insert(type_id_in, name.clone());
+        Ok(name)
+    }
+
+    fn read_heading(&mut self, entity_id_in: i64, depth_in: usize) -> Result<OrgHeading, anyhow::Error> {
+        self.written.insert(entity_id_in);
+        let mut entity = Entity::new2(self.db.clone(), self.transaction.clone(), entity_id_in)?;
+        let mut heading = OrgHeading::new(depth_in, entity.get_name(self.transaction.clone())?);
+        heading.om_id = Some(entity_id_in);
+        heading.om_instance_id = Some(self.instance_id.clone());
+        let children_allowed = self.options.levels.is_none_or(|levels| depth_in < levels);
+        let (attribute_tuples, _) = entity.get_sorted_attributes(self.transaction.clone(), 0, 0, false)?;
+        for (_, attribute) in attribute_tuples {
+            let mut attribute = attribute.borrow_mut();
+            let type_id = attribute.get_attr_type_id(self.transaction.clone())?;
+            // (The root is the file, which has no keyword or planning line, so its "done" and dates
+            // are properties.)
+            let special = depth_in > 0;
+            if let Some(text_attribute) = attribute.as_any_mut().downcast_mut::<TextAttribute>() {
+                let text = text_attribute.get_text(self.transaction.clone())?;
+                let type_name = self.get_type_name(type_id)?;
+                if type_name == BODY_TYPE_NAME && heading.body.is_empty() && !text.trim().is_empty() {
+                    heading.body = trim_blank_lines(&text);
+                } else {
+                    heading.properties.push((to_property_key(&type_name), text));
+                }
+            } else if let Some(date_attribute) = attribute.as_any_mut().downcast_mut::<DateAttribute>() {
+                let date = date_attribute.get_date(self.transaction.clone())?;
+                let type_name = self.get_type_name(type_id)?;
+                if special && type_name == SCHEDULED_TYPE_NAME && heading.scheduled.is_none() {
+                    heading.scheduled = Some(date);
+                } else if special && type_name == DEADLINE_TYPE_NAME && heading.deadline.is_none() {
+                    heading.deadline = Some(date);
+                } else {
+                    heading.properties.push((to_property_key(&type_name), format_org_timestamp(date)));
+                }
+            } else if let Some(boolean_attribute) = attribute.as_any_mut().downcast_mut::<BooleanAttribute>() {
+                let value = boolean_attribute.get_boolean(self.transaction.clone())?;
+                let type_name = self.get_type_name(type_id)?;
+                if special && type_name == DONE_TYPE_NAME && heading.done.is_none() {
+                    heading.done = Some(value);
+                } else {
+                    let value = if value { "t" } else { "nil" };
+                    heading.properties.push((to_property_key(&type_name), value.to_string()));
+                }
+            } else if let Some(rtle) = attribute.as_any_mut().downcast_mut::<RelationToLocalEntity>() {
+                if children_allowed {
+                    if let Some(mut child) = self.read_child(rtle.get_related_id2(), depth_in + 1)? {
+                        child.relation_attributes = get_relation_attribute_descriptions(
+                            self.db.clone(),
+                            self.transaction.clone(),
+                            rtle.get_form_id()?,
+                            rtle.get_id(),
+                        )?;
+                        heading.children.push(child);
+                    }
+                }
+            } else if let Some(rtg) = attribute.as_any_mut().downcast_mut::<RelationToGroup>() {
+                if children_allowed {
+                    let group_id = rtg.get_group_id(self.transaction.clone())?;
+                    let entry_ids =
+                        self.db.borrow().get_group_entry_ids(self.transaction.clone(), group_id, 0, None)?;
+                    for entry_id in entry_ids {
+                        if let Some(child) = self.read_child(entry_id, depth_in + 1)? {
+                            heading.children.push(child);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(heading)
+    }
+
+    fn read_child(&mut self, entity_id_in: i64, depth_in: usize) -> Result<Option<OrgHeading>, anyhow::Error> {
+        let mut entity = Entity::new2(self.db.clone(), self.transaction.clone(), entity_id_in)?;
+        if !self.options.allows(entity.get_public(self.transaction.clone())?) {
+            return Ok(None);
+        }
+        if self.written.contains(&entity_id_in) {
+            let mut heading = OrgHeading::new(depth_in, entity.get_name(self.transaction.clone())?);
+            heading.om_id = Some(entity_id_in);
+            heading.om_instance_id = Some(self.instance_id.clone());
+            return Ok(Some(heading));
+        }
+        Ok(Some(self.read_heading(entity_id_in, depth_in)?))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct OrgImportCounts {
+    pub created: usize,
+    pub updated: usize,
+}
+
+/// Imports the headings from parse_org into the database.  A heading whose OM_ID is of an
+/// existing entity in this instance updates it; others become new entities in a group on their parent heading's
+/// entity.  The file (root heading) is likewise an existing entity, or if it has a title a new
+/// one that containing_entity_id_in "has", or else containing_entity_id_in itself.
+pub fn import_org(
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    containing_entity_id_in: i64,
+    root_in: &OrgHeading,
+    make_them_public_in: Option<bool>,
+    observation_date_in: i64,
+) -> Result<OrgImportCounts, anyhow::Error> {
+    let has_relation_type_id =
+        db.borrow().find_relation_type(transaction.clone(), Util::THE_HAS_RELATION_TYPE_NAME)?;
+    let instance_id = db.borrow().id(transaction.clone())?;
+    let mut importer = OrgImporter {
+        db,
+        transaction,
+        instance_id,
+        make_them_public: make_them_public_in,
+        observation_date: observation_date_in,
+        has_relation_type_id,
+        type_ids: HashMap::new(),
+        counts: OrgImportCounts::default(),
+    };
+    let root_entity_id = match importer.find_existing(root_in)? {
+        Some(id) => {
+            importer.update_entity(id, root_in, true)?;
+            id
+        }
+        None if !root_in.title.trim().is_empty() => {
+            let (id, _, _) = importer.db.borrow().create_entity_and_add_has_local_relation_to_it(
+                importer.transaction.clone(),
+                containing_entity_id_in,
+                root_in.title.trim(),
+                observation_date_in,
+                make_them_public_in,
+            )?;
+            importer.counts.created += 1;
+            importer.update_entity(id, root_in, false)?;
+            id
+        }
+        None => {
+            importer.update_entity(containing_entity_id_in, root_in, false)?;
+            containing_entity_id_in
+        }
+    };
+    importer.import_children(root_entity_id, &root_in.children)?;
+    Ok(importer.counts)
+}
+
+/// A value for an attribute, from a heading.
+#[derive(Clone)]
+enum OrgValue {
+    Text(String),
+    Date(i64),
+    Boolean(bool),
+    /// From a property, so it could be any of the above.
+    Property(String),
+}
+
+struct OrgImporter<'t> {
+    db: Rc<RefCell<dyn Database>>,
+    transaction: Option<Rc<RefCell<Transaction<'t, Postgres>>>>,
+    /// This (local) instance's id: only OM_IDs with it are of entities here.
+    instance_id: String,
+    make_them_public: Option<bool>,
+    observation_date: i64,
+    has_relation_type_id: i64,
+    type_ids: HashMap<String, i64>,
+    counts: OrgImportCounts,
+}
+
+impl OrgImporter<'_> {
+    /// The entity heading_in was exported from, if it is in this instance and still exists.
+    fn find_existing(&self, heading_in: &OrgHeading) -> Result<Option<i64>, anyhow::Error> {
+        if heading_in.om_instance_id.as_deref() != Some(self.instance_id.as_str()) {
+            return Ok(None);
+        }
+        match heading_in.om_id {
+            Some(id) if self.db.borrow().entity_key_exists(self.transaction.clone(), id, true)? => Ok(Some(id)),
+            _ => Ok(None),
+        }
+    }
+
+    fn import_children(&mut self, parent_id_in: i64, children_in: &[OrgHeading]) -> Result<(), anyhow::Error> {
+        let mut group_id: Option<i64> = None;
+        for child in children_in {
+            let child_id = match self.find_existing(child)? {
+                Some(id) => {
+                    self.update_entity(id, child, true)?;
+                    id
+                }
+                None => {
+                    let group_id = match group_id {
+                        Some(id) => id,
+                        None => *group_id.insert(self.get_or_create_group(parent_id_in)?),
+                    };
+                    let db = self.db.borrow();
+                    let id =
+                        db.create_entity(self.transaction.clone(), child.title.trim(), None, self.make_them_public)?;
+                    db.add_entity_to_group(self.transaction.clone(), group_id, id, None)?;
+                    drop(db);
+                    self.counts.created += 1;
+                    self.update_entity(id, child, false)?;
+                    id
+                }
+            };
+            self.import_children(child_id, &child.children)?;
+        }
+        Ok(())
+    }
+
+    /// The entity's first group, or a new one named like it.
+    fn get_or_create_group(&self, entity_id_in: i64) -> Result<i64, anyhow::Error> {
+        let db = self.db.borrow();
+        let (_, _, group_id, _, _) =
+            db.find_relation_to_and_group_on_entity(self.transaction.clone(), entity_id_in, None)?;
+        if let Some(id) = group_id {
+            return Ok(id);
+        }
+        let name = Entity::new2(self.db.clone(), self.transaction.clone(), entity_id_in)?
+            .get_name(self.transaction.clone())?;
+        let (id, _) = db.create_group_and_relation_to_group(
+            self.transaction.clone(),
+            entity_id_in,
+            self.has_relation_type_id,
+            &name,
+            false,
+            None,
+            self.observation_date,
+            None,
+        )?;
+        Ok(id)
+    }
+
+    /// The attribute type (an entity) with the name, or the property key with "_" as spaces (see
+    /// to_property_key), else a new one.
+    fn get_type_id(&mut self, name_in: &str) -> Result<i64, anyhow::Error> {
+        if let Some(id) = self.type_ids.get(name_in) {
+            return Ok(*id);
+        }
+        let db = self.db.borrow();
+        let mut candidates = vec![name_in.to_string()];
+        if name_in.contains('_') {
+            candidates.push(name_in.replace('_', " "));
+        }
+        let mut found: Option<i64> = None;
+        for candidate in candidates {
+            found = Util::find_entity_id_by_name(&*db, self.transaction.clone(), &candidate)?;
+            if found.is_some() {
+                break;
+            }
+        }
+        let id = match found {
+            Some(id) => id,
+            None => db.create_entity(self.transaction.clone(), name_in, None, None)?,
+        };
+        drop(db);
+        self.type_ids.insert(name_in.to_string(), id);
+        Ok(id)
+    }
+
+    fn update_entity(&mut self, entity_id_in: i64, heading_in: &OrgHeading, existing_in: bool) -> Result<(), anyhow::Error> {
+        let tx = self.transaction.clone();
+        let mut entity = Entity::new2(self.db.clone(), tx.clone(), entity_id_in)?;
+        let title = heading_in.title.trim();
+        if existing_in {
+            self.counts.updated += 1;
+            if !title.is_empty() && entity.get_name(tx.clone())? != title {
+                self.db.borrow().update_entity_only_name(tx.clone(), entity_id_in, title)?;
+            }
+        }
+
+        let mut values: Vec<(String, OrgValue)> = Vec::new();
+        if let Some(done) = heading_in.done {
+            values.push((DONE_TYPE_NAME.to_string(), OrgValue::Boolean(done)));
+        }
+        if let Some(date) = heading_in.scheduled {
+            values.push((SCHEDULED_TYPE_NAME.to_string(), OrgValue::Date(date)));
+        }
+        if let Some(date) = heading_in.deadline {
+            values.push((DEADLINE_TYPE_NAME.to_string(), OrgValue::Date(date)));
+        }
+        if !heading_in.body.is_empty() {
+            values.push((BODY_TYPE_NAME.to_string(), OrgValue::Text(heading_in.body.clone())));
+        }
+        for (key, value) in &heading_in.properties {
+            values.push((key.clone(), OrgValue::Property(value.clone())));
+        }
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        // The entity's text, date and boolean attributes by type, so that the nth value of a type
+        // updates the nth such attribute.
+        let mut existing: HashMap<i64, Vec<Rc<RefCell<dyn Attribute>>>> = HashMap::new();
+        let (attribute_tuples, _) = entity.get_sorted_attributes(tx.clone(), 0, 0, false)?;
+        for (_, attribute) in attribute_tuples {
+            let type_id = {
+                let mut a = attribute.borrow_mut();
+                let any = a.as_any_mut();
+                if !(any.is::<TextAttribute>() || any.is::<DateAttribute>() || any.is::<BooleanAttribute>()) {
+                    continue;
+                }
+                a.get_attr_type_id(tx.clone())?
+            };
+            existing.entry(type_id).or_default().push(attribute);
+        }
+        let mut used: HashMap<i64, usize> = HashMap::new();
+        for (type_name, value) in values {
+            let type_id = self.get_type_id(&type_name)?;
+            let index = used.entry(type_id).or_insert(0);
+            let attribute = existing.get(&type_id).and_then(|v| v.get(*index)).cloned();
+            *index += 1;
+            match attribute {
+                Some(attribute) => self.update_attribute(&mut *attribute.borrow_mut(), type_id, &value)?,
+                None => self.create_attribute(entity_id_in, type_id, &value)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn update_attribute(&self, attribute_in: &mut dyn Attribute, type_id_in: i64, value_in: &OrgValue) -> Result<(), anyhow::Error> {
+        let tx = self.transaction.clone();
+        let any = attribute_in.as_any_mut();
+        if let Some(text_attribute) = any.downcast_mut::<TextAttribute>() {
+            let text = match value_in {
+                OrgValue::Text(s) | OrgValue::Property(s) => s.clone(),
+                OrgValue::Date(d) => format_org_timestamp(*d),
+                OrgValue::Boolean(b) => if *b { "t" } else { "nil" }.to_string(),
+            };
+            if text_attribute.get_text(tx.clone())? != text {
+                text_attribute.update(tx, type_id_in, &text, None, self.observation_date)?;
+            }
+        } else if let Some(date_attribute) = any.downcast_mut::<DateAttribute>() {
+            let date = match value_in {
+                OrgValue::Date(d) => Some(*d),
+                OrgValue::Property(s) => parse_org_timestamp(s),
+                _ => None,
+            };
+            if let Some(date) = date {
+                if date_attribute.get_date(tx.clone())? != date {
+                    date_attribute.update(tx, type_id_in, date)?;
+                }
+            }
+        } else if let Some(boolean_attribute) = any.downcast_mut::<BooleanAttribute>() {
+            let value = match value_in {
+                OrgValue::Boolean(b) => Some(*b),
+                OrgValue::Property(s) if s == "t" => Some(true),
+                OrgValue::Property(s) if s == "nil" => Some(false),
+                _ => None,
+            };
+            if let Some(value) = value {
+                if boolean_attribute.get_boolean(tx.clone())? != value {
+                    boolean_attribute.update(tx, type_id_in, value, None, self.observation_date)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn create_attribute(&self, entity_id_in: i64, type_id_in: i64, value_in: &OrgValue) -> Result<(), anyhow::Error> {
+        let db = self.db.borrow();
+        let tx = self.transaction.clone();
+        let value = match value_in {
+            OrgValue::Property(s) => {
+                // A property only says what text it has, so its attribute type's other uses (if
+                // any) decide whether it is a date or boolean, as exported.
+                let forms = db.get_attribute_type_forms(tx.clone(), type_id_in)?;
+                let is_only = |form: &str| forms.iter().any(|f| f == form) && !forms.iter().any(|f| f == Util::TEXT_TYPE);
+                match parse_org_timestamp(s) {
+                    Some(date) if is_only(Util::DATE_TYPE) => OrgValue::Date(date),
+                    _ if is_only(Util::BOOLEAN_TYPE) && (s == "t" || s == "nil") => OrgValue::Boolean(s == "t"),
+                    _ => OrgValue::Text(s.clone()),
+                }
+            }
+            _ => value_in.clone(),
+        };
+        match value {
+            OrgValue::Boolean(b) => {
+                db.create_boolean_attribute(tx, entity_id_in, type_id_in, b, None, self.observation_date, None)?;
+            }
+            OrgValue::Date(date) => {
+                db.create_date_attribute(tx, entity_id_in, type_id_in, date, None)?;
+            }
+            OrgValue::Text(s) | OrgValue::Property(s) => {
+                db.create_text_attribute(tx, entity_id_in, type_id_in, &s, None, self.observation_date, None)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+
+    #[test]
+    fn test_parse_and_write_org() {
+        let text = "#+TITLE: Projects\n\
+            #+STARTUP: overview\n\
+            :PROPERTIES:\n\
+            :OM_ID: 5\n\
+            :END:\n\
+            Intro text.\n\
+            * TODO Write the report\n\
+            SCHEDULED: <2025-01-15 Wed> DEADLINE: <2025-01-20 Mon 17:00>\n\
+            :PROPERTIES:\n\
+            :OM_ID: 2f1c-instance:7\n\
+            :owner: Pat\n\
+            :notes: two\\nlines\n\
+            :END:\n\
+            \n\
+            Some body\n\
+            ,* not a heading\n\
+            ** DONE Outline\n\
+            * Later\n";
+        let root = parse_org(text);
+        assert_eq!(root.title, "Projects");
+        assert_eq!(root.om_id, Some(5));
+        assert_eq!(root.body, "Intro text.");
+        assert_eq!(root.children.len(), 2);
+        let report = &root.children[0];
+        assert_eq!(report.title, "Write the report");
+        assert_eq!(report.done, Some(false));
+        assert_eq!(report.om_id, Some(7));
+        assert_eq!(report.om_instance_id.as_deref(), Some("2f1c-instance"));
+        // (A bare id, as from a hand-written file, has no instance.)
+        assert_eq!(root.om_instance_id, None);
+        assert_eq!(
+            report.properties,
+            vec![
+                ("owner".to_string(), "Pat".to_string()),
+                ("notes".to_string(), "two\nlines".to_string())
+            ]
+        );
+        assert_eq!(report.scheduled, parse_org_timestamp("<2025-01-15>"));
+        assert_eq!(report.deadline, parse_org_timestamp("[2025-01-20 Mon 17:00]"));
+        assert_eq!(report.body, "Some body\n* not a heading");
+        assert_eq!(report.children[0].title, "Outline");
+        assert_eq!(report.children[0].done, Some(true));
+        assert_eq!(root.children[1].title, "Later");
+        assert_eq!(root.children[1].done, None);
+
+        // Writing it back and reading it again loses nothing (only the ignored #+STARTUP):
+        let written = to_org_text(&root);
+        assert!(written.contains("SCHEDULED: <2025-01-15 Wed> DEADLINE: <2025-01-20 Mon 17:00>\n"));
+        assert!(written.contains(":notes: two\\nlines\n"));
+        assert!(written.contains("\n,* not a heading\n"));
+        assert_eq!(parse_org(&written), root);
+    }
+
+    #[test]
+    fn test_export_and_reimport_org() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let top = db_b.create_entity(tx.clone(), "org top", None, None).unwrap();
+        let options = ExportOptions {
+            levels: None,
+            include_public: true,
+            include_non_public: true,
+            include_unspecified: true,
+        };
+        let file = parse_org(
+            "* TODO org task\n\
+            DEADLINE: <2025-03-01 Sat>\n\
+            :PROPERTIES:\n\
+            :org_owner: Pat\n\
+            :END:\n\
+            The details.\n\
+            ** org subtask\n",
+        );
+        let counts = import_org(db_dyn.clone(), tx.clone(), top, &file, None, 0).unwrap();
+        assert_eq!(counts, OrgImportCounts { created: 2, updated: 0 });
+
+        let exported = export_org(db_dyn.clone(), tx.clone(), top, &options).unwrap();
+        // (Exporting writes nothing, not even the URI and quote classes it looks for.)
+        assert!(db_b.find_class_and_template_entity(tx.clone(), "URI").unwrap().is_none());
+        assert!(db_b.find_class_and_template_entity(tx.clone(), "quote").unwrap().is_none());
+        assert_eq!(exported.om_id, Some(top));
+        assert_eq!(exported.om_instance_id, Some(db_b.id(tx.clone()).unwrap()));
+        let task = &exported.children[0];
+        assert_eq!(task.title, "org task");
+        assert_eq!(task.done, Some(false));
+        assert_eq!(task.deadline, parse_org_timestamp("<2025-03-01>"));
+        assert_eq!(task.body, "The details.");
+        assert_eq!(task.properties, vec![("org_owner".to_string(), "Pat".to_string())]);
+        assert_eq!(task.children[0].title, "org subtask");
+
+        // Edit the exported text and re-import it: the same entities are updated, one is added.
+        let text = to_org_text(&exported)
+            .replace("TODO org task", "DONE org task (renamed)")
+            .replace("The details.", "New details.")
+            + "* org new task\n";
+        let counts = import_org(db_dyn.clone(), tx.clone(), top, &parse_org(&text), None, 0).unwrap();
+        assert_eq!(counts, OrgImportCounts { created: 1, updated: 3 });
+        let again = export_org(db_dyn.clone(), tx.clone(), top, &options).unwrap();
+        assert_eq!(again.children.len(), 2);
+        let task = &again.children[0];
+        assert_eq!(task.title, "org task (renamed)");
+        assert_eq!(task.done, Some(true));
+        assert_eq!(task.body, "New details.");
+        assert_eq!(task.properties.len(), 1);
+        assert_eq!(task.children.len(), 1);
+        assert_eq!(again.children[1].title, "org new task");
+
+        // From another instance (or with no instance), the ids are not of entities here, so
+        // nothing here is changed: they are all new.
+        let local_id = db_b.id(tx.clone()).unwrap();
+        let foreign = to_org_text(&again).replace(&format!(": {}:", local_id), ": some-other-instance:");
+        let counts = import_org(db_dyn.clone(), tx.clone(), top, &parse_org(&foreign), None, 0).unwrap();
+        assert_eq!(counts, OrgImportCounts { created: 4, updated: 0 });
+        let unqualified = to_org_text(&again).replace(&format!(": {}:", local_id), ": ");
+        let counts = import_org(db_dyn.clone(), tx.clone(), top, &parse_org(&unqualified), None, 0).unwrap();
+        assert_eq!(counts.updated, 0);
+        assert_eq!(Entity::new2(db_dyn, tx.clone(), top).unwrap().get_name(tx.clone()).unwrap(), "org top");
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+
+    #[test]
+    fn test_import_org_property_forms() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let top = db_b.create_entity(tx.clone(), "org forms top", None, None).unwrap();
+        // A type already used for dates makes a timestamp property a date; otherwise a property
+        // that only looks like a timestamp or boolean stays text.
+        let when_type = db_b.create_entity(tx.clone(), "org when type", None, None).unwrap();
+        db_b.create_date_attribute(tx.clone(), top, when_type, 0, None).unwrap();
+        let file = parse_org(
+            "* org forms task\n\
+            :PROPERTIES:\n\
+            :org_when_type: <2025-04-01 Tue>\n\
+            :org_note_type: <2025-04-01 Tue>\n\
+            :org_flag_type: t\n\
+            :END:\n",
+        );
+        import_org(db_dyn.clone(), tx.clone(), top, &file, None, 0).unwrap();
+        let forms_of = |name: &str| {
+            let id = Util::find_entity_id_by_name(&*db_dyn.borrow(), tx.clone(), name).unwrap().unwrap();
+            db_b.get_attribute_type_forms(tx.clone(), id).unwrap()
+        };
+        assert_eq!(forms_of("org when type"), vec![Util::DATE_TYPE.to_string()]);
+        assert_eq!(forms_of("org_note_type"), vec![Util::TEXT_TYPE.to_string()]);
+        assert_eq!(forms_of("org_flag_type"), vec![Util::TEXT_TYPE.to_string()]);
+        let exported = export_org(db_dyn.clone(), tx.clone(), top, &ExportOptions {
+            levels: None,
+            include_public: true,
+            include_non_public: true,
+            include_unspecified: true,
+        })
+        .unwrap();
+        let properties = &exported.children[0].properties;
+        assert!(properties.contains(&("org_note_type".to_string(), "<2025-04-01 Tue>".to_string())));
+        assert!(properties.contains(&("org_flag_type".to_string(), "t".to_string())));
+    }
+
+    #[test]
+    fn test_export_org_relation_attributes() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = Some(Rc::new(RefCell::new(db_b.begin_trans().unwrap())));
+        let top = db_b.create_entity(tx.clone(), "org employer", None, None).unwrap();
+        let (_, relation_id, _) =
+            db_b.create_entity_and_add_has_local_relation_to_it(tx.clone(), top, "org employee", 0, None).unwrap();
+        let form_id = db_b.get_attribute_form_id(Util::RELATION_TO_LOCAL_ENTITY_TYPE).unwrap();
+        let holder_id = db_b.get_or_create_relation_attribute_holder(tx.clone(), form_id, relation_id).unwrap();
+        // (Its type is one that exists outside tx, since get_display_string reads the type's name
+        // without it.)
+        let attr_type_id = db_b.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME).unwrap();
+        db_b.create_text_attribute(tx.clone(), holder_id, attr_type_id, "editor", None, 0, None).unwrap();
+        let options = ExportOptions {
+            levels: None,
+            include_public: true,
+            include_non_public: true,
+            include_unspecified: true,
+        };
+        let exported = export_org(db_dyn.clone(), tx.clone(), top, &options).unwrap();
+        let employee = &exported.children[0];
+        assert_eq!(employee.title, "org employee");
+        assert_eq!(employee.relation_attributes.len(), 1);
+        assert!(employee.relation_attributes[0].contains("editor"), "{:?}", employee.relation_attributes);
+        let text = to_org_text(&exported);
+        assert!(text.contains(&format!(":{}: ", RELATION_ATTRIBUTE_PROPERTY)), "{}", text);
+        let parsed = parse_org(&text);
+        assert_eq!(parsed.children[0].relation_attributes, employee.relation_attributes);
+        assert!(parsed.children[0].properties.is_empty());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
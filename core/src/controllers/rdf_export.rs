@@ -0,0 +1,561 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Writes exchange data (see model/exchange_format.rs) as RDF, in Turtle or JSON-LD, so it can
+//! be loaded into a triple store or reasoned over.
+//!
+//! Each entity's IRI is "urn:onemodel:<OmInstance id>:entity:<entity id>" (see entity_iri), so
+//! it stays the same across exports, and a relation to an entity in another instance can name
+//! that entity the same way its own instance does.  Attribute and relation types are used as
+//! predicates: an entity with a "has" relation to another gives <entity> <has> <other>, and a
+//! text attribute of type "serial number" gives <entity> <serial number> "the text".
+//! Quantities and files become blank nodes (with a number and unit, or the file details).  A
+//! relation's own attributes belong to an entity that is also made an rdf:Statement of it.  The
+//! valid-on and observation dates of attributes are not included; the JSON exchange format
+//! keeps those.
+use crate::model::exchange_format::{ExchangeAttributeValue, ExchangeData};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use std::collections::HashMap;
+
+pub const OM_NAMESPACE: &str = "http://www.onemodel.org/ns#";
+const RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const RDFS_NAMESPACE: &str = "http://www.w3.org/2000/01/rdf-schema#";
+const XSD_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema#";
+const OWL_NAMESPACE: &str = "http://www.w3.org/2002/07/owl#";
+const PREFIXES: [(&str, &str); 5] = [
+    ("rdf", RDF_NAMESPACE),
+    ("rdfs", RDFS_NAMESPACE),
+    ("xsd", XSD_NAMESPACE),
+    ("owl", OWL_NAMESPACE),
+    ("om", OM_NAMESPACE),
+];
+
+pub fn entity_iri(instance_id_in: &str, entity_id_in: i64) -> String {
+    format!("urn:onemodel:{}:entity:{}", instance_id_in, entity_id_in)
+}
+
+pub fn group_iri(instance_id_in: &str, group_id_in: i64) -> String {
+    format!("urn:onemodel:{}:group:{}", instance_id_in, group_id_in)
+}
+
+pub fn class_iri(instance_id_in: &str, class_id_in: i64) -> String {
+    format!("urn:onemodel:{}:class:{}", instance_id_in, class_id_in)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RdfTerm {
+    Iri(String),
+    /// A blank node's label (without the "_:").
+    Blank(String),
+    Literal {
+        value: String,
+        /// An IRI; None for a plain string.
+        datatype: Option<String>,
+    },
+    /// An RDF collection (an ordered list).
+    List(Vec<RdfTerm>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triple {
+    pub subject: RdfTerm,
+    pub predicate: String,
+    pub object: RdfTerm,
+}
+
+fn om(local_in: &str) -> String {
+    format!("{}{}", OM_NAMESPACE, local_in)
+}
+
+fn literal(value_in: impl ToString, datatype_in: Option<&str>) -> RdfTerm {
+    RdfTerm::Literal {
+        value: value_in.to_string(),
+        datatype: datatype_in.map(|d| format!("{}{}", XSD_NAMESPACE, d)),
+    }
+}
+
+fn date_literal(millis_in: i64) -> RdfTerm {
+    match Utc.timestamp_millis_opt(millis_in).single() {
+        Some(date) => literal(date.to_rfc3339_opts(SecondsFormat::Millis, true), Some("dateTime")),
+        None => literal(millis_in, Some("long")),
+    }
+}
+
+/// The statements for data_in, with entities of this instance (instance_id_in) named by
+/// entity_iri.  Statements about each subject are together, in the order of its attributes.
+pub fn to_rdf_graph(data_in: &ExchangeData, instance_id_in: &str) -> Vec<Triple> {
+    let mut triples: Vec<Triple> = Vec::new();
+    let rdf_type = format!("{}type", RDF_NAMESPACE);
+    let rdfs_label = format!("{}label", RDFS_NAMESPACE);
+    let entity = |id: i64| RdfTerm::Iri(entity_iri(instance_id_in, id));
+    let mut add = |subject: &RdfTerm, predicate: &str, object: RdfTerm| {
+        triples.push(Triple {
+            subject: subject.clone(),
+            predicate: predicate.to_string(),
+            object,
+        })
+    };
+
+    for class in &data_in.classes {
+        let subject = RdfTerm::Iri(class_iri(instance_id_in, class.id));
+        add(&subject, &rdf_type, RdfTerm::Iri(format!("{}Class", RDFS_NAMESPACE)));
+        add(&subject, &rdfs_label, literal(&class.name, None));
+        add(&subject, &om("templateEntity"), entity(class.defining_entity_id));
+        if let Some(parent_class_id) = class.parent_class_id {
+            add(
+                &subject,
+                &format!("{}subClassOf", RDFS_NAMESPACE),
+                RdfTerm::Iri(class_iri(instance_id_in, parent_class_id)),
+            );
+        }
+    }
+    let relation_types: HashMap<i64, _> = data_in.relation_types.iter().map(|rt| (rt.entity_id, rt)).collect();
+    let mut attributes_by_entity: HashMap<i64, Vec<_>> = HashMap::new();
+    for attribute in &data_in.attributes {
+        attributes_by_entity.entry(attribute.entity_id).or_default().push(attribute);
+    }
+    // Attribute types that are not relation types, to say they are properties:
+    let mut attribute_type_ids: Vec<i64> = data_in
+        .attributes
+        .iter()
+        .map(|a| a.attr_type_id)
+        .filter(|id| !relation_types.contains_key(id))
+        .collect();
+    attribute_type_ids.sort();
+    attribute_type_ids.dedup();
+
+    let mut blank_count = 0;
+    for e in &data_in.entities {
+        let subject = entity(e.id);
+        add(&subject, &rdf_type, RdfTerm::Iri(om("Entity")));
+        if let Some(relation_type) = relation_types.get(&e.id) {
+            add(&subject, &rdf_type, RdfTerm::Iri(om("RelationType")));
+            add(&subject, &rdf_type, RdfTerm::Iri(format!("{}ObjectProperty", OWL_NAMESPACE)));
+            if relation_type.directionality == "BI" || relation_type.symmetric {
+                add(&subject, &rdf_type, RdfTerm::Iri(format!("{}SymmetricProperty", OWL_NAMESPACE)));
+            }
+            if relation_type.transitive {
+                add(&subject, &rdf_type, RdfTerm::Iri(format!("{}TransitiveProperty", OWL_NAMESPACE)));
+            }
+            if let Some(inverse_id) = relation_type.inverse_of_id {
+                add(&subject, &format!("{}inverseOf", OWL_NAMESPACE), entity(inverse_id));
+            }
+            // (Several classes given as a domain or range would mean their intersection, not
+            // any of them, so those are left out.)
+            if let [class_id] = relation_type.source_class_ids[..] {
+                add(
+                    &subject,
+                    &format!("{}domain", RDFS_NAMESPACE),
+                    RdfTerm::Iri(class_iri(instance_id_in, class_id)),
+                );
+            }
+            if let [class_id] = relation_type.target_class_ids[..] {
+                add(
+                    &subject,
+                    &format!("{}range", RDFS_NAMESPACE),
+                    RdfTerm::Iri(class_iri(instance_id_in, class_id)),
+                );
+            }
+        } else if attribute_type_ids.binary_search(&e.id).is_ok() {
+            add(&subject, &rdf_type, RdfTerm::Iri(format!("{}Property", RDF_NAMESPACE)));
+        }
+        if let Some(class_id) = e.class_id {
+            add(&subject, &rdf_type, RdfTerm::Iri(class_iri(instance_id_in, class_id)));
+        }
+        add(&subject, &rdfs_label, literal(&e.name, None));
+        if let Some(relation_type) = relation_types.get(&e.id) {
+            if !relation_type.name_in_reverse_direction.is_empty() {
+                add(
+                    &subject,
+                    &om("nameInReverseDirection"),
+                    literal(&relation_type.name_in_reverse_direction, None),
+                );
+            }
+            add(&subject, &om("directionality"), literal(&relation_type.directionality, None));
+        }
+        if e.reference {
+            continue;
+        }
+        if let Some(public) = e.public {
+            add(&subject, &om("public"), literal(public, Some("boolean")));
+        }
+        if e.archived {
+            add(&subject, &om("archived"), literal(true, Some("boolean")));
+        }
+        for attribute in attributes_by_entity.get(&e.id).into_iter().flatten() {
+            let predicate = entity_iri(instance_id_in, attribute.attr_type_id);
+            let mut relation_object: Option<RdfTerm> = None;
+            match &attribute.value {
+                ExchangeAttributeValue::Quantity { unit_id, number, .. } => {
+                    blank_count += 1;
+                    let node = RdfTerm::Blank(format!("q{}", blank_count));
+                    add(&subject, &predicate, node.clone());
+                    add(&node, &rdf_type, RdfTerm::Iri(om("QuantityValue")));
+                    add(&node, &om("number"), literal(number, Some("double")));
+                    add(&node, &om("unit"), entity(*unit_id));
+                }
+                ExchangeAttributeValue::Date { date } => add(&subject, &predicate, date_literal(*date)),
+                ExchangeAttributeValue::Boolean { value, .. } => {
+                    if let Some(value) = value {
+                        add(&subject, &predicate, literal(value, Some("boolean")));
+                    }
+                }
+                ExchangeAttributeValue::File {
+                    description,
+                    original_file_date,
+                    original_file_path,
+                    size,
+                    md5hash,
+                    ..
+                } => {
+                    blank_count += 1;
+                    let node = RdfTerm::Blank(format!("f{}", blank_count));
+                    add(&subject, &predicate, node.clone());
+                    add(&node, &rdf_type, RdfTerm::Iri(om("File")));
+                    add(&node, &rdfs_label, literal(description, None));
+                    add(&node, &om("originalFilePath"), literal(original_file_path, None));
+                    add(&node, &om("originalFileDate"), date_literal(*original_file_date));
+                    add(&node, &om("size"), literal(size, Some("long")));
+                    add(&node, &om("md5hash"), literal(md5hash, None));
+                }
+                ExchangeAttributeValue::Text { text, .. } => add(&subject, &predicate, literal(text, None)),
+                ExchangeAttributeValue::RelationToLocalEntity { entity_id_2, .. } => {
+                    relation_object = Some(entity(*entity_id_2))
+                }
+                ExchangeAttributeValue::RelationToRemoteEntity {
+                    remote_instance_id,
+                    entity_id_2,
+                    ..
+                } => relation_object = Some(RdfTerm::Iri(entity_iri(remote_instance_id, *entity_id_2))),
+                ExchangeAttributeValue::RelationToGroup { group_id, .. } => {
+                    relation_object = Some(RdfTerm::Iri(group_iri(instance_id_in, *group_id)))
+                }
+            }
+            if let Some(object) = relation_object {
+                add(&subject, &predicate, object.clone());
+                // The entity holding the relation's own attributes (which are exported as its
+                // attributes) is made a reified statement of the relation.
+                if let Some(holder_id) = attribute.attributes_entity_id {
+                    let holder = entity(holder_id);
+                    add(&holder, &rdf_type, RdfTerm::Iri(format!("{}Statement", RDF_NAMESPACE)));
+                    add(&holder, &format!("{}subject", RDF_NAMESPACE), subject.clone());
+                    add(&holder, &format!("{}predicate", RDF_NAMESPACE), RdfTerm::Iri(predicate.clone()));
+                    add(&holder, &format!("{}object", RDF_NAMESPACE), object);
+                }
+            }
+        }
+    }
+    for group in &data_in.groups {
+        let subject = RdfTerm::Iri(group_iri(instance_id_in, group.id));
+        add(&subject, &rdf_type, RdfTerm::Iri(om("Group")));
+        add(&subject, &rdfs_label, literal(&group.name, None));
+        let mut entries = group.entries.clone();
+        entries.sort_by_key(|entry| entry.sorting_index);
+        add(
+            &subject,
+            &om("entries"),
+            RdfTerm::List(entries.iter().map(|entry| entity(entry.entity_id)).collect()),
+        );
+    }
+    triples
+}
+
+/// The subjects in order of first appearance, each with its (predicate, object) pairs.
+fn group_by_subject(triples_in: &[Triple]) -> Vec<(&RdfTerm, Vec<(&str, &RdfTerm)>)> {
+    let mut subjects: Vec<(&RdfTerm, Vec<(&str, &RdfTerm)>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for triple in triples_in {
+        let key = format!("{:?}", triple.subject);
+        let position = *positions.entry(key).or_insert_with(|| {
+            subjects.push((&triple.subject, Vec::new()));
+            subjects.len() - 1
+        });
+        subjects[position].1.push((triple.predicate.as_str(), &triple.object));
+    }
+    subjects
+}
+
+/// Uses a prefix (as "rdfs:label") where the rest is a plain name, else the whole IRI in <>.
+fn turtle_iri(iri_in: &str) -> String {
+    for (prefix, namespace) in PREFIXES {
+        if let Some(local) = iri_in.strip_prefix(namespace) {
+            if !local.is_empty() && local.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return format!("{}:{}", prefix, local);
+            }
+        }
+    }
+    format!("<{}>", iri_in.replace('>', "%3E"))
+}
+
+fn turtle_string(value_in: &str) -> String {
+    let mut escaped = String::with_capacity(value_in.len() + 2);
+    escaped.push('"');
+    for c in value_in.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn turtle_term(term_in: &RdfTerm) -> String {
+    match term_in {
+        RdfTerm::Iri(iri) => turtle_iri(iri),
+        RdfTerm::Blank(label) => format!("_:{}", label),
+        RdfTerm::Literal { value, datatype: None } => turtle_string(value),
+        RdfTerm::Literal {
+            value,
+            datatype: Some(datatype),
+        } => format!("{}^^{}", turtle_string(value), turtle_iri(datatype)),
+        RdfTerm::List(items) => format!(
+            "( {} )",
+            items.iter().map(turtle_term).collect::<Vec<String>>().join(" ")
+        ),
+    }
+}
+
+pub fn to_turtle(triples_in: &[Triple]) -> String {
+    let mut out = String::new();
+    for (prefix, namespace) in PREFIXES {
+        out.push_str(&format!("@prefix {}: <{}> .\n", prefix, namespace));
+    }
+    let rdf_type = format!("{}type", RDF_NAMESPACE);
+    for (subject, properties) in group_by_subject(triples_in) {
+        out.push('\n');
+        out.push_str(&turtle_term(subject));
+        for (i, (predicate, object)) in properties.iter().enumerate() {
+            let predicate = if *predicate == rdf_type {
+                "a".to_string()
+            } else {
+                turtle_iri(predicate)
+            };
+            let separator = if i == 0 { " " } else { " ;\n    " };
+            out.push_str(&format!("{}{} {}", separator, predicate, turtle_term(object)));
+        }
+        out.push_str(" .\n");
+    }
+    out
+}
+
+/// Like turtle_iri but for JSON-LD's compact IRIs, which need no brackets.
+fn json_ld_iri(iri_in: &str) -> String {
+    let compact = turtle_iri(iri_in);
+    if compact.starts_with('<') {
+        iri_in.to_string()
+    } else {
+        compact
+    }
+}
+
+fn json_ld_term(term_in: &RdfTerm) -> serde_json::Value {
+    match term_in {
+        RdfTerm::Iri(iri) => serde_json::json!({ "@id": json_ld_iri(iri) }),
+        RdfTerm::Blank(label) => serde_json::json!({ "@id": format!("_:{}", label) }),
+        RdfTerm::Literal { value, datatype: None } => serde_json::json!({ "@value": value }),
+        RdfTerm::Literal {
+            value,
+            datatype: Some(datatype),
+        } => serde_json::json!({ "@value": value, "@type": json_ld_iri(datatype) }),
+        RdfTerm::List(items) => serde_json::json!({ "@list": items.iter().map(json_ld_term).collect::<Vec<_>>() }),
+    }
+}
+
+/// Writes the statements as a JSON-LD document with one "@graph" node per subject.
+pub fn to_json_ld(triples_in: &[Triple]) -> Result<String, anyhow::Error> {
+    let rdf_type = format!("{}type", RDF_NAMESPACE);
+    let mut context = serde_json::Map::new();
+    for (prefix, namespace) in PREFIXES {
+        context.insert(prefix.to_string(), serde_json::Value::String(namespace.to_string()));
+    }
+    let mut graph: Vec<serde_json::Value> = Vec::new();
+    for (subject, properties) in group_by_subject(triples_in) {
+        let mut node = serde_json::Map::new();
+        node.insert("@id".to_string(), json_ld_term(subject)["@id"].clone());
+        for (predicate, object) in properties {
+            let (key, value) = if predicate == rdf_type {
+                match object {
+                    RdfTerm::Iri(iri) => ("@type".to_string(), serde_json::Value::String(json_ld_iri(iri))),
+                    _ => (json_ld_iri(predicate), json_ld_term(object)),
+                }
+            } else {
+                (json_ld_iri(predicate), json_ld_term(object))
+            };
+            match node.get_mut(&key) {
+                Some(serde_json::Value::Array(values)) => values.push(value),
+                _ => {
+                    node.insert(key, serde_json::Value::Array(vec![value]));
+                }
+            }
+        }
+        graph.push(serde_json::Value::Object(node));
+    }
+    let document = serde_json::json!({ "@context": context, "@graph": graph });
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::exchange_format::*;
+
+    fn exchange_entity(id: i64, name: &str, reference: bool) -> ExchangeEntity {
+        ExchangeEntity {
+            id,
+            name: name.to_string(),
+            class_id: None,
+            archived: false,
+            archived_date: None,
+            insertion_date: 0,
+            public: if reference { None } else { Some(true) },
+            new_entries_stick_to_top: false,
+            reference,
+        }
+    }
+
+    fn attribute(id: i64, entity_id: i64, attr_type_id: i64, value: ExchangeAttributeValue) -> ExchangeAttribute {
+        ExchangeAttribute {
+            id,
+            entity_id,
+            attr_type_id,
+            sorting_index: id,
+            attributes_entity_id: None,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_rdf_export() {
+        let mut top = exchange_entity(1, "a \"top\"", false);
+        top.class_id = Some(7);
+        let data = ExchangeData {
+            format: FORMAT_NAME.to_string(),
+            version: FORMAT_VERSION,
+            schema_version: 9,
+            exported_date: 0,
+            root_entity_ids: Some(vec![1]),
+            entities: vec![
+                top,
+                exchange_entity(2, "child", false),
+                exchange_entity(3, "sibling of", true),
+                exchange_entity(4, "weight", true),
+                exchange_entity(5, "kg", true),
+                exchange_entity(6, "template", false),
+            ],
+            relation_types: vec![ExchangeRelationType {
+                entity_id: 3,
+                name_in_reverse_direction: "sibling of".to_string(),
+                directionality: "BI".to_string(),
+                transitive: false,
+                symmetric: false,
+                inverse_of_id: None,
+                source_class_ids: Vec::new(),
+                target_class_ids: Vec::new(),
+            }],
+            classes: vec![ExchangeClass {
+                id: 7,
+                name: "person".to_string(),
+                defining_entity_id: 6,
+                create_default_attributes: None,
+                parent_class_id: None,
+            }],
+            actions: vec![],
+            om_instances: vec![],
+            groups: vec![ExchangeGroup {
+                id: 8,
+                name: "list".to_string(),
+                insertion_date: 0,
+                allow_mixed_classes: false,
+                new_entries_stick_to_top: false,
+                query: None,
+                entries: vec![
+                    ExchangeGroupEntry {
+                        entity_id: 6,
+                        sorting_index: 20,
+                    },
+                    ExchangeGroupEntry {
+                        entity_id: 2,
+                        sorting_index: 10,
+                    },
+                ],
+            }],
+            attributes: vec![
+                attribute(
+                    1,
+                    1,
+                    4,
+                    ExchangeAttributeValue::Quantity {
+                        unit_id: 5,
+                        number: 70.5,
+                        valid_on_date: None,
+                        observation_date: 0,
+                    },
+                ),
+                attribute(
+                    2,
+                    1,
+                    3,
+                    ExchangeAttributeValue::RelationToLocalEntity {
+                        entity_id_2: 2,
+                        valid_on_date: None,
+                        observation_date: 0,
+                    },
+                ),
+                attribute(
+                    3,
+                    1,
+                    3,
+                    ExchangeAttributeValue::RelationToRemoteEntity {
+                        remote_instance_id: "other".to_string(),
+                        entity_id_2: -5,
+                        valid_on_date: None,
+                        observation_date: 0,
+                    },
+                ),
+                attribute(4, 2, 4, ExchangeAttributeValue::Date { date: 0 }),
+            ],
+            unit_conversions: vec![],
+            canonical_units: vec![],
+        };
+        let triples = to_rdf_graph(&data, "here");
+        let turtle = to_turtle(&triples);
+        assert!(turtle.starts_with("@prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .\n"));
+        assert!(turtle.contains(
+            "\n<urn:onemodel:here:entity:1> a om:Entity ;\n    \
+            a <urn:onemodel:here:class:7> ;\n    \
+            rdfs:label \"a \\\"top\\\"\" ;\n    \
+            om:public \"true\"^^xsd:boolean ;\n    \
+            <urn:onemodel:here:entity:4> _:q1 ;\n    \
+            <urn:onemodel:here:entity:3> <urn:onemodel:here:entity:2> ;\n    \
+            <urn:onemodel:here:entity:3> <urn:onemodel:other:entity:-5> .\n"
+        ));
+        assert!(turtle.contains("\n_:q1 a om:QuantityValue ;\n    om:number \"70.5\"^^xsd:double ;\n"));
+        assert!(turtle.contains("a owl:SymmetricProperty ;\n    rdfs:label \"sibling of\""));
+        assert!(turtle.contains("<urn:onemodel:here:entity:4> \"1970-01-01T00:00:00.000Z\"^^xsd:dateTime"));
+        assert!(turtle.contains(
+            "om:entries ( <urn:onemodel:here:entity:2> <urn:onemodel:here:entity:6> ) .\n"
+        ));
+        assert!(turtle.contains("<urn:onemodel:here:class:7> a rdfs:Class ;\n    rdfs:label \"person\""));
+
+        let json: serde_json::Value = serde_json::from_str(&to_json_ld(&triples).unwrap()).unwrap();
+        assert_eq!(json["@context"]["om"], OM_NAMESPACE);
+        let graph = json["@graph"].as_array().unwrap();
+        let top = graph.iter().find(|n| n["@id"] == "urn:onemodel:here:entity:1").unwrap();
+        assert_eq!(top["@type"], serde_json::json!(["om:Entity", "urn:onemodel:here:class:7"]));
+        assert_eq!(top["rdfs:label"][0]["@value"], "a \"top\"");
+        assert_eq!(top["urn:onemodel:here:entity:3"].as_array().unwrap().len(), 2);
+        let group = graph.iter().find(|n| n["@id"] == "urn:onemodel:here:group:8").unwrap();
+        assert_eq!(group["om:entries"][0]["@list"][0]["@id"], "urn:onemodel:here:entity:2");
+    }
+}
@@ -0,0 +1,286 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Ways to get database login credentials without a hard-coded default password or a password
+//! on the command line: the PostgreSQL password file (~/.pgpass, or the file named by
+//! PGPASSFILE), and an optional OneModel credentials file encrypted with a passphrase (for
+//! systems without an OS keyring, or where one is not wanted).
+//! Passwords are held in Zeroizing<String>s so they are cleared from memory when dropped.
+use anyhow::anyhow;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+pub type Password = Zeroizing<String>;
+
+pub const DEFAULT_HOST: &str = "localhost";
+pub const DEFAULT_PORT: u16 = 5432;
+const CREDENTIALS_FILE_ENV_VAR: &str = "OM_CREDENTIALS_FILE";
+const CREDENTIALS_FILE_HEADER: &str = "onemodel-credentials-v1";
+const SALT_LENGTH: usize = 16;
+/// (Deliberately slow, to make guessing the passphrase of a stolen file expensive.)
+const KEY_DERIVATION_ROUNDS: u32 = 600_000;
+
+/// The port the database connection will use: PGPORT if set (as sqlx also honors), else 5432.
+pub fn get_port() -> u16 {
+    std::env::var("PGPORT")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(DEFAULT_PORT)
+}
+
+fn get_home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+/// PGPASSFILE if set, otherwise ~/.pgpass, as libpq does.
+pub fn get_pgpass_path() -> Option<PathBuf> {
+    match std::env::var_os("PGPASSFILE") {
+        Some(p) => Some(PathBuf::from(p)),
+        None => get_home_dir().map(|h| h.join(".pgpass")),
+    }
+}
+
+/// Returns the password for the first matching line of the PostgreSQL password file, if there
+/// is one.  Like libpq, ignores the file if others can read it (on unix).
+pub fn find_pgpass_password(
+    host: &str,
+    port: u16,
+    database: &str,
+    username: &str,
+) -> Result<Option<Password>, anyhow::Error> {
+    let Some(path) = get_pgpass_path() else {
+        return Ok(None);
+    };
+    if !path.exists() {
+        return Ok(None);
+    }
+    if !is_private_file(&path)? {
+        eprintln!(
+            "WARNING: ignoring password file {} because others can read it (it should be chmod 0600).",
+            path.display()
+        );
+        return Ok(None);
+    }
+    let contents = Zeroizing::new(std::fs::read_to_string(&path)?);
+    Ok(find_in_pgpass_contents(&contents, host, port, database, username))
+}
+
+/// Parses lines of the form hostname:port:database:username:password, where any of the first
+/// four may be "*", and ":" or "\" within a field are escaped with "\".
+fn find_in_pgpass_contents(
+    contents: &str,
+    host: &str,
+    port: u16,
+    database: &str,
+    username: &str,
+) -> Option<Password> {
+    let port = port.to_string();
+    let wanted = [host, port.as_str(), database, username];
+    for line in contents.lines() {
+        if line.trim_start().starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_pgpass_line(line);
+        if fields.len() != 5 {
+            continue;
+        }
+        let matches = fields[..4]
+            .iter()
+            .zip(wanted.iter())
+            .all(|(field, want)| field.as_str() == "*" || field.as_str() == *want);
+        if matches {
+            return Some(Zeroizing::new(fields[4].to_string()));
+        }
+    }
+    None
+}
+
+fn split_pgpass_line(line: &str) -> Vec<Zeroizing<String>> {
+    let mut fields: Vec<Zeroizing<String>> = vec![Zeroizing::new(String::new())];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    fields.last_mut().unwrap().push(escaped);
+                }
+            }
+            // (The password is the rest of the line, so it may contain unescaped colons.)
+            ':' if fields.len() < 5 => fields.push(Zeroizing::new(String::new())),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+#[cfg(unix)]
+fn is_private_file(path: &Path) -> Result<bool, anyhow::Error> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(std::fs::metadata(path)?.permissions().mode() & 0o077 == 0)
+}
+
+#[cfg(not(unix))]
+fn is_private_file(_path: &Path) -> Result<bool, anyhow::Error> {
+    Ok(true)
+}
+
+/// OM_CREDENTIALS_FILE if set, otherwise ~/.onemodel/credentials.
+pub fn get_credentials_file_path() -> Option<PathBuf> {
+    match std::env::var_os(CREDENTIALS_FILE_ENV_VAR) {
+        Some(p) => Some(PathBuf::from(p)),
+        None => get_home_dir().map(|h| h.join(".onemodel").join("credentials")),
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, KEY_DERIVATION_ROUNDS, &mut *key);
+    key
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, anyhow::Error> {
+    if !s.len().is_multiple_of(2) {
+        return Err(anyhow!("Odd-length hex string in credentials file."));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16)
+                .map_err(|e| anyhow!("Invalid hex in credentials file: {}", e))
+        })
+        .collect()
+}
+
+/// Returns the text of a credentials file holding username and password, encrypted (with
+/// ChaCha20-Poly1305) by a key derived from the passphrase.
+pub fn encrypt_credentials(
+    passphrase: &str,
+    username: &str,
+    password: &str,
+) -> Result<String, anyhow::Error> {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&*key));
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let plaintext = Zeroizing::new(format!("{}\n{}", username, password));
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|e| anyhow!("Unable to encrypt credentials: {}", e))?;
+    Ok(format!(
+        "{}:{}:{}:{}\n",
+        CREDENTIALS_FILE_HEADER,
+        to_hex(&salt),
+        to_hex(&nonce),
+        to_hex(&ciphertext)
+    ))
+}
+
+/// The reverse of encrypt_credentials: returns the username and password.
+pub fn decrypt_credentials(
+    passphrase: &str,
+    file_contents: &str,
+) -> Result<(String, Password), anyhow::Error> {
+    let parts: Vec<&str> = file_contents.trim().split(':').collect();
+    if parts.len() != 4 || parts[0] != CREDENTIALS_FILE_HEADER {
+        return Err(anyhow!("Unrecognized credentials file format."));
+    }
+    let salt = from_hex(parts[1])?;
+    let nonce = from_hex(parts[2])?;
+    if nonce.len() != 12 {
+        return Err(anyhow!("Invalid nonce in credentials file."));
+    }
+    let ciphertext = from_hex(parts[3])?;
+    let key = derive_key(passphrase, &salt);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&*key));
+    let plaintext = Zeroizing::new(
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow!("Unable to decrypt the credentials file: wrong passphrase, or the file was altered."))?,
+    );
+    let text = Zeroizing::new(
+        String::from_utf8(plaintext.to_vec())
+            .map_err(|_| anyhow!("The decrypted credentials are not valid text."))?,
+    );
+    match text.split_once('\n') {
+        Some((username, password)) => Ok((username.to_string(), Zeroizing::new(password.to_string()))),
+        None => Err(anyhow!("The decrypted credentials are missing a username or password.")),
+    }
+}
+
+/// Writes the encrypted credentials file, readable only by the current user (on unix).
+pub fn save_encrypted_credentials(
+    path: &Path,
+    passphrase: &str,
+    username: &str,
+    password: &str,
+) -> Result<(), anyhow::Error> {
+    let contents = encrypt_credentials(passphrase, username, password)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    use std::io::Write;
+    options.open(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+pub fn load_encrypted_credentials(
+    path: &Path,
+    passphrase: &str,
+) -> Result<(String, Password), anyhow::Error> {
+    let contents = std::fs::read_to_string(path)?;
+    decrypt_credentials(passphrase, &contents)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_in_pgpass_contents() {
+        let contents = "# comment\n\
+            otherhost:5432:*:alice:wrong\n\
+            localhost:5432:om_alice:alice:pa\\:ss:word\n\
+            *:*:*:bob:b\\\\pw\n";
+        let find = |db: &str, user: &str| {
+            find_in_pgpass_contents(contents, "localhost", 5432, db, user).map(|p| p.to_string())
+        };
+        assert_eq!(find("om_alice", "alice"), Some("pa:ss:word".to_string()));
+        assert_eq!(find("om_other", "alice"), None);
+        assert_eq!(find("anything", "bob"), Some("b\\pw".to_string()));
+        assert_eq!(find_in_pgpass_contents(contents, "localhost", 5433, "om_alice", "alice"), None);
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt_credentials() {
+        let contents = encrypt_credentials("a passphrase", "alice", "secret\nwith newline").unwrap();
+        assert!(!contents.contains("secret"));
+        let (username, password) = decrypt_credentials("a passphrase", &contents).unwrap();
+        assert_eq!(username, "alice");
+        assert_eq!(password.as_str(), "secret\nwith newline");
+        assert!(decrypt_credentials("wrong passphrase", &contents).is_err());
+        // Each encryption uses a new salt and nonce:
+        assert_ne!(contents, encrypt_credentials("a passphrase", "alice", "secret").unwrap());
+    }
+}
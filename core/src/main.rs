@@ -15,15 +15,18 @@
 //remove next line (or equivalently, change it to "deny")?  Or, keep it until all the warnings are fixed?
 //#![warn(elided_lifetimes_in_paths)]
 
+pub mod clipboard;
 pub mod color;
 pub mod controllers;
+pub mod credentials;
 pub mod model;
 pub mod om_exception;
 pub mod text_ui;
 pub mod util;
 use crate::controllers::controller::Controller;
+use crate::model::entity_query::EntityQuery;
+use crate::util::Util;
 use std::env;
-// use crate::util::Util;
 use crate::text_ui::TextUI;
 use std::rc::Rc;
 
@@ -38,12 +41,35 @@ fn main() -> Result<(), anyhow::Error> {
     // more verbose (rust internals):
     //env::set_var("RUST_BACKTRACE", "full");
 
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    // "--query <query>" runs one EntityQuery (see entity_query.rs), prints the matching entities'
+    // ids and names, and exits, instead of starting the interactive UI.
+    let query: Option<String> = match args.iter().position(|a| a == "--query") {
+        None => None,
+        Some(index) => {
+            if index + 1 >= args.len() {
+                eprintln!("Usage: --query \"<query>\"\n{}", Util::QUERY_SYNTAX_HELP);
+                std::process::exit(1);
+            }
+            let q = args.remove(index + 1);
+            args.remove(index);
+            Some(q)
+        }
+    };
     // dbg!(args.as_slice());
     //see std::env::args() docs: next 2 args dift on windows, might be 0 & 1 not 1 & 2? If a change,
     // see next cmt also about args.len() and adjust if needed, for windows.
     let default_username: Option<&String> = args.get(1);
     let default_password: Option<&String> = args.get(2);
+    if default_password.is_some() {
+        // (Still works for now, but is to be removed.)
+        eprintln!(
+            "WARNING: a password given on the command line can be seen by other users (as with \"ps\"), \
+            and will not be supported in a later version.  Instead put it in ~/.pgpass (or the file \
+            named by PGPASSFILE), or just type it when asked and optionally save it in an encrypted \
+            credentials file (see credentials.rs)."
+        );
+    }
     // If user provides a single command-line argument to the app, consider that a request to
     // prompt for username & password. (The first argument, in my environment, is always the name
     // of the app, so when the user provides another that makes 2.  That was not true under
@@ -61,6 +87,14 @@ fn main() -> Result<(), anyhow::Error> {
         default_username,
         default_password,
     )?;
+    if let Some(q) = query {
+        let entity_query = EntityQuery::parse(q.as_str())?;
+        let entities = entity_query.get_matching_entities(controller.db.clone(), None, 0, None)?;
+        for mut entity in entities {
+            println!("{}: {}", entity.get_id(), entity.get_name(None)?);
+        }
+        return Ok(());
+    }
     //controller.start();
     Controller::start(Rc::new(controller));
 
@@ -0,0 +1,597 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! The small language of class actions (the "action" column of the Action table), which a
+//! user runs on an entity of the action's class, like "postpone this task one week":
+//!
+//!     set "due" = get("due") + 1 week
+//!     if not get("done") then say "Still not done: " + name
+//!
+//! It is sandboxed by having nothing in it but what is here: a script can read the entity's
+//! name and attributes and update or add its attributes, only through an ActionScriptHost;
+//! it has no loops, so it always finishes; and it has no files, network, or other entities.
+//!
+//! Each line is one statement (a "#" starts a comment):
+//!     set "<attribute type name>" = <expression>
+//!     if <expression> then <statement>
+//!     say <expression>        (shown to the user after the script runs)
+//!     stop
+//! Expressions have numbers, "text", true, false, today, now, name (the entity's), durations
+//! like 3 days (or minutes, hours, weeks), get("<type name>"), has("<type name>"),
+//! date("2024-05-31"), the operators + - * /, = != < <= > >=, and, or, not, and parentheses.
+//! A date plus or minus a duration is a date; adding text to anything makes text.
+use crate::model::entity_query::parse_query_date;
+use anyhow::anyhow;
+use chrono::{TimeZone, Timelike, Utc};
+
+const MILLIS_PER_MINUTE: i64 = 60 * 1000;
+const MILLIS_PER_DAY: i64 = 24 * 60 * MILLIS_PER_MINUTE;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptValue {
+    /// The number of a quantity attribute (in its own unit).
+    Number(f64),
+    /// Milliseconds, UTC, as dates are stored.
+    Date(i64),
+    /// Milliseconds; not storable, but can be added to dates.
+    Duration(i64),
+    Boolean(bool),
+    Text(String),
+}
+
+impl ScriptValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ScriptValue::Number(_) => "number",
+            ScriptValue::Date(_) => "date",
+            ScriptValue::Duration(_) => "duration",
+            ScriptValue::Boolean(_) => "boolean",
+            ScriptValue::Text(_) => "text",
+        }
+    }
+
+    /// How say shows it, and how it is joined to text.
+    pub fn to_display_string(&self) -> String {
+        match self {
+            ScriptValue::Number(n) => n.to_string(),
+            ScriptValue::Date(millis) => match Utc.timestamp_millis_opt(*millis).single() {
+                Some(d) if d.num_seconds_from_midnight() == 0 => d.format("%Y-%m-%d").to_string(),
+                Some(d) => d.format("%Y-%m-%d %H:%M").to_string(),
+                None => millis.to_string(),
+            },
+            ScriptValue::Duration(millis) if millis % MILLIS_PER_DAY == 0 => {
+                format!("{} days", millis / MILLIS_PER_DAY)
+            }
+            ScriptValue::Duration(millis) => format!("{} minutes", millis / MILLIS_PER_MINUTE),
+            ScriptValue::Boolean(b) => b.to_string(),
+            ScriptValue::Text(t) => t.clone(),
+        }
+    }
+}
+
+/// All a script can see or change.  Attribute types are found by name, ignoring case.
+pub trait ActionScriptHost {
+    fn get_entity_name(&mut self) -> Result<String, anyhow::Error>;
+    /// The value of the entity's first quantity, date, boolean or text attribute of the type.
+    fn get_attribute(&mut self, type_name_in: &str) -> Result<Option<ScriptValue>, anyhow::Error>;
+    /// Updates that attribute, or adds one if the entity has none.
+    fn set_attribute(&mut self, type_name_in: &str, value_in: &ScriptValue) -> Result<(), anyhow::Error>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Word(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(line_in: &str) -> Result<Vec<Token>, anyhow::Error> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let chars: Vec<char> = line_in.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '#' {
+            break;
+        } else if c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(anyhow!("text is missing its closing \"")),
+                    Some('"') => break,
+                    Some('\\') if i + 1 < chars.len() => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push(Token::Text(text));
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(
+                number.parse().map_err(|_| anyhow!("\"{}\" is not a number", number))?,
+            ));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect::<String>().to_lowercase()));
+        } else {
+            let two: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let symbol = ["!=", "<=", ">="]
+                .into_iter()
+                .find(|s| *s == two)
+                .or_else(|| ["=", "<", ">", "+", "-", "*", "/", "(", ")", ","].into_iter().find(|s| s.starts_with(c)))
+                .ok_or_else(|| anyhow!("unexpected character '{}'", c))?;
+            i += symbol.len();
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Literal(ScriptValue),
+    Today,
+    Now,
+    Name,
+    Call(String, Vec<Expression>),
+    Not(Box<Expression>),
+    Negate(Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement {
+    Set(String, Expression),
+    If(Expression, Box<Statement>),
+    Say(Expression),
+    Stop,
+}
+
+/// A parsed script, checked for syntax so that errors show when it is saved, not run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionScript {
+    /// (line number, statement)
+    statements: Vec<(usize, Statement)>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn is_word(&self, word_in: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == word_in)
+    }
+
+    fn is_symbol(&self, symbol_in: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol_in)
+    }
+
+    fn expect_symbol(&mut self, symbol_in: &str) -> Result<(), anyhow::Error> {
+        if self.is_symbol(symbol_in) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(anyhow!("expected '{}'", symbol_in))
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, anyhow::Error> {
+        match self.next() {
+            Some(Token::Word(w)) if w == "set" => {
+                let Some(Token::Text(type_name)) = self.next() else {
+                    return Err(anyhow!("expected the attribute type's name in quotes after \"set\""));
+                };
+                self.expect_symbol("=")?;
+                Ok(Statement::Set(type_name, self.expression()?))
+            }
+            Some(Token::Word(w)) if w == "if" => {
+                let condition = self.expression()?;
+                if !self.is_word("then") {
+                    return Err(anyhow!("expected \"then\""));
+                }
+                self.position += 1;
+                Ok(Statement::If(condition, Box::new(self.statement()?)))
+            }
+            Some(Token::Word(w)) if w == "say" => Ok(Statement::Say(self.expression()?)),
+            Some(Token::Word(w)) if w == "stop" => Ok(Statement::Stop),
+            _ => Err(anyhow!("expected a line to start with set, if, say or stop")),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, anyhow::Error> {
+        let mut left = self.and_expression()?;
+        while self.is_word("or") {
+            self.position += 1;
+            left = Expression::Binary("or", Box::new(left), Box::new(self.and_expression()?));
+        }
+        Ok(left)
+    }
+
+    fn and_expression(&mut self) -> Result<Expression, anyhow::Error> {
+        let mut left = self.not_expression()?;
+        while self.is_word("and") {
+            self.position += 1;
+            left = Expression::Binary("and", Box::new(left), Box::new(self.not_expression()?));
+        }
+        Ok(left)
+    }
+
+    fn not_expression(&mut self) -> Result<Expression, anyhow::Error> {
+        if self.is_word("not") {
+            self.position += 1;
+            return Ok(Expression::Not(Box::new(self.not_expression()?)));
+        }
+        let left = self.sum()?;
+        for operator in ["=", "!=", "<", "<=", ">", ">="] {
+            if self.is_symbol(operator) {
+                self.position += 1;
+                return Ok(Expression::Binary(operator, Box::new(left), Box::new(self.sum()?)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn sum(&mut self) -> Result<Expression, anyhow::Error> {
+        let mut left = self.product()?;
+        while let Some(operator) = ["+", "-"].into_iter().find(|s| self.is_symbol(s)) {
+            self.position += 1;
+            left = Expression::Binary(operator, Box::new(left), Box::new(self.product()?));
+        }
+        Ok(left)
+    }
+
+    fn product(&mut self) -> Result<Expression, anyhow::Error> {
+        let mut left = self.unary()?;
+        while let Some(operator) = ["*", "/"].into_iter().find(|s| self.is_symbol(s)) {
+            self.position += 1;
+            left = Expression::Binary(operator, Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, anyhow::Error> {
+        if self.is_symbol("-") {
+            self.position += 1;
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, anyhow::Error> {
+        match self.next() {
+            Some(Token::Number(n)) => {
+                let unit_millis = match self.peek() {
+                    Some(Token::Word(w)) => match w.as_str() {
+                        "minute" | "minutes" => Some(MILLIS_PER_MINUTE),
+                        "hour" | "hours" => Some(60 * MILLIS_PER_MINUTE),
+                        "day" | "days" => Some(MILLIS_PER_DAY),
+                        "week" | "weeks" => Some(7 * MILLIS_PER_DAY),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match unit_millis {
+                    Some(millis) => {
+                        self.position += 1;
+                        Ok(Expression::Literal(ScriptValue::Duration(millis_from_f64(n * millis as f64)?)))
+                    }
+                    None => Ok(Expression::Literal(ScriptValue::Number(n))),
+                }
+            }
+            Some(Token::Text(t)) => Ok(Expression::Literal(ScriptValue::Text(t))),
+            Some(Token::Symbol("(")) => {
+                let inner = self.expression()?;
+                self.expect_symbol(")")?;
+                Ok(inner)
+            }
+            Some(Token::Word(w)) => match w.as_str() {
+                "true" => Ok(Expression::Literal(ScriptValue::Boolean(true))),
+                "false" => Ok(Expression::Literal(ScriptValue::Boolean(false))),
+                "today" => Ok(Expression::Today),
+                "now" => Ok(Expression::Now),
+                "name" => Ok(Expression::Name),
+                "get" | "has" | "date" => {
+                    self.expect_symbol("(")?;
+                    let mut arguments = vec![self.expression()?];
+                    while self.is_symbol(",") {
+                        self.position += 1;
+                        arguments.push(self.expression()?);
+                    }
+                    self.expect_symbol(")")?;
+                    if arguments.len() != 1 {
+                        return Err(anyhow!("{}() takes one value", w));
+                    }
+                    Ok(Expression::Call(w, arguments))
+                }
+                _ => Err(anyhow!("unknown word \"{}\"", w)),
+            },
+            Some(Token::Symbol(s)) => Err(anyhow!("unexpected '{}'", s)),
+            None => Err(anyhow!("the line ended too soon")),
+        }
+    }
+}
+
+impl ActionScript {
+    pub fn parse(script_in: &str) -> Result<ActionScript, anyhow::Error> {
+        let mut statements: Vec<(usize, Statement)> = Vec::new();
+        for (i, line) in script_in.lines().enumerate() {
+            let line_number = i + 1;
+            let tokens = tokenize(line).map_err(|e| anyhow!("Line {}: {}", line_number, e))?;
+            if tokens.is_empty() {
+                continue;
+            }
+            let mut parser = Parser { tokens, position: 0 };
+            let statement = parser.statement().map_err(|e| anyhow!("Line {}: {}", line_number, e))?;
+            if parser.position < parser.tokens.len() {
+                return Err(anyhow!("Line {}: unexpected {:?} after the statement", line_number, parser.peek().unwrap()));
+            }
+            statements.push((line_number, statement));
+        }
+        Ok(ActionScript { statements })
+    }
+
+    /// Runs the statements in order, stopping at the first error (so the caller should have
+    /// the host's changes in a transaction, to roll back).  Returns what "say" statements said.
+    pub fn run(&self, host: &mut dyn ActionScriptHost, now_in: i64) -> Result<Vec<String>, anyhow::Error> {
+        let mut said: Vec<String> = Vec::new();
+        let mut runner = Runner { host, now: now_in };
+        for (line_number, statement) in &self.statements {
+            let keep_going = runner
+                .execute(statement, &mut said)
+                .map_err(|e| anyhow!("Line {}: {}", line_number, e))?;
+            if !keep_going {
+                break;
+            }
+        }
+        Ok(said)
+    }
+}
+
+struct Runner<'a> {
+    host: &'a mut dyn ActionScriptHost,
+    now: i64,
+}
+
+impl Runner<'_> {
+    /// Returns false for stop.
+    fn execute(&mut self, statement_in: &Statement, said: &mut Vec<String>) -> Result<bool, anyhow::Error> {
+        match statement_in {
+            Statement::Set(type_name, expression) => {
+                let value = self.evaluate(expression)?;
+                if let ScriptValue::Duration(_) = value {
+                    return Err(anyhow!("a duration can't be stored; add it to a date instead"));
+                }
+                self.host.set_attribute(type_name, &value)?;
+            }
+            Statement::If(condition, then_statement) => {
+                if self.evaluate_boolean(condition)? {
+                    return self.execute(then_statement, said);
+                }
+            }
+            Statement::Say(expression) => said.push(self.evaluate(expression)?.to_display_string()),
+            Statement::Stop => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn evaluate_boolean(&mut self, expression_in: &Expression) -> Result<bool, anyhow::Error> {
+        match self.evaluate(expression_in)? {
+            ScriptValue::Boolean(b) => Ok(b),
+            other => Err(anyhow!("expected true or false but found a {}", other.type_name())),
+        }
+    }
+
+    fn evaluate(&mut self, expression_in: &Expression) -> Result<ScriptValue, anyhow::Error> {
+        match expression_in {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Today => Ok(ScriptValue::Date(self.now - self.now.rem_euclid(MILLIS_PER_DAY))),
+            Expression::Now => Ok(ScriptValue::Date(self.now)),
+            Expression::Name => Ok(ScriptValue::Text(self.host.get_entity_name()?)),
+            Expression::Call(function, arguments) => {
+                let argument = self.evaluate(&arguments[0])?;
+                let ScriptValue::Text(text) = argument else {
+                    return Err(anyhow!("{}() needs text, not a {}", function, argument.type_name()));
+                };
+                match function.as_str() {
+                    "get" => self
+                        .host
+                        .get_attribute(&text)?
+                        .ok_or_else(|| anyhow!("the entity has no \"{}\" attribute", text)),
+                    "has" => Ok(ScriptValue::Boolean(self.host.get_attribute(&text)?.is_some())),
+                    _ => Ok(ScriptValue::Date(parse_query_date(&text)?.0)),
+                }
+            }
+            Expression::Not(inner) => Ok(ScriptValue::Boolean(!self.evaluate_boolean(inner)?)),
+            Expression::Negate(inner) => match self.evaluate(inner)? {
+                ScriptValue::Number(n) => Ok(ScriptValue::Number(-n)),
+                ScriptValue::Duration(d) => Ok(ScriptValue::Duration(d.checked_neg().ok_or_else(out_of_range)?)),
+                other => Err(anyhow!("can't negate a {}", other.type_name())),
+            },
+            Expression::Binary("and", left, right) => {
+                Ok(ScriptValue::Boolean(self.evaluate_boolean(left)? && self.evaluate_boolean(right)?))
+            }
+            Expression::Binary("or", left, right) => {
+                Ok(ScriptValue::Boolean(self.evaluate_boolean(left)? || self.evaluate_boolean(right)?))
+            }
+            Expression::Binary(operator, left, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                apply_operator(operator, left, right)
+            }
+        }
+    }
+}
+
+fn out_of_range() -> anyhow::Error {
+    anyhow!("a date or duration is out of range")
+}
+
+/// Rounds millis_in to whole milliseconds, or fails if that is not a possible date or duration
+/// (instead of "as i64" quietly saturating).
+fn millis_from_f64(millis_in: f64) -> Result<i64, anyhow::Error> {
+    let millis = millis_in.round();
+    // (i64::MAX as f64 rounds up to 2^63, which is already out of range.)
+    if millis.is_finite() && millis >= i64::MIN as f64 && millis < i64::MAX as f64 {
+        Ok(millis as i64)
+    } else {
+        Err(out_of_range())
+    }
+}
+
+fn apply_operator(operator_in: &str, left_in: ScriptValue, right_in: ScriptValue) -> Result<ScriptValue, anyhow::Error> {
+    use ScriptValue::*;
+    let result = match (operator_in, &left_in, &right_in) {
+        ("+", Text(a), b) => Text(format!("{}{}", a, b.to_display_string())),
+        ("+", a, Text(b)) => Text(format!("{}{}", a.to_display_string(), b)),
+        ("+", Number(a), Number(b)) => Number(a + b),
+        ("-", Number(a), Number(b)) => Number(a - b),
+        ("*", Number(a), Number(b)) => Number(a * b),
+        ("/", Number(_), Number(b)) if *b == 0.0 => return Err(anyhow!("division by zero")),
+        ("/", Number(a), Number(b)) => Number(a / b),
+        ("+", Date(a), Duration(b)) | ("+", Duration(b), Date(a)) => Date(a.checked_add(*b).ok_or_else(out_of_range)?),
+        ("-", Date(a), Duration(b)) => Date(a.checked_sub(*b).ok_or_else(out_of_range)?),
+        ("-", Date(a), Date(b)) => Duration(a.checked_sub(*b).ok_or_else(out_of_range)?),
+        ("+", Duration(a), Duration(b)) => Duration(a.checked_add(*b).ok_or_else(out_of_range)?),
+        ("-", Duration(a), Duration(b)) => Duration(a.checked_sub(*b).ok_or_else(out_of_range)?),
+        ("*", Duration(a), Number(b)) | ("*", Number(b), Duration(a)) => Duration(millis_from_f64(*a as f64 * b)?),
+        ("=", a, b) if a.type_name() == b.type_name() => Boolean(a == b),
+        ("!=", a, b) if a.type_name() == b.type_name() => Boolean(a != b),
+        (comparison @ ("<" | "<=" | ">" | ">="), a, b) => {
+            let ordering = match (a, b) {
+                (Number(a), Number(b)) => a.partial_cmp(b),
+                (Date(a), Date(b)) | (Duration(a), Duration(b)) => Some(a.cmp(b)),
+                (Text(a), Text(b)) => Some(a.cmp(b)),
+                _ => None,
+            };
+            let Some(ordering) = ordering else {
+                return Err(anyhow!("can't compare a {} with a {}", a.type_name(), b.type_name()));
+            };
+            Boolean(match comparison {
+                "<" => ordering.is_lt(),
+                "<=" => ordering.is_le(),
+                ">" => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        _ => {
+            return Err(anyhow!(
+                "can't use '{}' with a {} and a {}",
+                operator_in,
+                left_in.type_name(),
+                right_in.type_name()
+            ))
+        }
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct TestHost {
+        attributes: HashMap<String, ScriptValue>,
+    }
+
+    impl ActionScriptHost for TestHost {
+        fn get_entity_name(&mut self) -> Result<String, anyhow::Error> {
+            Ok("task 1".to_string())
+        }
+        fn get_attribute(&mut self, type_name_in: &str) -> Result<Option<ScriptValue>, anyhow::Error> {
+            Ok(self.attributes.get(type_name_in).cloned())
+        }
+        fn set_attribute(&mut self, type_name_in: &str, value_in: &ScriptValue) -> Result<(), anyhow::Error> {
+            self.attributes.insert(type_name_in.to_string(), value_in.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let day = MILLIS_PER_DAY;
+        let mut host = TestHost {
+            attributes: HashMap::from([
+                ("due".to_string(), ScriptValue::Date(10 * day)),
+                ("done".to_string(), ScriptValue::Boolean(false)),
+                ("count".to_string(), ScriptValue::Number(2.0)),
+            ]),
+        };
+        let script = ActionScript::parse(
+            "# postpone, and count it\n\
+             set \"due\" = get(\"due\") + 1 week\n\
+             set \"count\" = get(\"count\") * (3 - 1) + 0.5\n\
+             if not get(\"done\") and has(\"due\") then say \"still open: \" + name + \", due \" + get(\"due\")\n\
+             if get(\"due\") - today >= 2 days then set \"done\" = true\n\
+             if has(\"nothing\") or get(\"count\") != 4.5 then say \"unexpected\"\n\
+             stop\n\
+             say \"never\"",
+        )
+        .unwrap();
+        let said = script.run(&mut host, 12 * day + 5).unwrap();
+        assert_eq!(said, vec!["still open: task 1, due 1970-01-18".to_string()]);
+        assert_eq!(host.attributes["due"], ScriptValue::Date(17 * day));
+        assert_eq!(host.attributes["count"], ScriptValue::Number(4.5));
+        assert_eq!(host.attributes["done"], ScriptValue::Boolean(true));
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse_error = |s: &str| ActionScript::parse(s).unwrap_err().to_string();
+        assert!(parse_error("say 1\nset due = 3").starts_with("Line 2: expected the attribute type's name"));
+        assert!(parse_error("say (1").contains("expected ')'"));
+        assert!(parse_error("say 1 2").contains("unexpected"));
+        assert!(parse_error("delete everything").contains("expected a line to start with"));
+        assert!(parse_error("say \"open").contains("closing"));
+        assert!(parse_error("say 99999999999999999 weeks").contains("out of range"));
+
+        let mut host = TestHost {
+            attributes: HashMap::new(),
+        };
+        let run_error = |s: &str, host: &mut TestHost| ActionScript::parse(s).unwrap().run(host, 0).unwrap_err().to_string();
+        assert_eq!(run_error("say get(\"due\")", &mut host), "Line 1: the entity has no \"due\" attribute");
+        assert!(run_error("set \"x\" = 2 days", &mut host).contains("duration can't be stored"));
+        assert!(run_error("say 1 + true", &mut host).contains("can't use '+' with a number and a boolean"));
+        assert!(run_error("if 1 then stop", &mut host).contains("expected true or false"));
+        assert!(run_error("say 10000000000 weeks + 10000000000 weeks", &mut host).contains("out of range"));
+        assert!(run_error("say 10000000000 weeks * 2", &mut host).contains("out of range"));
+        assert!(host.attributes.is_empty());
+        let said = ActionScript::parse("say date(\"2024-05-31\") + 1 day").unwrap().run(&mut host, 0).unwrap();
+        assert_eq!(said, vec!["2024-06-01".to_string()]);
+    }
+}
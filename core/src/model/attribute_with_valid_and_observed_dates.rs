@@ -13,6 +13,16 @@ use sqlx::{Postgres, Transaction};
 use std::cell::RefCell;
 use std::rc::Rc;
 
+/// For viewing an entity's attributes as of some past moment (milliseconds UTC), instead of
+/// everything ever recorded on it.  See Database::get_sorted_attributes_as_of for details.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AsOfDate {
+    /// What was true (per valid_on_date) at that time.
+    ValidOn(i64),
+    /// What had been recorded (per observation_date) by that time.
+    ObservedOn(i64),
+}
+
 // (For more info see "supertraits" in The Book (or in anki notes).)
 pub trait AttributeWithValidAndObservedDates: Attribute {
     //%%
@@ -0,0 +1,40 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Every place an entity is referred to from elsewhere (its "backlinks"), gathered in one read by
+//! Database::get_backlinks.  Referring entities are given by id; where one is only a holder of a
+//! relation's own attributes (see postgresql_relation_attributes.rs), the entity with that
+//! relation is given instead, since the holder is not shown anywhere by itself.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Backlinks {
+    /// The entities with a relation to this one, by relation type: (rel_type_id, entity ids).
+    pub containing_entities: Vec<(i64, Vec<i64>)>,
+    /// The groups this entity is in.
+    pub containing_group_ids: Vec<i64>,
+    /// The entities with attributes (or relations) whose type is this entity, by the form of
+    /// those attributes (like Util::TEXT_TYPE): (form, entity ids).
+    pub attribute_type_users: Vec<(String, Vec<i64>)>,
+    /// The entities with a quantity in this entity as a unit.
+    pub unit_users: Vec<i64>,
+    /// The entities with a relation to a "remote" entity which is really this one (the remote
+    /// instance being this one), as when importing what another instance had linked to here.
+    pub remote_referrers: Vec<i64>,
+}
+
+impl Backlinks {
+    /// How many references there are in all (an entity referring in two ways counts twice).
+    pub fn total_count(&self) -> usize {
+        self.containing_entities.iter().map(|(_, ids)| ids.len()).sum::<usize>()
+            + self.containing_group_ids.len()
+            + self.attribute_type_users.iter().map(|(_, ids)| ids.len()).sum::<usize>()
+            + self.unit_users.len()
+            + self.remote_referrers.len()
+    }
+}
@@ -0,0 +1,328 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! A class's schema: a rule per attribute type, saying which form the attributes of that type
+//! take on entities of the class, how many of them there must (or may) be, and which units and
+//! values are allowed.  The database checks the rules whenever an attribute is created or
+//! updated (see postgresql_class_schema.rs), except on the class's template entity, whose
+//! attributes are only placeholders.  A class's rules also apply to the members of its
+//! subclasses, unless a subclass has its own rule for the same attribute type.  A rule can't
+//! stop an entity from being created before it has its required attributes, or cover entities
+//! that were there before the rule was, so Database::validate_class_members reports on those.
+use crate::util::Util;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeRule {
+    pub attr_type_id: i64,
+    /// One of FORMS, like Util::QUANTITY_TYPE.
+    pub form: String,
+    /// How many attributes of the type an entity of the class must have (so 1 means required).
+    pub min_count: i32,
+    /// None for no limit.
+    pub max_count: Option<i32>,
+    /// Bounds on the number of a quantity (in whatever unit it has), on a date (in millis), or
+    /// on the length of a text.  Booleans have no bounds.
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+    /// For quantities: if not empty, the only units allowed.
+    pub allowed_unit_ids: Vec<i64>,
+}
+
+/// The forms a rule can require.
+pub const FORMS: [&str; 4] = [
+    Util::QUANTITY_TYPE,
+    Util::DATE_TYPE,
+    Util::BOOLEAN_TYPE,
+    Util::TEXT_TYPE,
+];
+
+/// Like "quantity", for messages.
+pub fn describe_form(form_in: &str) -> &str {
+    match form_in {
+        Util::QUANTITY_TYPE => "quantity",
+        Util::DATE_TYPE => "date",
+        Util::BOOLEAN_TYPE => "true/false",
+        Util::TEXT_TYPE => "text",
+        Util::FILE_TYPE => "file",
+        _ => form_in,
+    }
+}
+
+/// The value of an attribute being written or checked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleValue {
+    Quantity { unit_id: i64, number: f64 },
+    Date(i64),
+    Boolean(bool),
+    Text(String),
+}
+
+impl RuleValue {
+    pub fn form(&self) -> &'static str {
+        match self {
+            RuleValue::Quantity { .. } => Util::QUANTITY_TYPE,
+            RuleValue::Date(_) => Util::DATE_TYPE,
+            RuleValue::Boolean(_) => Util::BOOLEAN_TYPE,
+            RuleValue::Text(_) => Util::TEXT_TYPE,
+        }
+    }
+}
+
+/// A way an entity (or an attribute being written) breaks a rule.  Ids are kept so the caller
+/// can look up names for describe().
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleProblem {
+    TooFew { attr_type_id: i64, min_count: i32, count: usize },
+    TooMany { attr_type_id: i64, max_count: i32, count: usize },
+    WrongForm { attr_type_id: i64, expected: String, found: String },
+    UnitNotAllowed { attr_type_id: i64, unit_id: i64, allowed_unit_ids: Vec<i64> },
+    /// For dates the value is in millis, and for texts it is the length.
+    OutOfRange { attr_type_id: i64, form: String, value: f64, min_value: Option<f64>, max_value: Option<f64> },
+}
+
+impl RuleProblem {
+    /// name_of gives the name of an attribute type or unit entity, by id.
+    pub fn describe(&self, name_of: &dyn Fn(i64) -> String) -> String {
+        match self {
+            RuleProblem::TooFew { attr_type_id, min_count, count } => {
+                if *count == 0 && *min_count == 1 {
+                    format!("\"{}\" is required but missing.", name_of(*attr_type_id))
+                } else {
+                    format!(
+                        "\"{}\" needs at least {} but there {} {}.",
+                        name_of(*attr_type_id),
+                        min_count,
+                        if *count == 1 { "is" } else { "are" },
+                        count
+                    )
+                }
+            }
+            RuleProblem::TooMany { attr_type_id, max_count, count } => format!(
+                "\"{}\" allows at most {} but there would be {}.",
+                name_of(*attr_type_id),
+                max_count,
+                count
+            ),
+            RuleProblem::WrongForm { attr_type_id, expected, found } => format!(
+                "\"{}\" must be a {} attribute, not {}.",
+                name_of(*attr_type_id),
+                describe_form(expected),
+                describe_form(found)
+            ),
+            RuleProblem::UnitNotAllowed { attr_type_id, unit_id, allowed_unit_ids } => {
+                let allowed: Vec<String> = allowed_unit_ids.iter().map(|id| name_of(*id)).collect();
+                format!(
+                    "\"{}\" can't be in \"{}\"; allowed units: {}.",
+                    name_of(*attr_type_id),
+                    name_of(*unit_id),
+                    allowed.join(", ")
+                )
+            }
+            RuleProblem::OutOfRange { attr_type_id, form, value, min_value, max_value } => {
+                let show = |v: f64| -> String {
+                    if form == Util::DATE_TYPE {
+                        Util::useful_date_format(v as i64)
+                    } else {
+                        v.to_string()
+                    }
+                };
+                let what = if form == Util::TEXT_TYPE { "length" } else { "value" };
+                let range = match (min_value, max_value) {
+                    (Some(min), Some(max)) => format!("from {} to {}", show(*min), show(*max)),
+                    (Some(min), None) => format!("at least {}", show(*min)),
+                    (None, Some(max)) => format!("at most {}", show(*max)),
+                    (None, None) => "(any)".to_string(),
+                };
+                format!(
+                    "\"{}\" has {} {}, but it must be {}.",
+                    name_of(*attr_type_id),
+                    what,
+                    show(*value),
+                    range
+                )
+            }
+        }
+    }
+}
+
+impl AttributeRule {
+    /// Checks one attribute's form and value against the rule (not the count of them).
+    pub fn check_value(&self, value_in: &RuleValue) -> Vec<RuleProblem> {
+        let mut problems = Vec::new();
+        if value_in.form() != self.form {
+            problems.push(RuleProblem::WrongForm {
+                attr_type_id: self.attr_type_id,
+                expected: self.form.clone(),
+                found: value_in.form().to_string(),
+            });
+            return problems;
+        }
+        let bounded: Option<f64> = match value_in {
+            RuleValue::Quantity { unit_id, number } => {
+                if !self.allowed_unit_ids.is_empty() && !self.allowed_unit_ids.contains(unit_id) {
+                    problems.push(RuleProblem::UnitNotAllowed {
+                        attr_type_id: self.attr_type_id,
+                        unit_id: *unit_id,
+                        allowed_unit_ids: self.allowed_unit_ids.clone(),
+                    });
+                }
+                Some(*number)
+            }
+            RuleValue::Date(date) => Some(*date as f64),
+            RuleValue::Text(text) => Some(text.chars().count() as f64),
+            RuleValue::Boolean(_) => None,
+        };
+        if let Some(value) = bounded {
+            if self.min_value.is_some_and(|min| value < min) || self.max_value.is_some_and(|max| value > max) {
+                problems.push(RuleProblem::OutOfRange {
+                    attr_type_id: self.attr_type_id,
+                    form: self.form.clone(),
+                    value,
+                    min_value: self.min_value,
+                    max_value: self.max_value,
+                });
+            }
+        }
+        problems
+    }
+
+    pub fn check_count(&self, count_in: usize) -> Option<RuleProblem> {
+        if count_in < self.min_count.max(0) as usize {
+            Some(RuleProblem::TooFew {
+                attr_type_id: self.attr_type_id,
+                min_count: self.min_count,
+                count: count_in,
+            })
+        } else {
+            self.max_count
+                .filter(|max| count_in > (*max).max(0) as usize)
+                .map(|max| RuleProblem::TooMany {
+                    attr_type_id: self.attr_type_id,
+                    max_count: max,
+                    count: count_in,
+                })
+        }
+    }
+
+    /// Like "required quantity, 1 to 3, in kg or lb, from 0 to 500", for menus.
+    pub fn describe(&self, name_of: &dyn Fn(i64) -> String) -> String {
+        let mut parts: Vec<String> = vec![format!(
+            "{}{}",
+            if self.min_count > 0 { "required " } else { "optional " },
+            describe_form(&self.form)
+        )];
+        match (self.min_count, self.max_count) {
+            (min, Some(max)) if min == max => parts.push(format!("exactly {}", max)),
+            (min, Some(max)) => parts.push(format!("{} to {}", min, max)),
+            (min, None) if min > 1 => parts.push(format!("at least {}", min)),
+            _ => {}
+        }
+        if !self.allowed_unit_ids.is_empty() {
+            let units: Vec<String> = self.allowed_unit_ids.iter().map(|id| name_of(*id)).collect();
+            parts.push(format!("in {}", units.join(" or ")));
+        }
+        let show = |v: f64| -> String {
+            if self.form == Util::DATE_TYPE {
+                Util::useful_date_format(v as i64)
+            } else {
+                v.to_string()
+            }
+        };
+        let what = if self.form == Util::TEXT_TYPE { "length " } else { "" };
+        match (self.min_value, self.max_value) {
+            (Some(min), Some(max)) => parts.push(format!("{}from {} to {}", what, show(min), show(max))),
+            (Some(min), None) => parts.push(format!("{}at least {}", what, show(min))),
+            (None, Some(max)) => parts.push(format!("{}at most {}", what, show(max))),
+            (None, None) => {}
+        }
+        parts.join(", ")
+    }
+}
+
+/// Checks an entity's attributes (as (attr_type_id, value)) against all the rules of its class.
+pub fn check_entity(rules_in: &[AttributeRule], attributes_in: &[(i64, RuleValue)]) -> Vec<RuleProblem> {
+    let mut problems = Vec::new();
+    for rule in rules_in {
+        let values: Vec<&RuleValue> = attributes_in
+            .iter()
+            .filter(|(attr_type_id, _)| *attr_type_id == rule.attr_type_id)
+            .map(|(_, value)| value)
+            .collect();
+        if let Some(problem) = rule.check_count(values.len()) {
+            problems.push(problem);
+        }
+        for value in values {
+            problems.extend(rule.check_value(value));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_entity() {
+        let weight = AttributeRule {
+            attr_type_id: 1,
+            form: Util::QUANTITY_TYPE.to_string(),
+            min_count: 1,
+            max_count: Some(1),
+            min_value: Some(0.0),
+            max_value: Some(500.0),
+            allowed_unit_ids: vec![10, 11],
+        };
+        let title = AttributeRule {
+            attr_type_id: 2,
+            form: Util::TEXT_TYPE.to_string(),
+            min_count: 0,
+            max_count: None,
+            min_value: None,
+            max_value: Some(5.0),
+            allowed_unit_ids: Vec::new(),
+        };
+        let rules = vec![weight.clone(), title.clone()];
+        assert!(check_entity(&rules, &[(1, RuleValue::Quantity { unit_id: 10, number: 70.0 })]).is_empty());
+        assert_eq!(
+            check_entity(&rules, &[(2, RuleValue::Text("short".to_string()))]),
+            vec![RuleProblem::TooFew { attr_type_id: 1, min_count: 1, count: 0 }]
+        );
+        let problems = check_entity(
+            &rules,
+            &[
+                (1, RuleValue::Quantity { unit_id: 12, number: 600.0 }),
+                (1, RuleValue::Text("70".to_string())),
+                (2, RuleValue::Text("too long".to_string())),
+            ],
+        );
+        assert_eq!(problems.len(), 5);
+        assert!(matches!(problems[0], RuleProblem::TooMany { count: 2, .. }));
+        assert!(matches!(problems[1], RuleProblem::UnitNotAllowed { unit_id: 12, .. }));
+        assert!(matches!(problems[2], RuleProblem::OutOfRange { value, .. } if value == 600.0));
+        assert!(matches!(problems[3], RuleProblem::WrongForm { .. }));
+        assert!(matches!(problems[4], RuleProblem::OutOfRange { value, .. } if value == 8.0));
+
+        let name_of = |id: i64| match id {
+            1 => "weight".to_string(),
+            10 => "kg".to_string(),
+            11 => "lb".to_string(),
+            12 => "stone".to_string(),
+            _ => id.to_string(),
+        };
+        assert_eq!(problems[1].describe(&name_of), "\"weight\" can't be in \"stone\"; allowed units: kg, lb.");
+        assert_eq!(problems[2].describe(&name_of), "\"weight\" has value 600, but it must be from 0 to 500.");
+        assert_eq!(
+            problems[3].describe(&name_of),
+            "\"weight\" must be a quantity attribute, not text."
+        );
+        assert_eq!(weight.describe(&name_of), "required quantity, exactly 1, in kg or lb, from 0 to 500");
+        assert_eq!(title.describe(&name_of), "optional text, length at most 5");
+    }
+}
@@ -8,14 +8,23 @@
     You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
 */
 use crate::model::attribute::Attribute;
+use crate::model::attribute_with_valid_and_observed_dates::AsOfDate;
+use crate::model::backlinks::Backlinks;
+use crate::model::class_schema::AttributeRule;
 use crate::model::entity::Entity;
 use crate::model::entity_class::EntityClass;
+use crate::model::entity_query::EntityQuery;
+use crate::model::exchange_format::{ExchangeData, ExchangeImportCounts};
 use crate::model::group::Group;
+use crate::model::navigation_history::NavigationHistory;
+use crate::model::path_finder::{PathLink, PathOptions};
+use crate::model::relation_inference::{InferredRelation, RelationSemantics};
 use crate::model::relation_to_group::RelationToGroup;
 use crate::model::relation_to_local_entity::RelationToLocalEntity;
 use crate::model::relation_to_remote_entity::RelationToRemoteEntity;
-use crate::model::relation_type::RelationType;
+use crate::model::relation_type::{RelationClassConstraints, RelationType};
 use crate::model::text_attribute::TextAttribute;
+use crate::model::unit_conversion::UnitConverter;
 use crate::util::Util;
 use anyhow::anyhow;
 //use mockall::{automock, mock, predicate::*};
@@ -365,6 +374,13 @@ pub trait Database {
         transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
         class_name_in: &str,
     ) -> Result<(i64, i64), anyhow::Error>;
+    /// Like get_or_create_class_and_template_entity, but without creating them: None if there
+    /// is no class of that name.
+    fn find_class_and_template_entity(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_name_in: &str,
+    ) -> Result<Option<(i64, i64)>, anyhow::Error>;
     fn add_uri_entity_with_uri_attribute(
         &self,
         transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
@@ -1242,5 +1258,362 @@ pub trait Database {
         preference_name_in: &str,
         default_value_in: Option<i64>, /*= None*/
     ) -> Result<Option<i64>, anyhow::Error>;
+    /// Returns the entities matching query_in, ordered by id.  See EntityQuery for the syntax.
+    fn get_entities_matching_query(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        query_in: &EntityQuery,
+        starting_object_index_in: u64,
+        max_vals_in: Option<u64>, /*= None*/
+    ) -> Result<Vec<Entity>, anyhow::Error>;
+    /// Creates a saved search ("smart group"): a group whose entries are whichever entities
+    /// currently match query_in (see EntityQuery), so its membership is read-only.
+    fn create_smart_group(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        name_in: &str,
+        query_in: &str,
+    ) -> Result<i64, anyhow::Error>;
+    /// Returns None if the group is an ordinary one, or its query if it is a saved search.
+    fn get_group_query(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        group_id_in: i64,
+    ) -> Result<Option<String>, anyhow::Error>;
+    fn update_group_query(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        group_id_in: i64,
+        query_in: &str,
+    ) -> Result<u64, anyhow::Error>;
+    /// Like get_sorted_attributes, but when as_of_in is Some, leaves out what was not yet valid
+    /// (or not yet observed) by that date, and of several quantity, text or boolean attributes of
+    /// the same type keeps only the latest one by then, since it supersedes the others.
+    /// Relations are only filtered, not superseded, because several can legitimately coexist.
+    /// A missing valid_on_date is treated as the observation_date, and a file attribute uses its
+    /// original file date or stored date respectively.  Date attributes record neither date, so
+    /// they are always included.
+    fn get_sorted_attributes_as_of(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        as_of_in: Option<AsOfDate>,
+        starting_object_index_in: usize,
+        max_vals_in: usize,
+        only_public_entities_in: bool,
+    ) -> Result<(Vec<(i64, Rc<RefCell<dyn Attribute>>)>, usize), anyhow::Error>;
+    /// Returns rows of (attr_type_id, unit_id, date, quantity_number) for entity_id_in's quantity
+    /// attributes, ordered by attr_type_id, unit_id and date.  See QuantitySeries::get_for_entity.
+    fn get_quantity_series_data(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        by_valid_date_in: bool,
+    ) -> Result<Vec<Vec<Option<DataType>>>, anyhow::Error>;
+    /// Records that one from_unit_id is worth factor_in to_unit_ids (replacing any conversion
+    /// already recorded between the two, in either direction).
+    fn set_unit_conversion(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        from_unit_id_in: i64,
+        to_unit_id_in: i64,
+        factor_in: f64,
+    ) -> Result<(), anyhow::Error>;
+    /// Deletes the conversion between the two units, whichever direction it was recorded in.
+    fn delete_unit_conversion(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        unit_id1_in: i64,
+        unit_id2_in: i64,
+    ) -> Result<u64, anyhow::Error>;
+    /// Returns all recorded conversions as (from_unit_id, to_unit_id, factor).
+    fn get_unit_conversions(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<Vec<(i64, i64, f64)>, anyhow::Error>;
+    /// A converter for the committed conversions, read once and then kept until they are changed
+    /// or a transaction is committed, for showing many quantities without re-reading them for
+    /// each.  (Within a transaction that changes conversions, use UnitConverter::load.)
+    fn get_unit_converter(&self) -> Result<Rc<UnitConverter>, anyhow::Error>;
+    /// Sets (or with None, clears) the unit in which quantities of attr_type_id_in are displayed
+    /// and totaled.
+    fn set_canonical_unit(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+        unit_id_in: Option<i64>,
+    ) -> Result<(), anyhow::Error>;
+    fn get_canonical_unit(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+    ) -> Result<Option<i64>, anyhow::Error>;
+    /// For each of entity_ids_in that has a quantity attribute of type attr_type_id_in, returns
+    /// (entity_id, unit_id, number) for the most recently observed one.
+    fn get_latest_quantities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+        entity_ids_in: &[i64],
+    ) -> Result<Vec<(i64, i64, f64)>, anyhow::Error>;
+    /// Like add_uri_entity_with_uri_attribute but without relating the new entity to another
+    /// (as when it is to go in a group instead).  Returns the new entity's id.
+    fn create_uri_entity(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        new_entity_name_in: &str,
+        uri_in: &str,
+        observation_date_in: i64,
+        make_them_public_in: Option<bool>,
+        quote_in: Option<&str>,
+    ) -> Result<i64, anyhow::Error>;
+    /// The ids of URI entities (as made by add_uri_entity_with_uri_attribute) whose URI is
+    /// exactly uri_in.
+    fn find_uri_entity_ids(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        uri_in: &str,
+    ) -> Result<Vec<i64>, anyhow::Error>;
+    /// Reads everything for the exchange format (see exchange_format.rs): the entities reachable
+    /// from root_entity_ids_in (through relations to entities and groups) with all their
+    /// contents, or with None, the whole database.
+    fn export_exchange_data(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        root_entity_ids_in: Option<&[i64]>,
+    ) -> Result<ExchangeData, anyhow::Error>;
+    /// Adds data_in to this database with new ids, using existing relation types and classes
+    /// (and entities marked as references) of the same names instead of making duplicates.
+    /// Any root entities are related to containing_entity_id_in with "has".  Should be called
+    /// inside a transaction, so a failure part way leaves nothing half-imported.
+    fn import_exchange_data(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        data_in: &ExchangeData,
+        containing_entity_id_in: Option<i64>,
+        observation_date_in: i64,
+    ) -> Result<ExchangeImportCounts, anyhow::Error>;
+    /// Adds a named action to a class: a script in the language of action_script.rs, that the
+    /// user can run on entities of the class.  Returns the new action's id.
+    fn create_action(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+        name_in: &str,
+        action_in: &str,
+    ) -> Result<i64, anyhow::Error>;
+    fn update_action(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        id_in: i64,
+        name_in: &str,
+        action_in: &str,
+    ) -> Result<u64, anyhow::Error>;
+    fn delete_action(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        id_in: i64,
+    ) -> Result<u64, anyhow::Error>;
+    /// Returns (id, name, action) for each of class_id_in's actions, ordered by name.
+    fn get_actions(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<(i64, String, String)>, anyhow::Error>;
+    /// Sets class_id_in's rule for rule_in.attr_type_id (see class_schema.rs), replacing any it
+    /// had.  Existing entities of the class are not checked: see validate_class_members.
+    fn set_class_attribute_rule(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+        rule_in: &AttributeRule,
+    ) -> Result<(), anyhow::Error>;
+    fn delete_class_attribute_rule(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+        attr_type_id_in: i64,
+    ) -> Result<u64, anyhow::Error>;
+    /// Returns class_id_in's rules, ordered by the names of their attribute types.
+    fn get_class_attribute_rules(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<AttributeRule>, anyhow::Error>;
+    /// Describes each way entity_id_in's attributes break the rules of its class, including
+    /// those inherited (none if it has no class, or is the class's template).
+    fn get_class_rule_problems(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<String>, anyhow::Error>;
+    /// Returns (id, name, problems) for each entity of class_id_in or its subclasses (archived
+    /// ones too) that breaks the rules of its class, ordered by name.
+    fn validate_class_members(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<(i64, String, Vec<String>)>, anyhow::Error>;
+    /// Makes class_id_in a subclass of parent_class_id_in (or with None, of no class).  It is an
+    /// error if that would make a class its own ancestor.
+    fn update_class_parent(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+        parent_class_id_in: Option<i64>,
+    ) -> Result<(), anyhow::Error>;
+    /// Returns (class id, template entity id) for class_id_in, then its parent class, and so on
+    /// up to a class with no parent.
+    fn get_class_lineage(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<(i64, i64)>, anyhow::Error>;
+    /// Whether rel_type_id_in is transitive or symmetric, and which type (if any) is its inverse.
+    fn get_relation_type_semantics(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+    ) -> Result<RelationSemantics, anyhow::Error>;
+    /// Also makes semantics_in.inverse_of_id (if any) the inverse of rel_type_id_in, so each
+    /// relation type has at most one inverse.
+    fn update_relation_type_semantics(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        semantics_in: &RelationSemantics,
+    ) -> Result<(), anyhow::Error>;
+    /// The relations from entity_id_in to local entities that are implied by the entered ones
+    /// and their types' semantics, but not entered themselves.  See relation_inference.rs.
+    fn get_inferred_relations(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<InferredRelation>, anyhow::Error>;
+    fn get_relation_type_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+    ) -> Result<RelationClassConstraints, anyhow::Error>;
+    /// Replaces rel_type_id_in's allowed classes.  Existing relations that break them are left
+    /// alone, but can't be changed without fixing that.
+    fn set_relation_type_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        constraints_in: &RelationClassConstraints,
+    ) -> Result<(), anyhow::Error>;
+    /// Messages for each way that a relation of rel_type_id_in from source_id_in to target_id_in
+    /// would break the type's allowed classes (empty if it wouldn't).  Either end can be None to
+    /// check only the other.
+    fn get_relation_class_constraint_problems(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        source_id_in: Option<i64>,
+        target_id_in: Option<i64>,
+    ) -> Result<Vec<String>, anyhow::Error>;
+    /// The entity holding a relation's own attributes (like the start date or role of a "worked
+    /// at" relation), if any were added.  relation_form_id_in is the relation's
+    /// get_attribute_form_id, since the ids of the relation tables overlap.
+    fn get_relation_attribute_holder_id(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+    ) -> Result<Option<i64>, anyhow::Error>;
+    /// Like get_relation_attribute_holder_id, but creates the holding entity if there is none.
+    /// Attributes added to it are the relation's; it is deleted with the relation.
+    fn get_or_create_relation_attribute_holder(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+    ) -> Result<i64, anyhow::Error>;
+    /// The (form, attribute type id) of each kind of attribute that entity_id_in's relations have
+    /// of their own (see get_relation_attribute_holder_id), such as to sort them by.
+    fn get_relation_attribute_types(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<(String, i64)>, anyhow::Error>;
+    /// Reorders entity_id_in's relations by the value of their own attribute of the given form
+    /// (like Util::DATE_TYPE) and type, those without one last.  Its other attributes keep their
+    /// places.  Returns how many relations there were.
+    fn sort_relations_by_relation_attribute(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        attribute_form_in: &str,
+        attr_type_id_in: i64,
+        descending_in: bool,
+    ) -> Result<u64, anyhow::Error>;
+    /// Every place entity_id_in is referred to: see backlinks.rs.  Referring entities that are
+    /// archived are left out unless those are being shown.
+    fn get_backlinks(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Backlinks, anyhow::Error>;
+    /// The shortest paths from one entity to another, within the limits in options_in: see
+    /// path_finder.rs.  An empty result means there is no such path.
+    fn find_paths_between_entities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        from_entity_id_in: i64,
+        to_entity_id_in: i64,
+        options_in: &PathOptions,
+    ) -> Result<Vec<Vec<PathLink>>, anyhow::Error>;
+    /// The places the user last went to (see navigation_history.rs), as last saved.
+    fn get_navigation_history(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<NavigationHistory, anyhow::Error>;
+    /// Replaces the saved navigation history with history_in.
+    fn save_navigation_history(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        history_in: &NavigationHistory,
+    ) -> Result<(), anyhow::Error>;
+    /// The bookmarked entities (see postgresql_bookmarks.rs), in their order.
+    fn get_bookmarked_entity_ids(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<Vec<i64>, anyhow::Error>;
+    /// Bookmarks entity_id_in, if it is not already.
+    fn add_bookmark(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<(), anyhow::Error>;
+    /// Returns whether there was a bookmark to remove.
+    fn remove_bookmark(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<bool, anyhow::Error>;
+    /// Counts a view of entity_id_in, as of now.
+    fn record_entity_view(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<(), anyhow::Error>;
+    /// The viewed entities with their view counts: the most recently viewed first, or if
+    /// by_count_in, the most viewed first.  At most max_in of them.
+    fn get_viewed_entities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        by_count_in: bool,
+        max_in: usize,
+    ) -> Result<Vec<(i64, i64)>, anyhow::Error>;
+    /// The forms (of class_schema::FORMS, like Util::DATE_TYPE) of the attributes anywhere whose
+    /// type is attr_type_id_in, so a caller can tell what kind of value that type is used for.
+    fn get_attribute_type_forms(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+    ) -> Result<Vec<String>, anyhow::Error>;
     //%% fn get_om_instances(&self, transaction: &Option<&mut Transaction<Postgres>>, localIn: Option<bool> /*= None*/) -> Result<Vec<OmInstance>, anyhow::Error>;
 }
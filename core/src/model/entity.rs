@@ -8,6 +8,7 @@
     You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
 */
 use crate::model::attribute::Attribute;
+use crate::model::attribute_with_valid_and_observed_dates::AsOfDate;
 use crate::model::boolean_attribute::BooleanAttribute;
 use crate::model::database::{DataType, Database};
 use crate::model::date_attribute::DateAttribute;
@@ -1315,6 +1316,35 @@ impl Entity {
         Ok((group.get_id(), rtg.get_id()))
     }
 
+    /// Like add_group_and_relation_to_group, but the new group is a saved search (see
+    /// Group::create_smart_group).  Returns the ids of the new group and RelationToGroup.
+    pub fn add_smart_group_and_relation_to_group(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        new_group_name_in: &str,
+        query_in: &str,
+        valid_on_date_in: Option<i64>,
+        observation_date_in: i64,
+    ) -> Result<(i64, i64), anyhow::Error> {
+        let group = Group::create_smart_group(
+            self.db.clone(),
+            transaction.clone(),
+            new_group_name_in,
+            query_in,
+        )?;
+        let (rtg_id, _sorting_index) = self.db.borrow().create_relation_to_group(
+            transaction,
+            self.get_id(),
+            rel_type_id_in,
+            group.get_id(),
+            valid_on_date_in,
+            observation_date_in,
+            None,
+        )?;
+        Ok((group.get_id(), rtg_id))
+    }
+
     /// @return the id of the new RTE
     pub fn add_has_relation_to_local_entity(
         &self,
@@ -1496,6 +1526,26 @@ impl Entity {
         )
     }
 
+    /// See Database::get_sorted_attributes_as_of.
+    pub fn get_sorted_attributes_as_of(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        as_of_in: AsOfDate,
+        starting_object_index_in: usize,
+        max_vals_in: usize,
+        only_public_entities_in: bool,
+    ) -> Result<(Vec<(i64, Rc<RefCell<dyn Attribute>>)>, usize), anyhow::Error> {
+        self.db.borrow().get_sorted_attributes_as_of(
+            self.db.clone(),
+            transaction,
+            self.get_id(),
+            Some(as_of_in),
+            starting_object_index_in,
+            max_vals_in,
+            only_public_entities_in,
+        )
+    }
+
     pub fn update_class<'a, 'b>(
         &'a mut self,
         transaction: Option<Rc<RefCell<Transaction<'b, Postgres>>>>,
@@ -25,6 +25,7 @@ pub struct EntityClass {
     name: String,                            /*= null*/
     template_entity_id: i64,                 /*= 0*/
     create_default_attributes: Option<bool>, /*= None*/
+    parent_class_id: Option<i64>,            /*= None*/
 }
 
 impl EntityClass {
@@ -50,6 +51,7 @@ impl EntityClass {
         name_in: &str,
         template_entity_id: i64,
         create_default_attributes: Option<bool>, /*= None*/
+        parent_class_id: Option<i64>,            /*= None*/
     ) -> EntityClass {
         EntityClass {
             db,
@@ -57,6 +59,7 @@ impl EntityClass {
             name: name_in.to_string(),
             template_entity_id,
             create_default_attributes,
+            parent_class_id,
             already_read_data: true,
         }
     }
@@ -78,6 +81,7 @@ impl EntityClass {
                 name: "".to_string(),
                 template_entity_id: 0,
                 create_default_attributes: None,
+                parent_class_id: None,
             })
         }
     }
@@ -139,6 +143,30 @@ impl EntityClass {
         Ok(self.create_default_attributes)
     }
 
+    /// The class this one is a subclass of, if any.  Members of a subclass count as members of
+    /// its parent (in class-limited searches, and in groups that don't allow mixed classes), and
+    /// get its template's attributes as well as their own class's.
+    pub fn get_parent_class_id(
+        &mut self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<Option<i64>, Error> {
+        if !self.already_read_data {
+            self.read_data_from_db(transaction)?
+        }
+        Ok(self.parent_class_id)
+    }
+
+    pub fn update_parent_class_id(
+        &mut self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        parent_class_id_in: Option<i64>,
+    ) -> Result<(), Error> {
+        self.db.borrow()
+            .update_class_parent(transaction, self.get_id(), parent_class_id_in)?;
+        self.parent_class_id = parent_class_id_in;
+        Ok(())
+    }
+
     fn read_data_from_db(
         &mut self,
         transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
@@ -163,6 +191,11 @@ impl EntityClass {
             None => None,
             _ => return Err(anyhow!("How did we get here for {:?}?", data[2])),
         };
+        self.parent_class_id = match data[3] {
+            Some(DataType::Bigint(x)) => Some(x),
+            None => None,
+            _ => return Err(anyhow!("How did we get here for {:?}?", data[3])),
+        };
         self.already_read_data = true;
         Ok(())
     }
@@ -256,6 +289,7 @@ mod test {
     use super::EntityClass;
     use crate::model::database::{/*DataType, */Database};
     use crate::model::entity::Entity;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
     use crate::util::Util;
     use std::rc::Rc;
     use std::cell::RefCell;
@@ -401,4 +435,43 @@ mod test {
             Some(true)
         );
     }
+
+    #[test]
+    fn subclass_members_count_as_members_of_the_parent() {
+        Util::initialize_tracing();
+        let pg: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db: Rc<RefCell<dyn Database>> = pg.clone();
+        let pg_b = pg.borrow();
+        let tx = Some(Rc::new(RefCell::new(pg_b.begin_trans().unwrap())));
+        let (parent_id, parent_template_id) =
+            db.borrow().create_class_and_its_template_entity(tx.clone(), "vehicle").unwrap();
+        let (child_id, child_template_id) =
+            db.borrow().create_class_and_its_template_entity(tx.clone(), "car").unwrap();
+        let (other_id, _) = db.borrow().create_class_and_its_template_entity(tx.clone(), "fruit").unwrap();
+        let mut child = EntityClass::new2(db.clone(), tx.clone(), child_id).unwrap();
+        assert_eq!(child.get_parent_class_id(tx.clone()).unwrap(), None);
+        child.update_parent_class_id(tx.clone(), Some(parent_id)).unwrap();
+        assert_eq!(child.get_parent_class_id(tx.clone()).unwrap(), Some(parent_id));
+        let lineage = db.borrow().get_class_lineage(tx.clone(), child_id).unwrap();
+        assert_eq!(lineage.iter().map(|(id, _)| *id).collect::<Vec<i64>>(), vec![child_id, parent_id]);
+        assert!(db.borrow().update_class_parent(tx.clone(), parent_id, Some(child_id)).is_err());
+        assert!(db.borrow().update_class_parent(tx.clone(), child_id, Some(child_id)).is_err());
+
+        let bike = db.borrow().create_entity(tx.clone(), "bike", Some(parent_id), None).unwrap();
+        let sedan = db.borrow().create_entity(tx.clone(), "sedan", Some(child_id), None).unwrap();
+        // The parent's count includes the subclass member, but not the subclass's template entity.
+        let count =
+            db.borrow().get_entities_only_count(tx.clone(), true, Some(parent_id), Some(parent_template_id)).unwrap();
+        assert_eq!(count, 2);
+        let count =
+            db.borrow().get_entities_only_count(tx.clone(), true, Some(child_id), Some(child_template_id)).unwrap();
+        assert_eq!(count, 1);
+
+        let group_id = db.borrow().create_group(tx.clone(), "vehicles", false).unwrap();
+        db.borrow().add_entity_to_group(tx.clone(), group_id, bike, None).unwrap();
+        db.borrow().add_entity_to_group(tx.clone(), group_id, sedan, None).unwrap();
+        let apple = db.borrow().create_entity(tx.clone(), "apple", Some(other_id), None).unwrap();
+        assert!(db.borrow().add_entity_to_group(tx.clone(), group_id, apple, None).is_err());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
 }
@@ -0,0 +1,635 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+use crate::model::database::Database;
+use crate::model::entity::Entity;
+use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// A small query language for finding entities by class, name, attributes, relations and
+/// containment.  A query is one or more clauses joined by "and", each optionally preceded by
+/// "not".  For example:
+///     class = Book and date "read on" > 2024-01-01 and under 123 and not archived
+/// See Util::QUERY_SYNTAX_HELP for the user-facing description of the clauses.
+/// The query is only parsed here; a Database evaluates it (see
+/// Database::get_entities_matching_query).
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityQuery {
+    /// The query as the user wrote it, kept because relative dates like "-7d" must be
+    /// re-evaluated each time (as for a saved search).
+    text: String,
+    pub conditions: Vec<QueryCondition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    /// Case-insensitive regular expression match (only for strings).
+    Matches,
+}
+
+impl QueryOp {
+    fn from_token(token: &str) -> Option<QueryOp> {
+        match token {
+            "=" => Some(QueryOp::Equal),
+            "!=" => Some(QueryOp::NotEqual),
+            "<" => Some(QueryOp::Less),
+            "<=" => Some(QueryOp::LessOrEqual),
+            ">" => Some(QueryOp::Greater),
+            ">=" => Some(QueryOp::GreaterOrEqual),
+            "~" => Some(QueryOp::Matches),
+            _ => None,
+        }
+    }
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            QueryOp::Equal => "=",
+            QueryOp::NotEqual => "<>",
+            QueryOp::Less => "<",
+            QueryOp::LessOrEqual => "<=",
+            QueryOp::Greater => ">",
+            QueryOp::GreaterOrEqual => ">=",
+            QueryOp::Matches => "~*",
+        }
+    }
+}
+
+impl fmt::Display for QueryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            QueryOp::Equal => "=",
+            QueryOp::NotEqual => "!=",
+            QueryOp::Less => "<",
+            QueryOp::LessOrEqual => "<=",
+            QueryOp::Greater => ">",
+            QueryOp::GreaterOrEqual => ">=",
+            QueryOp::Matches => "~",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// An entity given in a query either by its id or by its (case-insensitive, exact) name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntityRef {
+    Id(i64),
+    Name(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Number(f64),
+    Text(String),
+    /// Milliseconds UTC; the bool is true if only a day (no time) was given, so that "=" can
+    /// match the whole day.
+    Date(i64, bool),
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttributeForm {
+    Quantity,
+    Text,
+    Date,
+    Boolean,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryCondition {
+    /// The entity's class name compared with op (only = != ~).
+    Class(QueryOp, String),
+    /// The entity's name compared with op (only = != ~).
+    Name(QueryOp, String),
+    /// The entity has an attribute of the given form whose type has the given name, and (if
+    /// provided) whose value compares as given.
+    Attribute(AttributeForm, String, Option<(QueryOp, AttributeValue)>),
+    /// The entity has a relation of the named type, optionally to the given entity.
+    Related(String, Option<EntityRef>),
+    /// The entity is contained (through relations and groups) under the given entity.
+    Under(EntityRef),
+    /// When the entity was added, compared with op.
+    Added(QueryOp, i64, bool),
+    Archived,
+    Not(Box<QueryCondition>),
+}
+
+impl EntityQuery {
+    /// How far down find_contained_local_entity_ids looks, for "under" clauses.
+    pub const CONTAINMENT_LEVELS: i32 = 20;
+
+    pub fn parse(query_in: &str) -> Result<EntityQuery, anyhow::Error> {
+        let tokens = tokenize(query_in)?;
+        if tokens.is_empty() {
+            return Err(anyhow!("The query is empty."));
+        }
+        let mut parser = Parser::new(tokens);
+        let conditions = parser.parse_clauses()?;
+        Ok(EntityQuery {
+            text: query_in.trim().to_string(),
+            conditions,
+        })
+    }
+
+    /// Returns query_in with each entity id in it (as after "under" or "related ... to")
+    /// replaced by what map_in gives for it, and otherwise unchanged.  For copying a saved
+    /// query to where the entities have other ids, as when importing (an id map_in doesn't
+    /// know is left as it was).
+    pub fn map_entity_ids(query_in: &str, map_in: impl Fn(i64) -> Option<i64>) -> Result<String, anyhow::Error> {
+        let (tokens, spans): (Vec<Token>, Vec<Range<usize>>) = tokenize_with_spans(query_in)?.into_iter().unzip();
+        let mut parser = Parser::new(tokens);
+        parser.parse_clauses()?;
+        let mut chars: Vec<char> = query_in.chars().collect();
+        // (Last first, so the earlier spans still apply.)
+        for (position, id) in parser.entity_ids.into_iter().rev() {
+            if let Some(new_id) = map_in(id) {
+                chars.splice(spans[position].clone(), new_id.to_string().chars());
+            }
+        }
+        Ok(chars.into_iter().collect())
+    }
+
+    /// The ids of the entities the query refers to by id.
+    pub fn get_entity_ids(&self) -> Vec<i64> {
+        fn add(c: &QueryCondition, ids: &mut Vec<i64>) {
+            match c {
+                QueryCondition::Under(EntityRef::Id(id)) | QueryCondition::Related(_, Some(EntityRef::Id(id))) => ids.push(*id),
+                QueryCondition::Not(inner) => add(inner, ids),
+                _ => {}
+            }
+        }
+        let mut ids = Vec::new();
+        for condition in &self.conditions {
+            add(condition, &mut ids);
+        }
+        ids
+    }
+
+    pub fn get_text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    /// True if any clause (negated or not) says something about archived status, in which case
+    /// the evaluator should not add its own default "not archived" filter.
+    pub fn mentions_archived(&self) -> bool {
+        fn check(c: &QueryCondition) -> bool {
+            match c {
+                QueryCondition::Archived => true,
+                QueryCondition::Not(inner) => check(inner),
+                _ => false,
+            }
+        }
+        self.conditions.iter().any(check)
+    }
+
+    /// Convenience wrapper around Database::get_entities_matching_query.
+    pub fn get_matching_entities(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        starting_object_index_in: u64,
+        max_vals_in: Option<u64>, /*= None*/
+    ) -> Result<Vec<Entity>, anyhow::Error> {
+        let result = db.borrow().get_entities_matching_query(
+            db.clone(),
+            transaction,
+            self,
+            starting_object_index_in,
+            max_vals_in,
+        );
+        result
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(QueryOp),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "\"{}\"", w),
+            Token::Quoted(q) => write!(f, "the quoted text \"{}\"", q),
+            Token::Op(op) => write!(f, "\"{}\"", op),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, anyhow::Error> {
+    Ok(tokenize_with_spans(s)?.into_iter().map(|(token, _)| token).collect())
+}
+
+/// Like tokenize, but also gives where (in chars) each token was in s.
+fn tokenize_with_spans(s: &str) -> Result<Vec<(Token, Range<usize>)>, anyhow::Error> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let quote = c;
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(anyhow!("Unterminated quoted text in query: {}{}", quote, value)),
+                    Some(ch) if *ch == quote => {
+                        i += 1;
+                        break;
+                    }
+                    Some(ch) => {
+                        value.push(*ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((Token::Quoted(value), start..i));
+        } else if "=!<>~".contains(c) {
+            let mut op = c.to_string();
+            if let Some('=') = chars.get(i + 1) {
+                if c != '=' && c != '~' {
+                    op.push('=');
+                }
+            }
+            i += op.len();
+            match QueryOp::from_token(op.as_str()) {
+                Some(o) => tokens.push((Token::Op(o), start..i)),
+                None => return Err(anyhow!("Unrecognized operator in query: {}", op)),
+            }
+        } else {
+            let mut word = String::new();
+            while i < chars.len() && !chars[i].is_whitespace() && !"=!<>~\"'".contains(chars[i]) {
+                word.push(chars[i]);
+                i += 1;
+            }
+            tokens.push((Token::Word(word), start..i));
+        }
+    }
+    // Dates with a time ("2024-01-01 13:30") are two words; join them back up here so the parser
+    // can treat them as one value.
+    let mut joined: Vec<(Token, Range<usize>)> = Vec::with_capacity(tokens.len());
+    for (token, span) in tokens {
+        if let (Some((Token::Word(prev), prev_span)), Token::Word(w)) = (joined.last_mut(), &token) {
+            if NaiveDate::parse_from_str(prev, "%Y-%m-%d").is_ok() && is_time_of_day(w) {
+                prev.push(' ');
+                prev.push_str(w);
+                prev_span.end = span.end;
+                continue;
+            }
+        }
+        joined.push((token, span));
+    }
+    Ok(joined)
+}
+
+fn is_time_of_day(s: &str) -> bool {
+    let parts: Vec<&str> = s.split(':').collect();
+    parts.len() == 2 && parts.iter().all(|p| !p.is_empty() && p.len() <= 2 && p.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Parses "YYYY-MM-DD", "YYYY-MM-DD HH:MM", "today", or "-Nd" (N days ago, at the start of that
+/// day), all as UTC.  Returns milliseconds and whether only a day was given.
+pub fn parse_query_date(s: &str) -> Result<(i64, bool), anyhow::Error> {
+    let today = Utc::now().date_naive();
+    let day_to_millis = |d: NaiveDate| -> Result<i64, anyhow::Error> {
+        match d.and_hms_opt(0, 0, 0) {
+            Some(dt) => Ok(dt.timestamp_millis()),
+            None => Err(anyhow!("Unable to make a date from {}", d)),
+        }
+    };
+    if s.eq_ignore_ascii_case("today") {
+        return Ok((day_to_millis(today)?, true));
+    }
+    if let Some(days) = s.strip_prefix('-').and_then(|r| r.strip_suffix('d')) {
+        if let Ok(n) = days.parse::<i64>() {
+            return Ok((day_to_millis(today - chrono::Duration::days(n))?, true));
+        }
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M") {
+        return Ok((dt.timestamp_millis(), false));
+    }
+    match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(d) => Ok((day_to_millis(d)?, true)),
+        Err(e) => Err(anyhow!(
+            "Unable to parse \"{}\" as a date (expected YYYY-MM-DD, YYYY-MM-DD HH:MM, today, or -Nd): {}",
+            s,
+            e
+        )),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// The entity ids found, with the positions of their tokens.
+    entity_ids: Vec<(usize, i64)>,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Parser {
+        Parser {
+            tokens,
+            position: 0,
+            entity_ids: Vec::new(),
+        }
+    }
+    fn parse_clauses(&mut self) -> Result<Vec<QueryCondition>, anyhow::Error> {
+        let mut conditions = Vec::new();
+        loop {
+            conditions.push(self.parse_clause()?);
+            match self.next() {
+                None => break,
+                Some(Token::Word(w)) if w.eq_ignore_ascii_case("and") => continue,
+                Some(t) => return Err(anyhow!("Expected \"and\" or the end of the query, but found {}.", t)),
+            }
+        }
+        Ok(conditions)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        t
+    }
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    fn peek_is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(word))
+    }
+
+    /// A name or value: either a bare word or quoted text.
+    fn expect_text(&mut self, what: &str) -> Result<String, anyhow::Error> {
+        match self.next() {
+            Some(Token::Word(w)) | Some(Token::Quoted(w)) => Ok(w),
+            Some(t) => Err(anyhow!("Expected {} but found {}.", what, t)),
+            None => Err(anyhow!("Expected {} but the query ended.", what)),
+        }
+    }
+    fn expect_op(&mut self, after: &str) -> Result<QueryOp, anyhow::Error> {
+        match self.next() {
+            Some(Token::Op(op)) => Ok(op),
+            Some(t) => Err(anyhow!("Expected an operator (= != < <= > >= ~) after \"{}\" but found {}.", after, t)),
+            None => Err(anyhow!("Expected an operator (= != < <= > >= ~) after \"{}\" but the query ended.", after)),
+        }
+    }
+    fn expect_entity_ref(&mut self, after: &str) -> Result<EntityRef, anyhow::Error> {
+        match self.next() {
+            Some(Token::Word(w)) => match w.parse::<i64>() {
+                Ok(id) => {
+                    self.entity_ids.push((self.position - 1, id));
+                    Ok(EntityRef::Id(id))
+                }
+                Err(_) => Ok(EntityRef::Name(w)),
+            },
+            Some(Token::Quoted(q)) => Ok(EntityRef::Name(q)),
+            Some(t) => Err(anyhow!("Expected an entity id or name after \"{}\" but found {}.", after, t)),
+            None => Err(anyhow!("Expected an entity id or name after \"{}\" but the query ended.", after)),
+        }
+    }
+
+    fn parse_clause(&mut self) -> Result<QueryCondition, anyhow::Error> {
+        let keyword = match self.next() {
+            Some(Token::Word(w)) => w.to_lowercase(),
+            Some(t) => return Err(anyhow!("Expected a clause keyword (class, name, quantity, text, date, boolean, related, under, added, archived, not) but found {}.", t)),
+            None => return Err(anyhow!("Expected a clause but the query ended.")),
+        };
+        match keyword.as_str() {
+            "not" => Ok(QueryCondition::Not(Box::new(self.parse_clause()?))),
+            "archived" => Ok(QueryCondition::Archived),
+            "class" | "name" => {
+                let op = self.expect_op(keyword.as_str())?;
+                if !matches!(op, QueryOp::Equal | QueryOp::NotEqual | QueryOp::Matches) {
+                    return Err(anyhow!("Only =, != or ~ can be used with \"{}\".", keyword));
+                }
+                let value = self.expect_text("a value")?;
+                if keyword == "class" {
+                    Ok(QueryCondition::Class(op, value))
+                } else {
+                    Ok(QueryCondition::Name(op, value))
+                }
+            }
+            "added" => {
+                let op = self.expect_op("added")?;
+                if op == QueryOp::Matches {
+                    return Err(anyhow!("\"~\" can not be used with dates."));
+                }
+                let (date, day_only) = parse_query_date(self.expect_text("a date")?.as_str())?;
+                Ok(QueryCondition::Added(op, date, day_only))
+            }
+            "quantity" | "text" | "date" | "boolean" => {
+                let form = match keyword.as_str() {
+                    "quantity" => AttributeForm::Quantity,
+                    "text" => AttributeForm::Text,
+                    "date" => AttributeForm::Date,
+                    _ => AttributeForm::Boolean,
+                };
+                let type_name = self.expect_text("an attribute type name")?;
+                let comparison = match self.peek() {
+                    Some(Token::Op(_)) => {
+                        let op = self.expect_op(type_name.as_str())?;
+                        let raw = self.expect_text("a value")?;
+                        Some((op, parse_value(form, op, raw.as_str())?))
+                    }
+                    _ => None,
+                };
+                Ok(QueryCondition::Attribute(form, type_name, comparison))
+            }
+            "related" => {
+                let rel_type_name = self.expect_text("a relation type name")?;
+                let target = if self.peek_is_word("to") {
+                    self.next();
+                    Some(self.expect_entity_ref("to")?)
+                } else {
+                    None
+                };
+                Ok(QueryCondition::Related(rel_type_name, target))
+            }
+            "under" => Ok(QueryCondition::Under(self.expect_entity_ref("under")?)),
+            other => Err(anyhow!(
+                "Unrecognized query clause \"{}\": expected one of class, name, quantity, text, date, boolean, related, under, added, archived, not.",
+                other
+            )),
+        }
+    }
+}
+
+fn parse_value(form: AttributeForm, op: QueryOp, raw: &str) -> Result<AttributeValue, anyhow::Error> {
+    if op == QueryOp::Matches && form != AttributeForm::Text {
+        return Err(anyhow!("\"~\" can only be used with text attributes."));
+    }
+    match form {
+        AttributeForm::Quantity => match raw.parse::<f64>() {
+            Ok(n) => Ok(AttributeValue::Number(n)),
+            Err(e) => Err(anyhow!("Unable to parse \"{}\" as a number: {}", raw, e)),
+        },
+        AttributeForm::Text => Ok(AttributeValue::Text(raw.to_string())),
+        AttributeForm::Date => {
+            let (d, day_only) = parse_query_date(raw)?;
+            Ok(AttributeValue::Date(d, day_only))
+        }
+        AttributeForm::Boolean => {
+            if !matches!(op, QueryOp::Equal | QueryOp::NotEqual) {
+                return Err(anyhow!("Only = or != can be used with boolean attributes."));
+            }
+            match raw.to_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" => Ok(AttributeValue::Boolean(true)),
+                "false" | "f" | "no" | "n" => Ok(AttributeValue::Boolean(false)),
+                _ => Err(anyhow!("Unable to parse \"{}\" as true or false.", raw)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+    use crate::util::Util;
+
+    #[test]
+    fn test_parse() {
+        let q = EntityQuery::parse(
+            "class = Book and date \"read on\" > 2024-01-01 and under 123 and not archived",
+        )
+        .unwrap();
+        assert_eq!(q.conditions.len(), 4);
+        assert_eq!(q.conditions[0], QueryCondition::Class(QueryOp::Equal, "Book".to_string()));
+        let jan1 = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap().timestamp_millis();
+        assert_eq!(
+            q.conditions[1],
+            QueryCondition::Attribute(
+                AttributeForm::Date,
+                "read on".to_string(),
+                Some((QueryOp::Greater, AttributeValue::Date(jan1, true)))
+            )
+        );
+        assert_eq!(q.conditions[2], QueryCondition::Under(EntityRef::Id(123)));
+        assert_eq!(q.conditions[3], QueryCondition::Not(Box::new(QueryCondition::Archived)));
+        assert!(q.mentions_archived());
+
+        let q = EntityQuery::parse("boolean done=false AND related 'is part of' to \"my house\" and quantity weight >= 2.5").unwrap();
+        assert_eq!(
+            q.conditions[0],
+            QueryCondition::Attribute(AttributeForm::Boolean, "done".to_string(), Some((QueryOp::Equal, AttributeValue::Boolean(false))))
+        );
+        assert_eq!(
+            q.conditions[1],
+            QueryCondition::Related("is part of".to_string(), Some(EntityRef::Name("my house".to_string())))
+        );
+        assert_eq!(
+            q.conditions[2],
+            QueryCondition::Attribute(AttributeForm::Quantity, "weight".to_string(), Some((QueryOp::GreaterOrEqual, AttributeValue::Number(2.5))))
+        );
+        assert!(!q.mentions_archived());
+
+        let q = EntityQuery::parse("date due < 2024-02-03 14:30 and text note").unwrap();
+        assert!(matches!(q.conditions[0], QueryCondition::Attribute(AttributeForm::Date, _, Some((QueryOp::Less, AttributeValue::Date(_, false))))));
+        assert_eq!(q.conditions[1], QueryCondition::Attribute(AttributeForm::Text, "note".to_string(), None));
+
+        assert!(EntityQuery::parse("").is_err());
+        assert!(EntityQuery::parse("class Book").is_err());
+        assert!(EntityQuery::parse("class = Book or name = x").is_err());
+        assert!(EntityQuery::parse("quantity weight ~ 3").is_err());
+        assert!(EntityQuery::parse("boolean done > true").is_err());
+        assert!(EntityQuery::parse("name = 'unterminated").is_err());
+        assert!(EntityQuery::parse("date due = 2024-13-01").is_err());
+        assert!(EntityQuery::parse("added >= -7d").is_ok());
+    }
+
+    #[test]
+    fn test_map_entity_ids() {
+        let text = "name ~ '12' and not under 12 and related \"has\" to 34 and related has and added > 2024-01-01 13:30";
+        assert_eq!(EntityQuery::parse(text).unwrap().get_entity_ids(), vec![12, 34]);
+        let mapped = EntityQuery::map_entity_ids(text, |id| if id == 12 { Some(1200) } else { None }).unwrap();
+        assert_eq!(
+            mapped,
+            "name ~ '12' and not under 1200 and related \"has\" to 34 and related has and added > 2024-01-01 13:30"
+        );
+        assert!(EntityQuery::map_entity_ids("under", |id| Some(id)).is_err());
+    }
+
+    #[test]
+    fn test_get_entities_matching_query() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> = Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let dbd: Rc<RefCell<dyn Database>> = db.clone();
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+
+        let (class_id, template_id) = db_b
+            .create_class_and_its_template_entity(tx.clone(), "queryTestBookClass")
+            .unwrap();
+        let container_id = db_b.create_entity(tx.clone(), "queryTestContainer", None, None).unwrap();
+        let book1 = db_b.create_entity(tx.clone(), "queryTestBook1", Some(class_id), None).unwrap();
+        let book2 = db_b.create_entity(tx.clone(), "queryTestBook2", Some(class_id), None).unwrap();
+        let other = db_b.create_entity(tx.clone(), "queryTestOther", None, None).unwrap();
+        let read_on_type = db_b.create_entity(tx.clone(), "queryTestReadOn", None, None).unwrap();
+        let rel_type = db_b
+            .create_relation_type(tx.clone(), "queryTestContains", "queryTestContainedIn", "UNI")
+            .unwrap();
+        for id in [book1, other] {
+            db_b.create_relation_to_local_entity(tx.clone(), rel_type, container_id, id, Some(0), 0, None)
+                .unwrap();
+        }
+        let (jan2, _) = parse_query_date("2024-01-02").unwrap();
+        let (dec31, _) = parse_query_date("2023-12-31").unwrap();
+        db_b.create_date_attribute(tx.clone(), book1, read_on_type, jan2, None).unwrap();
+        db_b.create_date_attribute(tx.clone(), book2, read_on_type, dec31, None).unwrap();
+
+        let ids = |query: &str| -> Vec<i64> {
+            let q = EntityQuery::parse(query).unwrap();
+            let mut ids: Vec<i64> = q
+                .get_matching_entities(dbd.clone(), tx.clone(), 0, None)
+                .unwrap()
+                .iter()
+                .map(|e| e.get_id())
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids("class = queryTestBookClass"), vec![template_id, book1, book2]);
+        assert_eq!(ids("class = queryTestBookClass and date queryTestReadOn > 2024-01-01"), vec![book1]);
+        assert_eq!(ids("class = queryTestBookClass and date queryTestReadOn = 2023-12-31"), vec![book2]);
+        assert_eq!(ids(format!("class = queryTestBookClass and under {}", container_id).as_str()), vec![book1]);
+        assert_eq!(ids("under queryTestContainer and not class = queryTestBookClass"), vec![other]);
+        assert_eq!(ids("name ~ '^querytestbook[0-9]' and not under queryTestContainer"), vec![book2]);
+        assert_eq!(ids(format!("related queryTestContains to {}", other).as_str()), vec![container_id]);
+        assert_eq!(ids(format!("related queryTestContains to {}", book2).as_str()), Vec::<i64>::new());
+        assert_eq!(ids("name = queryTestContainer and related queryTestContains"), vec![container_id]);
+        // An empty container has nothing under it, so "not under" it is everything:
+        let empty_id = db_b.create_entity(tx.clone(), "queryTestEmpty", None, None).unwrap();
+        assert_eq!(ids(format!("name ~ '^querytestbook[0-9]' and under {}", empty_id).as_str()), Vec::<i64>::new());
+        assert_eq!(ids(format!("name ~ '^querytestbook[0-9]' and not under {}", empty_id).as_str()), vec![book1, book2]);
+        assert_eq!(
+            ids(format!("name = queryTestContainer and not related queryTestContains to {}", empty_id).as_str()),
+            vec![container_id]
+        );
+
+        db_b.archive_entity(tx.clone(), book2).unwrap();
+        assert_eq!(ids("class = queryTestBookClass"), vec![template_id, book1]);
+        assert_eq!(ids("class = queryTestBookClass and archived"), vec![book2]);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
@@ -0,0 +1,307 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! A versioned JSON format for moving data between OM instances without losing anything the
+//! database holds: entities with their dates and flags, every attribute form with its sorting
+//! index, groups and their entries, classes with their template entities and actions, relation
+//! types, OmInstance rows, and unit conversions.  The ids in it are those of the exporting
+//! database; importing gives everything new ids (see Database::import_exchange_data).
+//!
+//! Entities that the exported ones only refer to (like attribute types, units and relation
+//! types outside an exported subtree) are included with "reference": true and just enough to
+//! find or recreate them by name when importing, instead of with their own contents.
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+pub const FORMAT_NAME: &str = "onemodel-exchange";
+/// Increment when the format changes so older programs can refuse what they can't read.
+pub const FORMAT_VERSION: i32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeData {
+    pub format: String,
+    pub version: i32,
+    /// The PostgreSQLDatabase::SCHEMA_VERSION of the exporting database, for information.
+    pub schema_version: i32,
+    pub exported_date: i64,
+    /// The entities a subtree export started from; None for a whole-database export.
+    pub root_entity_ids: Option<Vec<i64>>,
+    pub entities: Vec<ExchangeEntity>,
+    pub relation_types: Vec<ExchangeRelationType>,
+    pub classes: Vec<ExchangeClass>,
+    pub actions: Vec<ExchangeAction>,
+    pub om_instances: Vec<ExchangeOmInstance>,
+    pub groups: Vec<ExchangeGroup>,
+    pub attributes: Vec<ExchangeAttribute>,
+    pub unit_conversions: Vec<ExchangeUnitConversion>,
+    pub canonical_units: Vec<ExchangeCanonicalUnit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeEntity {
+    pub id: i64,
+    pub name: String,
+    pub class_id: Option<i64>,
+    pub archived: bool,
+    pub archived_date: Option<i64>,
+    pub insertion_date: i64,
+    pub public: Option<bool>,
+    pub new_entries_stick_to_top: bool,
+    /// True if only referred to by the exported data, so its attributes are not included, and
+    /// importing uses an existing entity of the same name if there is one.
+    #[serde(default)]
+    pub reference: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeRelationType {
+    /// The relation type's entity, in ExchangeData::entities (where its name is).
+    pub entity_id: i64,
+    pub name_in_reverse_direction: String,
+    /// "BI", "UNI" or "NON".
+    pub directionality: String,
+    #[serde(default)]
+    pub transitive: bool,
+    #[serde(default)]
+    pub symmetric: bool,
+    /// Another relation type's entity id.  Kept when importing only if that type is imported too
+    /// (or matches an existing one).
+    #[serde(default)]
+    pub inverse_of_id: Option<i64>,
+    /// The classes allowed for the entities each relation is from and to (see
+    /// RelationClassConstraints).  Kept when importing only if those classes are imported too.
+    #[serde(default)]
+    pub source_class_ids: Vec<i64>,
+    #[serde(default)]
+    pub target_class_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeClass {
+    pub id: i64,
+    pub name: String,
+    pub defining_entity_id: i64,
+    pub create_default_attributes: Option<bool>,
+    /// Kept when importing only if the parent class is imported too.
+    #[serde(default)]
+    pub parent_class_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeAction {
+    pub id: i64,
+    pub class_id: i64,
+    pub name: String,
+    pub action: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeOmInstance {
+    /// The instance's uuid, which is the same in every database that knows of it.
+    pub id: String,
+    pub local: bool,
+    pub address: String,
+    pub insertion_date: i64,
+    pub entity_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeGroup {
+    pub id: i64,
+    pub name: String,
+    pub insertion_date: i64,
+    pub allow_mixed_classes: bool,
+    pub new_entries_stick_to_top: bool,
+    pub query: Option<String>,
+    pub entries: Vec<ExchangeGroupEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeGroupEntry {
+    pub entity_id: i64,
+    pub sorting_index: i64,
+}
+
+/// An attribute of any form.  For the relation forms, attr_type_id is the relation type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeAttribute {
+    pub id: i64,
+    pub entity_id: i64,
+    pub attr_type_id: i64,
+    /// From AttributeSorting: where it is displayed among its entity's attributes.
+    pub sorting_index: i64,
+    /// For a relation with attributes of its own: the entity holding them, in
+    /// ExchangeData::entities.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes_entity_id: Option<i64>,
+    #[serde(flatten)]
+    pub value: ExchangeAttributeValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "form", rename_all = "snake_case")]
+pub enum ExchangeAttributeValue {
+    Quantity {
+        unit_id: i64,
+        number: f64,
+        valid_on_date: Option<i64>,
+        observation_date: i64,
+    },
+    Date {
+        date: i64,
+    },
+    Boolean {
+        value: Option<bool>,
+        valid_on_date: Option<i64>,
+        observation_date: i64,
+    },
+    File {
+        description: String,
+        original_file_date: i64,
+        stored_date: i64,
+        original_file_path: String,
+        readable: bool,
+        writable: bool,
+        executable: bool,
+        size: i64,
+        md5hash: String,
+        /// The file's contents in base64, or None if the database had none stored for it.
+        content_base64: Option<String>,
+    },
+    Text {
+        text: String,
+        valid_on_date: Option<i64>,
+        observation_date: i64,
+    },
+    RelationToLocalEntity {
+        entity_id_2: i64,
+        valid_on_date: Option<i64>,
+        observation_date: i64,
+    },
+    RelationToRemoteEntity {
+        remote_instance_id: String,
+        /// An id in the remote instance, so not remapped by importing.
+        entity_id_2: i64,
+        valid_on_date: Option<i64>,
+        observation_date: i64,
+    },
+    RelationToGroup {
+        group_id: i64,
+        valid_on_date: Option<i64>,
+        observation_date: i64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeUnitConversion {
+    pub from_unit_id: i64,
+    pub to_unit_id: i64,
+    pub factor: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeCanonicalUnit {
+    pub attr_type_id: i64,
+    pub unit_id: i64,
+}
+
+/// What Database::import_exchange_data did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExchangeImportCounts {
+    pub entities_created: usize,
+    /// Reference entities, relation types and classes found already in the database by name.
+    pub entities_matched: usize,
+    pub groups: usize,
+    pub attributes: usize,
+    /// The new ids of ExchangeData::root_entity_ids, in the same order.
+    pub root_entity_ids: Vec<i64>,
+}
+
+impl ExchangeData {
+    pub fn to_json(&self) -> Result<String, anyhow::Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json_in: &str) -> Result<ExchangeData, anyhow::Error> {
+        // Checking these first gives a clearer message for a file that is not ours at all, than
+        // whatever field serde would first find missing.
+        let value: serde_json::Value = serde_json::from_str(json_in)?;
+        if value.get("format").and_then(|f| f.as_str()) != Some(FORMAT_NAME) {
+            return Err(anyhow!("This is not a OneModel exchange file (expected \"format\": \"{}\").", FORMAT_NAME));
+        }
+        match value.get("version").and_then(|v| v.as_i64()) {
+            Some(v) if v >= 1 && v <= FORMAT_VERSION as i64 => {}
+            v => {
+                return Err(anyhow!(
+                    "Unsupported exchange format version {:?}: this program reads versions 1 through {}.",
+                    v,
+                    FORMAT_VERSION
+                ))
+            }
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn get_entity(&self, id_in: i64) -> Option<&ExchangeEntity> {
+        self.entities.iter().find(|e| e.id == id_in)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let data = ExchangeData {
+            format: FORMAT_NAME.to_string(),
+            version: FORMAT_VERSION,
+            schema_version: 9,
+            exported_date: 1000,
+            root_entity_ids: Some(vec![5]),
+            entities: vec![ExchangeEntity {
+                id: 5,
+                name: "it's".to_string(),
+                class_id: None,
+                archived: false,
+                archived_date: None,
+                insertion_date: 900,
+                public: Some(true),
+                new_entries_stick_to_top: false,
+                reference: false,
+            }],
+            relation_types: vec![],
+            classes: vec![],
+            actions: vec![],
+            om_instances: vec![],
+            groups: vec![],
+            attributes: vec![ExchangeAttribute {
+                id: 7,
+                entity_id: 5,
+                attr_type_id: 6,
+                sorting_index: -3,
+                attributes_entity_id: None,
+                value: ExchangeAttributeValue::Text {
+                    text: "a; b".to_string(),
+                    valid_on_date: None,
+                    observation_date: 950,
+                },
+            }],
+            unit_conversions: vec![],
+            canonical_units: vec![],
+        };
+        let json = data.to_json().unwrap();
+        assert!(json.contains("\"form\": \"text\""));
+        assert_eq!(ExchangeData::from_json(&json).unwrap(), data);
+
+        assert!(ExchangeData::from_json("{\"format\": \"other\"}").is_err());
+        let newer = json.replace("\"version\": 1", "\"version\": 99");
+        assert!(ExchangeData::from_json(&newer).unwrap_err().to_string().contains("99"));
+    }
+}
@@ -47,6 +47,20 @@ impl Group {
         Group::new2(db_in, transaction, id)
     }
 
+    /// Creates a saved search ("smart group"), whose entries are computed from query_in (see
+    /// EntityQuery) each time they are read, so its membership can't be edited directly.
+    pub fn create_smart_group(
+        db_in: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        in_name: &str,
+        query_in: &str,
+    ) -> Result<Group, Error> {
+        let id: i64 = db_in
+            .borrow()
+            .create_smart_group(transaction.clone(), in_name, query_in)?;
+        Group::new2(db_in, transaction, id)
+    }
+
     /// This is for times when you want None if it doesn't exist, instead of the exception thrown by the Entity constructor.  Or for convenience in tests.
     fn get_group(
         db_in: Rc<RefCell<dyn Database>>,
@@ -173,6 +187,9 @@ impl Group {
             formatted_name.as_str()
         });
         if !simplify_in {
+            if let Some(query) = self.get_query(transaction.clone())? {
+                result.push_str(format!(", saved search: {}", query).as_str());
+            }
             result.push_str(", class: ");
             let class_name = {
                 if self.get_mixed_classes_allowed(transaction.clone())? {
@@ -319,6 +336,22 @@ impl Group {
             .add_entity_to_group(tx, id, in_entity_id, sorting_index_in)
     }
 
+    /// Returns the query if this group is a saved search (see create_smart_group), else None.
+    /// (Not cached like the other fields, so it is current even if the query was edited.)
+    pub fn get_query(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<Option<String>, Error> {
+        self.db.borrow().get_group_query(transaction, self.id)
+    }
+
+    pub fn is_saved_search(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<bool, Error> {
+        Ok(self.get_query(transaction)?.is_some())
+    }
+
     // (See comment on fn get_id in quantity_attribute.rs, about no call to read_data_from_db().)
     pub fn get_db(&self) -> Rc<RefCell<dyn Database>> {
         self.db.clone()
@@ -961,4 +994,54 @@ mod test {
             .unwrap()
             .is_some());
     }
+
+    #[test]
+    fn saved_search_entries_are_computed_and_read_only() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> =
+            Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let done_type = db_b.create_entity(tx.clone(), "smartGroupTestDone", None, None).unwrap();
+        let mut task_ids: Vec<i64> = Vec::new();
+        for (name, done) in [("smartGroupTestTask1", false), ("smartGroupTestTask2", true), ("smartGroupTestTask3", false)] {
+            let id = db_b.create_entity(tx.clone(), name, None, None).unwrap();
+            db_b.create_boolean_attribute(tx.clone(), id, done_type, done, None, 0, None).unwrap();
+            task_ids.push(id);
+        }
+        let group = Group::create_smart_group(
+            db.clone(),
+            tx.clone(),
+            "not done",
+            "boolean smartGroupTestDone = false",
+        )
+        .unwrap();
+        assert!(group.is_saved_search(tx.clone()).unwrap());
+        assert_eq!(group.get_size(tx.clone(), 3).unwrap(), 2);
+        let ids = db_b.get_group_entry_ids(tx.clone(), group.get_id(), 0, None).unwrap();
+        assert_eq!(ids, vec![task_ids[0], task_ids[2]]);
+        // paging works as for a regular group:
+        let ids = db_b.get_group_entry_ids(tx.clone(), group.get_id(), 1, Some(1)).unwrap();
+        assert_eq!(ids, vec![task_ids[2]]);
+
+        // membership follows the data:
+        db_b.archive_entity(tx.clone(), task_ids[2]).unwrap();
+        assert_eq!(group.get_size(tx.clone(), 1).unwrap(), 1);
+        assert_eq!(group.get_size(tx.clone(), 2).unwrap(), 1);
+
+        // ...and can't be edited directly:
+        assert!(db_b.add_entity_to_group(tx.clone(), group.get_id(), task_ids[1], None).is_err());
+        assert!(db_b.remove_entity_from_group(tx.clone(), group.get_id(), task_ids[0]).is_err());
+        assert!(db_b.delete_group_relations_to_it_and_its_entries(tx.clone(), group.get_id()).is_err());
+
+        assert!(db_b.update_group_query(tx.clone(), group.get_id(), "not a query").is_err());
+        db_b.update_group_query(tx.clone(), group.get_id(), "boolean smartGroupTestDone = true").unwrap();
+        assert_eq!(group.get_size(tx.clone(), 3).unwrap(), 1);
+
+        let regular_group_id = db_b.create_group(tx.clone(), "smartGroupTestRegular", false).unwrap();
+        assert_eq!(db_b.get_group_query(tx.clone(), regular_group_id).unwrap(), None);
+        assert!(db_b.update_group_query(tx.clone(), regular_group_id, "archived").is_err());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
 }
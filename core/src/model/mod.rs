@@ -8,21 +8,30 @@
     You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
 */
 
+pub mod action_script;
 pub mod attribute;
 pub mod attribute_data_holder;
 pub mod attribute_with_valid_and_observed_dates;
+pub mod backlinks;
 pub mod boolean_attribute;
+pub mod class_schema;
 pub mod database;
 pub mod date_attribute;
 pub mod entity;
 pub mod entity_class;
+pub mod entity_query;
+pub mod exchange_format;
 pub mod file_attribute;
 pub mod group;
+pub mod navigation_history;
 pub mod has_id;
 pub mod id_wrapper;
 pub mod om_instance;
+pub mod path_finder;
 pub mod postgres;
 pub mod quantity_attribute;
+pub mod quantity_series;
+pub mod relation_inference;
 pub mod relation_to_entity;
 pub mod relation_to_group;
 pub mod relation_to_local_entity;
@@ -30,3 +39,4 @@ pub mod relation_to_remote_entity;
 pub mod relation_type;
 pub mod rest_database;
 pub mod text_attribute;
+pub mod unit_conversion;
@@ -0,0 +1,157 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Where the user has been (entities and groups viewed), like a web browser's history: going
+//! back and forward moves within it, and visiting somewhere new after going back drops what was
+//! forward of there.  The Controller keeps one, and saves it (see
+//! Database::save_navigation_history) so OM can reopen where the user left off.
+//! (Groups are recorded when their entries are listed: see Controller::group_entries_menu.)
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Entity(i64),
+    Group(i64),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NavigationHistory {
+    locations: Vec<Location>,
+    /// Index into locations; None only when it is empty.
+    current: Option<usize>,
+}
+
+/// How many locations are kept (the oldest are dropped).
+pub const MAX_LOCATIONS: usize = 50;
+
+impl NavigationHistory {
+    /// For restoring a saved history.  current_in is put within locations_in if it is not.
+    pub fn new(locations_in: Vec<Location>, current_in: Option<usize>) -> NavigationHistory {
+        let current = if locations_in.is_empty() {
+            None
+        } else {
+            Some(current_in.unwrap_or(locations_in.len() - 1).min(locations_in.len() - 1))
+        };
+        NavigationHistory {
+            locations: locations_in,
+            current,
+        }
+    }
+
+    pub fn locations(&self) -> &[Location] {
+        &self.locations
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<Location> {
+        self.current.map(|i| self.locations[i])
+    }
+
+    /// Records going to location_in, unless it is where the history already is (as when a menu
+    /// is shown again, or after going back to it).  Returns whether anything changed.
+    pub fn visit(&mut self, location_in: Location) -> bool {
+        if self.current() == Some(location_in) {
+            return false;
+        }
+        if let Some(current) = self.current {
+            self.locations.truncate(current + 1);
+        }
+        self.locations.push(location_in);
+        if self.locations.len() > MAX_LOCATIONS {
+            self.locations.drain(..self.locations.len() - MAX_LOCATIONS);
+        }
+        self.current = Some(self.locations.len() - 1);
+        true
+    }
+
+    pub fn peek_back(&self) -> Option<Location> {
+        self.current.filter(|i| *i > 0).map(|i| self.locations[i - 1])
+    }
+
+    pub fn peek_forward(&self) -> Option<Location> {
+        self.current.and_then(|i| self.locations.get(i + 1).copied())
+    }
+
+    pub fn back(&mut self) -> Option<Location> {
+        let location = self.peek_back()?;
+        self.current = self.current.map(|i| i - 1);
+        Some(location)
+    }
+
+    pub fn forward(&mut self) -> Option<Location> {
+        let location = self.peek_forward()?;
+        self.current = self.current.map(|i| i + 1);
+        Some(location)
+    }
+
+    /// Moves to the location at index_in (as from locations()), keeping those after it for
+    /// going forward.
+    pub fn go_to(&mut self, index_in: usize) -> Option<Location> {
+        let location = *self.locations.get(index_in)?;
+        self.current = Some(index_in);
+        Some(location)
+    }
+
+    /// The last max_in locations up to and including the current one, oldest first.
+    pub fn breadcrumbs(&self, max_in: usize) -> &[Location] {
+        match self.current {
+            None => &[],
+            Some(i) => &self.locations[(i + 1).saturating_sub(max_in)..=i],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_navigation_history() {
+        let mut history = NavigationHistory::default();
+        assert_eq!(history.back(), None);
+        assert!(history.breadcrumbs(3).is_empty());
+        assert!(history.visit(Location::Entity(1)));
+        assert!(history.visit(Location::Group(2)));
+        assert!(history.visit(Location::Entity(3)));
+        assert!(!history.visit(Location::Entity(3)));
+        assert_eq!(history.breadcrumbs(2), &[Location::Group(2), Location::Entity(3)]);
+
+        assert_eq!(history.back(), Some(Location::Group(2)));
+        assert_eq!(history.back(), Some(Location::Entity(1)));
+        assert_eq!(history.back(), None);
+        // Showing where it went back to does not change anything:
+        assert!(!history.visit(Location::Entity(1)));
+        assert_eq!(history.forward(), Some(Location::Group(2)));
+        assert_eq!(history.peek_forward(), Some(Location::Entity(3)));
+        assert_eq!(history.breadcrumbs(5), &[Location::Entity(1), Location::Group(2)]);
+
+        // Going somewhere new drops what was forward:
+        assert!(history.visit(Location::Entity(4)));
+        assert_eq!(history.peek_forward(), None);
+        assert_eq!(
+            history.locations(),
+            &[Location::Entity(1), Location::Group(2), Location::Entity(4)]
+        );
+        assert_eq!(history.go_to(0), Some(Location::Entity(1)));
+        assert_eq!(history.go_to(9), None);
+        assert_eq!(history.current_index(), Some(0));
+
+        for id in 0..(MAX_LOCATIONS as i64 + 5) {
+            history.visit(Location::Entity(100 + id));
+        }
+        assert_eq!(history.locations().len(), MAX_LOCATIONS);
+        assert_eq!(history.current_index(), Some(MAX_LOCATIONS - 1));
+
+        let restored = NavigationHistory::new(vec![Location::Entity(1), Location::Entity(2)], Some(7));
+        assert_eq!(restored.current(), Some(Location::Entity(2)));
+        assert_eq!(NavigationHistory::new(Vec::new(), Some(0)).current(), None);
+    }
+}
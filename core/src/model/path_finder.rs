@@ -0,0 +1,278 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Finding how two entities are connected: the shortest paths between them through relations to
+//! local entities, groups (an entity's group, to each entity in it), and optionally attribute
+//! types (an entity, to the type of one of its attributes).  Links are followed either way, so
+//! "A is part of B" connects B to A too; each step says which way it was entered.
+use anyhow::Error;
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkKind {
+    /// A relation of this type.
+    Relation(i64),
+    /// An entity's relation (of rel_type_id) to a group, and the group's entry.
+    Group { group_id: i64, rel_type_id: i64 },
+    /// An attribute of this form (like Util::TEXT_TYPE), whose type is the other entity.
+    AttributeType(String),
+}
+
+/// One step of a path, from one entity to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathLink {
+    pub from_entity_id: i64,
+    pub to_entity_id: i64,
+    pub kind: LinkKind,
+    /// True if the link was entered from from_entity_id (like a relation from it, or its group
+    /// containing the other), false if it is being followed backward.
+    pub forward: bool,
+}
+
+impl PathLink {
+    pub fn describe(&self, name_of: &dyn Fn(i64) -> String) -> String {
+        match (&self.kind, self.forward) {
+            (LinkKind::Relation(rel_type_id), true) => format!("\"{}\"", name_of(*rel_type_id)),
+            (LinkKind::Relation(rel_type_id), false) => format!("(is the other end of) \"{}\"", name_of(*rel_type_id)),
+            (LinkKind::Group { .. }, true) => "has in its group".to_string(),
+            (LinkKind::Group { .. }, false) => "is in the group of".to_string(),
+            (LinkKind::AttributeType(_), true) => "has an attribute of type".to_string(),
+            (LinkKind::AttributeType(_), false) => "is the type of an attribute of".to_string(),
+        }
+    }
+}
+
+/// Like "A \"is part of\" B has in its group C".
+pub fn describe_path(path_in: &[PathLink], name_of: &dyn Fn(i64) -> String) -> String {
+    let Some(first) = path_in.first() else {
+        return String::new();
+    };
+    let mut description = name_of(first.from_entity_id);
+    for link in path_in {
+        description.push_str(&format!(" {} {}", link.describe(name_of), name_of(link.to_entity_id)));
+    }
+    description
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathOptions {
+    /// If not empty, the only relation types followed (for relations, and relations to groups).
+    pub rel_type_ids: Vec<i64>,
+    /// The most steps a path can have.
+    pub max_depth: usize,
+    pub include_attribute_types: bool,
+    /// The most paths to return, when several are equally short.
+    pub max_paths: usize,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        PathOptions {
+            rel_type_ids: Vec::new(),
+            max_depth: 6,
+            include_attribute_types: false,
+            max_paths: 10,
+        }
+    }
+}
+
+impl PathOptions {
+    fn allows(&self, link_in: &PathLink) -> bool {
+        match &link_in.kind {
+            LinkKind::Relation(rel_type_id) | LinkKind::Group { rel_type_id, .. } => {
+                self.rel_type_ids.is_empty() || self.rel_type_ids.contains(rel_type_id)
+            }
+            LinkKind::AttributeType(_) => self.include_attribute_types,
+        }
+    }
+}
+
+/// How many entities to visit before giving up, so a huge database can't hang the search.
+pub const MAX_ENTITIES_TO_VISIT: usize = 100_000;
+
+/// Returns the shortest paths (each a list of links, from from_entity_id to to_entity_id), at
+/// most options.max_paths of them, or none if there is no path within options.max_depth.
+/// links_of_in returns the links touching an entity, each with that entity as from_entity_id,
+/// and is called once per entity visited.  The search is breadth-first, and stops at the depth
+/// where the target is first reached.
+pub fn find_shortest_paths(
+    from_entity_id: i64,
+    to_entity_id: i64,
+    options: &PathOptions,
+    links_of_in: &mut dyn FnMut(i64) -> Result<Vec<PathLink>, Error>,
+) -> Result<Vec<Vec<PathLink>>, Error> {
+    if from_entity_id == to_entity_id {
+        return Ok(vec![Vec::new()]);
+    }
+    let mut depth_of: HashMap<i64, usize> = HashMap::new();
+    depth_of.insert(from_entity_id, 0);
+    // The links by which each entity was reached at its depth (several if equally short):
+    let mut reached_by: HashMap<i64, Vec<PathLink>> = HashMap::new();
+    let mut frontier: VecDeque<i64> = VecDeque::from([from_entity_id]);
+    let mut depth = 0;
+    while !frontier.is_empty() && depth < options.max_depth && !depth_of.contains_key(&to_entity_id) {
+        depth += 1;
+        let mut next_frontier: VecDeque<i64> = VecDeque::new();
+        while let Some(current) = frontier.pop_front() {
+            for link in links_of_in(current)? {
+                if !options.allows(&link) || link.to_entity_id == current {
+                    continue;
+                }
+                let next = link.to_entity_id;
+                match depth_of.get(&next) {
+                    Some(d) if *d < depth => continue,
+                    Some(_) => {}
+                    None => {
+                        depth_of.insert(next, depth);
+                        next_frontier.push_back(next);
+                    }
+                }
+                let links = reached_by.entry(next).or_default();
+                if !links.contains(&link) {
+                    links.push(link);
+                }
+            }
+        }
+        if depth_of.len() >= MAX_ENTITIES_TO_VISIT {
+            break;
+        }
+        frontier = next_frontier;
+    }
+    if !depth_of.contains_key(&to_entity_id) {
+        return Ok(Vec::new());
+    }
+    // Walk back from the target, building each path in reverse:
+    let mut paths: Vec<Vec<PathLink>> = Vec::new();
+    let mut partial: Vec<Vec<PathLink>> = vec![Vec::new()];
+    while let Some(path) = partial.pop() {
+        let at = path.last().map(|l: &PathLink| l.from_entity_id).unwrap_or(to_entity_id);
+        if at == from_entity_id {
+            let mut path = path;
+            path.reverse();
+            paths.push(path);
+            if paths.len() >= options.max_paths {
+                break;
+            }
+            continue;
+        }
+        // (Pushed in reverse so the first-found links are tried first.)
+        for link in reached_by.get(&at).into_iter().flatten().rev() {
+            let mut longer = path.clone();
+            longer.push(link.clone());
+            partial.push(longer);
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PART_OF: i64 = 1;
+    const KNOWS: i64 = 2;
+
+    fn link(from_entity_id: i64, to_entity_id: i64, kind: LinkKind) -> PathLink {
+        PathLink {
+            from_entity_id,
+            to_entity_id,
+            kind,
+            forward: true,
+        }
+    }
+
+    /// Finds paths in a graph given as links entered (forward), following them both ways.
+    fn find(from: i64, to: i64, options: &PathOptions, entered: &[PathLink]) -> Vec<Vec<PathLink>> {
+        let mut visits = 0;
+        let paths = find_shortest_paths(from, to, options, &mut |id| {
+            visits += 1;
+            let mut links: Vec<PathLink> = entered.iter().filter(|l| l.from_entity_id == id).cloned().collect();
+            for l in entered.iter().filter(|l| l.to_entity_id == id) {
+                links.push(PathLink {
+                    from_entity_id: id,
+                    to_entity_id: l.from_entity_id,
+                    kind: l.kind.clone(),
+                    forward: false,
+                });
+            }
+            Ok(links)
+        })
+        .unwrap();
+        assert!(visits <= 100, "visited {} times", visits);
+        paths
+    }
+
+    #[test]
+    fn test_find_shortest_paths() {
+        // 1 is part of 2, which is part of 3; 4 knows 3; 5's group has 1 and 4 in it; 6 is alone.
+        let group = LinkKind::Group {
+            group_id: 50,
+            rel_type_id: KNOWS,
+        };
+        let entered = vec![
+            link(1, 2, LinkKind::Relation(PART_OF)),
+            link(2, 3, LinkKind::Relation(PART_OF)),
+            link(4, 3, LinkKind::Relation(KNOWS)),
+            link(5, 1, group.clone()),
+            link(5, 4, group.clone()),
+            link(1, 6, LinkKind::AttributeType("TextAttribute".to_string())),
+        ];
+        let options = PathOptions::default();
+        let paths = find(1, 3, &options, &entered);
+        assert_eq!(
+            paths,
+            vec![vec![link(1, 2, LinkKind::Relation(PART_OF)), link(2, 3, LinkKind::Relation(PART_OF))]]
+        );
+        // Backward, and through the group:
+        let paths = find(4, 1, &options, &entered);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0][0].to_entity_id, 5);
+        assert!(!paths[0][0].forward && paths[0][1].forward);
+        // From 2 to 4, the way through 3 is shorter than through 1 and the group:
+        assert_eq!(find(2, 4, &options, &entered).len(), 1);
+        // Only "part of": 4 can't be reached.
+        let part_of_only = PathOptions {
+            rel_type_ids: vec![PART_OF],
+            ..PathOptions::default()
+        };
+        assert!(find(1, 4, &part_of_only, &entered).is_empty());
+        // Too shallow:
+        let shallow = PathOptions {
+            max_depth: 1,
+            ..PathOptions::default()
+        };
+        assert!(find(1, 3, &shallow, &entered).is_empty());
+        // Attribute types are only followed when asked:
+        assert!(find(1, 6, &options, &entered).is_empty());
+        let with_types = PathOptions {
+            include_attribute_types: true,
+            ..PathOptions::default()
+        };
+        assert_eq!(find(1, 6, &with_types, &entered).len(), 1);
+
+        // Two equally short paths, both found, within max_paths:
+        let mut diamond = entered.clone();
+        diamond.push(link(1, 7, LinkKind::Relation(KNOWS)));
+        diamond.push(link(7, 3, LinkKind::Relation(KNOWS)));
+        assert_eq!(find(1, 3, &options, &diamond).len(), 2);
+        let one_path = PathOptions {
+            max_paths: 1,
+            ..PathOptions::default()
+        };
+        assert_eq!(find(1, 3, &one_path, &diamond).len(), 1);
+        assert_eq!(find(1, 1, &options, &entered), vec![Vec::<PathLink>::new()]);
+
+        let name_of = |id: i64| format!("e{}", id);
+        assert_eq!(describe_path(&find(1, 3, &options, &entered)[0], &name_of), "e1 \"e1\" e2 \"e1\" e3");
+        assert_eq!(
+            describe_path(&find(3, 1, &part_of_only, &entered)[0], &name_of),
+            "e3 (is the other end of) \"e1\" e2 (is the other end of) \"e1\" e1"
+        );
+    }
+}
@@ -8,7 +8,16 @@
     You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
 */
 
+pub mod postgresql_backlinks;
+pub mod postgresql_bookmarks;
+pub mod postgresql_class_schema;
 pub mod postgresql_database;
 pub mod postgresql_database2;
 pub mod postgresql_database3;
 pub mod postgresql_database_tests;
+pub mod postgresql_exchange;
+pub mod postgresql_navigation_history;
+pub mod postgresql_path_finder;
+pub mod postgresql_relation_attributes;
+pub mod postgresql_relation_constraints;
+pub mod postgresql_relation_inference;
@@ -0,0 +1,191 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Reading an entity's backlinks (see model/backlinks.rs).
+use crate::model::backlinks::Backlinks;
+use crate::model::database::Database;
+use crate::model::postgres::postgresql_database::*;
+use crate::util::Util;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Each holder of a relation's own attributes, with the entity that has the relation, so a
+/// reference from a holder can be shown as one from that entity.
+const RELATION_OWNERS_OF_HOLDERS: &str = "(select h.entity_id as holder_id, r.entity_id as owner_id \
+    from RelationAttributeHolder h, (select form_id, id, entity_id from RelationToEntity \
+    union all select form_id, id, entity_id from RelationToGroup \
+    union all select form_id, id, entity_id from RelationToRemoteEntity) r \
+    where h.relation_form_id=r.form_id and h.relation_id=r.id)";
+
+impl PostgreSQLDatabase {
+    /// The entities with a row in table_in matching condition_in (on "x"), as
+    /// (value of key_column_in, entity id) ordered by key then entity name.  Without a
+    /// key_column_in the keys are all 0.
+    fn read_referring_entities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        table_in: &str,
+        condition_in: &str,
+        key_column_in: Option<&str>,
+    ) -> Result<Vec<(i64, i64)>, anyhow::Error> {
+        let key = key_column_in.map(|c| format!("x.{}", c)).unwrap_or_else(|| "0::bigint".to_string());
+        let not_archived = if self.include_archived_entities {
+            ""
+        } else {
+            " and (not e.archived)"
+        };
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select distinct {}, e.id, e.name from {} x left join {} o on o.holder_id=x.entity_id, Entity e \
+                where {} and e.id=coalesce(o.owner_id, x.entity_id){} order by 1, e.name, e.id",
+                key, table_in, RELATION_OWNERS_OF_HOLDERS, condition_in, not_archived
+            )
+            .as_str(),
+            "i64,i64,String",
+        )?;
+        let mut results: Vec<(i64, i64)> = Vec::new();
+        for row in rows {
+            results.push((
+                Util::get_value_bigint("key", &row[0])?,
+                Util::get_value_bigint("id", &row[1])?,
+            ));
+        }
+        Ok(results)
+    }
+
+    fn read_referring_entity_ids(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        table_in: &str,
+        condition_in: &str,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        Ok(self
+            .read_referring_entities(transaction, table_in, condition_in, None)?
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect())
+    }
+
+    pub(super) fn read_backlinks(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Backlinks, anyhow::Error> {
+        let mut backlinks = Backlinks::default();
+        // (Relations from the preferences, like bookmarks, are settings, not references.)
+        let preference_entity_ids = self.preference_entity_ids_sql(transaction.clone())?;
+        for (rel_type_id, entity_id) in self.read_referring_entities(
+            transaction.clone(),
+            Util::RELATION_TO_LOCAL_ENTITY_TYPE,
+            format!("x.entity_id_2={} and x.entity_id not in {}", entity_id_in, preference_entity_ids).as_str(),
+            Some("rel_type_id"),
+        )? {
+            match backlinks.containing_entities.last_mut() {
+                Some((last_type_id, ids)) if *last_type_id == rel_type_id => ids.push(entity_id),
+                _ => backlinks.containing_entities.push((rel_type_id, vec![entity_id])),
+            }
+        }
+        backlinks.containing_group_ids = self.get_containing_groups_ids(transaction.clone(), entity_id_in)?;
+        let type_columns = Util::NON_RELATION_ATTR_TYPE_NAMES.iter().map(|form| (*form, "attr_type_id")).chain(
+            [
+                Util::RELATION_TO_LOCAL_ENTITY_TYPE,
+                Util::RELATION_TO_GROUP_TYPE,
+                Util::RELATION_TO_REMOTE_ENTITY_TYPE,
+            ]
+            .iter()
+            .map(|form| (*form, "rel_type_id")),
+        );
+        for (form, column) in type_columns {
+            // (The form names are also the table names.)
+            let ids = self.read_referring_entity_ids(
+                transaction.clone(),
+                form,
+                format!("x.{}={}", column, entity_id_in).as_str(),
+            )?;
+            if !ids.is_empty() {
+                backlinks.attribute_type_users.push((form.to_string(), ids));
+            }
+        }
+        backlinks.unit_users = self.read_referring_entity_ids(
+            transaction.clone(),
+            Util::QUANTITY_TYPE,
+            format!("x.unit_id={}", entity_id_in).as_str(),
+        )?;
+        backlinks.remote_referrers = self.read_referring_entity_ids(
+            transaction,
+            Util::RELATION_TO_REMOTE_ENTITY_TYPE,
+            format!(
+                "x.entity_id_2={} and x.remote_instance_id in (select id from OmInstance where local)",
+                entity_id_in
+            )
+            .as_str(),
+        )?;
+        Ok(backlinks)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::database::Database;
+    use crate::util::Util;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_backlinks_cover_every_kind_of_reference() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let target = db.create_entity(tx.clone(), "backlinks target", None, None).unwrap();
+        assert_eq!(db.get_backlinks(tx.clone(), target).unwrap().total_count(), 0);
+
+        let cites = db.create_relation_type(tx.clone(), "backlinks cites", "", "UNI").unwrap();
+        let paper = db.create_entity(tx.clone(), "backlinks paper", None, None).unwrap();
+        let book = db.create_entity(tx.clone(), "backlinks book", None, None).unwrap();
+        db.create_relation_to_local_entity(tx.clone(), cites, paper, target, None, 0, None).unwrap();
+        db.create_relation_to_local_entity(tx.clone(), cites, book, target, None, 0, None).unwrap();
+        let has = db.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME).unwrap();
+        let (group_id, _) = db
+            .create_group_and_relation_to_group(tx.clone(), paper, has, "backlinks list", false, None, 0, None)
+            .unwrap();
+        db.add_entity_to_group(tx.clone(), group_id, target, None).unwrap();
+        // The target used as a text attribute's type, and as a unit:
+        db.create_text_attribute(tx.clone(), book, target, "some text", None, 0, None).unwrap();
+        let length = db.create_entity(tx.clone(), "backlinks length", None, None).unwrap();
+        db.create_quantity_attribute(tx.clone(), paper, length, target, 3.0, None, 0, None).unwrap();
+        // A reference from a relation's own attributes shows as one from the relation's entity:
+        let (rte_id, _) = db.create_relation_to_local_entity(tx.clone(), has, paper, book, None, 0, None).unwrap();
+        let rte_form_id = db.get_attribute_form_id(Util::RELATION_TO_LOCAL_ENTITY_TYPE).unwrap();
+        let holder = db.get_or_create_relation_attribute_holder(tx.clone(), rte_form_id, rte_id).unwrap();
+        db.create_date_attribute(tx.clone(), holder, target, 0, None).unwrap();
+
+        let backlinks = db.get_backlinks(tx.clone(), target).unwrap();
+        // (Ordered by name within the relation type.)
+        assert_eq!(backlinks.containing_entities, vec![(cites, vec![book, paper])]);
+        assert_eq!(backlinks.containing_group_ids, vec![group_id]);
+        assert_eq!(
+            backlinks.attribute_type_users,
+            vec![
+                (Util::DATE_TYPE.to_string(), vec![paper]),
+                (Util::TEXT_TYPE.to_string(), vec![book]),
+            ]
+        );
+        assert_eq!(backlinks.unit_users, vec![paper]);
+        assert!(backlinks.remote_referrers.is_empty());
+        assert_eq!(backlinks.total_count(), 6);
+
+        db.archive_entity(tx.clone(), book).unwrap();
+        let backlinks = db.get_backlinks(tx.clone(), target).unwrap();
+        assert_eq!(backlinks.containing_entities, vec![(cites, vec![paper])]);
+        assert_eq!(backlinks.total_count(), 4);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
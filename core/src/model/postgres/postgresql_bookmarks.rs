@@ -0,0 +1,290 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! The user's bookmarks, and the entities they viewed most recently or most often.
+//!
+//! Bookmarks are ordinary data: an entity named Util::BOOKMARKS_PREFERENCE under the preferences
+//! container, with a "has" relation to each bookmarked entity, so they can also be seen,
+//! reordered and exported like anything else.  Views are counted in the EntityView table.
+//! Since those relations (like the others from the preferences) are settings, not part of what
+//! the user's data says, the path finder, backlinks and relation type class constraints leave
+//! them out (see preference_entity_ids_sql).
+use crate::model::database::Database;
+use crate::model::postgres::postgresql_database::*;
+use crate::util::Util;
+use chrono::Utc;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+impl PostgreSQLDatabase {
+    fn not_archived_condition(&self) -> &'static str {
+        if self.include_archived_entities {
+            ""
+        } else {
+            " and (not e.archived)"
+        }
+    }
+
+    /// SQL for the ids of the preferences container and the entities it has (like the one
+    /// holding the bookmarks), whose relations are the user's settings.
+    pub(super) fn preference_entity_ids_sql(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<String, anyhow::Error> {
+        let preferences_container_id = self.get_preferences_container_id(transaction)?;
+        Ok(format!(
+            "(select {} union select entity_id_2 from RelationToEntity where entity_id={})",
+            preferences_container_id, preferences_container_id
+        ))
+    }
+
+    pub(super) fn is_preference_entity(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<bool, anyhow::Error> {
+        let preference_entity_ids = self.preference_entity_ids_sql(transaction.clone())?;
+        let count = self.extract_row_count_from_count_query(
+            transaction,
+            format!("select count(1) from Entity where id={} and id in {}", entity_id_in, preference_entity_ids).as_str(),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// The entity holding the bookmarks, if there is one yet.
+    fn get_bookmarks_container_id(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<Option<i64>, anyhow::Error> {
+        let preferences_container_id = self.get_preferences_container_id(transaction.clone())?;
+        self.get_relation_to_local_entity_by_name(transaction, preferences_container_id, Util::BOOKMARKS_PREFERENCE)
+    }
+
+    /// In the order shown in the bookmarks entity (its attribute sorting), then as added.
+    pub(super) fn read_bookmarked_entity_ids(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        let Some(container_id) = self.get_bookmarks_container_id(transaction.clone())? else {
+            return Ok(Vec::new());
+        };
+        let has = self.find_relation_type(transaction.clone(), Util::THE_HAS_RELATION_TYPE_NAME)?;
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select r.entity_id_2 from RelationToEntity r left join AttributeSorting s on s.entity_id=r.entity_id \
+                and s.attribute_form_id=r.form_id and s.attribute_id=r.id, Entity e where r.entity_id={} \
+                and r.rel_type_id={} and e.id=r.entity_id_2{} order by s.sorting_index, r.id",
+                container_id,
+                has,
+                self.not_archived_condition()
+            )
+            .as_str(),
+            "i64",
+        )?;
+        get_i64s_from_rows(&rows)
+    }
+
+    /// Does nothing if entity_id_in is already bookmarked.
+    pub(super) fn write_bookmark(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        let has = self.find_relation_type(transaction.clone(), Util::THE_HAS_RELATION_TYPE_NAME)?;
+        let now = Utc::now().timestamp_millis();
+        let container_id = match self.get_bookmarks_container_id(transaction.clone())? {
+            Some(id) => id,
+            None => {
+                let preferences_container_id = self.get_preferences_container_id(transaction.clone())?;
+                self.create_entity_and_relation_to_local_entity(
+                    transaction.clone(),
+                    preferences_container_id,
+                    has,
+                    Util::BOOKMARKS_PREFERENCE,
+                    None,
+                    Some(now),
+                    now,
+                )?
+                .0
+            }
+        };
+        let already = self.extract_row_count_from_count_query(
+            transaction.clone(),
+            format!(
+                "select count(1) from RelationToEntity where rel_type_id={} and entity_id={} and entity_id_2={}",
+                has, container_id, entity_id_in
+            )
+            .as_str(),
+        )?;
+        if already == 0 {
+            self.create_relation_to_local_entity(transaction, has, container_id, entity_id_in, Some(now), now, None)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether entity_id_in was bookmarked.
+    pub(super) fn delete_bookmark(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<bool, anyhow::Error> {
+        let Some(container_id) = self.get_bookmarks_container_id(transaction.clone())? else {
+            return Ok(false);
+        };
+        if !self.read_bookmarked_entity_ids(transaction.clone())?.contains(&entity_id_in) {
+            return Ok(false);
+        }
+        let has = self.find_relation_type(transaction.clone(), Util::THE_HAS_RELATION_TYPE_NAME)?;
+        self.delete_relation_to_local_entity(transaction, has, container_id, entity_id_in)?;
+        Ok(true)
+    }
+
+    pub(super) fn write_entity_view(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        self.db_action(
+            transaction,
+            format!(
+                "insert into EntityView (entity_id, view_count, last_viewed) values ({}, 1, {}) \
+                on conflict (entity_id) do update set view_count=EntityView.view_count+1, last_viewed=excluded.last_viewed",
+                entity_id_in,
+                Utc::now().timestamp_millis()
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// The viewed entities, as (id, view count), most recent first or (if by_count_in) most
+    /// viewed first, at most max_in of them.
+    pub(super) fn read_viewed_entities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        by_count_in: bool,
+        max_in: usize,
+    ) -> Result<Vec<(i64, i64)>, anyhow::Error> {
+        let order = if by_count_in {
+            "v.view_count desc, v.last_viewed desc"
+        } else {
+            "v.last_viewed desc"
+        };
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select v.entity_id, v.view_count from EntityView v, Entity e where e.id=v.entity_id{} \
+                order by {} limit {}",
+                self.not_archived_condition(),
+                order,
+                max_in
+            )
+            .as_str(),
+            "i64,i64",
+        )?;
+        let mut results: Vec<(i64, i64)> = Vec::new();
+        for row in rows {
+            results.push((
+                Util::get_value_bigint("entity_id", &row[0])?,
+                Util::get_value_bigint("view_count", &row[1])?,
+            ));
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::database::Database;
+    use crate::model::path_finder::PathOptions;
+    use crate::model::relation_type::RelationClassConstraints;
+    use crate::util::Util;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_bookmarks() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let project_a = db.create_entity(tx.clone(), "bookmarks project a", None, None).unwrap();
+        let project_b = db.create_entity(tx.clone(), "bookmarks project b", None, None).unwrap();
+        assert!(db.get_bookmarked_entity_ids(tx.clone()).unwrap().is_empty());
+        assert!(!db.remove_bookmark(tx.clone(), project_a).unwrap());
+
+        db.add_bookmark(tx.clone(), project_b).unwrap();
+        db.add_bookmark(tx.clone(), project_a).unwrap();
+        db.add_bookmark(tx.clone(), project_b).unwrap();
+        assert_eq!(db.get_bookmarked_entity_ids(tx.clone()).unwrap(), vec![project_b, project_a]);
+        // They are kept under the preferences:
+        let preferences_id = db.get_preferences_container_id(tx.clone()).unwrap();
+        let mut found = std::collections::HashSet::new();
+        db.find_contained_local_entity_ids(tx.clone(), &mut found, preferences_id, Util::BOOKMARKS_PREFERENCE, 1, true)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+
+        db.archive_entity(tx.clone(), project_a).unwrap();
+        assert_eq!(db.get_bookmarked_entity_ids(tx.clone()).unwrap(), vec![project_b]);
+        assert!(db.remove_bookmark(tx.clone(), project_b).unwrap());
+        assert!(db.get_bookmarked_entity_ids(tx.clone()).unwrap().is_empty());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+
+    #[test]
+    fn test_bookmarks_are_not_connections() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let (class_id, _) = db.create_class_and_its_template_entity(tx.clone(), "bookmarks project class").unwrap();
+        let project_a = db.create_entity(tx.clone(), "bookmarks unrelated a", None, None).unwrap();
+        let project_b = db.create_entity(tx.clone(), "bookmarks unrelated b", None, None).unwrap();
+        // Even if "has" relations can only be to entities of some class, anything can be bookmarked:
+        let has = db.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME).unwrap();
+        let constraints = RelationClassConstraints {
+            source_class_ids: vec![class_id],
+            target_class_ids: vec![class_id],
+        };
+        db.set_relation_type_class_constraints(tx.clone(), has, &constraints).unwrap();
+        db.add_bookmark(tx.clone(), project_a).unwrap();
+        db.add_bookmark(tx.clone(), project_b).unwrap();
+        assert_eq!(db.get_bookmarked_entity_ids(tx.clone()).unwrap(), vec![project_a, project_b]);
+
+        // Being bookmarked together doesn't connect them, nor refer to them:
+        let options = PathOptions::default();
+        assert!(db.find_paths_between_entities(tx.clone(), project_a, project_b, &options).unwrap().is_empty());
+        assert!(db.get_backlinks(tx.clone(), project_a).unwrap().containing_entities.is_empty());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+
+    #[test]
+    fn test_recently_and_frequently_viewed() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let often = db.create_entity(tx.clone(), "viewed often", None, None).unwrap();
+        let lately = db.create_entity(tx.clone(), "viewed lately", None, None).unwrap();
+        for _ in 0..3 {
+            db.record_entity_view(tx.clone(), often).unwrap();
+        }
+        // (So the last_viewed times differ.)
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        db.record_entity_view(tx.clone(), lately).unwrap();
+
+        assert_eq!(db.get_viewed_entities(tx.clone(), false, 10).unwrap(), vec![(lately, 1), (often, 3)]);
+        assert_eq!(db.get_viewed_entities(tx.clone(), true, 10).unwrap(), vec![(often, 3), (lately, 1)]);
+        assert_eq!(db.get_viewed_entities(tx.clone(), true, 1).unwrap(), vec![(often, 3)]);
+        db.delete_entity(tx.clone(), often).unwrap();
+        assert_eq!(db.get_viewed_entities(tx.clone(), true, 10).unwrap(), vec![(lately, 1)]);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
@@ -0,0 +1,446 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Enforcing classes' schemas (see model/class_schema.rs) when attributes are written, and
+//! reporting on entities that break them.
+use crate::model::class_schema::{self, AttributeRule, RuleProblem, RuleValue};
+use crate::model::database::Database;
+use crate::model::postgres::postgresql_database::*;
+use crate::util::Util;
+use anyhow::anyhow;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+impl PostgreSQLDatabase {
+    /// Returns the rules that apply to entity_id_in's attributes, with its class id, or None
+    /// if it has no class or is the class's template entity.
+    fn get_class_rules_for_entity(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Option<(i64, Vec<AttributeRule>)>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select c.id, c.defining_entity_id from Entity e, Class c where e.id={} and e.class_id=c.id",
+                entity_id_in
+            )
+            .as_str(),
+            "i64,i64",
+        )?;
+        let Some(row) = rows.first() else {
+            return Ok(None);
+        };
+        let class_id = Util::get_value_bigint("class id", &row[0])?;
+        if Util::get_value_bigint("defining entity id", &row[1])? == entity_id_in {
+            return Ok(None);
+        }
+        let rules = self.get_class_rules_with_inherited(transaction, class_id)?;
+        Ok(Some((class_id, rules)))
+    }
+
+    /// class_id_in's rules, and then those of its ancestors for attribute types it has no rule
+    /// for.
+    fn get_class_rules_with_inherited(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<AttributeRule>, anyhow::Error> {
+        let mut rules: Vec<AttributeRule> = Vec::new();
+        for (class_id, _) in self.get_class_lineage(transaction.clone(), class_id_in)? {
+            for rule in self.get_class_attribute_rules(transaction.clone(), class_id)? {
+                if !rules.iter().any(|r| r.attr_type_id == rule.attr_type_id) {
+                    rules.push(rule);
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    /// For messages: the entity's name, or its id if it has none (or is gone).
+    fn get_name_for_message(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        id_in: i64,
+    ) -> String {
+        match self.get_entity_name(transaction, id_in) {
+            Ok(Some(name)) => name,
+            _ => format!("#{}", id_in),
+        }
+    }
+
+    fn describe_rule_problems(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        problems_in: &[RuleProblem],
+    ) -> Vec<String> {
+        let name_of = |id: i64| self.get_name_for_message(transaction.clone(), id);
+        problems_in.iter().map(|p| p.describe(&name_of)).collect()
+    }
+
+    /// The values of entity_id_in's quantity, date, boolean and text attributes (of any type),
+    /// as (attr_type_id, value).
+    fn get_rule_values(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<(i64, RuleValue)>, anyhow::Error> {
+        let mut values: Vec<(i64, RuleValue)> = Vec::new();
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select attr_type_id, unit_id, quantity_number from QuantityAttribute where entity_id={} order by id",
+                entity_id_in
+            )
+            .as_str(),
+            "i64,i64,Float",
+        )?;
+        for row in rows {
+            values.push((
+                Util::get_value_bigint("attr_type_id", &row[0])?,
+                RuleValue::Quantity {
+                    unit_id: Util::get_value_bigint("unit_id", &row[1])?,
+                    number: Util::get_value_float("quantity_number", &row[2])?,
+                },
+            ));
+        }
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select attr_type_id, date from DateAttribute where entity_id={} order by id",
+                entity_id_in
+            )
+            .as_str(),
+            "i64,i64",
+        )?;
+        for row in rows {
+            values.push((
+                Util::get_value_bigint("attr_type_id", &row[0])?,
+                RuleValue::Date(Util::get_value_bigint("date", &row[1])?),
+            ));
+        }
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select attr_type_id, booleanvalue from BooleanAttribute where entity_id={} order by id",
+                entity_id_in
+            )
+            .as_str(),
+            "i64,bool",
+        )?;
+        for row in rows {
+            values.push((
+                Util::get_value_bigint("attr_type_id", &row[0])?,
+                RuleValue::Boolean(Util::get_value_bool("booleanvalue", &row[1])?),
+            ));
+        }
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select attr_type_id, textvalue from TextAttribute where entity_id={} order by id",
+                entity_id_in
+            )
+            .as_str(),
+            "i64,String",
+        )?;
+        for row in rows {
+            values.push((
+                Util::get_value_bigint("attr_type_id", &row[0])?,
+                RuleValue::Text(Util::get_value_string("textvalue", &row[1])?),
+            ));
+        }
+        Ok(values)
+    }
+
+    /// Checks an attribute about to be written on entity_id_in (a new one, or if existing_id_in
+    /// is given, a change to that one, which has the form of value_in), against the rules of
+    /// the entity's class.  Returns an error describing what is wrong, if anything.
+    pub(super) fn check_attribute_against_class_rules(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        attr_type_id_in: i64,
+        value_in: &RuleValue,
+        existing_id_in: Option<i64>,
+    ) -> Result<(), anyhow::Error> {
+        let Some((class_id, rules)) = self.get_class_rules_for_entity(transaction.clone(), entity_id_in)? else {
+            return Ok(());
+        };
+        let Some(rule) = rules.iter().find(|r| r.attr_type_id == attr_type_id_in) else {
+            return Ok(());
+        };
+        let mut problems = rule.check_value(value_in);
+        if rule.max_count.is_some() {
+            let mut count: usize = 0;
+            for form in class_schema::FORMS {
+                let excluding = match existing_id_in {
+                    Some(id) if form == value_in.form() => format!(" and id<>{}", id),
+                    _ => String::new(),
+                };
+                let rows = self.db_query(
+                    transaction.clone(),
+                    format!(
+                        "select count(*) from {} where entity_id={} and attr_type_id={}{}",
+                        form, entity_id_in, attr_type_id_in, excluding
+                    )
+                    .as_str(),
+                    "i64",
+                )?;
+                count += get_i64_from_row(&rows[0], 0)? as usize;
+            }
+            if let Some(problem @ RuleProblem::TooMany { .. }) = rule.check_count(count + 1) {
+                problems.push(problem);
+            }
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "\"{}\" is of class \"{}\", whose rules don't allow that: {}",
+            self.get_name_for_message(transaction.clone(), entity_id_in),
+            self.get_class_name(transaction.clone(), class_id)?.unwrap_or_default(),
+            self.describe_rule_problems(transaction, &problems).join("  ")
+        ))
+    }
+
+    /// Checks that deleting the attribute id_in (of form attribute_form_in) would leave its
+    /// entity with as many attributes of that type as its class requires.  Returns an error
+    /// saying what is required, if not.
+    pub(super) fn check_attribute_removal_against_class_rules(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attribute_form_in: &str,
+        id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        let rows = self.db_query(
+            transaction.clone(),
+            format!("select entity_id, attr_type_id from {} where id={}", attribute_form_in, id_in).as_str(),
+            "i64,i64",
+        )?;
+        let Some(row) = rows.first() else {
+            return Ok(());
+        };
+        let entity_id = Util::get_value_bigint("entity_id", &row[0])?;
+        let attr_type_id = Util::get_value_bigint("attr_type_id", &row[1])?;
+        let Some((class_id, rules)) = self.get_class_rules_for_entity(transaction.clone(), entity_id)? else {
+            return Ok(());
+        };
+        let Some(rule) = rules.iter().find(|r| r.attr_type_id == attr_type_id && r.min_count > 0) else {
+            return Ok(());
+        };
+        let mut count: usize = 0;
+        for form in class_schema::FORMS {
+            let excluding = if form == attribute_form_in {
+                format!(" and id<>{}", id_in)
+            } else {
+                String::new()
+            };
+            let rows = self.db_query(
+                transaction.clone(),
+                format!(
+                    "select count(*) from {} where entity_id={} and attr_type_id={}{}",
+                    form, entity_id, attr_type_id, excluding
+                )
+                .as_str(),
+                "i64",
+            )?;
+            count += get_i64_from_row(&rows[0], 0)? as usize;
+        }
+        let Some(problem @ RuleProblem::TooFew { .. }) = rule.check_count(count) else {
+            return Ok(());
+        };
+        Err(anyhow!(
+            "\"{}\" is of class \"{}\", whose rules don't allow removing that: {}",
+            self.get_name_for_message(transaction.clone(), entity_id),
+            self.get_class_name(transaction.clone(), class_id)?.unwrap_or_default(),
+            self.describe_rule_problems(transaction, &[problem]).join("  ")
+        ))
+    }
+
+    pub(super) fn check_entity_against_class_rules(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        match self.get_class_rules_for_entity(transaction.clone(), entity_id_in)? {
+            Some((_, rules)) if !rules.is_empty() => {
+                let values = self.get_rule_values(transaction.clone(), entity_id_in)?;
+                Ok(self.describe_rule_problems(transaction, &class_schema::check_entity(&rules, &values)))
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Like check_entity_against_class_rules, but for if entity_id_in were of class_id_in.
+    pub(super) fn check_entity_against_rules_of_class(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        class_id_in: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        // (The template's placeholder values aren't checked.)
+        let template_count = self.extract_row_count_from_count_query(
+            transaction.clone(),
+            format!(
+                "select count(1) from Class where id={} and defining_entity_id={}",
+                class_id_in, entity_id_in
+            )
+            .as_str(),
+        )?;
+        if template_count > 0 {
+            return Ok(Vec::new());
+        }
+        let rules = self.get_class_rules_with_inherited(transaction.clone(), class_id_in)?;
+        if rules.is_empty() {
+            return Ok(Vec::new());
+        }
+        let values = self.get_rule_values(transaction.clone(), entity_id_in)?;
+        Ok(self.describe_rule_problems(transaction, &class_schema::check_entity(&rules, &values)))
+    }
+
+    pub(super) fn check_class_members_against_rules(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<(i64, String, Vec<String>)>, anyhow::Error> {
+        let mut results: Vec<(i64, String, Vec<String>)> = Vec::new();
+        // (Members of subclasses are checked against their own classes' rules, which include
+        // the inherited ones.)
+        let classes = Self::class_and_subclasses_sql(class_id_in);
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select e.id, e.name, e.class_id from Entity e where e.class_id in {} \
+                and e.id not in (select defining_entity_id from Class where id in {}) order by e.name, e.id",
+                classes, classes
+            )
+            .as_str(),
+            "i64,String,i64",
+        )?;
+        let mut rules_by_class: HashMap<i64, Vec<AttributeRule>> = HashMap::new();
+        for row in rows {
+            let entity_id = Util::get_value_bigint("id", &row[0])?;
+            let class_id = Util::get_value_bigint("class_id", &row[2])?;
+            let rules = match rules_by_class.entry(class_id) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(self.get_class_rules_with_inherited(transaction.clone(), class_id)?),
+            };
+            if rules.is_empty() {
+                continue;
+            }
+            let values = self.get_rule_values(transaction.clone(), entity_id)?;
+            let problems = class_schema::check_entity(rules, &values);
+            if !problems.is_empty() {
+                results.push((
+                    entity_id,
+                    Util::get_value_string("name", &row[1])?,
+                    self.describe_rule_problems(transaction.clone(), &problems),
+                ));
+            }
+        }
+        Ok(results)
+    }
+
+    pub(super) fn read_attribute_type_forms(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let mut forms = Vec::new();
+        for form in class_schema::FORMS {
+            let rows = self.db_query(
+                transaction.clone(),
+                format!("select count(*) from {} where attr_type_id={}", form, attr_type_id_in).as_str(),
+                "i64",
+            )?;
+            if get_i64_from_row(&rows[0], 0)? > 0 {
+                forms.push(form.to_string());
+            }
+        }
+        Ok(forms)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_class_rules_enforced_on_write() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let (class_id, template_id) = db.create_class_and_its_template_entity(tx.clone(), "schema person").unwrap();
+        let weight = db.create_entity(tx.clone(), "schema weight", None, None).unwrap();
+        let kg = db.create_entity(tx.clone(), "schema kg", None, None).unwrap();
+        let lb = db.create_entity(tx.clone(), "schema lb", None, None).unwrap();
+        let stone = db.create_entity(tx.clone(), "schema stone", None, None).unwrap();
+        let rule = AttributeRule {
+            attr_type_id: weight,
+            form: Util::QUANTITY_TYPE.to_string(),
+            min_count: 1,
+            max_count: Some(1),
+            min_value: Some(0.0),
+            max_value: Some(500.0),
+            allowed_unit_ids: vec![lb, kg],
+        };
+        db.set_class_attribute_rule(tx.clone(), class_id, &rule).unwrap();
+        let rules = db.get_class_attribute_rules(tx.clone(), class_id).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].allowed_unit_ids, vec![kg.min(lb), kg.max(lb)]);
+        assert_eq!(rules[0].max_value, Some(500.0));
+
+        // The template's placeholder values aren't checked.
+        db.create_quantity_attribute(tx.clone(), template_id, weight, stone, -1.0, None, 0, None)
+            .unwrap();
+        let person = db.create_entity(tx.clone(), "schema Ann", Some(class_id), None).unwrap();
+        assert_eq!(
+            db.get_class_rule_problems(tx.clone(), person).unwrap(),
+            vec!["\"schema weight\" is required but missing."]
+        );
+        let err = db
+            .create_quantity_attribute(tx.clone(), person, weight, stone, 10.0, None, 0, None)
+            .unwrap_err();
+        assert!(err.to_string().contains("can't be in \"schema stone\""), "{}", err);
+        assert!(db.create_quantity_attribute(tx.clone(), person, weight, kg, 600.0, None, 0, None).is_err());
+        assert!(db.create_text_attribute(tx.clone(), person, weight, "heavy", None, 0, None).is_err());
+        let id = db.create_quantity_attribute(tx.clone(), person, weight, kg, 70.0, None, 0, None).unwrap();
+        assert!(db.create_quantity_attribute(tx.clone(), person, weight, kg, 71.0, None, 0, None).is_err());
+        db.update_quantity_attribute(tx.clone(), id, person, weight, lb, 154.0, None, 0).unwrap();
+        assert!(db.update_quantity_attribute(tx.clone(), id, person, weight, lb, -1.0, None, 0).is_err());
+        assert!(db.get_class_rule_problems(tx.clone(), person).unwrap().is_empty());
+
+        // A required attribute can't be deleted, nor an entity put in the class without it.
+        let err = db.delete_quantity_attribute(tx.clone(), id).unwrap_err();
+        assert!(err.to_string().contains("is required but missing"), "{}", err);
+        let carl = db.create_entity(tx.clone(), "schema Carl", None, None).unwrap();
+        let err = db.update_entitys_class(tx.clone(), carl, Some(class_id)).unwrap_err();
+        assert!(err.to_string().contains("is required but missing"), "{}", err);
+        assert!(db.get_entity_data(tx.clone(), carl).unwrap()[1].is_none());
+        let carls_weight = db.create_quantity_attribute(tx.clone(), carl, weight, kg, 80.0, None, 0, None).unwrap();
+        db.update_entitys_class(tx.clone(), carl, Some(class_id)).unwrap();
+        assert!(db.delete_quantity_attribute(tx.clone(), carls_weight).is_err());
+        db.update_entitys_class(tx.clone(), carl, None).unwrap();
+        assert_eq!(db.delete_quantity_attribute(tx.clone(), carls_weight).unwrap(), 1);
+
+        let other = db.create_entity(tx.clone(), "schema Bob", Some(class_id), None).unwrap();
+        let report = db.validate_class_members(tx.clone(), class_id).unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].0, other);
+
+        assert_eq!(db.delete_class_attribute_rule(tx.clone(), class_id, weight).unwrap(), 1);
+        db.create_quantity_attribute(tx.clone(), person, weight, stone, 11.0, None, 0, None)
+            .unwrap();
+        assert!(db.validate_class_members(tx.clone(), class_id).unwrap().is_empty());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
@@ -11,11 +11,13 @@
 // use crate::model::boolean_attribute::BooleanAttribute;
 use crate::model::database::DataType;
 use crate::model::database::Database;
+use crate::model::unit_conversion::UnitConverter;
 // use crate::model::entity::Entity;
 // use crate::model::postgres::postgresql_database2::*;
 // use crate::model::postgres::*;
 // use crate::model::RelationToLocalEntity::RelationToLocalEntity;
 // use crate::model::relation_to_remote_entity::RelationToRemoteEntity;
+use crate::credentials;
 use crate::util::Util;
 use anyhow::anyhow;
 use chrono::Utc;
@@ -40,6 +42,9 @@ pub struct PostgreSQLDatabase {
     pub pool: PgPool,
     // When true, this means to override the usual settings and show the archived entities too (like a global temporary "un-archive"):
     pub include_archived_entities: bool,
+    // The unit conversions as last read (see Database::get_unit_converter), so that showing a
+    // list of quantities does not read them all again for each one.
+    pub unit_converter: RefCell<Option<Rc<UnitConverter>>>,
 }
 
 impl PostgreSQLDatabase {
@@ -47,10 +52,10 @@ impl PostgreSQLDatabase {
     // or in postgresql_database2.rs (split up to make smaller files,
     // for rust-analyzer speed during editing or compiler for compiling).
 
-    pub const SCHEMA_VERSION: i32 = 7;
+    pub const SCHEMA_VERSION: i32 = 17;
     pub const ENTITY_ONLY_SELECT_PART: &'static str = "SELECT e.id";
 
-    fn db_name(db_name_without_prefix: &str) -> String {
+    pub fn db_name(db_name_without_prefix: &str) -> String {
         format!("{}{}", Util::DB_NAME_PREFIX, db_name_without_prefix)
     }
 
@@ -334,7 +339,15 @@ impl PostgreSQLDatabase {
         self.drop(None, "table", Util::RELATION_TO_LOCAL_ENTITY_TYPE)?;
         self.drop(None, "table", Util::RELATION_TO_REMOTE_ENTITY_TYPE)?;
         self.drop(None, "table", "EntitiesInAGroup")?;
+        self.drop(None, "table", "NavigationHistory")?;
+        self.drop(None, "table", "EntityView")?;
         self.drop(None, "table", Util::RELATION_TO_GROUP_TYPE)?;
+        self.drop(None, "table", "RelationAttributeHolder")?;
+        self.drop(None, "table", "RelationTypeAllowedClass")?;
+        self.drop(None, "table", "ClassAttributeRuleUnit")?;
+        self.drop(None, "table", "ClassAttributeRule")?;
+        self.drop(None, "table", "UnitConversion")?;
+        self.drop(None, "table", "CanonicalUnit")?;
         self.drop(None, "table", "action")?;
         self.drop(None, "table", "grupo")?;
         self.drop(None, "table", Util::RELATION_TYPE_TYPE)?;
@@ -554,6 +567,7 @@ impl PostgreSQLDatabase {
             rt,
             pool,
             include_archived_entities,
+            unit_converter: RefCell::new(None),
         };
         new_db.setup_db()?;
         Ok(Rc::new(RefCell::new(new_db)))
@@ -671,12 +685,13 @@ impl PostgreSQLDatabase {
     ) -> Result<PgPool, sqlx::Error> {
         // (to connect to remote hosts, see logic in the connect() method and jdbcUrl(), in the older
         // PostgreSQLDatabase.scala file.  db_name() has replaced it here for now.)
-        let connect_str = format!(
-            "postgres://{}:{}@localhost/{}",
-            username,
-            password,
-            Self::db_name(db_name_without_prefix)
-        );
+        // (Using options rather than a connection URL, so the password is not copied into yet
+        // another string, and needs no escaping.  The port comes from PGPORT if set.)
+        let connect_options = PgConnectOptions::new_without_pgpass()
+            .host(credentials::DEFAULT_HOST)
+            .username(username)
+            .password(password)
+            .database(Self::db_name(db_name_without_prefix).as_str());
         let future = PgPoolOptions::new()
             // idea: the example had 5, could switch to not using pools, or use pools again now/later if it matters?
             // I had max_connections(10), but then a test fails with "pool timed out while waiting for an open connection",
@@ -702,7 +717,7 @@ impl PostgreSQLDatabase {
             //%%use .connect_with and pass options?? for transaction isolation levell...?  Is also
             // mentioned in one of the early parts of below "mod tests" below I think.
             // Search for related cmts w/ "isolation".
-            .connect(connect_str.as_str());
+            .connect_with(connect_options);
         let pool = rt.block_on(future)?;
         // pool.options().
         // let pool = future;
@@ -844,7 +859,9 @@ impl PostgreSQLDatabase {
             name varchar({}) NOT NULL, \
             defining_entity_id bigint UNIQUE NOT NULL, \
             create_default_attributes boolean, \
-            CONSTRAINT valid_related_to_entity_id FOREIGN KEY (defining_entity_id) REFERENCES entity (id) \
+            parent_class_id bigint, \
+            CONSTRAINT valid_related_to_entity_id FOREIGN KEY (defining_entity_id) REFERENCES entity (id), \
+            CONSTRAINT valid_parent_class_id FOREIGN KEY (parent_class_id) REFERENCES Class (id) ON DELETE SET NULL \
             )", Util::class_name_length());
         self.db_action(transaction.clone(), sql.as_str(), false, false)?;
 
@@ -879,11 +896,18 @@ impl PostgreSQLDatabase {
         // Valid values for directionality are "BI ","UNI","NON"-directional for this relationship. example: 
         // parent/child is unidirectional. sibling is bidirectional,
         // And for nondirectional see Controller's mention of "nondir" and/or elsewhere for comments
+        // The is_transitive, is_symmetric and inverse_of_id columns are only used to infer relations that
+        // are implied by the ones entered (see relation_inference.rs).  An inverse pair is stored on
+        // both of its relation types.
         self.db_action(transaction.clone(), format!("create table RelationType (\
             entity_id bigint PRIMARY KEY, \
             name_in_reverse_direction varchar({}), \
             directionality char(3) CHECK (directionality in ('BI','UNI','NON')), \
-            CONSTRAINT valid_rel_entity_id FOREIGN KEY (entity_id) REFERENCES Entity (id) ON DELETE CASCADE \
+            is_transitive boolean NOT NULL default false, \
+            is_symmetric boolean NOT NULL default false, \
+            inverse_of_id bigint, \
+            CONSTRAINT valid_rel_entity_id FOREIGN KEY (entity_id) REFERENCES Entity (id) ON DELETE CASCADE, \
+            CONSTRAINT valid_inverse_of_id FOREIGN KEY (inverse_of_id) REFERENCES RelationType (entity_id) ON DELETE SET NULL \
             ) ", Util::relation_type_name_length()).as_str(), false, false)?;
 
         /* This table maintains the users' preferred display sorting information for entities' attributes (including relations to groups/entities).
@@ -1275,6 +1299,9 @@ impl PostgreSQLDatabase {
         // since 1970-1-1 or such) when this row was inserted (ie, when the object was created
         // in the db).
         // For new_entries... see comment at same field in Entity table.
+        // If query is not null, the group is a saved search (a "smart group"): its entries are
+        // not in EntitiesInAGroup, but are computed when read, from the query (see
+        // entity_query.rs), and its membership can't be edited directly.
         self.db_action(
             transaction.clone(),
             format!(
@@ -1283,7 +1310,8 @@ impl PostgreSQLDatabase {
             name varchar({}) NOT NULL, \
             insertion_date bigint not null, \
             allow_mixed_classes boolean NOT NULL, \
-            new_entries_stick_to_top boolean NOT NULL  default false\
+            new_entries_stick_to_top boolean NOT NULL  default false, \
+            query text\
             ) ",
                 Util::entity_name_length()
             )
@@ -1395,9 +1423,9 @@ impl PostgreSQLDatabase {
             id bigint DEFAULT nextval('ActionKeySequence') PRIMARY KEY, \
             class_id bigint NOT NULL, \
             name varchar({}) NOT NULL, \
-            action varchar({}) NOT NULL, \
+            action text NOT NULL, \
             CONSTRAINT valid_related_to_class_id FOREIGN KEY (class_id) REFERENCES Class (id) ON DELETE CASCADE \
-            ) ", Util::entity_name_length(), Util::entity_name_length()).as_str(), false, false)?;
+            ) ", Util::entity_name_length()).as_str(), false, false)?;
         self.db_action(
             transaction.clone(),
             "create index action_class_id on Action (class_id)",
@@ -1405,6 +1433,13 @@ impl PostgreSQLDatabase {
             false,
         )?;
 
+        self.create_unit_tables(transaction.clone())?;
+        self.create_class_attribute_rule_tables(transaction.clone())?;
+        self.create_relation_type_allowed_class_table(transaction.clone())?;
+        self.create_relation_attribute_holder_table(transaction.clone())?;
+        self.create_navigation_history_table(transaction.clone())?;
+        self.create_entity_view_table(transaction.clone())?;
+
         /* This current database is one OM instance, and known (remote or local) databases
          * to which this one might refer are other instances.
           Design musings:
@@ -1516,7 +1551,141 @@ impl PostgreSQLDatabase {
         Ok(())
     }
 
-    fn create_attribute_sorting_deletion_trigger(
+    /// Creates the tables that relate unit entities (the unit_id of a QuantityAttribute) to each
+    /// other, so quantities in different units can be converted and totaled.  "If not exists" so
+    /// that upgrade_db_from_8_to_9 can share it.
+    pub(super) fn create_unit_tables(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<(), anyhow::Error> {
+        // One from_unit_id is worth factor to_unit_ids (eg, from km to m, 1000).  Only one
+        // direction is stored for a pair; the inverse is computed when needed.
+        self.db_action(transaction.clone(), "create table if not exists UnitConversion (\
+            from_unit_id bigint NOT NULL, \
+            to_unit_id bigint NOT NULL, \
+            factor double precision NOT NULL CHECK (factor <> 0), \
+            PRIMARY KEY (from_unit_id, to_unit_id), \
+            CONSTRAINT valid_from_unit_id FOREIGN KEY (from_unit_id) REFERENCES entity (id) ON DELETE CASCADE, \
+            CONSTRAINT valid_to_unit_id FOREIGN KEY (to_unit_id) REFERENCES entity (id) ON DELETE CASCADE \
+            ) ", false, false)?;
+        // The unit in which to display and total quantities of a given attribute type (entity).
+        self.db_action(transaction, "create table if not exists CanonicalUnit (\
+            attr_type_id bigint PRIMARY KEY, \
+            unit_id bigint NOT NULL, \
+            CONSTRAINT valid_attr_type_id FOREIGN KEY (attr_type_id) REFERENCES entity (id) ON DELETE CASCADE, \
+            CONSTRAINT valid_unit_id FOREIGN KEY (unit_id) REFERENCES entity (id) ON DELETE CASCADE \
+            ) ", false, false)?;
+        Ok(())
+    }
+
+    /// Creates the tables for classes' schemas (see class_schema.rs).  "If not exists" so that
+    /// upgrade_db_from_10_to_11 can share it.
+    pub(super) fn create_class_attribute_rule_tables(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<(), anyhow::Error> {
+        // form_id is as from get_attribute_form_id.  min_value and max_value bound a quantity's
+        // number, a date (in millis), or a text's length.
+        self.db_action(transaction.clone(), "create table if not exists ClassAttributeRule (\
+            class_id bigint NOT NULL, \
+            attr_type_id bigint NOT NULL, \
+            form_id integer NOT NULL, \
+            min_count integer NOT NULL default 0 CHECK (min_count >= 0), \
+            max_count integer CHECK (max_count is NULL or (max_count >= min_count and max_count > 0)), \
+            min_value double precision, \
+            max_value double precision CHECK (min_value is NULL or max_value is NULL or max_value >= min_value), \
+            PRIMARY KEY (class_id, attr_type_id), \
+            CONSTRAINT valid_class_id FOREIGN KEY (class_id) REFERENCES Class (id) ON DELETE CASCADE, \
+            CONSTRAINT valid_attr_type_id FOREIGN KEY (attr_type_id) REFERENCES entity (id) ON DELETE CASCADE \
+            ) ", false, false)?;
+        // The units allowed for a quantity rule; none means any.
+        self.db_action(transaction, "create table if not exists ClassAttributeRuleUnit (\
+            class_id bigint NOT NULL, \
+            attr_type_id bigint NOT NULL, \
+            unit_id bigint NOT NULL, \
+            PRIMARY KEY (class_id, attr_type_id, unit_id), \
+            CONSTRAINT valid_rule FOREIGN KEY (class_id, attr_type_id) REFERENCES ClassAttributeRule (class_id, attr_type_id) ON DELETE CASCADE, \
+            CONSTRAINT valid_unit_id FOREIGN KEY (unit_id) REFERENCES entity (id) ON DELETE CASCADE \
+            ) ", false, false)?;
+        Ok(())
+    }
+
+    pub(super) fn create_relation_type_allowed_class_table(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<(), anyhow::Error> {
+        // The classes allowed for the entity at one end of a relation of the type: the source
+        // (entity_id in RelationToEntity) if for_target is false, else the target (entity_id_2).
+        // None for an end means any entity is allowed there.  (See
+        // Database::set_relation_type_class_constraints.)
+        self.db_action(transaction, "create table if not exists RelationTypeAllowedClass (\
+            rel_type_id bigint NOT NULL, \
+            for_target boolean NOT NULL, \
+            class_id bigint NOT NULL, \
+            PRIMARY KEY (rel_type_id, for_target, class_id), \
+            CONSTRAINT valid_rel_type_id FOREIGN KEY (rel_type_id) REFERENCES RelationType (entity_id) ON DELETE CASCADE, \
+            CONSTRAINT valid_class_id FOREIGN KEY (class_id) REFERENCES Class (id) ON DELETE CASCADE \
+            ) ", false, false)?;
+        Ok(())
+    }
+
+    pub(super) fn create_relation_attribute_holder_table(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<(), anyhow::Error> {
+        // A relation's own attributes (like when a "worked at" relation began, or the role in
+        // it) are those of an entity made to hold them: the entity_id here.  The relation is
+        // identified as in AttributeSorting, by its form (RelationToEntity, RelationToGroup or
+        // RelationToRemoteEntity) and id.  When the relation is deleted, so is the entity (see
+        // create_attribute_sorting_deletion_trigger), and these entities are left out of entity
+        // lists (see limit_to_entities_only).  (See Database::get_relation_attribute_holder_id.)
+        self.db_action(transaction, "create table if not exists RelationAttributeHolder (\
+            relation_form_id smallint NOT NULL, \
+            relation_id bigint NOT NULL, \
+            entity_id bigint NOT NULL UNIQUE, \
+            PRIMARY KEY (relation_form_id, relation_id), \
+            CONSTRAINT valid_relation_form_id CHECK (relation_form_id >= 6 AND relation_form_id <= 8), \
+            CONSTRAINT valid_holder_entity_id FOREIGN KEY (entity_id) REFERENCES Entity (id) ON DELETE CASCADE \
+            ) ", false, false)?;
+        Ok(())
+    }
+
+    pub(super) fn create_navigation_history_table(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<(), anyhow::Error> {
+        // The entities and groups most recently viewed, in order (position), and which one the
+        // user was at (is_current), so OM can reopen there.  Each row is either an entity or a
+        // group, and goes away with it.  (See model/navigation_history.rs and
+        // Database::save_navigation_history.)
+        self.db_action(transaction, "create table if not exists NavigationHistory (\
+            position integer PRIMARY KEY, \
+            entity_id bigint, \
+            group_id bigint, \
+            is_current boolean NOT NULL, \
+            CONSTRAINT one_location CHECK ((entity_id is null) <> (group_id is null)), \
+            CONSTRAINT valid_entity_id FOREIGN KEY (entity_id) REFERENCES Entity (id) ON DELETE CASCADE, \
+            CONSTRAINT valid_group_id FOREIGN KEY (group_id) REFERENCES grupo (id) ON DELETE CASCADE \
+            ) ", false, false)?;
+        Ok(())
+    }
+
+    pub(super) fn create_entity_view_table(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<(), anyhow::Error> {
+        // How often, and when last, the user viewed each entity, for the lists of recently and
+        // frequently viewed entities.  (See Database::record_entity_view.)
+        self.db_action(transaction, "create table if not exists EntityView (\
+            entity_id bigint PRIMARY KEY, \
+            view_count bigint NOT NULL, \
+            last_viewed bigint NOT NULL, \
+            CONSTRAINT valid_entity_id FOREIGN KEY (entity_id) REFERENCES Entity (id) ON DELETE CASCADE \
+            ) ", false, false)?;
+        Ok(())
+    }
+
+    pub(super) fn create_attribute_sorting_deletion_trigger(
         &self,
         transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
     ) -> Result<u64, anyhow::Error> {
@@ -1524,9 +1693,13 @@ impl PostgreSQLDatabase {
         // I.e., an attempt to enforce (with triggers that call this procedure) that the AttributeSorting table's attribute_id value is found
         // in *one of the* 7 attribute tables' id column,  Doing it in application code is not as simple or as reliable as doing it at the DDL level.
         // (OLD is a special PL/pgsql variable of type RECORD, which contains the attribute row before the deletion.)
+        // For a relation, the entity holding its own attributes (if any) is deleted too, which also removes its
+        // RelationAttributeHolder row.
         let sql = "CREATE OR REPLACE FUNCTION attribute_sorting_cleanup() RETURNS trigger AS $attribute_sorting_cleanup$ \
           BEGIN \
                 DELETE FROM AttributeSorting WHERE entity_id=OLD.entity_id and attribute_form_id=OLD.form_id and attribute_id=OLD.id; \
+                DELETE FROM Entity WHERE id in (select entity_id from RelationAttributeHolder \
+                  where relation_form_id=OLD.form_id and relation_id=OLD.id); \
                 RETURN OLD; \
               END;\
             $attribute_sorting_cleanup$ LANGUAGE plpgsql;";
@@ -1945,7 +2118,7 @@ impl PostgreSQLDatabase {
         // nulls don't show up in a count(class_id), so get those separately
         //%%but does it matter that we are not doing such a count(class_id)? have a test4this?
         let num_null_classes_in_group_entities = self.extract_row_count_from_count_query(
-            transaction,
+            transaction.clone(),
             format!(
                 "select count(entity_id) from EntitiesInAGroup \
             eiag, entity e where eiag.entity_id=e.id and group_id={} and class_id is NULL ",
@@ -1953,10 +2126,24 @@ impl PostgreSQLDatabase {
             )
             .as_str(),
         )?;
-        if num_classes_in_group_entities > 1
-            || (num_classes_in_group_entities >= 1 && num_null_classes_in_group_entities > 0)
-        {
+        if num_classes_in_group_entities >= 1 && num_null_classes_in_group_entities > 0 {
             Ok(true)
+        } else if num_classes_in_group_entities > 1 {
+            // Members of subclasses count as members of their parent classes, so the entries
+            // are only mixed if their classes have no ancestor (or themselves) in common.
+            let mut common: Option<Vec<i64>> = None;
+            for class_id in get_i64s_from_rows(&rows)? {
+                let lineage: Vec<i64> = self
+                    .get_class_lineage(transaction.clone(), class_id)?
+                    .iter()
+                    .map(|(id, _)| *id)
+                    .collect();
+                common = Some(match common {
+                    None => lineage,
+                    Some(ids) => ids.into_iter().filter(|id| lineage.contains(id)).collect(),
+                });
+            }
+            Ok(common.is_none_or(|ids| ids.is_empty()))
         } else {
             Ok(false)
         }
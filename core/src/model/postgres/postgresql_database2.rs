@@ -10,9 +10,13 @@
 /// Created this file to reduce the size of postgresql_database.rs, so the IDE can process things
 /// faster.
 use crate::model::attribute::Attribute;
+use crate::model::attribute_with_valid_and_observed_dates::AsOfDate;
 use crate::model::database::DataType;
 use crate::model::database::Database;
 use crate::model::entity::Entity;
+use crate::model::entity_query::{
+    AttributeForm, AttributeValue, EntityQuery, EntityRef, QueryCondition, QueryOp,
+};
 use crate::model::om_instance::OmInstance;
 use crate::model::postgres::postgresql_database::*;
 use crate::model::relation_to_group::RelationToGroup;
@@ -64,6 +68,9 @@ impl PostgreSQLDatabase {
         sql.push_str("except (");
         sql.push_str(select_column_names);
         sql.push_str(" from entity e, relationtype t where e.id=t.entity_id) ");
+        sql.push_str("except (");
+        sql.push_str(select_column_names);
+        sql.push_str(" from entity e, RelationAttributeHolder t where e.id=t.entity_id) ");
         sql
     }
 
@@ -443,7 +450,7 @@ impl PostgreSQLDatabase {
             self.create_version_table(transaction.clone())?;
         }
         let db_version_row: Vec<Option<DataType>> = self.db_query_wrapper_for_one_row(
-            transaction,
+            transaction.clone(),
             "select version from odb_version",
             "Int",
         )?;
@@ -456,10 +463,37 @@ impl PostgreSQLDatabase {
                 ))
             }
         };
-        // PUT THIS BACK & delete this cmt, when there is another upgrade, and similar ones to follow:
-        // if db_version == 7 {
-        //     db_version = upgradeDbFrom7to8()
-        // }
+        let mut db_version = db_version;
+        if db_version == 7 {
+            db_version = self.upgrade_db_from_7_to_8(transaction.clone())?;
+        }
+        if db_version == 8 {
+            db_version = self.upgrade_db_from_8_to_9(transaction.clone())?;
+        }
+        if db_version == 9 {
+            db_version = self.upgrade_db_from_9_to_10(transaction.clone())?;
+        }
+        if db_version == 10 {
+            db_version = self.upgrade_db_from_10_to_11(transaction.clone())?;
+        }
+        if db_version == 11 {
+            db_version = self.upgrade_db_from_11_to_12(transaction.clone())?;
+        }
+        if db_version == 12 {
+            db_version = self.upgrade_db_from_12_to_13(transaction.clone())?;
+        }
+        if db_version == 13 {
+            db_version = self.upgrade_db_from_13_to_14(transaction.clone())?;
+        }
+        if db_version == 14 {
+            db_version = self.upgrade_db_from_14_to_15(transaction.clone())?;
+        }
+        if db_version == 15 {
+            db_version = self.upgrade_db_from_15_to_16(transaction.clone())?;
+        }
+        if db_version == 16 {
+            db_version = self.upgrade_db_from_16_to_17(transaction.clone())?;
+        }
 
         /* NOTE FOR FUTURE METHODS LIKE upgradeDbFrom0to1: methods like this should be designed carefully and very well-tested:
          0) make & test periodic backups of your live data to be safe!
@@ -484,6 +518,269 @@ impl PostgreSQLDatabase {
         Ok(())
     }
 
+    /// Makes entity_id_in a URI entity: puts it in the "URI" class, and adds a text attribute
+    /// of that class' template type for the URI, and one of the "quote" class' for any quote.
+    pub(super) fn add_uri_class_and_attributes(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        uri_in: &str,
+        observation_date_in: i64,
+        quote_in: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let (uri_class_id, uri_class_template_id) =
+            self.get_or_create_class_and_template_entity(transaction.clone(), "URI")?;
+        self.create_text_attribute(
+            transaction.clone(),
+            entity_id_in,
+            uri_class_template_id,
+            uri_in,
+            None,
+            observation_date_in,
+            None,
+        )?;
+        if let Some(quote) = quote_in {
+            let (_, quotation_class_template_id) =
+                self.get_or_create_class_and_template_entity(transaction.clone(), "quote")?;
+            self.create_text_attribute(
+                transaction.clone(),
+                entity_id_in,
+                quotation_class_template_id,
+                quote,
+                None,
+                observation_date_in,
+                None,
+            )?;
+        }
+        // (After the attributes, so any rules of the class requiring them are met.)
+        self.update_entitys_class(transaction, entity_id_in, Some(uri_class_id))?;
+        Ok(())
+    }
+
+    /// Returns SQL (starting with " and ") limiting rows of table_name to those in effect as of
+    /// as_of_in, for get_sorted_attributes_as_of, or "" for a table with no such dates.
+    pub(super) fn as_of_condition_sql(table_name: &str, as_of_in: AsOfDate) -> String {
+        let date_column_of = |t: &str| match as_of_in {
+            AsOfDate::ValidOn(_) => format!("coalesce({}.valid_on_date, {}.observation_date)", t, t),
+            AsOfDate::ObservedOn(_) => format!("{}.observation_date", t),
+        };
+        let date = match as_of_in {
+            AsOfDate::ValidOn(date) | AsOfDate::ObservedOn(date) => date,
+        };
+        if table_name == Util::DATE_TYPE {
+            String::new()
+        } else if table_name == Util::FILE_TYPE {
+            let column = match as_of_in {
+                AsOfDate::ValidOn(_) => "original_file_date",
+                AsOfDate::ObservedOn(_) => "stored_date",
+            };
+            format!(" and {}.{} <= {}", table_name, column, date)
+        } else if table_name == Util::QUANTITY_TYPE
+            || table_name == Util::TEXT_TYPE
+            || table_name == Util::BOOLEAN_TYPE
+        {
+            // Of those of the same type, only the one most recently in effect by then is kept
+            // (ties go to the later observation, then to the later id).
+            format!(
+                " and {} <= {} and not exists(select 1 from {} newer where newer.entity_id={}.entity_id \
+                and newer.attr_type_id={}.attr_type_id and {} <= {} \
+                and ({}, newer.observation_date, newer.id) > ({}, {}.observation_date, {}.id))",
+                date_column_of(table_name),
+                date,
+                table_name,
+                table_name,
+                table_name,
+                date_column_of("newer"),
+                date,
+                date_column_of("newer"),
+                date_column_of(table_name),
+                table_name,
+                table_name
+            )
+        } else {
+            format!(" and {} <= {}", date_column_of(table_name), date)
+        }
+    }
+
+    /// Adds grupo.query, for saved searches ("smart groups").  Returns the new version number.
+    fn upgrade_db_from_7_to_8(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.db_action(
+            transaction.clone(),
+            "ALTER TABLE grupo ADD COLUMN IF NOT EXISTS query text",
+            false,
+            false,
+        )?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(8)",
+            false,
+            false,
+        )?;
+        Ok(8)
+    }
+
+    /// Adds the UnitConversion and CanonicalUnit tables.  Returns the new version number.
+    fn upgrade_db_from_8_to_9(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.create_unit_tables(transaction.clone())?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(9)",
+            false,
+            false,
+        )?;
+        Ok(9)
+    }
+
+    /// Makes Action.action text, since it now holds scripts (see action_script.rs) that can be
+    /// longer than a name.  Returns the new version number.
+    fn upgrade_db_from_9_to_10(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.db_action(
+            transaction.clone(),
+            "ALTER TABLE Action ALTER COLUMN action TYPE text",
+            false,
+            false,
+        )?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(10)",
+            false,
+            false,
+        )?;
+        Ok(10)
+    }
+
+    /// Adds the ClassAttributeRule and ClassAttributeRuleUnit tables.  Returns the new version
+    /// number.
+    fn upgrade_db_from_10_to_11(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.create_class_attribute_rule_tables(transaction.clone())?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(11)",
+            false,
+            false,
+        )?;
+        Ok(11)
+    }
+
+    /// Adds Class.parent_class_id, for subclasses.  Returns the new version number.
+    fn upgrade_db_from_11_to_12(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.db_action(
+            transaction.clone(),
+            "ALTER TABLE Class ADD COLUMN IF NOT EXISTS parent_class_id bigint \
+            CONSTRAINT valid_parent_class_id REFERENCES Class (id) ON DELETE SET NULL",
+            false,
+            false,
+        )?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(12)",
+            false,
+            false,
+        )?;
+        Ok(12)
+    }
+
+    /// Adds RelationType.is_transitive, is_symmetric and inverse_of_id, for inferring relations.
+    /// Returns the new version number.
+    fn upgrade_db_from_12_to_13(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.db_action(
+            transaction.clone(),
+            "ALTER TABLE RelationType ADD COLUMN IF NOT EXISTS is_transitive boolean NOT NULL default false, \
+            ADD COLUMN IF NOT EXISTS is_symmetric boolean NOT NULL default false, \
+            ADD COLUMN IF NOT EXISTS inverse_of_id bigint \
+            CONSTRAINT valid_inverse_of_id REFERENCES RelationType (entity_id) ON DELETE SET NULL",
+            false,
+            false,
+        )?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(13)",
+            false,
+            false,
+        )?;
+        Ok(13)
+    }
+
+    /// Adds the RelationTypeAllowedClass table.  Returns the new version number.
+    fn upgrade_db_from_13_to_14(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.create_relation_type_allowed_class_table(transaction.clone())?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(14)",
+            false,
+            false,
+        )?;
+        Ok(14)
+    }
+
+    /// Adds the RelationAttributeHolder table, and has deleting a relation delete its holder
+    /// entity.  Returns the new version number.
+    fn upgrade_db_from_14_to_15(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.create_relation_attribute_holder_table(transaction.clone())?;
+        self.create_attribute_sorting_deletion_trigger(transaction.clone())?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(15)",
+            false,
+            false,
+        )?;
+        Ok(15)
+    }
+
+    /// Adds the NavigationHistory table.  Returns the new version number.
+    fn upgrade_db_from_15_to_16(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.create_navigation_history_table(transaction.clone())?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(16)",
+            false,
+            false,
+        )?;
+        Ok(16)
+    }
+
+    /// Adds the EntityView table.  Returns the new version number.
+    fn upgrade_db_from_16_to_17(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<i32, anyhow::Error> {
+        self.create_entity_view_table(transaction.clone())?;
+        self.db_action(
+            transaction,
+            "UPDATE odb_version SET (version) = ROW(17)",
+            false,
+            false,
+        )?;
+        Ok(17)
+    }
+
     // See comment in ImportExport.processUriContent method which uses it, about where the
     // code should really go. Not sure if that idea includes this method or not.
     pub fn find_first_class_id_by_name(
@@ -616,13 +913,31 @@ impl PostgreSQLDatabase {
         }
     }
 
+    /// A subquery giving the ids of class_id_in and all its subclasses (to any depth).
+    pub fn class_and_subclasses_sql(class_id_in: i64) -> String {
+        format!(
+            "(with recursive subclasses(id) as (select ({})::bigint union select c.id from Class c, subclasses s \
+            where c.parent_class_id=s.id) select id from subclasses)",
+            class_id_in
+        )
+    }
+
+    /// Members of subclasses count as members of the class, but subclasses' template entities
+    /// don't (the class's own template is left for callers to omit or not).
     pub fn class_limit(
         limit_by_class: bool,
         class_id_in: Option<i64>,
     ) -> Result<String, anyhow::Error> {
         let res = if limit_by_class {
             match class_id_in {
-                Some(x) => format!(" and e.class_id={} ", x).to_string(),
+                Some(x) => {
+                    let classes = Self::class_and_subclasses_sql(x);
+                    format!(
+                        " and e.class_id in {} and e.id not in (select defining_entity_id from Class where id<>{} \
+                        and id in {}) ",
+                        classes, x, classes
+                    )
+                }
                 _ => " and e.class_id is NULL ".to_string(),
             }
         } else {
@@ -1389,4 +1704,207 @@ impl PostgreSQLDatabase {
         //    self,
         Ok((id, true, address, insertion_date, entity_id))
     }
+
+    /// Builds the "where" expression (against "entity e") for one condition of an EntityQuery.
+    /// Containment ("under") is resolved here by walking the data, so the result is a list of ids.
+    pub fn entity_query_condition_sql(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition: &QueryCondition,
+    ) -> Result<String, anyhow::Error> {
+        const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+        // For a date given as only a day, "=" means anywhere in that day, ">" means after it, etc.
+        let date_comparison = |column: &str, op: QueryOp, date: i64, day_only: bool| -> String {
+            if !day_only {
+                return format!("{} {} {}", column, op.as_sql(), date);
+            }
+            let next_day = date + MILLIS_PER_DAY;
+            match op {
+                QueryOp::Equal => format!("({} >= {} and {} < {})", column, date, column, next_day),
+                QueryOp::NotEqual => format!("({} < {} or {} >= {})", column, date, column, next_day),
+                QueryOp::Greater => format!("{} >= {}", column, next_day),
+                QueryOp::LessOrEqual => format!("{} < {}", column, next_day),
+                _ => format!("{} {} {}", column, op.as_sql(), date),
+            }
+        };
+        // ("false" for no ids, since "in (null)" would be null, and stay null when negated.)
+        let in_ids_sql = |column: &str, ids: &Vec<i64>| -> String {
+            if ids.is_empty() {
+                "false".to_string()
+            } else {
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                format!("{} in ({})", column, ids.join(","))
+            }
+        };
+        let sql = match condition {
+            QueryCondition::Not(inner) => {
+                format!("not ({})", self.entity_query_condition_sql(transaction, inner)?)
+            }
+            QueryCondition::Archived => "e.archived".to_string(),
+            QueryCondition::Name(op, value) => {
+                let value = Self::escape_quotes_etc(value.clone());
+                match op {
+                    QueryOp::Matches => format!("e.name ~* '{}'", value),
+                    _ => format!("lower(e.name) {} lower('{}')", op.as_sql(), value),
+                }
+            }
+            QueryCondition::Class(op, value) => {
+                let value = Self::escape_quotes_etc(value.clone());
+                let class_condition = match op {
+                    QueryOp::Matches => format!("c.name ~* '{}'", value),
+                    _ => format!("lower(c.name) = lower('{}')", value),
+                };
+                // (coalesce so that entities without a class give false, not null, which
+                // matters when this is negated.  Members of subclasses are included.)
+                let in_class = format!(
+                    "coalesce(e.class_id in (with recursive subclasses(id) as (select c.id from class c \
+                    where {} union select c.id from Class c, subclasses s where c.parent_class_id=s.id) \
+                    select id from subclasses), false)",
+                    class_condition
+                );
+                if *op == QueryOp::NotEqual {
+                    format!("not {}", in_class)
+                } else {
+                    in_class
+                }
+            }
+            QueryCondition::Added(op, date, day_only) => {
+                date_comparison("e.insertion_date", *op, *date, *day_only)
+            }
+            QueryCondition::Attribute(form, type_name, comparison) => {
+                let (table, column) = match form {
+                    AttributeForm::Quantity => ("QuantityAttribute", "quantity_number"),
+                    AttributeForm::Text => ("TextAttribute", "textvalue"),
+                    AttributeForm::Date => ("DateAttribute", "date"),
+                    AttributeForm::Boolean => ("BooleanAttribute", "booleanvalue"),
+                };
+                let value_condition = match comparison {
+                    None => String::new(),
+                    Some((op, value)) => {
+                        let column = format!("a.{}", column);
+                        let c = match value {
+                            AttributeValue::Number(n) => format!("{} {} {}", column, op.as_sql(), n),
+                            AttributeValue::Boolean(b) => format!("{} {} {}", column, op.as_sql(), b),
+                            AttributeValue::Date(d, day_only) => {
+                                date_comparison(column.as_str(), *op, *d, *day_only)
+                            }
+                            AttributeValue::Text(t) => {
+                                let t = Self::escape_quotes_etc(t.clone());
+                                match op {
+                                    QueryOp::Matches => format!("{} ~* '{}'", column, t),
+                                    _ => format!("{} {} '{}'", column, op.as_sql(), t),
+                                }
+                            }
+                        };
+                        format!(" and {}", c)
+                    }
+                };
+                format!(
+                    "exists (select 1 from {} a, entity t where a.entity_id=e.id and a.attr_type_id=t.id \
+                     and lower(t.name) = lower('{}'){})",
+                    table,
+                    Self::escape_quotes_etc(type_name.clone()),
+                    value_condition
+                )
+            }
+            QueryCondition::Related(rel_type_name, target) => {
+                let target_condition = match target {
+                    None => String::new(),
+                    Some(entity_ref) => format!(
+                        " and {}",
+                        in_ids_sql("r.entity_id_2", &self.resolve_entity_ref(transaction, entity_ref)?)
+                    ),
+                };
+                format!(
+                    "exists (select 1 from RelationToEntity r, entity rt where r.entity_id=e.id and r.rel_type_id=rt.id \
+                     and lower(rt.name) = lower('{}'){})",
+                    Self::escape_quotes_etc(rel_type_name.clone()),
+                    target_condition
+                )
+            }
+            QueryCondition::Under(entity_ref) => {
+                let mut contained: HashSet<i64> = HashSet::new();
+                for id in self.resolve_entity_ref(transaction.clone(), entity_ref)? {
+                    self.find_contained_local_entity_ids(
+                        transaction.clone(),
+                        &mut contained,
+                        id,
+                        "",
+                        EntityQuery::CONTAINMENT_LEVELS,
+                        false,
+                    )?;
+                }
+                let mut ids: Vec<i64> = contained.into_iter().collect();
+                ids.sort();
+                in_ids_sql("e.id", &ids)
+            }
+        };
+        Ok(sql)
+    }
+
+    fn resolve_entity_ref(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_ref: &EntityRef,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        match entity_ref {
+            EntityRef::Id(id) => Ok(vec![*id]),
+            EntityRef::Name(name) => {
+                self.find_all_entity_ids_by_name(transaction, Self::escape_quotes_etc(name.clone()), false)
+            }
+        }
+    }
+
+    /// The "where" expression (against "entity e") for a whole EntityQuery.  If
+    /// archived_condition_in is provided it is used instead of the usual handling of archived
+    /// entities (as for the include_which_entities_in parameter of get_group_size).
+    pub fn entity_query_where_clause(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        query_in: &EntityQuery,
+        archived_condition_in: Option<&str>,
+    ) -> Result<String, anyhow::Error> {
+        let mut conditions: Vec<String> = Vec::new();
+        match archived_condition_in {
+            Some(c) => conditions.push(c.to_string()),
+            None => {
+                if !self.include_archived_entities && !query_in.mentions_archived() {
+                    conditions.push("not e.archived".to_string());
+                }
+            }
+        }
+        for condition in &query_in.conditions {
+            conditions.push(self.entity_query_condition_sql(transaction.clone(), condition)?);
+        }
+        Ok(conditions.join(" and "))
+    }
+
+    /// Returns the parsed query if the group is a saved search ("smart group"), whose entries are
+    /// computed from the query instead of being stored in EntitiesInAGroup.
+    pub fn get_smart_group_query(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        group_id_in: i64,
+    ) -> Result<Option<EntityQuery>, anyhow::Error> {
+        match self.get_group_query(transaction, group_id_in)? {
+            None => Ok(None),
+            Some(q) => Ok(Some(EntityQuery::parse(q.as_str())?)),
+        }
+    }
+
+    pub fn check_group_membership_is_editable(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        group_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        match self.get_group_query(transaction, group_id_in)? {
+            None => Ok(()),
+            Some(q) => Err(anyhow!(
+                "Group {} is a saved search, so its entries come from its query (\"{}\") and can't be \
+                added, removed, moved or reordered directly.",
+                group_id_in,
+                q
+            )),
+        }
+    }
 }
@@ -9,12 +9,20 @@
 */
 /// Created this file to reduce the size of postgresql_database.rs, so the IDE can process things
 /// faster.
+use crate::model::backlinks::Backlinks;
 use crate::model::boolean_attribute::BooleanAttribute;
+use crate::model::class_schema::{self, AttributeRule, RuleValue};
+use crate::model::relation_inference::{InferredRelation, RelationSemantics};
 use crate::model::database::DataType;
 use crate::model::database::Database;
 use crate::model::entity::Entity;
 use crate::model::entity_class::EntityClass;
+use crate::model::entity_query::EntityQuery;
+use crate::model::exchange_format::{ExchangeData, ExchangeImportCounts};
 use crate::model::group::Group;
+use crate::model::navigation_history::NavigationHistory;
+use crate::model::path_finder::{PathLink, PathOptions};
+use crate::model::unit_conversion::UnitConverter;
 use crate::model::postgres::postgresql_database::*;
 // use crate::model::postgres::*;
 use crate::model::date_attribute::DateAttribute;
@@ -24,7 +32,7 @@ use crate::model::relation_to_entity::RelationToEntity;
 use crate::model::relation_to_group::RelationToGroup;
 use crate::model::relation_to_local_entity::RelationToLocalEntity;
 use crate::model::relation_to_remote_entity::RelationToRemoteEntity;
-use crate::model::relation_type::RelationType;
+use crate::model::relation_type::{RelationClassConstraints, RelationType};
 use crate::model::text_attribute::TextAttribute;
 use crate::util::Util;
 use anyhow::anyhow;
@@ -38,7 +46,9 @@ use sqlx::{Postgres, Transaction};
 use std::collections::HashSet;
 // use std::fmt::format;
 use crate::model::attribute::Attribute;
-use crate::model::attribute_with_valid_and_observed_dates::AttributeWithValidAndObservedDates;
+use crate::model::attribute_with_valid_and_observed_dates::{
+    AsOfDate, AttributeWithValidAndObservedDates,
+};
 use std::cell::RefCell;
 use std::fmt;
 use std::rc::Rc;
@@ -69,10 +79,6 @@ impl Database for PostgreSQLDatabase {
         // those in the same package? It was in Controller, but moved here
         // because it seemed like things that manage transactions should be in the db layer.  So maybe it needs un-mixing of layers (and maybe other places do too).
 
-        let (uri_class_id, uri_class_template_id) =
-            self.get_or_create_class_and_template_entity(transaction.clone(), "URI")?;
-        let (_, quotation_class_template_id) =
-            self.get_or_create_class_and_template_entity(transaction.clone(), "quote")?;
         let (new_entity_id, new_rtle_id, _relation_type_id) = self
             .create_entity_and_add_has_local_relation_to_it(
                 transaction.clone(),
@@ -81,27 +87,13 @@ impl Database for PostgreSQLDatabase {
                 observation_date_in,
                 make_them_public_in,
             )?;
-        self.update_entitys_class(transaction.clone(), new_entity_id, Some(uri_class_id))?;
-        self.create_text_attribute(
+        self.add_uri_class_and_attributes(
             transaction.clone(),
             new_entity_id,
-            uri_class_template_id,
             uri_in,
-            None,
             observation_date_in,
-            None,
+            quote_in,
         )?;
-        if quote_in.is_some() {
-            self.create_text_attribute(
-                transaction.clone(),
-                new_entity_id,
-                quotation_class_template_id,
-                quote_in.unwrap(),
-                None,
-                observation_date_in,
-                None,
-            )?;
-        };
         //rollbacketc%%FIX NEXT LINE AFTERI SEE HOW OTHERS DO!
         // if transaction_in.is_none() {self.commit_trans() }
         Ok((new_entity_id, new_rtle_id))
@@ -314,6 +306,8 @@ impl Database for PostgreSQLDatabase {
         if let Err(e) = self.rt.block_on(tx.commit()) {
             return Err(anyhow!(e.to_string()));
         }
+        // (It might have changed the unit conversions: see get_unit_converter.)
+        *self.unit_converter.borrow_mut() = None;
         Ok(())
         // so future work is auto- committed unless programmer explicitly opens another transaction
         //%% see comments in fn connect() re this
@@ -360,7 +354,7 @@ impl Database for PostgreSQLDatabase {
             if case_sensitive {
                 format!("name = '{}'", name_in)
             } else {
-                format!("lower(name) = lower('{})", name_in)
+                format!("lower(name) = lower('{}')", name_in)
             }
         };
         let sql = format!("select id from entity where {}{}", not_archived, case);
@@ -748,6 +742,13 @@ impl Database for PostgreSQLDatabase {
             self.commit_local_trans(local_tx_option)?;
             return Ok(id);
         };
+        self.check_attribute_against_class_rules(
+            transaction_in.clone(),
+            parent_id_in,
+            attr_type_id_in,
+            &RuleValue::Quantity { unit_id: unit_id_in, number: number_in },
+            None,
+        )?;
         let id: i64 = self.get_new_key(transaction_in.clone(), "QuantityAttributeKeySequence")?;
         let form_id = self.get_attribute_form_id(Util::QUANTITY_TYPE)?;
         self.add_attribute_sorting_row(
@@ -781,6 +782,13 @@ impl Database for PostgreSQLDatabase {
         valid_on_date_in: Option<i64>,
         observation_date_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_attribute_against_class_rules(
+            transaction.clone(),
+            parent_id_in,
+            attr_type_id_in,
+            &RuleValue::Quantity { unit_id: unit_id_in, number: number_in },
+            Some(id_in),
+        )?;
         // NOTE: IF ADDING COLUMNS TO WHAT IS UPDATED, SIMILARLY UPDATE caller's update method! (else some fields don't get updated
         // in memory when the db updates, and the behavior gets weird.
         let valid_on = match valid_on_date_in {
@@ -803,6 +811,13 @@ impl Database for PostgreSQLDatabase {
         valid_on_date_in: Option<i64>,
         observation_date_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_attribute_against_class_rules(
+            transaction.clone(),
+            parent_id_in,
+            attr_type_id_in,
+            &RuleValue::Text(text_in.to_string()),
+            Some(id_in),
+        )?;
         let text: String = Self::escape_quotes_etc(text_in.to_string());
         let valid_on = match valid_on_date_in {
             None => "NULL".to_string(),
@@ -824,6 +839,13 @@ impl Database for PostgreSQLDatabase {
         date_in: i64,
         attr_type_id_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_attribute_against_class_rules(
+            transaction.clone(),
+            parent_id_in,
+            attr_type_id_in,
+            &RuleValue::Date(date_in),
+            Some(id_in),
+        )?;
         // NOTE: IF ADDING COLUMNS TO WHAT IS UPDATED, SIMILARLY UPDATE caller's update method! (else some fields don't get updated
         // in memory when the db updates, and the behavior gets weird.
         self.db_action(
@@ -848,6 +870,13 @@ impl Database for PostgreSQLDatabase {
         valid_on_date_in: Option<i64>,
         observation_date_in: i64,
     ) -> Result<(), anyhow::Error> {
+        self.check_attribute_against_class_rules(
+            transaction.clone(),
+            parent_id_in,
+            attr_type_id_in,
+            &RuleValue::Boolean(boolean_in),
+            Some(id_in),
+        )?;
         // NOTE: IF ADDING COLUMNS TO WHAT IS UPDATED, SIMILARLY UPDATE caller's update method! (else some fields don't get updated
         // in memory when the db updates, and the behavior gets weird.
         let if_valid_on_date = match valid_on_date_in {
@@ -1060,6 +1089,17 @@ impl Database for PostgreSQLDatabase {
             return Ok(id);
         };
 
+        if let Some(new_class_id) = class_id {
+            let problems = self.check_entity_against_rules_of_class(transaction_in.clone(), entity_id, new_class_id)?;
+            if !problems.is_empty() {
+                return Err(anyhow!(
+                    "\"{}\" can't be put in the class \"{}\" until it meets that class' rules: {}",
+                    self.get_entity_name(transaction_in.clone(), entity_id)?.unwrap_or_default(),
+                    self.get_class_name(transaction_in.clone(), new_class_id)?.unwrap_or_default(),
+                    problems.join("  ")
+                ));
+            }
+        }
         let ci = match class_id {
             None => "NULL".to_string(),
             Some(x) => format!("{}", x),
@@ -1201,6 +1241,13 @@ impl Database for PostgreSQLDatabase {
             return Ok(id);
         };
 
+        self.check_attribute_against_class_rules(
+            transaction_in.clone(),
+            parent_id_in,
+            attr_type_id_in,
+            &RuleValue::Text(text_in.to_string()),
+            None,
+        )?;
         let text: String = Self::escape_quotes_etc(text_in.to_string());
         let id: i64 = self.get_new_key(transaction_in.clone(), "TextAttributeKeySequence")?;
         let add_result = self.add_attribute_sorting_row(
@@ -1271,6 +1318,13 @@ impl Database for PostgreSQLDatabase {
             return Ok(id);
         };
 
+        self.check_attribute_against_class_rules(
+            transaction_in.clone(),
+            parent_id_in,
+            attr_type_id_in,
+            &RuleValue::Date(date_in),
+            None,
+        )?;
         let id: i64 = self.get_new_key(transaction_in.clone(), "DateAttributeKeySequence")?;
         self.add_attribute_sorting_row(
             transaction_in.clone(),
@@ -1323,6 +1377,13 @@ impl Database for PostgreSQLDatabase {
             return Ok(id);
         };
 
+        self.check_attribute_against_class_rules(
+            transaction_in.clone(),
+            parent_id_in,
+            attr_type_id_in,
+            &RuleValue::Boolean(boolean_in),
+            None,
+        )?;
         let id: i64 = self.get_new_key(transaction_in.clone(), "BooleanAttributeKeySequence")?;
         // try {
         self.add_attribute_sorting_row(
@@ -1456,6 +1517,12 @@ impl Database for PostgreSQLDatabase {
             return Ok(id);
         };
 
+        self.check_relation_against_class_constraints(
+            transaction_in.clone(),
+            relation_type_id_in,
+            entity_id1_in,
+            entity_id2_in,
+        )?;
         debug!("in create_relation_to_local_entity 1");
         let rte_id: i64 =
             self.get_new_key(transaction_in.clone(), "RelationToEntityKeySequence")?;
@@ -1567,6 +1634,12 @@ impl Database for PostgreSQLDatabase {
     ) -> Result<u64, anyhow::Error> {
         // NOTE: IF ADDING COLUMNS TO WHAT IS UPDATED, SIMILARLY UPDATE caller's update method! (else some fields don't get updated
         // in memory when the db updates, and the behavior gets weird.
+        self.check_relation_against_class_constraints(
+            transaction.clone(),
+            new_relation_type_id_in,
+            entity_id1_in,
+            entity_id2_in,
+        )?;
         let valid = match valid_on_date_in {
             None => "NULL".to_string(),
             Some(v) => format!("{}", v),
@@ -1642,6 +1715,9 @@ impl Database for PostgreSQLDatabase {
             }
         };
         let observed_date = get_i64_from_row(&rte_data, 6)?;
+        // (So the relation's own attributes, if any, are not deleted with it.)
+        let form_id = self.get_attribute_form_id(Util::RELATION_TO_LOCAL_ENTITY_TYPE)?;
+        let holder_id = self.detach_relation_attribute_holder(transaction.clone(), form_id, rtle_id_in)?;
         self.delete_relation_to_local_entity(
             transaction.clone(),
             old_rte_rel_type,
@@ -1657,6 +1733,9 @@ impl Database for PostgreSQLDatabase {
             observed_date,
             Some(sorting_index_in),
         )?;
+        if let Some(holder_id) = holder_id {
+            self.attach_relation_attribute_holder(transaction.clone(), form_id, new_rte_id, holder_id)?;
+        }
         //Something like the next line might have been more efficient than the above code to run, but not to write, given that it adds a complexity about updating
         //the attributesorting table, which might be more tricky in future when something is added to prevent those from being orphaned. The above avoids that or
         //centralizes the question to one place in the code.
@@ -1713,6 +1792,10 @@ impl Database for PostgreSQLDatabase {
             }
         };
         let observed_date = get_i64_from_row(&rte_data, 6)?;
+        // (See the similar lines in move_relation_to_local_entity_into_local_entity.)
+        let form_id = self.get_attribute_form_id(Util::RELATION_TO_REMOTE_ENTITY_TYPE)?;
+        let holder_id =
+            self.detach_relation_attribute_holder(transaction.clone(), form_id, relation_to_remote_entity_id_in)?;
         self.delete_relation_to_remote_entity(
             transaction.clone(),
             old_rte_rel_type,
@@ -1730,6 +1813,24 @@ impl Database for PostgreSQLDatabase {
             remote_instance_id_in,
             Some(sorting_index_in),
         )?;
+        if let Some(holder_id) = holder_id {
+            // (RelationToRemoteEntity doesn't carry its id yet, so look it up.)
+            let rows = self.db_query(
+                transaction.clone(),
+                format!(
+                    "select id from RelationToRemoteEntity where rel_type_id={} and entity_id={} \
+                    and remote_instance_id='{}' and entity_id_2={}",
+                    old_rte_rel_type, to_containing_entity_id_in, remote_instance_id_in, old_rte_entity_2
+                )
+                .as_str(),
+                "i64",
+            )?;
+            let Some(row) = rows.first() else {
+                return Err(anyhow!("Didn't find the relation to remote entity just created."));
+            };
+            let new_rte_id = get_i64_from_row(row, 0)?;
+            self.attach_relation_attribute_holder(transaction.clone(), form_id, new_rte_id, holder_id)?;
+        }
         // see comments at similar location in delete_objects about local_tx
         // see comments in delete_objects about rollback
         let local_tx_cell: Option<RefCell<Transaction<Postgres>>> =
@@ -2021,6 +2122,9 @@ impl Database for PostgreSQLDatabase {
             }
         };
         let observed_date = get_i64_from_row(&rtg_data, 6)?;
+        // (See the similar lines in move_relation_to_local_entity_into_local_entity.)
+        let form_id = self.get_attribute_form_id(Util::RELATION_TO_GROUP_TYPE)?;
+        let holder_id = self.detach_relation_attribute_holder(transaction.clone(), form_id, relation_to_group_id_in)?;
 
         self.delete_relation_to_group(
             transaction.clone(),
@@ -2037,6 +2141,9 @@ impl Database for PostgreSQLDatabase {
             observed_date,
             Some(sorting_index_in),
         )?;
+        if let Some(holder_id) = holder_id {
+            self.attach_relation_attribute_holder(transaction.clone(), form_id, new_rtg_id, holder_id)?;
+        }
 
         // (see comment at similar commented line in move_relation_to_local_entity_into_local_entity)
         //db_action("UPDATE RelationToGroup SET (entity_id) = ROW(" + new_containing_entity_id_in + ")" + " where id=" + relation_to_group_id_in)
@@ -2265,6 +2372,7 @@ impl Database for PostgreSQLDatabase {
             return Ok(id);
         };
 
+        self.check_group_membership_is_editable(transaction_in.clone(), group_id_in)?;
         // start from the beginning index, if it's the 1st record (otherwise later sorting/renumbering gets messed up if we start w/ the last #):
         let sorting_index: i64 = {
             let index = match sorting_index_in {
@@ -2506,6 +2614,7 @@ impl Database for PostgreSQLDatabase {
         transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
         id_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_attribute_removal_against_class_rules(transaction.clone(), Util::QUANTITY_TYPE, id_in)?;
         self.delete_object_by_id(transaction, Util::QUANTITY_TYPE, id_in)
     }
 
@@ -2514,6 +2623,7 @@ impl Database for PostgreSQLDatabase {
         transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
         id_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_attribute_removal_against_class_rules(transaction.clone(), Util::TEXT_TYPE, id_in)?;
         self.delete_object_by_id(transaction, Util::TEXT_TYPE, id_in)
     }
 
@@ -2522,6 +2632,7 @@ impl Database for PostgreSQLDatabase {
         transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
         id_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_attribute_removal_against_class_rules(transaction.clone(), Util::DATE_TYPE, id_in)?;
         self.delete_object_by_id(transaction, Util::DATE_TYPE, id_in)
     }
 
@@ -2530,6 +2641,7 @@ impl Database for PostgreSQLDatabase {
         transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
         id_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_attribute_removal_against_class_rules(transaction.clone(), Util::BOOLEAN_TYPE, id_in)?;
         self.delete_object_by_id(transaction, Util::BOOLEAN_TYPE, id_in)
     }
 
@@ -2641,6 +2753,7 @@ impl Database for PostgreSQLDatabase {
         group_id_in: i64,
         contained_entity_id_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_group_membership_is_editable(transaction.clone(), group_id_in)?;
         self.delete_objects(
             transaction,
             "EntitiesInAGroup",
@@ -2674,6 +2787,9 @@ impl Database for PostgreSQLDatabase {
             return Ok(id);
         };
 
+        // (For a saved search, this would delete the entities that its query happens to match,
+        // which is surely not intended: the group can be deleted by itself instead.)
+        self.check_group_membership_is_editable(transaction_in.clone(), group_id_in)?;
         let entity_count = self.get_group_size(transaction_in.clone(), group_id_in, 3)?;
         let (deletions1, deletions2) =
             self.delete_relation_to_group_and_all_recursively(transaction_in.clone(), group_id_in)?;
@@ -3432,15 +3548,22 @@ impl Database for PostgreSQLDatabase {
                 ))
             }
         };
-        let count = self.extract_row_count_from_count_query(
-            transaction,
-            format!(
+        let sql = match self.get_smart_group_query(transaction.clone(), group_id_in)? {
+            Some(query) => format!(
+                "select count(1) from entity e where {}",
+                self.entity_query_where_clause(
+                    transaction.clone(),
+                    &query,
+                    Some(archived_sql_condition)
+                )?
+            ),
+            None => format!(
                 "select count(1) from entity e, EntitiesInAGroup \
                 eiag where e.id=eiag.entity_id and {} and eiag.group_id={}",
                 archived_sql_condition, group_id_in
-            )
-            .as_str(),
-        )?;
+            ),
+        };
+        let count = self.extract_row_count_from_count_query(transaction, sql.as_str())?;
         Ok(count)
     }
     /// For all groups to which the parameter belongs, returns a collection of the *containing* RelationToGroups, in the form of "entity_name -> group_name"'s.
@@ -3843,6 +3966,7 @@ impl Database for PostgreSQLDatabase {
         entity_id_in: i64,
         sorting_index_in: i64,
     ) -> Result<u64, anyhow::Error> {
+        self.check_group_membership_is_editable(transaction.clone(), group_id_in)?;
         self.db_action(transaction,
                        format!("update EntitiesInAGroup set (sorting_index) = ROW({}) where group_id={} and entity_id={}",
                                sorting_index_in, group_id_in, entity_id_in).as_str(),
@@ -4761,13 +4885,14 @@ impl Database for PostgreSQLDatabase {
         max_vals_in: Option<u64>, // = None
     ) -> Result<Vec<EntityClass>, anyhow::Error> {
         let sql: String = format!(
-            "SELECT id, name, defining_entity_id, create_default_attributes from class order by id limit {} offset {}",
+            "SELECT id, name, defining_entity_id, create_default_attributes, parent_class_id from class order by id \
+            limit {} offset {}",
             Self::check_if_should_be_all_results(max_vals_in),
             starting_object_index_in
         );
 
         let early_results: Vec<Vec<Option<DataType>>> =
-            self.db_query(transaction.clone(), &sql, "i64,String,i64,bool")?;
+            self.db_query(transaction.clone(), &sql, "i64,String,i64,bool,i64")?;
         let mut final_results: Vec<EntityClass> = Vec::new();
         // idea: should the remainder of this method be moved to EntityClass, so the
         // persistence layer doesn't know anything about the Model? (helps avoid circular
@@ -4780,12 +4905,14 @@ impl Database for PostgreSQLDatabase {
             let defining_entity_id = Util::get_value_bigint("result[2]", &result[2])?;
             let create_default_attributes: Option<bool> =
                 Util::get_value_bool_option("result[3]", &result[3])?;
+            let parent_class_id: Option<i64> = Util::get_value_bigint_option("result[4]", &result[4])?;
             let entity_class = EntityClass::new(
                 db.clone(), //Rc::new(self as dyn Database),
                 id,
                 &name,
                 defining_entity_id,
                 create_default_attributes,
+                parent_class_id,
             );
             final_results.push(entity_class);
         }
@@ -4814,9 +4941,18 @@ impl Database for PostgreSQLDatabase {
         } else {
             ""
         };
-        let sql = format!("select eiag.entity_id, eiag.sorting_index from entity e, entitiesinagroup eiag where e.id=eiag.entity_id \
+        let sql = match self.get_smart_group_query(transaction.clone(), group_id_in)? {
+            // (For a saved search, the entity id stands in for the sorting_index.)
+            Some(query) => {
+                let archived_condition = if archived.is_empty() { "true" } else { "(not e.archived)" };
+                format!("select e.id, e.id from entity e where {} order by e.id limit {}",
+                        self.entity_query_where_clause(transaction.clone(), &query, Some(archived_condition))?,
+                        Self::check_if_should_be_all_results(limit_in))
+            }
+            None => format!("select eiag.entity_id, eiag.sorting_index from entity e, entitiesinagroup eiag where e.id=eiag.entity_id \
                                     and eiag.group_id={}{} order by eiag.sorting_index, eiag.entity_id limit {}",
-                          group_id_in, archived, Self::check_if_should_be_all_results(limit_in));
+                          group_id_in, archived, Self::check_if_should_be_all_results(limit_in)),
+        };
         self.db_query(
             transaction,
             sql.as_str(),
@@ -5010,9 +5146,14 @@ impl Database for PostgreSQLDatabase {
             ""
         };
         // see comments in get_group_entries_data
-        let sql = format!("select entity_id, sorting_index from entity e, EntitiesInAGroup eiag where e.id=eiag.entity_id\
+        let sql = match self.get_smart_group_query(transaction.clone(), group_id_in)? {
+            Some(query) => format!("select e.id, e.id from entity e where {} order by e.id limit {} offset {}",
+                                   self.entity_query_where_clause(transaction.clone(), &query, None)?,
+                                   Self::check_if_should_be_all_results(max_vals_in), starting_object_index_in),
+            None => format!("select entity_id, sorting_index from entity e, EntitiesInAGroup eiag where e.id=eiag.entity_id\
                                     {} and eiag.group_id={} order by eiag.sorting_index, eiag.entity_id limit {} offset {}",
-                          not_archived, group_id_in, Self::check_if_should_be_all_results(max_vals_in), starting_object_index_in);
+                          not_archived, group_id_in, Self::check_if_should_be_all_results(max_vals_in), starting_object_index_in),
+        };
         let early_results = self.db_query(transaction.clone(), sql.as_str(), "i64,i64")?;
         let early_results_len = early_results.len();
         let mut final_results: Vec<i64> = Vec::new();
@@ -5089,7 +5230,7 @@ impl Database for PostgreSQLDatabase {
         self.db_query_wrapper_for_one_row(
             transaction,
             format!(
-                "SELECT name, defining_entity_id, create_default_attributes from class where id={}",
+                "SELECT name, defining_entity_id, create_default_attributes, parent_class_id from class where id={}",
                 id_in
             )
             .as_str(),
@@ -5270,6 +5411,20 @@ impl Database for PostgreSQLDatabase {
         //          }
     }
 
+    fn find_class_and_template_entity(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_name_in: &str,
+    ) -> Result<Option<(i64, i64)>, anyhow::Error> {
+        match self.find_first_class_id_by_name(transaction.clone(), class_name_in, true)? {
+            Some(class_id) => {
+                let template_entity_id = EntityClass::get_template_entity_id_2(self, transaction, class_id)?;
+                Ok(Some((class_id, template_entity_id)))
+            }
+            None => Ok(None),
+        }
+    }
+
     fn set_include_archived_entities(&mut self, iae_in: bool) {
         self.include_archived_entities = iae_in;
     }
@@ -5519,6 +5674,27 @@ impl Database for PostgreSQLDatabase {
         starting_object_index_in: usize, // = 0
         max_vals_in: usize,              // = 0
         only_public_entities_in: bool,   // = true
+    ) -> Result<(Vec<(i64, Rc<RefCell<dyn Attribute>>)>, usize), anyhow::Error> {
+        self.get_sorted_attributes_as_of(
+            db,
+            transaction,
+            entity_id_in,
+            None,
+            starting_object_index_in,
+            max_vals_in,
+            only_public_entities_in,
+        )
+    }
+
+    fn get_sorted_attributes_as_of(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        as_of_in: Option<AsOfDate>,
+        starting_object_index_in: usize, // = 0
+        max_vals_in: usize,              // = 0
+        only_public_entities_in: bool,   // = true
     ) -> Result<(Vec<(i64, Rc<RefCell<dyn Attribute>>)>, usize), anyhow::Error> {
         let mut all_results: Vec<(Option<i64>, Rc<RefCell<dyn Attribute>>)> = Vec::new();
         // First select the counts from each table, keep a running total so we know when to select
@@ -5647,6 +5823,9 @@ impl Database for PostgreSQLDatabase {
                 if !include_archived_entities { "(not entity.archived) and " } else { "" },
                 where_clauses_by_table[table_list_index]
             );
+            if let Some(as_of) = as_of_in {
+                sql.push_str(&Self::as_of_condition_sql(table_name, as_of));
+            }
             if table_name == Util::RELATION_TO_LOCAL_ENTITY_TYPE && !include_archived_entities {
                 sql.push_str(" and not exists(select 1 from entity e2, relationtoentity rte2 where 
                     e2.id=rte2.entity_id_2 and relationtoentity.entity_id_2=rte2.entity_id_2 and e2.archived)");
@@ -5685,7 +5864,7 @@ impl Database for PostgreSQLDatabase {
                             db.clone(),
                             Util::get_value_bigint("result[1]", &result[1])?,
                             Util::get_value_bigint("result[2]", &result[2])?,
-                            Util::get_value_bigint("result[3]", &result[1])?,
+                            Util::get_value_bigint("result[3]", &result[3])?,
                             Util::get_value_bigint("result[4]", &result[4])?,
                             Util::get_value_float("result[5]", &result[5])?,
                             Util::get_value_bigint_option("result[6]", &result[6])?,
@@ -5871,4 +6050,867 @@ impl Database for PostgreSQLDatabase {
             return_attrs.len(), starting_object_index_in, max_vals_in, until);
         Ok((return_attrs, all_results_array.len()))
     }
+
+    fn get_entities_matching_query(
+        &self,
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        query_in: &EntityQuery,
+        starting_object_index_in: u64,
+        max_vals_in: Option<u64>, /*= None*/
+    ) -> Result<Vec<Entity>, anyhow::Error> {
+        let sql = format!(
+            "{} from entity e where {} ORDER BY e.id limit {} offset {}",
+            Util::SELECT_ENTITY_START,
+            self.entity_query_where_clause(transaction.clone(), query_in, None)?,
+            Self::check_if_should_be_all_results(max_vals_in),
+            starting_object_index_in
+        );
+        let early_results = self.db_query(
+            transaction,
+            sql.as_str(),
+            "i64,String,i64,i64,bool,bool,bool",
+        )?;
+        let mut final_results: Vec<Entity> = Vec::new();
+        for result in early_results {
+            self.add_new_entity_to_results(db.clone(), &mut final_results, &result)?;
+        }
+        Ok(final_results)
+    }
+
+    fn create_smart_group(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        name_in: &str,
+        query_in: &str,
+    ) -> Result<i64, anyhow::Error> {
+        // Fail now, rather than every time the group is displayed, if the query is not usable.
+        EntityQuery::parse(query_in)?;
+        let group_id: i64 = self.get_new_key(transaction.clone(), "RelationToGroupKeySequence")?;
+        self.db_action(
+            transaction,
+            format!(
+                "INSERT INTO grupo (id, name, insertion_date, allow_mixed_classes, query) \
+                         VALUES ({}, '{}', {}, TRUE, '{}')",
+                group_id,
+                Self::escape_quotes_etc(name_in.to_string()),
+                Utc::now().timestamp_millis(),
+                Self::escape_quotes_etc(query_in.to_string())
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        Ok(group_id)
+    }
+
+    fn get_group_query(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        group_id_in: i64,
+    ) -> Result<Option<String>, anyhow::Error> {
+        // (No row, i.e. a nonexistent group, is treated like an ordinary group, since callers
+        // such as get_group_size have always allowed that.)
+        let rows = self.db_query(
+            transaction,
+            format!("select query from grupo where id={}", group_id_in).as_str(),
+            "String",
+        )?;
+        match rows.first().and_then(|row| row.first()) {
+            None | Some(None) => Ok(None),
+            Some(Some(DataType::String(q))) => Ok(Some(q.clone())),
+            Some(x) => Err(anyhow!(
+                "In get_group_query, unexpected result for group {}: {:?}",
+                group_id_in,
+                x
+            )),
+        }
+    }
+
+    fn update_group_query(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        group_id_in: i64,
+        query_in: &str,
+    ) -> Result<u64, anyhow::Error> {
+        EntityQuery::parse(query_in)?;
+        if self.get_group_query(transaction.clone(), group_id_in)?.is_none() {
+            return Err(anyhow!(
+                "Group {} is not a saved search, so it has no query to update.",
+                group_id_in
+            ));
+        }
+        self.db_action(
+            transaction,
+            format!(
+                "update grupo set (query) = ROW('{}') where id={}",
+                Self::escape_quotes_etc(query_in.to_string()),
+                group_id_in
+            )
+            .as_str(),
+            false,
+            false,
+        )
+    }
+
+    fn get_quantity_series_data(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        by_valid_date_in: bool,
+    ) -> Result<Vec<Vec<Option<DataType>>>, anyhow::Error> {
+        // (A valid_on_date of 0 means "all time", which is no use as a point in a series.)
+        let date_column = if by_valid_date_in {
+            "coalesce(nullif(valid_on_date, 0), observation_date)"
+        } else {
+            "observation_date"
+        };
+        self.db_query(
+            transaction,
+            format!(
+                "select attr_type_id, unit_id, {}, quantity_number from QuantityAttribute \
+                where entity_id={} order by attr_type_id, unit_id, 3, id",
+                date_column, entity_id_in
+            )
+            .as_str(),
+            "i64,i64,i64,Float",
+        )
+    }
+
+    fn set_unit_conversion(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        from_unit_id_in: i64,
+        to_unit_id_in: i64,
+        factor_in: f64,
+    ) -> Result<(), anyhow::Error> {
+        if from_unit_id_in == to_unit_id_in {
+            return Err(anyhow!("A unit cannot be converted to itself ({}).", from_unit_id_in));
+        }
+        if factor_in == 0.0 || !factor_in.is_finite() {
+            return Err(anyhow!("Invalid conversion factor: {}", factor_in));
+        }
+        self.delete_unit_conversion(transaction.clone(), from_unit_id_in, to_unit_id_in)?;
+        *self.unit_converter.borrow_mut() = None;
+        self.db_action(
+            transaction,
+            format!(
+                "insert into UnitConversion (from_unit_id, to_unit_id, factor) values ({},{},{})",
+                from_unit_id_in, to_unit_id_in, factor_in
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        Ok(())
+    }
+
+    fn delete_unit_conversion(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        unit_id1_in: i64,
+        unit_id2_in: i64,
+    ) -> Result<u64, anyhow::Error> {
+        *self.unit_converter.borrow_mut() = None;
+        self.db_action(
+            transaction,
+            format!(
+                "delete from UnitConversion where (from_unit_id={} and to_unit_id={}) \
+                or (from_unit_id={} and to_unit_id={})",
+                unit_id1_in, unit_id2_in, unit_id2_in, unit_id1_in
+            )
+            .as_str(),
+            true,
+            false,
+        )
+    }
+
+    fn get_unit_conversions(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<Vec<(i64, i64, f64)>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            "select from_unit_id, to_unit_id, factor from UnitConversion order by from_unit_id, to_unit_id",
+            "i64,i64,Float",
+        )?;
+        let mut results: Vec<(i64, i64, f64)> = Vec::new();
+        for row in rows {
+            results.push((
+                Util::get_value_bigint("from_unit_id", &row[0])?,
+                Util::get_value_bigint("to_unit_id", &row[1])?,
+                Util::get_value_float("factor", &row[2])?,
+            ));
+        }
+        Ok(results)
+    }
+
+    fn get_unit_converter(&self) -> Result<Rc<UnitConverter>, anyhow::Error> {
+        if let Some(converter) = self.unit_converter.borrow().as_ref() {
+            return Ok(converter.clone());
+        }
+        let converter = Rc::new(UnitConverter::new(&self.get_unit_conversions(None)?));
+        *self.unit_converter.borrow_mut() = Some(converter.clone());
+        Ok(converter)
+    }
+
+    fn set_canonical_unit(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+        unit_id_in: Option<i64>,
+    ) -> Result<(), anyhow::Error> {
+        self.db_action(
+            transaction.clone(),
+            format!("delete from CanonicalUnit where attr_type_id={}", attr_type_id_in).as_str(),
+            true,
+            false,
+        )?;
+        if let Some(unit_id) = unit_id_in {
+            self.db_action(
+                transaction,
+                format!(
+                    "insert into CanonicalUnit (attr_type_id, unit_id) values ({},{})",
+                    attr_type_id_in, unit_id
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn get_canonical_unit(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+    ) -> Result<Option<i64>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!("select unit_id from CanonicalUnit where attr_type_id={}", attr_type_id_in).as_str(),
+            "i64",
+        )?;
+        match rows.first() {
+            None => Ok(None),
+            Some(row) => Ok(Some(Util::get_value_bigint("unit_id", &row[0])?)),
+        }
+    }
+
+    fn get_latest_quantities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+        entity_ids_in: &[i64],
+    ) -> Result<Vec<(i64, i64, f64)>, anyhow::Error> {
+        if entity_ids_in.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids: Vec<String> = entity_ids_in.iter().map(|id| id.to_string()).collect();
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select distinct on (entity_id) entity_id, unit_id, quantity_number from QuantityAttribute \
+                where attr_type_id={} and entity_id in ({}) order by entity_id, observation_date desc, id desc",
+                attr_type_id_in,
+                ids.join(",")
+            )
+            .as_str(),
+            "i64,i64,Float",
+        )?;
+        let mut results: Vec<(i64, i64, f64)> = Vec::new();
+        for row in rows {
+            results.push((
+                Util::get_value_bigint("entity_id", &row[0])?,
+                Util::get_value_bigint("unit_id", &row[1])?,
+                Util::get_value_float("quantity_number", &row[2])?,
+            ));
+        }
+        Ok(results)
+    }
+
+    fn create_uri_entity(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        new_entity_name_in: &str,
+        uri_in: &str,
+        observation_date_in: i64,
+        make_them_public_in: Option<bool>,
+        quote_in: Option<&str>,
+    ) -> Result<i64, anyhow::Error> {
+        if let Some("") = quote_in {
+            return Err(anyhow!("It doesn't make sense to store a blank quotation; there was probably a program error."));
+        }
+        let new_entity_id =
+            self.create_entity(transaction.clone(), new_entity_name_in, None, make_them_public_in)?;
+        self.add_uri_class_and_attributes(
+            transaction,
+            new_entity_id,
+            uri_in,
+            observation_date_in,
+            quote_in,
+        )?;
+        Ok(new_entity_id)
+    }
+
+    fn find_uri_entity_ids(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        uri_in: &str,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        let not_archived = if !self.include_archived_entities {
+            "(not e.archived) and "
+        } else {
+            ""
+        };
+        let sql = format!(
+            "select distinct e.id from Entity e, Class c, TextAttribute ta where {}c.name='URI' \
+            and e.class_id=c.id and ta.entity_id=e.id and ta.attr_type_id=c.defining_entity_id \
+            and ta.textvalue='{}' order by e.id",
+            not_archived,
+            Self::escape_quotes_etc(uri_in.to_string())
+        );
+        let rows = self.db_query(transaction, sql.as_str(), "i64")?;
+        get_i64s_from_rows(&rows)
+    }
+
+    fn export_exchange_data(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        root_entity_ids_in: Option<&[i64]>,
+    ) -> Result<ExchangeData, anyhow::Error> {
+        self.export_exchange(transaction, root_entity_ids_in)
+    }
+
+    fn import_exchange_data(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        data_in: &ExchangeData,
+        containing_entity_id_in: Option<i64>,
+        observation_date_in: i64,
+    ) -> Result<ExchangeImportCounts, anyhow::Error> {
+        self.import_exchange(transaction, data_in, containing_entity_id_in, observation_date_in)
+    }
+
+    fn create_action(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+        name_in: &str,
+        action_in: &str,
+    ) -> Result<i64, anyhow::Error> {
+        let name = Self::escape_quotes_etc(name_in.trim().to_string());
+        if name.is_empty() {
+            return Err(anyhow!("An action's name must have a value."));
+        }
+        let id: i64 = self.get_new_key(transaction.clone(), "ActionKeySequence")?;
+        self.db_action(
+            transaction,
+            format!(
+                "insert into Action (id, class_id, name, action) values ({},{},'{}','{}')",
+                id,
+                class_id_in,
+                name,
+                Self::escape_quotes_etc(action_in.to_string())
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        Ok(id)
+    }
+
+    fn update_action(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        id_in: i64,
+        name_in: &str,
+        action_in: &str,
+    ) -> Result<u64, anyhow::Error> {
+        let name = Self::escape_quotes_etc(name_in.trim().to_string());
+        if name.is_empty() {
+            return Err(anyhow!("An action's name must have a value."));
+        }
+        self.db_action(
+            transaction,
+            format!(
+                "update Action set (name, action) = ROW('{}','{}') where id={}",
+                name,
+                Self::escape_quotes_etc(action_in.to_string()),
+                id_in
+            )
+            .as_str(),
+            false,
+            false,
+        )
+    }
+
+    fn delete_action(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        id_in: i64,
+    ) -> Result<u64, anyhow::Error> {
+        self.delete_object_by_id(transaction, "Action", id_in)
+    }
+
+    fn get_actions(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<(i64, String, String)>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select id, name, action from Action where class_id={} order by name, id",
+                class_id_in
+            )
+            .as_str(),
+            "i64,String,String",
+        )?;
+        let mut results: Vec<(i64, String, String)> = Vec::new();
+        for row in rows {
+            results.push((
+                Util::get_value_bigint("id", &row[0])?,
+                Util::get_value_string("name", &row[1])?,
+                Util::get_value_string("action", &row[2])?,
+            ));
+        }
+        Ok(results)
+    }
+
+    fn set_class_attribute_rule(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+        rule_in: &AttributeRule,
+    ) -> Result<(), anyhow::Error> {
+        if !class_schema::FORMS.contains(&rule_in.form.as_str()) {
+            return Err(anyhow!("A class's rule can't require the form {}.", rule_in.form));
+        }
+        if rule_in.min_count < 0 || rule_in.max_count.is_some_and(|max| max < 1 || max < rule_in.min_count) {
+            return Err(anyhow!(
+                "A rule's count must be from a minimum of 0 or more, to a maximum of at least the minimum and 1."
+            ));
+        }
+        if let (Some(min), Some(max)) = (rule_in.min_value, rule_in.max_value) {
+            if max < min {
+                return Err(anyhow!("A rule's maximum value can't be less than its minimum."));
+            }
+        }
+        let to_sql = |x: Option<String>| x.unwrap_or_else(|| "NULL".to_string());
+        self.delete_class_attribute_rule(transaction.clone(), class_id_in, rule_in.attr_type_id)?;
+        self.db_action(
+            transaction.clone(),
+            format!(
+                "insert into ClassAttributeRule (class_id, attr_type_id, form_id, min_count, max_count, \
+                min_value, max_value) values ({},{},{},{},{},{},{})",
+                class_id_in,
+                rule_in.attr_type_id,
+                self.get_attribute_form_id(&rule_in.form)?,
+                rule_in.min_count,
+                to_sql(rule_in.max_count.map(|x| x.to_string())),
+                to_sql(rule_in.min_value.map(|x| x.to_string())),
+                to_sql(rule_in.max_value.map(|x| x.to_string()))
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        let mut unit_ids: Vec<i64> = rule_in.allowed_unit_ids.clone();
+        unit_ids.sort();
+        unit_ids.dedup();
+        for unit_id in unit_ids {
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into ClassAttributeRuleUnit (class_id, attr_type_id, unit_id) values ({},{},{})",
+                    class_id_in, rule_in.attr_type_id, unit_id
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn delete_class_attribute_rule(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+        attr_type_id_in: i64,
+    ) -> Result<u64, anyhow::Error> {
+        self.db_action(
+            transaction,
+            format!(
+                "delete from ClassAttributeRule where class_id={} and attr_type_id={}",
+                class_id_in, attr_type_id_in
+            )
+            .as_str(),
+            true,
+            false,
+        )
+    }
+
+    fn get_class_attribute_rules(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<AttributeRule>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select r.attr_type_id, r.form_id, r.min_count, r.max_count, r.min_value, r.max_value \
+                from ClassAttributeRule r, Entity e where r.class_id={} and e.id=r.attr_type_id \
+                order by e.name, r.attr_type_id",
+                class_id_in
+            )
+            .as_str(),
+            "i64,Int,Int,Int,Float,Float",
+        )?;
+        let mut rules: Vec<AttributeRule> = Vec::new();
+        for row in rows {
+            let attr_type_id = Util::get_value_bigint("attr_type_id", &row[0])?;
+            let unit_rows = self.db_query(
+                transaction.clone(),
+                format!(
+                    "select unit_id from ClassAttributeRuleUnit where class_id={} and attr_type_id={} order by unit_id",
+                    class_id_in, attr_type_id
+                )
+                .as_str(),
+                "i64",
+            )?;
+            rules.push(AttributeRule {
+                attr_type_id,
+                form: self
+                    .get_attribute_form_name(Util::get_value_smallint("form_id", &row[1])?)?
+                    .to_string(),
+                min_count: Util::get_value_smallint("min_count", &row[2])?,
+                max_count: match row[3] {
+                    None => None,
+                    _ => Some(Util::get_value_smallint("max_count", &row[3])?),
+                },
+                min_value: match row[4] {
+                    None => None,
+                    _ => Some(Util::get_value_float("min_value", &row[4])?),
+                },
+                max_value: match row[5] {
+                    None => None,
+                    _ => Some(Util::get_value_float("max_value", &row[5])?),
+                },
+                allowed_unit_ids: get_i64s_from_rows(&unit_rows)?,
+            });
+        }
+        Ok(rules)
+    }
+
+    fn get_class_rule_problems(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        self.check_entity_against_class_rules(transaction, entity_id_in)
+    }
+
+    fn validate_class_members(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<(i64, String, Vec<String>)>, anyhow::Error> {
+        self.check_class_members_against_rules(transaction, class_id_in)
+    }
+
+    fn update_class_parent(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+        parent_class_id_in: Option<i64>,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(parent_id) = parent_class_id_in {
+            let lineage = self.get_class_lineage(transaction.clone(), parent_id)?;
+            if lineage.iter().any(|(id, _)| *id == class_id_in) {
+                return Err(anyhow!(
+                    "The class \"{}\" can't be a subclass of \"{}\", which is itself or one of its subclasses.",
+                    self.get_class_name(transaction.clone(), class_id_in)?.unwrap_or_default(),
+                    self.get_class_name(transaction.clone(), parent_id)?.unwrap_or_default()
+                ));
+            }
+        }
+        self.db_action(
+            transaction,
+            format!(
+                "update Class set (parent_class_id) = ROW({}) where id={}",
+                match parent_class_id_in {
+                    Some(id) => id.to_string(),
+                    None => "NULL".to_string(),
+                },
+                class_id_in
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        Ok(())
+    }
+
+    fn get_class_lineage(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        class_id_in: i64,
+    ) -> Result<Vec<(i64, i64)>, anyhow::Error> {
+        // (The depth limit is only a guard: update_class_parent doesn't allow cycles.)
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "with recursive lineage(id, parent_class_id, defining_entity_id, depth) as (\
+                select id, parent_class_id, defining_entity_id, 0 from Class where id={} \
+                union all select c.id, c.parent_class_id, c.defining_entity_id, l.depth + 1 \
+                from Class c, lineage l where c.id=l.parent_class_id and l.depth < 1000) \
+                select id, defining_entity_id from lineage order by depth",
+                class_id_in
+            )
+            .as_str(),
+            "i64,i64",
+        )?;
+        let mut lineage: Vec<(i64, i64)> = Vec::new();
+        for row in rows {
+            lineage.push((
+                Util::get_value_bigint("id", &row[0])?,
+                Util::get_value_bigint("defining_entity_id", &row[1])?,
+            ));
+        }
+        Ok(lineage)
+    }
+
+    fn get_relation_type_semantics(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+    ) -> Result<RelationSemantics, anyhow::Error> {
+        self.read_relation_type_semantics(transaction, rel_type_id_in)
+    }
+
+    fn update_relation_type_semantics(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        semantics_in: &RelationSemantics,
+    ) -> Result<(), anyhow::Error> {
+        if transaction.is_none() {
+            let local_tx: Transaction<Postgres> = self.begin_trans()?;
+            let local_tx_option = Some(Rc::new(RefCell::new(local_tx)));
+            // see comments in delete_objects about rollback (if next line returns due to error)
+            self.write_relation_type_semantics(local_tx_option.clone(), rel_type_id_in, semantics_in)?;
+            return self.commit_local_trans(local_tx_option);
+        }
+        self.write_relation_type_semantics(transaction, rel_type_id_in, semantics_in)
+    }
+
+    fn get_inferred_relations(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<InferredRelation>, anyhow::Error> {
+        self.infer_relations_from(transaction, entity_id_in)
+    }
+
+    fn get_relation_type_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+    ) -> Result<RelationClassConstraints, anyhow::Error> {
+        self.read_relation_class_constraints(transaction, rel_type_id_in)
+    }
+
+    fn set_relation_type_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        constraints_in: &RelationClassConstraints,
+    ) -> Result<(), anyhow::Error> {
+        if transaction.is_none() {
+            let local_tx: Transaction<Postgres> = self.begin_trans()?;
+            let local_tx_option = Some(Rc::new(RefCell::new(local_tx)));
+            // see comments in delete_objects about rollback (if next line returns due to error)
+            self.write_relation_class_constraints(local_tx_option.clone(), rel_type_id_in, constraints_in)?;
+            return self.commit_local_trans(local_tx_option);
+        }
+        self.write_relation_class_constraints(transaction, rel_type_id_in, constraints_in)
+    }
+
+    fn get_relation_class_constraint_problems(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        source_id_in: Option<i64>,
+        target_id_in: Option<i64>,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        self.relation_class_constraint_problems(transaction, rel_type_id_in, source_id_in, target_id_in)
+    }
+
+    fn get_relation_attribute_holder_id(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+    ) -> Result<Option<i64>, anyhow::Error> {
+        self.read_relation_attribute_holder_id(transaction, relation_form_id_in, relation_id_in)
+    }
+
+    fn get_or_create_relation_attribute_holder(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+    ) -> Result<i64, anyhow::Error> {
+        if let Some(holder_id) =
+            self.read_relation_attribute_holder_id(transaction.clone(), relation_form_id_in, relation_id_in)?
+        {
+            return Ok(holder_id);
+        }
+        if transaction.is_none() {
+            let local_tx = self.begin_trans()?;
+            let local_tx_option = Some(Rc::new(RefCell::new(local_tx)));
+            // see comments in delete_objects about rollback
+            let holder_id =
+                self.create_relation_attribute_holder(local_tx_option.clone(), relation_form_id_in, relation_id_in)?;
+            self.commit_local_trans(local_tx_option)?;
+            return Ok(holder_id);
+        }
+        self.create_relation_attribute_holder(transaction, relation_form_id_in, relation_id_in)
+    }
+
+    fn get_relation_attribute_types(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<(String, i64)>, anyhow::Error> {
+        self.read_relation_attribute_types(transaction, entity_id_in)
+    }
+
+    fn sort_relations_by_relation_attribute(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        attribute_form_in: &str,
+        attr_type_id_in: i64,
+        descending_in: bool,
+    ) -> Result<u64, anyhow::Error> {
+        if transaction.is_none() {
+            let local_tx = self.begin_trans()?;
+            let local_tx_option = Some(Rc::new(RefCell::new(local_tx)));
+            // see comments in delete_objects about rollback
+            let count = self.write_relations_sorted_by_relation_attribute(
+                local_tx_option.clone(),
+                entity_id_in,
+                attribute_form_in,
+                attr_type_id_in,
+                descending_in,
+            )?;
+            self.commit_local_trans(local_tx_option)?;
+            return Ok(count);
+        }
+        self.write_relations_sorted_by_relation_attribute(
+            transaction,
+            entity_id_in,
+            attribute_form_in,
+            attr_type_id_in,
+            descending_in,
+        )
+    }
+
+    fn get_backlinks(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Backlinks, anyhow::Error> {
+        self.read_backlinks(transaction, entity_id_in)
+    }
+
+    fn find_paths_between_entities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        from_entity_id_in: i64,
+        to_entity_id_in: i64,
+        options_in: &PathOptions,
+    ) -> Result<Vec<Vec<PathLink>>, anyhow::Error> {
+        self.find_paths(transaction, from_entity_id_in, to_entity_id_in, options_in)
+    }
+
+    fn get_navigation_history(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<NavigationHistory, anyhow::Error> {
+        self.read_navigation_history(transaction)
+    }
+
+    fn save_navigation_history(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        history_in: &NavigationHistory,
+    ) -> Result<(), anyhow::Error> {
+        if transaction.is_none() {
+            let local_tx = self.begin_trans()?;
+            let local_tx_option = Some(Rc::new(RefCell::new(local_tx)));
+            // see comments in delete_objects about rollback
+            self.write_navigation_history(local_tx_option.clone(), history_in)?;
+            return self.commit_local_trans(local_tx_option);
+        }
+        self.write_navigation_history(transaction, history_in)
+    }
+
+    fn get_bookmarked_entity_ids(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        self.read_bookmarked_entity_ids(transaction)
+    }
+
+    fn add_bookmark(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        if transaction.is_none() {
+            let local_tx = self.begin_trans()?;
+            let local_tx_option = Some(Rc::new(RefCell::new(local_tx)));
+            // see comments in delete_objects about rollback
+            self.write_bookmark(local_tx_option.clone(), entity_id_in)?;
+            return self.commit_local_trans(local_tx_option);
+        }
+        self.write_bookmark(transaction, entity_id_in)
+    }
+
+    fn remove_bookmark(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<bool, anyhow::Error> {
+        self.delete_bookmark(transaction, entity_id_in)
+    }
+
+    fn record_entity_view(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        self.write_entity_view(transaction, entity_id_in)
+    }
+
+    fn get_viewed_entities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        by_count_in: bool,
+        max_in: usize,
+    ) -> Result<Vec<(i64, i64)>, anyhow::Error> {
+        self.read_viewed_entities(transaction, by_count_in, max_in)
+    }
+
+    fn get_attribute_type_forms(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        self.read_attribute_type_forms(transaction, attr_type_id_in)
+    }
 }
@@ -49,7 +49,9 @@ use tracing::*;
 mod test {
     use super::*;
     use crate::model::attribute::Attribute;
-    use crate::model::attribute_with_valid_and_observed_dates::AttributeWithValidAndObservedDates;
+    use crate::model::attribute_with_valid_and_observed_dates::{
+        AsOfDate, AttributeWithValidAndObservedDates,
+    };
     //use crate::model::relation_to_group::RelationToGroup;
     use std::any::Any;
 
@@ -2712,6 +2714,115 @@ mod test {
         //assert!(found_fa);
     }
 
+    #[test]
+    fn get_sorted_attributes_as_of_filters_by_valid_and_observed_dates() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> =
+            Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let entity_id = db_b
+            .create_entity(tx.clone(), "as-of test entity", None, None)
+            .unwrap();
+        let weight_type_id = db_b
+            .create_entity(tx.clone(), "as-of test weight", None, None)
+            .unwrap();
+        let unit_id = db_b
+            .create_entity(tx.clone(), "as-of test unit", None, None)
+            .unwrap();
+        let note_type_id = db_b
+            .create_entity(tx.clone(), "as-of test note", None, None)
+            .unwrap();
+        let related_entity_id = db_b
+            .create_entity(tx.clone(), "as-of test related", None, None)
+            .unwrap();
+        let rel_type_id = db_b
+            .create_relation_type(tx.clone(), "as-of test rel", "", RelationType::UNIDIRECTIONAL)
+            .unwrap();
+        let older_weight_id = db_b
+            .create_quantity_attribute(
+                tx.clone(),
+                entity_id,
+                weight_type_id,
+                unit_id,
+                10.0,
+                Some(1000),
+                2000,
+                None,
+            )
+            .unwrap();
+        let newer_weight_id = db_b
+            .create_quantity_attribute(
+                tx.clone(),
+                entity_id,
+                weight_type_id,
+                unit_id,
+                20.0,
+                Some(5000),
+                6000,
+                None,
+            )
+            .unwrap();
+        // (No valid_on_date, so it counts as valid from when it was observed.)
+        let note_id = db_b
+            .create_text_attribute(tx.clone(), entity_id, note_type_id, "a note", None, 3000, None)
+            .unwrap();
+        db_b.create_relation_to_local_entity(
+            tx.clone(),
+            rel_type_id,
+            entity_id,
+            related_entity_id,
+            None,
+            7000,
+            None,
+        )
+        .unwrap();
+        // Date attributes have no valid/observed dates, so are always included.
+        db_b.create_date_attribute(tx.clone(), entity_id, note_type_id, 4000, None)
+            .unwrap();
+
+        let get_as_of = |as_of: Option<AsOfDate>| -> Vec<(String, i64)> {
+            let (attrs, total) = db_b
+                .get_sorted_attributes_as_of(db.clone(), tx.clone(), entity_id, as_of, 0, 0, false)
+                .unwrap();
+            assert_eq!(attrs.len(), total);
+            let mut found: Vec<(String, i64)> = Vec::new();
+            for (_, attr) in attrs {
+                let form_id = attr.borrow().get_form_id().unwrap();
+                let form_name = db_b.get_attribute_form_name(form_id).unwrap().to_string();
+                found.push((form_name, attr.borrow().get_id()));
+            }
+            found.sort();
+            found
+        };
+        let quantity_ids = |found: &Vec<(String, i64)>| -> Vec<i64> {
+            found
+                .iter()
+                .filter(|(form, _)| form == Util::QUANTITY_TYPE)
+                .map(|(_, id)| *id)
+                .collect()
+        };
+
+        assert_eq!(get_as_of(None).len(), 5);
+        assert_eq!(get_as_of(Some(AsOfDate::ValidOn(500))).len(), 1);
+
+        let valid_later = get_as_of(Some(AsOfDate::ValidOn(5500)));
+        assert_eq!(valid_later.len(), 3);
+        assert_eq!(quantity_ids(&valid_later), vec![newer_weight_id]);
+        assert!(valid_later.contains(&(Util::TEXT_TYPE.to_string(), note_id)));
+
+        let observed_early = get_as_of(Some(AsOfDate::ObservedOn(2500)));
+        assert_eq!(observed_early.len(), 2);
+        assert_eq!(quantity_ids(&observed_early), vec![older_weight_id]);
+
+        // Known by 5500 was only the older weight, even though the newer one says it was valid then:
+        let observed_later = get_as_of(Some(AsOfDate::ObservedOn(5500)));
+        assert_eq!(quantity_ids(&observed_later), vec![older_weight_id]);
+        assert_eq!(get_as_of(Some(AsOfDate::ObservedOn(8000))).len(), 4);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+
     fn get_type_info(attr: Box<dyn Any>) -> Result<i32, anyhow::Error> {
         //I *really* hope there is a better (or any successful??) way to do this.
 
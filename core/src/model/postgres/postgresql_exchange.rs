@@ -0,0 +1,1347 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Reading and writing the tables for the exchange format in model/exchange_format.rs.  This
+//! works on the tables directly rather than through the model objects, so that everything is
+//! copied as stored, including what those objects don't expose (like insertion dates and
+//! sorting indexes).
+use crate::model::database::DataType;
+use crate::model::database::Database;
+use crate::model::entity_query::EntityQuery;
+use crate::model::exchange_format::*;
+use crate::model::postgres::postgresql_database::*;
+use crate::model::relation_inference::RelationSemantics;
+use crate::model::relation_type::RelationClassConstraints;
+use crate::util::Util;
+use anyhow::anyhow;
+use chrono::Utc;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::rc::Rc;
+
+fn join_ids<'a>(ids_in: impl IntoIterator<Item = &'a i64>) -> String {
+    ids_in.into_iter().map(|id| id.to_string()).collect::<Vec<String>>().join(",")
+}
+
+/// For putting a possibly-null value into SQL.
+fn to_sql<T: Display>(value_in: Option<T>) -> String {
+    match value_in {
+        Some(value) => value.to_string(),
+        None => "NULL".to_string(),
+    }
+}
+
+fn get_value_string_option(x: &Option<DataType>) -> Option<String> {
+    match x {
+        Some(DataType::String(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+impl PostgreSQLDatabase {
+    /// See Database::export_exchange_data.
+    pub(super) fn export_exchange(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        root_entity_ids_in: Option<&[i64]>,
+    ) -> Result<ExchangeData, anyhow::Error> {
+        // None means everything.
+        let included: Option<BTreeSet<i64>> = match root_entity_ids_in {
+            None => None,
+            Some(roots) => Some(self.find_exchange_subtree(transaction.clone(), roots)?),
+        };
+        let condition = |column: &str, ids: &Option<BTreeSet<i64>>| -> String {
+            match ids {
+                None => "true".to_string(),
+                Some(ids) if ids.is_empty() => "false".to_string(),
+                Some(ids) => format!("{} in ({})", column, join_ids(ids)),
+            }
+        };
+        let attributes = self.read_exchange_attributes(transaction.clone(), &condition("x.entity_id", &included))?;
+
+        // What the attributes refer to, so it can be included too (some as references only):
+        let mut referenced: BTreeSet<i64> = BTreeSet::new();
+        let mut relation_type_ids: BTreeSet<i64> = BTreeSet::new();
+        let mut group_ids: BTreeSet<i64> = BTreeSet::new();
+        let mut om_instance_ids: BTreeSet<String> = BTreeSet::new();
+        for attribute in &attributes {
+            referenced.insert(attribute.attr_type_id);
+            match &attribute.value {
+                ExchangeAttributeValue::Quantity { unit_id, .. } => {
+                    referenced.insert(*unit_id);
+                }
+                ExchangeAttributeValue::RelationToLocalEntity { entity_id_2, .. } => {
+                    relation_type_ids.insert(attribute.attr_type_id);
+                    referenced.insert(*entity_id_2);
+                }
+                ExchangeAttributeValue::RelationToRemoteEntity { remote_instance_id, .. } => {
+                    relation_type_ids.insert(attribute.attr_type_id);
+                    om_instance_ids.insert(remote_instance_id.clone());
+                }
+                ExchangeAttributeValue::RelationToGroup { group_id, .. } => {
+                    relation_type_ids.insert(attribute.attr_type_id);
+                    group_ids.insert(*group_id);
+                }
+                _ => {}
+            }
+        }
+        let groups = self.read_exchange_groups(
+            transaction.clone(),
+            &match included {
+                None => "true".to_string(),
+                Some(_) => condition("id", &Some(group_ids)),
+            },
+        )?;
+        for group in &groups {
+            referenced.extend(group.entries.iter().map(|entry| entry.entity_id));
+            // (So that importing can find or create the entities a smart group's query refers to,
+            // and give the query their new ids.)
+            if let Some(query) = &group.query {
+                if let Ok(query) = EntityQuery::parse(query) {
+                    referenced.extend(query.get_entity_ids());
+                }
+            }
+        }
+        let om_instances = self.read_exchange_om_instances(
+            transaction.clone(),
+            &match included {
+                None => "true".to_string(),
+                Some(_) if om_instance_ids.is_empty() => "false".to_string(),
+                Some(_) => format!(
+                    "id::text in ({})",
+                    om_instance_ids
+                        .iter()
+                        .map(|id| format!("'{}'", Self::escape_quotes_etc(id.clone())))
+                        .collect::<Vec<String>>()
+                        .join(",")
+                ),
+            },
+        )?;
+        referenced.extend(om_instances.iter().filter_map(|instance| instance.entity_id));
+
+        let known: Option<BTreeSet<i64>> = included.as_ref().map(|included| referenced.union(included).copied().collect());
+        let unit_conversions = self.read_exchange_unit_conversions(
+            transaction.clone(),
+            &format!("{} and {}", condition("from_unit_id", &known), condition("to_unit_id", &known)),
+        )?;
+        let canonical_units = self.read_exchange_canonical_units(transaction.clone(), &condition("attr_type_id", &known))?;
+        referenced.extend(canonical_units.iter().map(|c| c.unit_id));
+
+        let relation_types = self.read_exchange_relation_types(
+            transaction.clone(),
+            &match included {
+                None => "true".to_string(),
+                Some(_) => condition("entity_id", &Some(relation_type_ids)),
+            },
+        )?;
+        let mut entities = self.read_exchange_entities(transaction.clone(), &condition("id", &included), false)?;
+        if let Some(included) = &included {
+            let references: BTreeSet<i64> = referenced.difference(included).copied().collect();
+            entities.extend(self.read_exchange_entities(transaction.clone(), &condition("id", &Some(references)), true)?);
+        }
+        let class_ids: BTreeSet<i64> = entities.iter().filter_map(|e| e.class_id).collect();
+        let classes = self.read_exchange_classes(transaction.clone(), &condition("id", &Some(class_ids.clone())))?;
+        let actions = self.read_exchange_actions(transaction, &condition("class_id", &Some(class_ids)))?;
+
+        Ok(ExchangeData {
+            format: FORMAT_NAME.to_string(),
+            version: FORMAT_VERSION,
+            schema_version: Self::SCHEMA_VERSION,
+            exported_date: Utc::now().timestamp_millis(),
+            root_entity_ids: root_entity_ids_in.map(|ids| ids.to_vec()),
+            entities,
+            relation_types,
+            classes,
+            actions,
+            om_instances,
+            groups,
+            attributes,
+            unit_conversions,
+            canonical_units,
+        })
+    }
+
+    /// The entities reachable from roots_in through relations to entities and groups, plus
+    /// the template entities of their classes (so the classes can be recreated) and the
+    /// entities holding their relations' own attributes.  Archived
+    /// entities are included, since the export is meant to lose nothing.
+    fn find_exchange_subtree(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        roots_in: &[i64],
+    ) -> Result<BTreeSet<i64>, anyhow::Error> {
+        let mut included: BTreeSet<i64> = BTreeSet::new();
+        let mut queue: VecDeque<i64> = VecDeque::new();
+        for root in roots_in {
+            if !self.entity_key_exists(transaction.clone(), *root, true)? {
+                return Err(anyhow!("There is no entity with id {} to export.", root));
+            }
+            if included.insert(*root) {
+                queue.push_back(*root);
+            }
+        }
+        while let Some(id) = queue.pop_front() {
+            let sql = format!(
+                "select entity_id_2 from RelationToEntity where entity_id={id} \
+                union select eiag.entity_id from RelationToGroup rtg, EntitiesInAGroup eiag \
+                where rtg.entity_id={id} and eiag.group_id=rtg.group_id \
+                union select c.defining_entity_id from Entity e, Class c where e.id={id} and c.id=e.class_id \
+                union select h.entity_id from RelationAttributeHolder h, AttributeSorting s where s.entity_id={id} \
+                and h.relation_form_id=s.attribute_form_id and h.relation_id=s.attribute_id"
+            );
+            let rows = self.db_query(transaction.clone(), sql.as_str(), "i64")?;
+            for next in get_i64s_from_rows(&rows)? {
+                if included.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        Ok(included)
+    }
+
+    fn read_exchange_entities(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+        reference_in: bool,
+    ) -> Result<Vec<ExchangeEntity>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select id, name, class_id, archived, archived_date, insertion_date, public, \
+                new_entries_stick_to_top from Entity where {} order by id",
+                condition_in
+            )
+            .as_str(),
+            "i64,String,i64,bool,i64,i64,bool,bool",
+        )?;
+        let mut entities: Vec<ExchangeEntity> = Vec::new();
+        for row in rows {
+            entities.push(ExchangeEntity {
+                id: Util::get_value_bigint("id", &row[0])?,
+                name: Util::get_value_string("name", &row[1])?,
+                // (A reference's class is not exported, so would mean nothing.)
+                class_id: if reference_in {
+                    None
+                } else {
+                    Util::get_value_bigint_option("class_id", &row[2])?
+                },
+                archived: Util::get_value_bool("archived", &row[3])?,
+                archived_date: Util::get_value_bigint_option("archived_date", &row[4])?,
+                insertion_date: Util::get_value_bigint("insertion_date", &row[5])?,
+                public: Util::get_value_bool_option("public", &row[6])?,
+                new_entries_stick_to_top: Util::get_value_bool("new_entries_stick_to_top", &row[7])?,
+                reference: reference_in,
+            });
+        }
+        Ok(entities)
+    }
+
+    fn read_exchange_relation_types(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+    ) -> Result<Vec<ExchangeRelationType>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select entity_id, name_in_reverse_direction, directionality, is_transitive, is_symmetric, inverse_of_id \
+                from RelationType where {} order by entity_id",
+                condition_in
+            )
+            .as_str(),
+            "i64,String,String,bool,bool,i64",
+        )?;
+        let mut relation_types: Vec<ExchangeRelationType> = Vec::new();
+        for row in rows {
+            let entity_id = Util::get_value_bigint("entity_id", &row[0])?;
+            let constraints = self.read_relation_class_constraints(transaction.clone(), entity_id)?;
+            relation_types.push(ExchangeRelationType {
+                entity_id,
+                name_in_reverse_direction: get_value_string_option(&row[1]).unwrap_or_default(),
+                // (It is a char(3), so "BI" comes back padded.)
+                directionality: get_value_string_option(&row[2]).unwrap_or_default().trim().to_string(),
+                transitive: Util::get_value_bool_option("is_transitive", &row[3])?.unwrap_or(false),
+                symmetric: Util::get_value_bool_option("is_symmetric", &row[4])?.unwrap_or(false),
+                inverse_of_id: Util::get_value_bigint_option("inverse_of_id", &row[5])?,
+                source_class_ids: constraints.source_class_ids,
+                target_class_ids: constraints.target_class_ids,
+            });
+        }
+        Ok(relation_types)
+    }
+
+    fn read_exchange_classes(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+    ) -> Result<Vec<ExchangeClass>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select id, name, defining_entity_id, create_default_attributes, parent_class_id from Class \
+                where {} order by id",
+                condition_in
+            )
+            .as_str(),
+            "i64,String,i64,bool,i64",
+        )?;
+        let mut classes: Vec<ExchangeClass> = Vec::new();
+        for row in rows {
+            classes.push(ExchangeClass {
+                id: Util::get_value_bigint("id", &row[0])?,
+                name: Util::get_value_string("name", &row[1])?,
+                defining_entity_id: Util::get_value_bigint("defining_entity_id", &row[2])?,
+                create_default_attributes: Util::get_value_bool_option("create_default_attributes", &row[3])?,
+                parent_class_id: Util::get_value_bigint_option("parent_class_id", &row[4])?,
+            });
+        }
+        Ok(classes)
+    }
+
+    fn read_exchange_actions(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+    ) -> Result<Vec<ExchangeAction>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!("select id, class_id, name, action from Action where {} order by id", condition_in).as_str(),
+            "i64,i64,String,String",
+        )?;
+        let mut actions: Vec<ExchangeAction> = Vec::new();
+        for row in rows {
+            actions.push(ExchangeAction {
+                id: Util::get_value_bigint("id", &row[0])?,
+                class_id: Util::get_value_bigint("class_id", &row[1])?,
+                name: Util::get_value_string("name", &row[2])?,
+                action: Util::get_value_string("action", &row[3])?,
+            });
+        }
+        Ok(actions)
+    }
+
+    fn read_exchange_om_instances(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+    ) -> Result<Vec<ExchangeOmInstance>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select id::text, local, address, insertion_date, entity_id from OmInstance where {} order by insertion_date",
+                condition_in
+            )
+            .as_str(),
+            "String,bool,String,i64,i64",
+        )?;
+        let mut instances: Vec<ExchangeOmInstance> = Vec::new();
+        for row in rows {
+            instances.push(ExchangeOmInstance {
+                id: Util::get_value_string("id", &row[0])?,
+                local: Util::get_value_bool("local", &row[1])?,
+                address: Util::get_value_string("address", &row[2])?,
+                insertion_date: Util::get_value_bigint("insertion_date", &row[3])?,
+                entity_id: Util::get_value_bigint_option("entity_id", &row[4])?,
+            });
+        }
+        Ok(instances)
+    }
+
+    fn read_exchange_groups(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+    ) -> Result<Vec<ExchangeGroup>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select id, name, insertion_date, allow_mixed_classes, new_entries_stick_to_top, query \
+                from grupo where {} order by id",
+                condition_in
+            )
+            .as_str(),
+            "i64,String,i64,bool,bool,String",
+        )?;
+        let mut groups: Vec<ExchangeGroup> = Vec::new();
+        for row in rows {
+            let id = Util::get_value_bigint("id", &row[0])?;
+            let entry_rows = self.db_query(
+                transaction.clone(),
+                format!(
+                    "select entity_id, sorting_index from EntitiesInAGroup where group_id={} order by sorting_index",
+                    id
+                )
+                .as_str(),
+                "i64,i64",
+            )?;
+            let mut entries: Vec<ExchangeGroupEntry> = Vec::new();
+            for entry_row in entry_rows {
+                entries.push(ExchangeGroupEntry {
+                    entity_id: Util::get_value_bigint("entity_id", &entry_row[0])?,
+                    sorting_index: Util::get_value_bigint("sorting_index", &entry_row[1])?,
+                });
+            }
+            groups.push(ExchangeGroup {
+                id,
+                name: Util::get_value_string("name", &row[1])?,
+                insertion_date: Util::get_value_bigint("insertion_date", &row[2])?,
+                allow_mixed_classes: Util::get_value_bool("allow_mixed_classes", &row[3])?,
+                new_entries_stick_to_top: Util::get_value_bool("new_entries_stick_to_top", &row[4])?,
+                query: get_value_string_option(&row[5]),
+                entries,
+            });
+        }
+        Ok(groups)
+    }
+
+    fn read_exchange_unit_conversions(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+    ) -> Result<Vec<ExchangeUnitConversion>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select from_unit_id, to_unit_id, factor from UnitConversion where {} order by from_unit_id, to_unit_id",
+                condition_in
+            )
+            .as_str(),
+            "i64,i64,Float",
+        )?;
+        let mut conversions: Vec<ExchangeUnitConversion> = Vec::new();
+        for row in rows {
+            conversions.push(ExchangeUnitConversion {
+                from_unit_id: Util::get_value_bigint("from_unit_id", &row[0])?,
+                to_unit_id: Util::get_value_bigint("to_unit_id", &row[1])?,
+                factor: Util::get_value_float("factor", &row[2])?,
+            });
+        }
+        Ok(conversions)
+    }
+
+    fn read_exchange_canonical_units(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+    ) -> Result<Vec<ExchangeCanonicalUnit>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!("select attr_type_id, unit_id from CanonicalUnit where {} order by attr_type_id", condition_in).as_str(),
+            "i64,i64",
+        )?;
+        let mut units: Vec<ExchangeCanonicalUnit> = Vec::new();
+        for row in rows {
+            units.push(ExchangeCanonicalUnit {
+                attr_type_id: Util::get_value_bigint("attr_type_id", &row[0])?,
+                unit_id: Util::get_value_bigint("unit_id", &row[1])?,
+            });
+        }
+        Ok(units)
+    }
+
+    /// Attributes of all forms for which condition_in (on the attribute table, as "x") is true.
+    fn read_exchange_attributes(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        condition_in: &str,
+    ) -> Result<Vec<ExchangeAttribute>, anyhow::Error> {
+        let dates = |row: &[Option<DataType>], i: usize| -> Result<(Option<i64>, i64), anyhow::Error> {
+            Ok((
+                Util::get_value_bigint_option("valid_on_date", &row[i])?,
+                Util::get_value_bigint("observation_date", &row[i + 1])?,
+            ))
+        };
+        let mut attributes: Vec<ExchangeAttribute> = Vec::new();
+        attributes.extend(self.read_exchange_attributes_of_form(
+            transaction.clone(),
+            "QuantityAttribute",
+            "attr_type_id",
+            "x.unit_id, x.quantity_number, x.valid_on_date, x.observation_date",
+            "i64,Float,i64,i64",
+            condition_in,
+            |row| {
+                let (valid_on_date, observation_date) = dates(row, 2)?;
+                Ok(ExchangeAttributeValue::Quantity {
+                    unit_id: Util::get_value_bigint("unit_id", &row[0])?,
+                    number: Util::get_value_float("quantity_number", &row[1])?,
+                    valid_on_date,
+                    observation_date,
+                })
+            },
+        )?);
+        attributes.extend(self.read_exchange_attributes_of_form(
+            transaction.clone(),
+            "DateAttribute",
+            "attr_type_id",
+            "x.date",
+            "i64",
+            condition_in,
+            |row| {
+                Ok(ExchangeAttributeValue::Date {
+                    date: Util::get_value_bigint("date", &row[0])?,
+                })
+            },
+        )?);
+        attributes.extend(self.read_exchange_attributes_of_form(
+            transaction.clone(),
+            "BooleanAttribute",
+            "attr_type_id",
+            "x.booleanvalue, x.valid_on_date, x.observation_date",
+            "bool,i64,i64",
+            condition_in,
+            |row| {
+                let (valid_on_date, observation_date) = dates(row, 1)?;
+                Ok(ExchangeAttributeValue::Boolean {
+                    value: Util::get_value_bool_option("booleanvalue", &row[0])?,
+                    valid_on_date,
+                    observation_date,
+                })
+            },
+        )?);
+        attributes.extend(self.read_exchange_attributes_of_form(
+            transaction.clone(),
+            "FileAttribute",
+            "attr_type_id",
+            "x.description, x.original_file_date, x.stored_date, x.original_file_path, x.readable, x.writable, \
+            x.executable, x.size, x.md5hash, \
+            (select encode(lo_get(fac.contents_oid), 'base64') from FileAttributeContent fac where fac.file_attribute_id=x.id)",
+            "String,i64,i64,String,bool,bool,bool,i64,String,String",
+            condition_in,
+            |row| {
+                Ok(ExchangeAttributeValue::File {
+                    description: Util::get_value_string("description", &row[0])?,
+                    original_file_date: Util::get_value_bigint("original_file_date", &row[1])?,
+                    stored_date: Util::get_value_bigint("stored_date", &row[2])?,
+                    original_file_path: Util::get_value_string("original_file_path", &row[3])?,
+                    readable: Util::get_value_bool("readable", &row[4])?,
+                    writable: Util::get_value_bool("writable", &row[5])?,
+                    executable: Util::get_value_bool("executable", &row[6])?,
+                    size: Util::get_value_bigint("size", &row[7])?,
+                    md5hash: Util::get_value_string("md5hash", &row[8])?,
+                    // (postgresql breaks base64 into lines, which is no use here.)
+                    content_base64: get_value_string_option(&row[9]).map(|s| s.replace(['\n', '\r'], "")),
+                })
+            },
+        )?);
+        attributes.extend(self.read_exchange_attributes_of_form(
+            transaction.clone(),
+            "TextAttribute",
+            "attr_type_id",
+            "x.textvalue, x.valid_on_date, x.observation_date",
+            "String,i64,i64",
+            condition_in,
+            |row| {
+                let (valid_on_date, observation_date) = dates(row, 1)?;
+                Ok(ExchangeAttributeValue::Text {
+                    text: Util::get_value_string("textvalue", &row[0])?,
+                    valid_on_date,
+                    observation_date,
+                })
+            },
+        )?);
+        attributes.extend(self.read_exchange_attributes_of_form(
+            transaction.clone(),
+            "RelationToEntity",
+            "rel_type_id",
+            "x.entity_id_2, x.valid_on_date, x.observation_date",
+            "i64,i64,i64",
+            condition_in,
+            |row| {
+                let (valid_on_date, observation_date) = dates(row, 1)?;
+                Ok(ExchangeAttributeValue::RelationToLocalEntity {
+                    entity_id_2: Util::get_value_bigint("entity_id_2", &row[0])?,
+                    valid_on_date,
+                    observation_date,
+                })
+            },
+        )?);
+        attributes.extend(self.read_exchange_attributes_of_form(
+            transaction.clone(),
+            "RelationToRemoteEntity",
+            "rel_type_id",
+            "x.remote_instance_id::text, x.entity_id_2, x.valid_on_date, x.observation_date",
+            "String,i64,i64,i64",
+            condition_in,
+            |row| {
+                let (valid_on_date, observation_date) = dates(row, 2)?;
+                Ok(ExchangeAttributeValue::RelationToRemoteEntity {
+                    remote_instance_id: Util::get_value_string("remote_instance_id", &row[0])?,
+                    entity_id_2: Util::get_value_bigint("entity_id_2", &row[1])?,
+                    valid_on_date,
+                    observation_date,
+                })
+            },
+        )?);
+        attributes.extend(self.read_exchange_attributes_of_form(
+            transaction,
+            "RelationToGroup",
+            "rel_type_id",
+            "x.group_id, x.valid_on_date, x.observation_date",
+            "i64,i64,i64",
+            condition_in,
+            |row| {
+                let (valid_on_date, observation_date) = dates(row, 1)?;
+                Ok(ExchangeAttributeValue::RelationToGroup {
+                    group_id: Util::get_value_bigint("group_id", &row[0])?,
+                    valid_on_date,
+                    observation_date,
+                })
+            },
+        )?);
+        attributes.sort_by_key(|a| (a.entity_id, a.sorting_index));
+        Ok(attributes)
+    }
+
+    /// make_value_in gets the row's columns after the 5 common ones (id, entity_id, the
+    /// attribute type, sorting_index, and the entity holding the attribute's own attributes if
+    /// it is a relation that has them).
+    #[allow(clippy::too_many_arguments)]
+    fn read_exchange_attributes_of_form(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        table_in: &str,
+        attr_type_column_in: &str,
+        value_columns_in: &str,
+        value_types_in: &str,
+        condition_in: &str,
+        make_value_in: impl Fn(&[Option<DataType>]) -> Result<ExchangeAttributeValue, anyhow::Error>,
+    ) -> Result<Vec<ExchangeAttribute>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select x.id, x.entity_id, x.{}, s.sorting_index, (select h.entity_id from RelationAttributeHolder h \
+                where h.relation_form_id=x.form_id and h.relation_id=x.id), {} from {} x, AttributeSorting s \
+                where s.entity_id=x.entity_id and s.attribute_form_id=x.form_id and s.attribute_id=x.id and {}",
+                attr_type_column_in, value_columns_in, table_in, condition_in
+            )
+            .as_str(),
+            format!("i64,i64,i64,i64,i64,{}", value_types_in).as_str(),
+        )?;
+        let mut attributes: Vec<ExchangeAttribute> = Vec::new();
+        for row in rows {
+            attributes.push(ExchangeAttribute {
+                id: Util::get_value_bigint("id", &row[0])?,
+                entity_id: Util::get_value_bigint("entity_id", &row[1])?,
+                attr_type_id: Util::get_value_bigint(attr_type_column_in, &row[2])?,
+                sorting_index: Util::get_value_bigint("sorting_index", &row[3])?,
+                attributes_entity_id: Util::get_value_bigint_option("attributes_entity_id", &row[4])?,
+                value: make_value_in(&row[5..])?,
+            });
+        }
+        Ok(attributes)
+    }
+
+    /// See Database::import_exchange_data.
+    pub(super) fn import_exchange(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        data_in: &ExchangeData,
+        containing_entity_id_in: Option<i64>,
+        observation_date_in: i64,
+    ) -> Result<ExchangeImportCounts, anyhow::Error> {
+        let mut counts = ExchangeImportCounts::default();
+        // From ids in data_in to ids here:
+        let mut entity_ids: HashMap<i64, i64> = HashMap::new();
+        let mut class_ids: HashMap<i64, i64> = HashMap::new();
+        let mut group_ids: HashMap<i64, i64> = HashMap::new();
+        // The (old ids of) entities newly created with their contents, whose attributes and
+        // class are therefore imported too:
+        let mut created: HashSet<i64> = HashSet::new();
+        let mut inserted: HashSet<i64> = HashSet::new();
+
+        // Relation types and classes are matched by name first, so that importing does not
+        // make a second "has" or "URI" (for example) which other code would not find.
+        let relation_type_ids: HashSet<i64> = data_in.relation_types.iter().map(|rt| rt.entity_id).collect();
+        for relation_type in &data_in.relation_types {
+            let entity = data_in.get_entity(relation_type.entity_id).ok_or_else(|| {
+                anyhow!("Relation type {} is not among the entities.", relation_type.entity_id)
+            })?;
+            let rows = self.db_query(
+                transaction.clone(),
+                format!(
+                    "select e.id from Entity e, RelationType rt where e.id=rt.entity_id and e.name='{}' order by e.id limit 1",
+                    Self::escape_quotes_etc(entity.name.clone())
+                )
+                .as_str(),
+                "i64",
+            )?;
+            if let Some(existing) = get_i64s_from_rows(&rows)?.first() {
+                entity_ids.insert(relation_type.entity_id, *existing);
+                counts.entities_matched += 1;
+            }
+        }
+        let mut new_class_ids: HashSet<i64> = HashSet::new();
+        for class in &data_in.classes {
+            let rows = self.db_query(
+                transaction.clone(),
+                format!(
+                    "select id, defining_entity_id from Class where name='{}' order by id limit 1",
+                    Self::escape_quotes_etc(class.name.clone())
+                )
+                .as_str(),
+                "i64,i64",
+            )?;
+            match rows.first() {
+                Some(row) => {
+                    class_ids.insert(class.id, Util::get_value_bigint("id", &row[0])?);
+                    entity_ids.insert(class.defining_entity_id, Util::get_value_bigint("defining_entity_id", &row[1])?);
+                    counts.entities_matched += 1;
+                }
+                None => {
+                    new_class_ids.insert(class.id);
+                }
+            }
+        }
+
+        for entity in &data_in.entities {
+            if entity_ids.contains_key(&entity.id) {
+                continue;
+            }
+            // (A relation type not found above can't be any other existing entity.)
+            if entity.reference && !relation_type_ids.contains(&entity.id) {
+                let existing = self
+                    .find_all_entity_ids_by_name(transaction.clone(), Self::escape_quotes_etc(entity.name.clone()), true)?
+                    .into_iter()
+                    .min();
+                if let Some(existing) = existing {
+                    entity_ids.insert(entity.id, existing);
+                    counts.entities_matched += 1;
+                    continue;
+                }
+            }
+            let new_id = self.insert_exchange_entity(transaction.clone(), entity)?;
+            entity_ids.insert(entity.id, new_id);
+            inserted.insert(entity.id);
+            if !entity.reference {
+                created.insert(entity.id);
+            }
+            counts.entities_created += 1;
+        }
+        let get_entity_id = |old_id: i64| -> Result<i64, anyhow::Error> {
+            entity_ids
+                .get(&old_id)
+                .copied()
+                .ok_or_else(|| anyhow!("Entity {} is referred to but is not among the entities.", old_id))
+        };
+
+        for relation_type in data_in.relation_types.iter().filter(|rt| inserted.contains(&rt.entity_id)) {
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into RelationType (entity_id, name_in_reverse_direction, directionality) values ({},'{}','{}')",
+                    get_entity_id(relation_type.entity_id)?,
+                    Self::escape_quotes_etc(relation_type.name_in_reverse_direction.clone()),
+                    Self::escape_quotes_etc(relation_type.directionality.clone())
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        for relation_type in data_in.relation_types.iter().filter(|rt| inserted.contains(&rt.entity_id)) {
+            let semantics = RelationSemantics {
+                transitive: relation_type.transitive,
+                symmetric: relation_type.symmetric,
+                inverse_of_id: relation_type
+                    .inverse_of_id
+                    .filter(|id| relation_type_ids.contains(id))
+                    .and_then(|id| entity_ids.get(&id).copied()),
+            };
+            if !semantics.is_plain() {
+                self.write_relation_type_semantics(
+                    transaction.clone(),
+                    get_entity_id(relation_type.entity_id)?,
+                    &semantics,
+                )?;
+            }
+        }
+        for class in data_in.classes.iter().filter(|c| new_class_ids.contains(&c.id)) {
+            let new_id = self.get_new_key(transaction.clone(), "ClassKeySequence")?;
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into Class (id, name, defining_entity_id, create_default_attributes) values ({},'{}',{},{})",
+                    new_id,
+                    Self::escape_quotes_etc(class.name.clone()),
+                    get_entity_id(class.defining_entity_id)?,
+                    to_sql(class.create_default_attributes)
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+            class_ids.insert(class.id, new_id);
+        }
+        for class in data_in.classes.iter().filter(|c| new_class_ids.contains(&c.id)) {
+            if let Some(parent_id) = class.parent_class_id.and_then(|p| class_ids.get(&p)) {
+                self.db_action(
+                    transaction.clone(),
+                    format!("update Class set (parent_class_id) = ROW({}) where id={}", parent_id, class_ids[&class.id])
+                        .as_str(),
+                    false,
+                    false,
+                )?;
+            }
+        }
+        for relation_type in data_in.relation_types.iter().filter(|rt| inserted.contains(&rt.entity_id)) {
+            let imported = |ids: &[i64]| -> Vec<i64> { ids.iter().filter_map(|id| class_ids.get(id).copied()).collect() };
+            let constraints = RelationClassConstraints {
+                source_class_ids: imported(&relation_type.source_class_ids),
+                target_class_ids: imported(&relation_type.target_class_ids),
+            };
+            if constraints != RelationClassConstraints::default() {
+                self.write_relation_class_constraints(
+                    transaction.clone(),
+                    get_entity_id(relation_type.entity_id)?,
+                    &constraints,
+                )?;
+            }
+        }
+        for entity in data_in.entities.iter().filter(|e| created.contains(&e.id)) {
+            if let Some(class_id) = entity.class_id.and_then(|c| class_ids.get(&c)) {
+                self.db_action(
+                    transaction.clone(),
+                    format!("update Entity set class_id={} where id={}", class_id, get_entity_id(entity.id)?).as_str(),
+                    false,
+                    false,
+                )?;
+            }
+        }
+        for action in data_in.actions.iter().filter(|a| new_class_ids.contains(&a.class_id)) {
+            let new_id = self.get_new_key(transaction.clone(), "ActionKeySequence")?;
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into Action (id, class_id, name, action) values ({},{},'{}','{}')",
+                    new_id,
+                    class_ids[&action.class_id],
+                    Self::escape_quotes_etc(action.name.clone()),
+                    Self::escape_quotes_etc(action.action.clone())
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+
+        // Instances keep their uuids, being the same instances wherever they are known.  None
+        // is imported as local, since this database already has its own local one.
+        for instance in &data_in.om_instances {
+            let id = Self::escape_quotes_etc(instance.id.clone());
+            let rows = self.db_query(
+                transaction.clone(),
+                format!("select count(1) from OmInstance where id='{}'", id).as_str(),
+                "i64",
+            )?;
+            if get_i64s_from_rows(&rows)?.first() != Some(&0) {
+                continue;
+            }
+            let entity_id = match instance.entity_id {
+                Some(old_id) => Some(get_entity_id(old_id)?),
+                None => None,
+            };
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into OmInstance (id, local, address, insertion_date, entity_id) values ('{}',false,'{}',{},{})",
+                    id,
+                    Self::escape_quotes_etc(instance.address.clone()),
+                    instance.insertion_date,
+                    to_sql(entity_id)
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+
+        for group in &data_in.groups {
+            let new_id = self.get_new_key(transaction.clone(), "RelationToGroupKeySequence")?;
+            // (A query that doesn't parse is kept as it is, for the user to see and fix.)
+            let query = group.query.as_ref().map(|q| {
+                EntityQuery::map_entity_ids(q, |id| entity_ids.get(&id).copied()).unwrap_or_else(|_| q.clone())
+            });
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into grupo (id, name, insertion_date, allow_mixed_classes, new_entries_stick_to_top, query) \
+                    values ({},'{}',{},{},{},{})",
+                    new_id,
+                    Self::escape_quotes_etc(group.name.clone()),
+                    group.insertion_date,
+                    group.allow_mixed_classes,
+                    group.new_entries_stick_to_top,
+                    to_sql(query.map(|q| format!("'{}'", Self::escape_quotes_etc(q))))
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+            for entry in &group.entries {
+                self.db_action(
+                    transaction.clone(),
+                    format!(
+                        "insert into EntitiesInAGroup (group_id, entity_id, sorting_index) values ({},{},{})",
+                        new_id,
+                        get_entity_id(entry.entity_id)?,
+                        entry.sorting_index
+                    )
+                    .as_str(),
+                    false,
+                    false,
+                )?;
+            }
+            group_ids.insert(group.id, new_id);
+            counts.groups += 1;
+        }
+
+        for attribute in data_in.attributes.iter().filter(|a| created.contains(&a.entity_id)) {
+            self.insert_exchange_attribute(
+                transaction.clone(),
+                attribute,
+                get_entity_id(attribute.entity_id)?,
+                get_entity_id(attribute.attr_type_id)?,
+                &entity_ids,
+                &group_ids,
+            )?;
+            counts.attributes += 1;
+        }
+
+        for conversion in &data_in.unit_conversions {
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into UnitConversion (from_unit_id, to_unit_id, factor) values ({},{},{}) on conflict do nothing",
+                    get_entity_id(conversion.from_unit_id)?,
+                    get_entity_id(conversion.to_unit_id)?,
+                    conversion.factor
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        for canonical_unit in &data_in.canonical_units {
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into CanonicalUnit (attr_type_id, unit_id) values ({},{}) on conflict do nothing",
+                    get_entity_id(canonical_unit.attr_type_id)?,
+                    get_entity_id(canonical_unit.unit_id)?
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+
+        let has_relation_type_id = match containing_entity_id_in {
+            Some(_) => Some(self.find_relation_type(transaction.clone(), Util::THE_HAS_RELATION_TYPE_NAME)?),
+            None => None,
+        };
+        for root in data_in.root_entity_ids.iter().flatten() {
+            let new_id = get_entity_id(*root)?;
+            if let (Some(containing_id), Some(has_id)) = (containing_entity_id_in, has_relation_type_id) {
+                if created.contains(root) {
+                    self.create_relation_to_local_entity(
+                        transaction.clone(),
+                        has_id,
+                        containing_id,
+                        new_id,
+                        None,
+                        observation_date_in,
+                        None,
+                    )?;
+                }
+            }
+            counts.root_entity_ids.push(new_id);
+        }
+        Ok(counts)
+    }
+
+    fn insert_exchange_entity(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_in: &ExchangeEntity,
+    ) -> Result<i64, anyhow::Error> {
+        let new_id = self.get_new_key(transaction.clone(), "EntityKeySequence")?;
+        self.db_action(
+            transaction,
+            format!(
+                "insert into Entity (id, name, archived, archived_date, insertion_date, public, new_entries_stick_to_top) \
+                values ({},'{}',{},{},{},{},{})",
+                new_id,
+                Self::escape_quotes_etc(entity_in.name.clone()),
+                entity_in.archived,
+                to_sql(entity_in.archived_date),
+                entity_in.insertion_date,
+                to_sql(entity_in.public),
+                entity_in.new_entries_stick_to_top
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        Ok(new_id)
+    }
+
+    fn insert_exchange_attribute(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attribute_in: &ExchangeAttribute,
+        entity_id_in: i64,
+        attr_type_id_in: i64,
+        entity_ids_in: &HashMap<i64, i64>,
+        group_ids_in: &HashMap<i64, i64>,
+    ) -> Result<(), anyhow::Error> {
+        let get_entity_id = |old_id: &i64| -> Result<i64, anyhow::Error> {
+            entity_ids_in
+                .get(old_id)
+                .copied()
+                .ok_or_else(|| anyhow!("Entity {} is referred to but is not among the entities.", old_id))
+        };
+        let (form, sequence, sql_start): (&str, &str, String) = match &attribute_in.value {
+            ExchangeAttributeValue::Quantity {
+                unit_id,
+                number,
+                valid_on_date,
+                observation_date,
+            } => (
+                Util::QUANTITY_TYPE,
+                "QuantityAttributeKeySequence",
+                format!(
+                    "insert into QuantityAttribute (entity_id, attr_type_id, unit_id, quantity_number, valid_on_date, \
+                    observation_date, id) values ({},{},{},{},{},{}",
+                    entity_id_in,
+                    attr_type_id_in,
+                    get_entity_id(unit_id)?,
+                    number,
+                    to_sql(*valid_on_date),
+                    observation_date
+                ),
+            ),
+            ExchangeAttributeValue::Date { date } => (
+                Util::DATE_TYPE,
+                "DateAttributeKeySequence",
+                format!(
+                    "insert into DateAttribute (entity_id, attr_type_id, date, id) values ({},{},{}",
+                    entity_id_in, attr_type_id_in, date
+                ),
+            ),
+            ExchangeAttributeValue::Boolean {
+                value,
+                valid_on_date,
+                observation_date,
+            } => (
+                Util::BOOLEAN_TYPE,
+                "BooleanAttributeKeySequence",
+                format!(
+                    "insert into BooleanAttribute (entity_id, attr_type_id, booleanvalue, valid_on_date, observation_date, id) \
+                    values ({},{},{},{},{}",
+                    entity_id_in,
+                    attr_type_id_in,
+                    to_sql(*value),
+                    to_sql(*valid_on_date),
+                    observation_date
+                ),
+            ),
+            ExchangeAttributeValue::File {
+                description,
+                original_file_date,
+                stored_date,
+                original_file_path,
+                readable,
+                writable,
+                executable,
+                size,
+                md5hash,
+                ..
+            } => (
+                Util::FILE_TYPE,
+                "FileAttributeKeySequence",
+                format!(
+                    "insert into FileAttribute (entity_id, attr_type_id, description, original_file_date, stored_date, \
+                    original_file_path, readable, writable, executable, size, md5hash, id) \
+                    values ({},{},'{}',{},{},'{}',{},{},{},{},'{}'",
+                    entity_id_in,
+                    attr_type_id_in,
+                    Self::escape_quotes_etc(description.clone()),
+                    original_file_date,
+                    stored_date,
+                    Self::escape_quotes_etc(original_file_path.clone()),
+                    readable,
+                    writable,
+                    executable,
+                    size,
+                    Self::escape_quotes_etc(md5hash.clone())
+                ),
+            ),
+            ExchangeAttributeValue::Text {
+                text,
+                valid_on_date,
+                observation_date,
+            } => (
+                Util::TEXT_TYPE,
+                "TextAttributeKeySequence",
+                format!(
+                    "insert into TextAttribute (entity_id, attr_type_id, textvalue, valid_on_date, observation_date, id) \
+                    values ({},{},'{}',{},{}",
+                    entity_id_in,
+                    attr_type_id_in,
+                    Self::escape_quotes_etc(text.clone()),
+                    to_sql(*valid_on_date),
+                    observation_date
+                ),
+            ),
+            ExchangeAttributeValue::RelationToLocalEntity {
+                entity_id_2,
+                valid_on_date,
+                observation_date,
+            } => (
+                Util::RELATION_TO_LOCAL_ENTITY_TYPE,
+                "RelationToEntityKeySequence",
+                format!(
+                    "insert into RelationToEntity (entity_id, rel_type_id, entity_id_2, valid_on_date, observation_date, id) \
+                    values ({},{},{},{},{}",
+                    entity_id_in,
+                    attr_type_id_in,
+                    get_entity_id(entity_id_2)?,
+                    to_sql(*valid_on_date),
+                    observation_date
+                ),
+            ),
+            ExchangeAttributeValue::RelationToRemoteEntity {
+                remote_instance_id,
+                entity_id_2,
+                valid_on_date,
+                observation_date,
+            } => (
+                Util::RELATION_TO_REMOTE_ENTITY_TYPE,
+                "RelationToRemoteEntityKeySequence",
+                format!(
+                    "insert into RelationToRemoteEntity (entity_id, rel_type_id, remote_instance_id, entity_id_2, \
+                    valid_on_date, observation_date, id) values ({},{},'{}',{},{},{}",
+                    entity_id_in,
+                    attr_type_id_in,
+                    Self::escape_quotes_etc(remote_instance_id.clone()),
+                    entity_id_2,
+                    to_sql(*valid_on_date),
+                    observation_date
+                ),
+            ),
+            ExchangeAttributeValue::RelationToGroup {
+                group_id,
+                valid_on_date,
+                observation_date,
+            } => (
+                Util::RELATION_TO_GROUP_TYPE,
+                "RelationToGroupKeySequence2",
+                format!(
+                    "insert into RelationToGroup (entity_id, rel_type_id, group_id, valid_on_date, observation_date, id) \
+                    values ({},{},{},{},{}",
+                    entity_id_in,
+                    attr_type_id_in,
+                    group_ids_in
+                        .get(group_id)
+                        .ok_or_else(|| anyhow!("Group {} is referred to but is not among the groups.", group_id))?,
+                    to_sql(*valid_on_date),
+                    observation_date
+                ),
+            ),
+        };
+        let new_id = self.get_new_key(transaction.clone(), sequence)?;
+        self.db_action(transaction.clone(), format!("{},{})", sql_start, new_id).as_str(), false, false)?;
+        if let ExchangeAttributeValue::File {
+            content_base64: Some(content),
+            ..
+        } = &attribute_in.value
+        {
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into FileAttributeContent (file_attribute_id, contents_oid) \
+                    values ({}, lo_from_bytea(0, decode('{}', 'base64')))",
+                    new_id,
+                    Self::escape_quotes_etc(content.clone())
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        let form_id = self.get_attribute_form_id(form)?;
+        self.add_attribute_sorting_row(
+            transaction.clone(),
+            entity_id_in,
+            form_id,
+            new_id,
+            Some(attribute_in.sorting_index),
+        )?;
+        if let Some(holder_id) = attribute_in.attributes_entity_id.and_then(|id| entity_ids_in.get(&id)) {
+            self.attach_relation_attribute_holder(transaction, form_id, new_id, *holder_id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_export_and_import_exchange_data() {
+        Util::initialize_tracing();
+        let db: PostgreSQLDatabase = Util::initialize_test_db().unwrap();
+        let tx = db.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let (class_id, template_id) = db.create_class_and_its_template_entity(tx.clone(), "exchange class").unwrap();
+        let top = db.create_entity(tx.clone(), "exchange top", Some(class_id), Some(true)).unwrap();
+        let weight_type = db.create_entity(tx.clone(), "exchange weight", None, None).unwrap();
+        let kg = db.create_entity(tx.clone(), "exchange kg", None, None).unwrap();
+        db.create_quantity_attribute(tx.clone(), top, weight_type, kg, 70.5, None, 1000, Some(-50))
+            .unwrap();
+        let note_type = db.create_entity(tx.clone(), "exchange note", None, None).unwrap();
+        db.create_text_attribute(tx.clone(), top, note_type, "it's; here", Some(900), 1000, Some(-40))
+            .unwrap();
+        let rel_type = db.create_relation_type(tx.clone(), "exchange mentors", "is mentored by", "UNI").unwrap();
+        let transitive = RelationSemantics {
+            transitive: true,
+            ..Default::default()
+        };
+        db.update_relation_type_semantics(tx.clone(), rel_type, &transitive).unwrap();
+        let from_class = RelationClassConstraints {
+            source_class_ids: vec![class_id],
+            target_class_ids: Vec::new(),
+        };
+        db.set_relation_type_class_constraints(tx.clone(), rel_type, &from_class).unwrap();
+        let child = db.create_entity(tx.clone(), "exchange child", None, Some(false)).unwrap();
+        let (rte_id, _) = db
+            .create_relation_to_local_entity(tx.clone(), rel_type, top, child, Some(5), 1000, Some(-30))
+            .unwrap();
+        let rte_form_id = db.get_attribute_form_id(Util::RELATION_TO_LOCAL_ENTITY_TYPE).unwrap();
+        let holder = db.get_or_create_relation_attribute_holder(tx.clone(), rte_form_id, rte_id).unwrap();
+        db.create_text_attribute(tx.clone(), holder, note_type, "since spring", None, 1000, None)
+            .unwrap();
+        let has = db.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME).unwrap();
+        let (group_id, _) = db
+            .create_group_and_relation_to_group(tx.clone(), top, has, "exchange list", false, None, 1000, Some(-20))
+            .unwrap();
+        let first = db.create_entity(tx.clone(), "exchange first", None, None).unwrap();
+        let second = db.create_entity(tx.clone(), "exchange second", None, None).unwrap();
+        db.add_entity_to_group(tx.clone(), group_id, first, Some(300)).unwrap();
+        db.add_entity_to_group(tx.clone(), group_id, second, Some(200)).unwrap();
+        let outside = db.create_entity(tx.clone(), "exchange outside", None, None).unwrap();
+
+        let data = db.export_exchange_data(tx.clone(), Some(&[top])).unwrap();
+        let ids: Vec<i64> = data.entities.iter().filter(|e| !e.reference).map(|e| e.id).collect();
+        for id in [top, child, first, second, template_id, holder] {
+            assert!(ids.contains(&id), "missing {}", id);
+        }
+        assert!(data.get_entity(outside).is_none());
+        assert!(data.get_entity(kg).unwrap().reference);
+        assert!(data.get_entity(has).unwrap().reference);
+        assert_eq!(data.attributes.iter().filter(|a| a.entity_id == top).count(), 4);
+        let relation_type = data.relation_types.iter().find(|rt| rt.entity_id == rel_type).unwrap();
+        assert_eq!(relation_type.name_in_reverse_direction, "is mentored by");
+        assert_eq!(relation_type.directionality, "UNI");
+        assert!(relation_type.transitive);
+        assert_eq!(relation_type.source_class_ids, vec![class_id]);
+        assert_eq!(data.classes.len(), 1);
+        let rel = data.attributes.iter().find(|a| a.entity_id == top && a.attr_type_id == rel_type).unwrap();
+        assert_eq!(rel.attributes_entity_id, Some(holder));
+        let data = ExchangeData::from_json(&data.to_json().unwrap()).unwrap();
+
+        // Importing it as is: relation types and the class are found by name.
+        let container = db.create_entity(tx.clone(), "exchange container", None, None).unwrap();
+        let counts = db.import_exchange_data(tx.clone(), &data, Some(container), 2000).unwrap();
+        assert_eq!(counts.groups, 1);
+        assert_eq!(counts.attributes, 5); // (Including the one on the relation.)
+        let new_top = counts.root_entity_ids[0];
+        assert_ne!(new_top, top);
+        let rows = db
+            .db_query(
+                tx.clone(),
+                format!("select name, class_id, public from Entity where id={}", new_top).as_str(),
+                "String,i64,bool",
+            )
+            .unwrap();
+        assert_eq!(Util::get_value_string("name", &rows[0][0]).unwrap(), "exchange top");
+        assert_eq!(Util::get_value_bigint("class_id", &rows[0][1]).unwrap(), class_id);
+        assert!(Util::get_value_bool("public", &rows[0][2]).unwrap());
+        let sql = format!("select entity_id_2 from RelationToEntity where entity_id={} and rel_type_id={}", container, has);
+        let rows = db.db_query(tx.clone(), sql.as_str(), "i64").unwrap();
+        assert!(get_i64s_from_rows(&rows).unwrap().contains(&new_top));
+        let again = db.export_exchange_data(tx.clone(), Some(&[new_top])).unwrap();
+        let sorting_indexes = |d: &ExchangeData, id: i64| -> Vec<i64> {
+            d.attributes.iter().filter(|a| a.entity_id == id).map(|a| a.sorting_index).collect()
+        };
+        assert_eq!(sorting_indexes(&again, new_top), sorting_indexes(&data, top));
+        assert_eq!(again.groups[0].entries.iter().map(|e| e.sorting_index).collect::<Vec<i64>>(), vec![200, 300]);
+        assert_ne!(again.groups[0].entries[0].entity_id, second);
+        let new_rel = again.attributes.iter().find(|a| matches!(a.value, ExchangeAttributeValue::RelationToLocalEntity { .. }));
+        assert_eq!(new_rel.unwrap().attr_type_id, rel_type);
+        // The relation's own attributes came with it:
+        let new_holder = new_rel.unwrap().attributes_entity_id.unwrap();
+        assert_ne!(new_holder, holder);
+        let holder_texts: Vec<&ExchangeAttributeValue> =
+            again.attributes.iter().filter(|a| a.entity_id == new_holder).map(|a| &a.value).collect();
+        assert!(matches!(holder_texts[..], [ExchangeAttributeValue::Text { ref text, .. }] if text == "since spring"));
+        assert_eq!(again.get_entity(kg).map(|e| e.reference), Some(true));
+
+        // With a relation type and class not already here, they are created, with their details.
+        let mut renamed = data.clone();
+        for e in renamed.entities.iter_mut().filter(|e| e.id == rel_type) {
+            e.name = "exchange new relation".to_string();
+        }
+        renamed.classes[0].name = "exchange new class".to_string();
+        let counts = db.import_exchange_data(tx.clone(), &renamed, None, 2000).unwrap();
+        let again = db.export_exchange_data(tx.clone(), Some(&counts.root_entity_ids)).unwrap();
+        assert_eq!(again.classes[0].name, "exchange new class");
+        assert_ne!(again.classes[0].id, class_id);
+        let relation_type = &again.relation_types.iter().find(|rt| rt.entity_id != has).unwrap();
+        assert_ne!(relation_type.entity_id, rel_type);
+        assert_eq!(relation_type.name_in_reverse_direction, "is mentored by");
+        assert_eq!(relation_type.directionality, "UNI");
+        assert!(relation_type.transitive && !relation_type.symmetric);
+        assert_eq!(relation_type.source_class_ids, vec![again.classes[0].id]);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+
+    #[test]
+    fn test_exchange_smart_group_query() {
+        Util::initialize_tracing();
+        let db: PostgreSQLDatabase = Util::initialize_test_db().unwrap();
+        let tx = db.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let has = db.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME).unwrap();
+        let top = db.create_entity(tx.clone(), "exchange query top", None, None).unwrap();
+        let child = db.create_entity(tx.clone(), "exchange query child", None, None).unwrap();
+        db.create_relation_to_local_entity(tx.clone(), has, top, child, None, 1000, None).unwrap();
+        let outside = db.create_entity(tx.clone(), "exchange query outside", None, None).unwrap();
+        // A smart group's query refers to entities by id, so importing has to give it their new
+        // ids (and exporting includes any it refers to from outside, as references).
+        let query = format!("under {} and not related has to {}", child, outside);
+        let group_id = db.create_smart_group(tx.clone(), "exchange query group", &query).unwrap();
+        db.create_relation_to_group(tx.clone(), top, has, group_id, None, 1000, None).unwrap();
+
+        let data = db.export_exchange_data(tx.clone(), Some(&[top])).unwrap();
+        assert_eq!(data.groups[0].query, Some(query));
+        assert!(data.get_entity(outside).unwrap().reference);
+        let counts = db.import_exchange_data(tx.clone(), &data, None, 2000).unwrap();
+        let again = db.export_exchange_data(tx.clone(), Some(&counts.root_entity_ids)).unwrap();
+        let new_child = again.entities.iter().find(|e| e.name == "exchange query child").unwrap().id;
+        assert_ne!(new_child, child);
+        // (The reference to an entity here by the same name is to that one.)
+        assert_eq!(again.groups[0].query, Some(format!("under {} and not related has to {}", new_child, outside)));
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
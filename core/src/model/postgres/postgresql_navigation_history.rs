@@ -0,0 +1,113 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Reading and saving the navigation history (see model/navigation_history.rs), in the
+//! NavigationHistory table.
+use crate::model::navigation_history::{Location, NavigationHistory};
+use crate::model::postgres::postgresql_database::*;
+use crate::util::Util;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+impl PostgreSQLDatabase {
+    /// If the row that was current is gone (its entity or group deleted), the last one is
+    /// current instead.
+    pub(super) fn read_navigation_history(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<NavigationHistory, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            "select entity_id, group_id, is_current from NavigationHistory order by position",
+            "i64,i64,bool",
+        )?;
+        let mut locations: Vec<Location> = Vec::new();
+        let mut current: Option<usize> = None;
+        for row in rows {
+            let location = match Util::get_value_bigint_option("entity_id", &row[0])? {
+                Some(entity_id) => Location::Entity(entity_id),
+                None => Location::Group(Util::get_value_bigint("group_id", &row[1])?),
+            };
+            if Util::get_value_bool("is_current", &row[2])? {
+                current = Some(locations.len());
+            }
+            locations.push(location);
+        }
+        Ok(NavigationHistory::new(locations, current))
+    }
+
+    /// Replaces what was saved with history_in.
+    pub(super) fn write_navigation_history(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        history_in: &NavigationHistory,
+    ) -> Result<(), anyhow::Error> {
+        self.db_action(transaction.clone(), "delete from NavigationHistory", true, false)?;
+        for (position, location) in history_in.locations().iter().enumerate() {
+            let (entity_id, group_id) = match location {
+                Location::Entity(id) => (id.to_string(), "NULL".to_string()),
+                Location::Group(id) => ("NULL".to_string(), id.to_string()),
+            };
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into NavigationHistory (position, entity_id, group_id, is_current) values ({},{},{},{})",
+                    position,
+                    entity_id,
+                    group_id,
+                    history_in.current_index() == Some(position)
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::database::Database;
+    use crate::model::navigation_history::{Location, NavigationHistory};
+    use crate::util::Util;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_save_and_get_navigation_history() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        db.save_navigation_history(tx.clone(), &NavigationHistory::default()).unwrap();
+        assert_eq!(db.get_navigation_history(tx.clone()).unwrap(), NavigationHistory::default());
+
+        let first = db.create_entity(tx.clone(), "navigation first", None, None).unwrap();
+        let second = db.create_entity(tx.clone(), "navigation second", None, None).unwrap();
+        let has = db.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME).unwrap();
+        let (group_id, _) = db
+            .create_group_and_relation_to_group(tx.clone(), first, has, "navigation group", false, None, 0, None)
+            .unwrap();
+        let mut history = NavigationHistory::default();
+        history.visit(Location::Entity(first));
+        history.visit(Location::Group(group_id));
+        history.visit(Location::Entity(second));
+        history.back();
+        db.save_navigation_history(tx.clone(), &history).unwrap();
+        assert_eq!(db.get_navigation_history(tx.clone()).unwrap(), history);
+
+        // Deleting where the user was drops it, and the last remaining place is current:
+        db.delete_group_and_relations_to_it(tx.clone(), group_id).unwrap();
+        let history = db.get_navigation_history(tx.clone()).unwrap();
+        assert_eq!(history.locations(), &[Location::Entity(first), Location::Entity(second)]);
+        assert_eq!(history.current(), Some(Location::Entity(second)));
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
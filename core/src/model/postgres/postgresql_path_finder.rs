@@ -0,0 +1,202 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Reading the links touching an entity, for finding paths between entities (see
+//! model/path_finder.rs).
+use crate::model::database::Database;
+use crate::model::path_finder::{self, LinkKind, PathLink, PathOptions};
+use crate::model::postgres::postgresql_database::*;
+use crate::util::Util;
+use anyhow::anyhow;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+impl PostgreSQLDatabase {
+    /// The links from or to entity_id_in, each with it as the from_entity_id, leaving out any to
+    /// an archived entity unless those are being shown, and any to a holder of a relation's own
+    /// attributes.
+    fn read_entity_links(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        include_attribute_types_in: bool,
+    ) -> Result<Vec<PathLink>, anyhow::Error> {
+        let not_archived = if self.include_archived_entities {
+            ""
+        } else {
+            " and (not e.archived)"
+        };
+        let not_holder = " and e.id not in (select entity_id from RelationAttributeHolder)";
+        // (Relations from the preferences, like bookmarks, are settings, not connections.)
+        let not_preference =
+            format!(" and r.entity_id not in {}", self.preference_entity_ids_sql(transaction.clone())?);
+        let mut links: Vec<PathLink> = Vec::new();
+        let mut read = |sql: String, make_kind: &dyn Fn(i64, i64) -> LinkKind, forward: bool| -> Result<(), anyhow::Error> {
+            let rows = self.db_query(transaction.clone(), sql.as_str(), "i64,i64,i64")?;
+            for row in rows {
+                links.push(PathLink {
+                    from_entity_id: entity_id_in,
+                    to_entity_id: Util::get_value_bigint("entity_id", &row[0])?,
+                    kind: make_kind(
+                        Util::get_value_bigint("rel_type_id", &row[1])?,
+                        Util::get_value_bigint("group_id", &row[2])?,
+                    ),
+                    forward,
+                });
+            }
+            Ok(())
+        };
+        let relation = |rel_type_id: i64, _| LinkKind::Relation(rel_type_id);
+        read(
+            format!(
+                "select r.entity_id_2, r.rel_type_id, 0::bigint from RelationToEntity r, Entity e \
+                where r.entity_id={} and e.id=r.entity_id_2{}{}{} order by r.rel_type_id, e.id",
+                entity_id_in, not_archived, not_holder, not_preference
+            ),
+            &relation,
+            true,
+        )?;
+        read(
+            format!(
+                "select r.entity_id, r.rel_type_id, 0::bigint from RelationToEntity r, Entity e \
+                where r.entity_id_2={} and e.id=r.entity_id{}{}{} order by r.rel_type_id, e.id",
+                entity_id_in, not_archived, not_holder, not_preference
+            ),
+            &relation,
+            false,
+        )?;
+        let group = |rel_type_id: i64, group_id: i64| LinkKind::Group { group_id, rel_type_id };
+        read(
+            format!(
+                "select eiag.entity_id, rtg.rel_type_id, rtg.group_id from RelationToGroup rtg, EntitiesInAGroup eiag, \
+                Entity e where rtg.entity_id={} and eiag.group_id=rtg.group_id and e.id=eiag.entity_id{}{} \
+                order by rtg.group_id, eiag.sorting_index",
+                entity_id_in, not_archived, not_holder
+            ),
+            &group,
+            true,
+        )?;
+        read(
+            format!(
+                "select rtg.entity_id, rtg.rel_type_id, rtg.group_id from EntitiesInAGroup eiag, RelationToGroup rtg, \
+                Entity e where eiag.entity_id={} and rtg.group_id=eiag.group_id and e.id=rtg.entity_id{}{} \
+                order by rtg.group_id, e.id",
+                entity_id_in, not_archived, not_holder
+            ),
+            &group,
+            false,
+        )?;
+        if include_attribute_types_in {
+            for form in Util::NON_RELATION_ATTR_TYPE_NAMES {
+                let attribute_type = |_, _| LinkKind::AttributeType(form.to_string());
+                // (The form names are also the table names.)
+                read(
+                    format!(
+                        "select distinct x.attr_type_id, 0::bigint, 0::bigint from {} x, Entity e \
+                        where x.entity_id={} and e.id=x.attr_type_id{}{} order by 1",
+                        form, entity_id_in, not_archived, not_holder
+                    ),
+                    &attribute_type,
+                    true,
+                )?;
+                read(
+                    format!(
+                        "select distinct x.entity_id, 0::bigint, 0::bigint from {} x, Entity e \
+                        where x.attr_type_id={} and e.id=x.entity_id{}{} order by 1",
+                        form, entity_id_in, not_archived, not_holder
+                    ),
+                    &attribute_type,
+                    false,
+                )?;
+            }
+        }
+        Ok(links)
+    }
+
+    pub(super) fn find_paths(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        from_entity_id_in: i64,
+        to_entity_id_in: i64,
+        options_in: &PathOptions,
+    ) -> Result<Vec<Vec<PathLink>>, anyhow::Error> {
+        for id in [from_entity_id_in, to_entity_id_in] {
+            if !self.entity_key_exists(transaction.clone(), id, true)? {
+                return Err(anyhow!("Key {}{}", id, Util::DOES_NOT_EXIST));
+            }
+        }
+        path_finder::find_shortest_paths(from_entity_id_in, to_entity_id_in, options_in, &mut |id| {
+            self.read_entity_links(transaction.clone(), id, options_in.include_attribute_types)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::database::Database;
+    use crate::model::path_finder::{LinkKind, PathOptions};
+    use crate::util::Util;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_find_paths_between_entities() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let works_at = db.create_relation_type(tx.clone(), "paths works at", "", "UNI").unwrap();
+        let alice = db.create_entity(tx.clone(), "paths alice", None, None).unwrap();
+        let acme = db.create_entity(tx.clone(), "paths acme", None, None).unwrap();
+        let bob = db.create_entity(tx.clone(), "paths bob", None, None).unwrap();
+        let carol = db.create_entity(tx.clone(), "paths carol", None, None).unwrap();
+        db.create_relation_to_local_entity(tx.clone(), works_at, alice, acme, None, 0, None).unwrap();
+        db.create_relation_to_local_entity(tx.clone(), works_at, bob, acme, None, 0, None).unwrap();
+        let has = db.find_relation_type(tx.clone(), Util::THE_HAS_RELATION_TYPE_NAME).unwrap();
+        let (group_id, _) = db
+            .create_group_and_relation_to_group(tx.clone(), bob, has, "paths friends", false, None, 0, None)
+            .unwrap();
+        db.add_entity_to_group(tx.clone(), group_id, carol, None).unwrap();
+
+        let options = PathOptions::default();
+        let paths = db.find_paths_between_entities(tx.clone(), alice, carol, &options).unwrap();
+        assert_eq!(paths.len(), 1);
+        let path = &paths[0];
+        assert_eq!(
+            path.iter().map(|l| l.to_entity_id).collect::<Vec<i64>>(),
+            vec![acme, bob, carol]
+        );
+        assert!(path[0].forward && !path[1].forward && path[2].forward);
+        assert_eq!(path[2].kind, LinkKind::Group { group_id, rel_type_id: has });
+
+        // Without following "has" (so not the group), or archived entities, there is no path:
+        let works_at_only = PathOptions {
+            rel_type_ids: vec![works_at],
+            ..PathOptions::default()
+        };
+        assert!(db.find_paths_between_entities(tx.clone(), alice, carol, &works_at_only).unwrap().is_empty());
+        db.archive_entity(tx.clone(), bob).unwrap();
+        assert!(db.find_paths_between_entities(tx.clone(), alice, carol, &options).unwrap().is_empty());
+        db.unarchive_entity(tx.clone(), bob).unwrap();
+
+        // Through an attribute type, only when asked:
+        let dave = db.create_entity(tx.clone(), "paths dave", None, None).unwrap();
+        db.create_text_attribute(tx.clone(), dave, carol, "a note", None, 0, None).unwrap();
+        assert!(db.find_paths_between_entities(tx.clone(), alice, dave, &options).unwrap().is_empty());
+        let with_types = PathOptions {
+            include_attribute_types: true,
+            ..PathOptions::default()
+        };
+        let paths = db.find_paths_between_entities(tx.clone(), alice, dave, &with_types).unwrap();
+        assert_eq!(paths[0].len(), 4);
+        assert_eq!(paths[0][3].kind, LinkKind::AttributeType(Util::TEXT_TYPE.to_string()));
+        assert!(db.find_paths_between_entities(tx.clone(), alice, -1_000_000, &options).is_err());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
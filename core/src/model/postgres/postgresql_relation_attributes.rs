@@ -0,0 +1,347 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! A relation's own attributes (like the start date, role or salary of a "worked at" relation),
+//! which are those of an entity made to hold them: see the RelationAttributeHolder table.
+//! They are shown and edited with the relation, and included in the exports (the JSON exchange
+//! format and RDF with their values, and the Markdown, OPML and org ones as displayed).  An
+//! entity's relations can be sorted by one of them (like by start date), which reorders the
+//! relations' sorting indexes (see AttributeSorting) among themselves, leaving its other
+//! attributes where they were.
+use crate::model::class_schema;
+use crate::model::database::Database;
+use crate::model::postgres::postgresql_database::*;
+use crate::util::Util;
+use anyhow::anyhow;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The name given to each entity that holds a relation's attributes.  It is not shown in entity
+/// lists, so is mostly seen only in exports.
+pub const RELATION_ATTRIBUTE_HOLDER_NAME: &str = "(attributes of a relation)";
+
+impl PostgreSQLDatabase {
+    pub(super) fn read_relation_attribute_holder_id(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+    ) -> Result<Option<i64>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select entity_id from RelationAttributeHolder where relation_form_id={} and relation_id={}",
+                relation_form_id_in, relation_id_in
+            )
+            .as_str(),
+            "i64",
+        )?;
+        match rows.first() {
+            Some(row) => Ok(Some(Util::get_value_bigint("entity_id", &row[0])?)),
+            None => Ok(None),
+        }
+    }
+
+    pub(super) fn create_relation_attribute_holder(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+    ) -> Result<i64, anyhow::Error> {
+        if !self.is_relation_form_id(relation_form_id_in)?
+            || !self.attribute_key_exists(transaction.clone(), relation_form_id_in, relation_id_in)?
+        {
+            return Err(anyhow!(
+                "There is no relation with form {} and id {} to hold attributes for.",
+                relation_form_id_in,
+                relation_id_in
+            ));
+        }
+        let entity_id = self.create_entity(transaction.clone(), RELATION_ATTRIBUTE_HOLDER_NAME, None, None)?;
+        self.attach_relation_attribute_holder(transaction, relation_form_id_in, relation_id_in, entity_id)?;
+        Ok(entity_id)
+    }
+
+    pub(super) fn attach_relation_attribute_holder(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+        entity_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        self.db_action(
+            transaction,
+            format!(
+                "insert into RelationAttributeHolder (relation_form_id, relation_id, entity_id) values ({},{},{})",
+                relation_form_id_in, relation_id_in, entity_id_in
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// Unlinks the holder of a relation's attributes without deleting it, so the relation can be
+    /// deleted and recreated (as the move_relation_* methods do) and the holder attached to the
+    /// new one.  Returns the holder's entity id, if there was one.
+    pub(super) fn detach_relation_attribute_holder(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        relation_form_id_in: i32,
+        relation_id_in: i64,
+    ) -> Result<Option<i64>, anyhow::Error> {
+        let holder_id = self.read_relation_attribute_holder_id(transaction.clone(), relation_form_id_in, relation_id_in)?;
+        if holder_id.is_some() {
+            self.db_action(
+                transaction,
+                format!(
+                    "delete from RelationAttributeHolder where relation_form_id={} and relation_id={}",
+                    relation_form_id_in, relation_id_in
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        Ok(holder_id)
+    }
+
+    /// See Database::get_relation_attribute_types.
+    pub(super) fn read_relation_attribute_types(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<(String, i64)>, anyhow::Error> {
+        let relation_form_ids = self.get_relation_form_ids_sql()?;
+        let mut types: Vec<(String, i64)> = Vec::new();
+        for form in class_schema::FORMS {
+            let rows = self.db_query(
+                transaction.clone(),
+                format!(
+                    "select distinct a.attr_type_id from AttributeSorting s, RelationAttributeHolder h, {} a \
+                    where s.entity_id={} and s.attribute_form_id in ({}) and h.relation_form_id=s.attribute_form_id \
+                    and h.relation_id=s.attribute_id and a.entity_id=h.entity_id order by a.attr_type_id",
+                    form, entity_id_in, relation_form_ids
+                )
+                .as_str(),
+                "i64",
+            )?;
+            for row in rows {
+                types.push((form.to_string(), get_i64_from_row(&row, 0)?));
+            }
+        }
+        Ok(types)
+    }
+
+    /// See Database::sort_relations_by_relation_attribute.
+    pub(super) fn write_relations_sorted_by_relation_attribute(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        attribute_form_in: &str,
+        attr_type_id_in: i64,
+        descending_in: bool,
+    ) -> Result<u64, anyhow::Error> {
+        let value_column = match attribute_form_in {
+            Util::QUANTITY_TYPE => "quantity_number",
+            Util::DATE_TYPE => "date",
+            Util::BOOLEAN_TYPE => "booleanvalue",
+            Util::TEXT_TYPE => "textvalue",
+            _ => return Err(anyhow!("Relations can't be sorted by a {}.", attribute_form_in)),
+        };
+        let direction = if descending_in { "desc" } else { "asc" };
+        // (Relations without such an attribute go last, in their current order, as do ties.)
+        let rows = self.db_query(
+            transaction.clone(),
+            format!(
+                "select s.attribute_form_id::integer, s.attribute_id, s.sorting_index from AttributeSorting s \
+                left join RelationAttributeHolder h on h.relation_form_id=s.attribute_form_id and h.relation_id=s.attribute_id \
+                where s.entity_id={} and s.attribute_form_id in ({}) \
+                order by (select a.{} from {} a where a.entity_id=h.entity_id and a.attr_type_id={} \
+                order by a.{} {} limit 1) {} nulls last, s.sorting_index",
+                entity_id_in,
+                self.get_relation_form_ids_sql()?,
+                value_column,
+                attribute_form_in,
+                attr_type_id_in,
+                value_column,
+                direction,
+                direction
+            )
+            .as_str(),
+            "Int,i64,i64",
+        )?;
+        // The relations keep the sorting indexes they had among them, just reassigned in the new
+        // order, so the entity's other attributes stay where they were.
+        let mut sorting_indexes: Vec<i64> = Vec::new();
+        for row in &rows {
+            sorting_indexes.push(Util::get_value_bigint("sorting_index", &row[2])?);
+        }
+        sorting_indexes.sort();
+        // (Deleted first, since the indexes must be unique for the entity.)
+        for row in &rows {
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "delete from AttributeSorting where entity_id={} and attribute_form_id={} and attribute_id={}",
+                    entity_id_in,
+                    Util::get_value_smallint("attribute_form_id", &row[0])?,
+                    Util::get_value_bigint("attribute_id", &row[1])?
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        for (row, sorting_index) in rows.iter().zip(sorting_indexes) {
+            self.db_action(
+                transaction.clone(),
+                format!(
+                    "insert into AttributeSorting (entity_id, attribute_form_id, attribute_id, sorting_index) \
+                    values ({},{},{},{})",
+                    entity_id_in,
+                    Util::get_value_smallint("attribute_form_id", &row[0])?,
+                    Util::get_value_bigint("attribute_id", &row[1])?,
+                    sorting_index
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        Ok(rows.len() as u64)
+    }
+
+    fn get_relation_form_ids(&self) -> Result<Vec<i32>, anyhow::Error> {
+        [
+            Util::RELATION_TO_LOCAL_ENTITY_TYPE,
+            Util::RELATION_TO_GROUP_TYPE,
+            Util::RELATION_TO_REMOTE_ENTITY_TYPE,
+        ]
+        .iter()
+        .map(|form| self.get_attribute_form_id(form))
+        .collect()
+    }
+
+    fn get_relation_form_ids_sql(&self) -> Result<String, anyhow::Error> {
+        Ok(self
+            .get_relation_form_ids()?
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(","))
+    }
+
+    fn is_relation_form_id(&self, form_id_in: i32) -> Result<bool, anyhow::Error> {
+        Ok(self.get_relation_form_ids()?.contains(&form_id_in))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::database::Database;
+    use crate::model::postgres::postgresql_database::get_i64s_from_rows;
+    use crate::util::Util;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_relation_attributes_follow_the_relation() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let worked_at = db.create_relation_type(tx.clone(), "rel attrs worked at", "", "UNI").unwrap();
+        let person = db.create_entity(tx.clone(), "rel attrs person", None, None).unwrap();
+        let company = db.create_entity(tx.clone(), "rel attrs company", None, None).unwrap();
+        let role_type = db.create_entity(tx.clone(), "rel attrs role", None, None).unwrap();
+        let (rte_id, _) = db
+            .create_relation_to_local_entity(tx.clone(), worked_at, person, company, None, 0, None)
+            .unwrap();
+        let form_id = db.get_attribute_form_id(Util::RELATION_TO_LOCAL_ENTITY_TYPE).unwrap();
+        assert_eq!(db.get_relation_attribute_holder_id(tx.clone(), form_id, rte_id).unwrap(), None);
+        assert!(db
+            .get_or_create_relation_attribute_holder(tx.clone(), form_id, rte_id + 1_000_000)
+            .is_err());
+
+        let entity_count = db.get_entities_only_count(tx.clone(), false, None, None).unwrap();
+        let holder = db.get_or_create_relation_attribute_holder(tx.clone(), form_id, rte_id).unwrap();
+        assert_eq!(db.get_or_create_relation_attribute_holder(tx.clone(), form_id, rte_id).unwrap(), holder);
+        // The holder is not listed among entities:
+        assert_eq!(db.get_entities_only_count(tx.clone(), false, None, None).unwrap(), entity_count);
+        db.create_text_attribute(tx.clone(), holder, role_type, "engineer", None, 0, None)
+            .unwrap();
+        assert_eq!(db.get_attribute_count(tx.clone(), holder, false).unwrap(), 1);
+
+        // Detaching (as moving a relation does, before recreating it) keeps the holder:
+        assert_eq!(db.detach_relation_attribute_holder(tx.clone(), form_id, rte_id).unwrap(), Some(holder));
+        assert_eq!(db.get_relation_attribute_holder_id(tx.clone(), form_id, rte_id).unwrap(), None);
+        assert!(db.entity_key_exists(tx.clone(), holder, true).unwrap());
+        db.attach_relation_attribute_holder(tx.clone(), form_id, rte_id, holder).unwrap();
+
+        // Deleting the relation deletes the holder and so its attributes:
+        db.delete_relation_to_local_entity(tx.clone(), worked_at, person, company).unwrap();
+        assert!(!db.entity_key_exists(tx.clone(), holder, true).unwrap());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+
+    #[test]
+    fn test_sort_relations_by_relation_attribute() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let worked_at = db.create_relation_type(tx.clone(), "rel sort worked at", "", "UNI").unwrap();
+        let started_type = db.create_entity(tx.clone(), "rel sort started", None, None).unwrap();
+        let note_type = db.create_entity(tx.clone(), "rel sort note", None, None).unwrap();
+        let person = db.create_entity(tx.clone(), "rel sort person", None, None).unwrap();
+        let form_id = db.get_attribute_form_id(Util::RELATION_TO_LOCAL_ENTITY_TYPE).unwrap();
+        let mut relation_ids = Vec::new();
+        for (name, started) in [("rel sort a", Some(300)), ("rel sort b", Some(100)), ("rel sort c", None)] {
+            let company = db.create_entity(tx.clone(), name, None, None).unwrap();
+            let (rte_id, _) = db
+                .create_relation_to_local_entity(tx.clone(), worked_at, person, company, None, 0, None)
+                .unwrap();
+            if let Some(started) = started {
+                let holder = db.get_or_create_relation_attribute_holder(tx.clone(), form_id, rte_id).unwrap();
+                db.create_date_attribute(tx.clone(), holder, started_type, started, None).unwrap();
+            }
+            relation_ids.push(rte_id);
+        }
+        let note_id = db.create_text_attribute(tx.clone(), person, note_type, "rel sort", None, 0, None).unwrap();
+        let order = || -> Vec<i64> {
+            let sql = format!("select attribute_id from AttributeSorting where entity_id={} order by sorting_index", person);
+            get_i64s_from_rows(&db.db_query(tx.clone(), sql.as_str(), "i64").unwrap()).unwrap()
+        };
+        let [a, b, c] = relation_ids[..] else { panic!() };
+        // (The note's place, and the order of the rest, which are the relations:)
+        let place_and_rest = || -> (usize, Vec<i64>) {
+            let order = order();
+            let place = order.iter().position(|id| *id == note_id).unwrap();
+            (place, order.into_iter().filter(|id| *id != note_id).collect())
+        };
+        let (note_place, relations) = place_and_rest();
+        assert_eq!(relations, vec![a, b, c]);
+        assert_eq!(
+            db.get_relation_attribute_types(tx.clone(), person).unwrap(),
+            vec![(Util::DATE_TYPE.to_string(), started_type)]
+        );
+
+        // Earliest first, and the one with no start date last; the note stays where it was:
+        let count = db
+            .sort_relations_by_relation_attribute(tx.clone(), person, Util::DATE_TYPE, started_type, false)
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(place_and_rest(), (note_place, vec![b, a, c]));
+        db.sort_relations_by_relation_attribute(tx.clone(), person, Util::DATE_TYPE, started_type, true)
+            .unwrap();
+        assert_eq!(place_and_rest(), (note_place, vec![a, b, c]));
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
@@ -0,0 +1,222 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Relation types' allowed classes for the entities at each end (see RelationClassConstraints),
+//! and checking relations against them when they are written.
+use crate::model::database::Database;
+use crate::model::postgres::postgresql_database::*;
+use crate::model::relation_type::RelationClassConstraints;
+use crate::util::Util;
+use anyhow::anyhow;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+impl PostgreSQLDatabase {
+    pub(super) fn read_relation_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+    ) -> Result<RelationClassConstraints, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select a.for_target, a.class_id from RelationTypeAllowedClass a, Class c \
+                where a.rel_type_id={} and a.class_id=c.id order by c.name, c.id",
+                rel_type_id_in
+            )
+            .as_str(),
+            "bool,i64",
+        )?;
+        let mut constraints = RelationClassConstraints::default();
+        for row in rows {
+            let class_id = Util::get_value_bigint("class_id", &row[1])?;
+            if Util::get_value_bool("for_target", &row[0])? {
+                constraints.target_class_ids.push(class_id);
+            } else {
+                constraints.source_class_ids.push(class_id);
+            }
+        }
+        Ok(constraints)
+    }
+
+    pub(super) fn write_relation_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        constraints_in: &RelationClassConstraints,
+    ) -> Result<(), anyhow::Error> {
+        if !self.relation_type_key_exists(transaction.clone(), rel_type_id_in)? {
+            return Err(anyhow!("Key {}{}", rel_type_id_in, Util::DOES_NOT_EXIST));
+        }
+        self.db_action(
+            transaction.clone(),
+            format!("delete from RelationTypeAllowedClass where rel_type_id={}", rel_type_id_in).as_str(),
+            true,
+            false,
+        )?;
+        for (for_target, class_ids) in [
+            (false, &constraints_in.source_class_ids),
+            (true, &constraints_in.target_class_ids),
+        ] {
+            let mut class_ids = class_ids.clone();
+            class_ids.sort();
+            class_ids.dedup();
+            for class_id in class_ids {
+                self.db_action(
+                    transaction.clone(),
+                    format!(
+                        "insert into RelationTypeAllowedClass (rel_type_id, for_target, class_id) values ({},{},{})",
+                        rel_type_id_in, for_target, class_id
+                    )
+                    .as_str(),
+                    false,
+                    false,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether entity_id_in's class, or one of its ancestors, is among class_ids_in.
+    fn is_entity_in_any_class(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        class_ids_in: &[i64],
+    ) -> Result<bool, anyhow::Error> {
+        let rows = self.db_query(
+            transaction.clone(),
+            format!("select class_id from Entity where id={}", entity_id_in).as_str(),
+            "i64",
+        )?;
+        let Some(Some(class_id)) = rows.first().map(|row| Util::get_value_bigint_option("class_id", &row[0])).transpose()?
+        else {
+            return Ok(false);
+        };
+        Ok(self
+            .get_class_lineage(transaction, class_id)?
+            .iter()
+            .any(|(id, _)| class_ids_in.contains(id)))
+    }
+
+    pub(super) fn relation_class_constraint_problems(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        source_id_in: Option<i64>,
+        target_id_in: Option<i64>,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let constraints = self.read_relation_class_constraints(transaction.clone(), rel_type_id_in)?;
+        if constraints.source_class_ids.is_empty() && constraints.target_class_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        // (Relations from the preferences, like bookmarks, are settings, so can be to anything.)
+        if let Some(source_id) = source_id_in {
+            if self.is_preference_entity(transaction.clone(), source_id)? {
+                return Ok(Vec::new());
+            }
+        }
+        let name_of = |id: i64| -> String {
+            match self.get_entity_name(transaction.clone(), id) {
+                Ok(Some(name)) => name,
+                _ => format!("#{}", id),
+            }
+        };
+        let mut problems: Vec<String> = Vec::new();
+        for (entity_id, class_ids, end) in [
+            (source_id_in, &constraints.source_class_ids, "from"),
+            (target_id_in, &constraints.target_class_ids, "to"),
+        ] {
+            let Some(entity_id) = entity_id else {
+                continue;
+            };
+            if class_ids.is_empty() || self.is_entity_in_any_class(transaction.clone(), entity_id, class_ids)? {
+                continue;
+            }
+            let mut class_names: Vec<String> = Vec::new();
+            for class_id in class_ids.iter() {
+                class_names.push(self.get_class_name(transaction.clone(), *class_id)?.unwrap_or_default());
+            }
+            problems.push(format!(
+                "A \"{}\" relation can't be {} \"{}\": it must be in the class {} (or a subclass).",
+                name_of(rel_type_id_in),
+                end,
+                name_of(entity_id),
+                class_names.iter().map(|n| format!("\"{}\"", n)).collect::<Vec<String>>().join(" or ")
+            ));
+        }
+        Ok(problems)
+    }
+
+    /// Returns an error if a relation of rel_type_id_in from source_id_in to target_id_in would
+    /// break the type's allowed classes.
+    pub(super) fn check_relation_against_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        source_id_in: i64,
+        target_id_in: i64,
+    ) -> Result<(), anyhow::Error> {
+        let problems =
+            self.relation_class_constraint_problems(transaction, rel_type_id_in, Some(source_id_in), Some(target_id_in))?;
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(problems.join("  ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::database::Database;
+    use crate::model::relation_type::RelationClassConstraints;
+    use crate::util::Util;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_relation_class_constraints_enforced_on_write() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let (book_class, _) = db.create_class_and_its_template_entity(tx.clone(), "constraint book").unwrap();
+        let (person_class, _) = db.create_class_and_its_template_entity(tx.clone(), "constraint person").unwrap();
+        let (author_class, _) = db.create_class_and_its_template_entity(tx.clone(), "constraint author").unwrap();
+        db.update_class_parent(tx.clone(), author_class, Some(person_class)).unwrap();
+        let has_author = db.create_relation_type(tx.clone(), "constraint has author", "wrote", "UNI").unwrap();
+        let book = db.create_entity(tx.clone(), "constraint a book", Some(book_class), None).unwrap();
+        let writer = db.create_entity(tx.clone(), "constraint a writer", Some(author_class), None).unwrap();
+        let kg = db.create_entity(tx.clone(), "constraint kg", None, None).unwrap();
+        // Without constraints anything goes:
+        assert!(db.get_relation_class_constraint_problems(tx.clone(), has_author, Some(book), Some(kg)).unwrap().is_empty());
+
+        let constraints = RelationClassConstraints {
+            source_class_ids: vec![book_class],
+            target_class_ids: vec![person_class],
+        };
+        db.set_relation_type_class_constraints(tx.clone(), has_author, &constraints).unwrap();
+        assert_eq!(db.get_relation_type_class_constraints(tx.clone(), has_author).unwrap(), constraints);
+        let problems = db.get_relation_class_constraint_problems(tx.clone(), has_author, Some(kg), Some(kg)).unwrap();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[1].contains("\"constraint person\""), "{}", problems[1]);
+        assert!(db.get_relation_class_constraint_problems(tx.clone(), has_author, None, Some(book)).unwrap().len() == 1);
+
+        assert!(db.create_relation_to_local_entity(tx.clone(), has_author, book, kg, None, 0, None).is_err());
+        assert!(db.create_relation_to_local_entity(tx.clone(), has_author, kg, writer, None, 0, None).is_err());
+        // A member of a subclass of the allowed class is fine:
+        db.create_relation_to_local_entity(tx.clone(), has_author, book, writer, None, 0, None).unwrap();
+
+        let other = db.create_relation_type(tx.clone(), "constraint mentions", "", "UNI").unwrap();
+        db.create_relation_to_local_entity(tx.clone(), other, book, kg, None, 0, None).unwrap();
+        assert!(db.update_relation_to_local_entity(tx.clone(), other, book, kg, has_author, None, 0).is_err());
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
@@ -0,0 +1,268 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Storing what relation types mean (transitive, symmetric, inverse), and reading the relations
+//! needed to infer the ones they imply (see model/relation_inference.rs).
+use crate::model::postgres::postgresql_database::*;
+use crate::model::relation_inference::{self, AssertedRelation, InferredRelation, RelationSemantics};
+use crate::util::Util;
+use anyhow::anyhow;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+impl PostgreSQLDatabase {
+    pub(super) fn read_relation_type_semantics(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+    ) -> Result<RelationSemantics, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select is_transitive, is_symmetric, inverse_of_id from RelationType where entity_id={}",
+                rel_type_id_in
+            )
+            .as_str(),
+            "bool,bool,i64",
+        )?;
+        let Some(row) = rows.first() else {
+            return Err(anyhow!("Key {}{}", rel_type_id_in, Util::DOES_NOT_EXIST));
+        };
+        Ok(RelationSemantics {
+            transitive: Util::get_value_bool_option("transitive", &row[0])?.unwrap_or(false),
+            symmetric: Util::get_value_bool_option("symmetric", &row[1])?.unwrap_or(false),
+            inverse_of_id: Util::get_value_bigint_option("inverse_of_id", &row[2])?,
+        })
+    }
+
+    /// Saves semantics_in for rel_type_id_in.  Its inverse (if any) is also set to point back, and
+    /// any other type that was an inverse of either of the pair no longer is.
+    pub(super) fn write_relation_type_semantics(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        rel_type_id_in: i64,
+        semantics_in: &RelationSemantics,
+    ) -> Result<(), anyhow::Error> {
+        if semantics_in.inverse_of_id == Some(rel_type_id_in) {
+            return Err(anyhow!(
+                "A relation type can't be its own inverse; to say that its relations go both ways, mark it symmetric."
+            ));
+        }
+        if let Some(inverse_id) = semantics_in.inverse_of_id {
+            // (Checks that it is a relation type.)
+            self.read_relation_type_semantics(transaction.clone(), inverse_id)?;
+        }
+        let pair: Vec<String> = std::iter::once(rel_type_id_in)
+            .chain(semantics_in.inverse_of_id)
+            .map(|id| id.to_string())
+            .collect();
+        self.db_action(
+            transaction.clone(),
+            format!(
+                "update RelationType set (inverse_of_id) = ROW(NULL) where inverse_of_id in ({0}) \
+                or (entity_id in ({0}) and inverse_of_id is not NULL)",
+                pair.join(",")
+            )
+            .as_str(),
+            true,
+            false,
+        )?;
+        self.db_action(
+            transaction.clone(),
+            format!(
+                "update RelationType set (is_transitive, is_symmetric, inverse_of_id) = ROW({},{},{}) where entity_id={}",
+                semantics_in.transitive,
+                semantics_in.symmetric,
+                semantics_in.inverse_of_id.map(|id| id.to_string()).unwrap_or_else(|| "NULL".to_string()),
+                rel_type_id_in
+            )
+            .as_str(),
+            false,
+            false,
+        )?;
+        if let Some(inverse_id) = semantics_in.inverse_of_id {
+            self.db_action(
+                transaction,
+                format!(
+                    "update RelationType set (inverse_of_id) = ROW({}) where entity_id={}",
+                    rel_type_id_in, inverse_id
+                )
+                .as_str(),
+                false,
+                false,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The relation types that are not plain, by id.
+    fn get_relation_semantics_by_type(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<HashMap<i64, RelationSemantics>, anyhow::Error> {
+        let rows = self.db_query(
+            transaction,
+            "select entity_id, is_transitive, is_symmetric, inverse_of_id from RelationType \
+            where is_transitive or is_symmetric or inverse_of_id is not NULL",
+            "i64,bool,bool,i64",
+        )?;
+        let mut semantics: HashMap<i64, RelationSemantics> = HashMap::new();
+        for row in rows {
+            semantics.insert(
+                Util::get_value_bigint("entity_id", &row[0])?,
+                RelationSemantics {
+                    transitive: Util::get_value_bool_option("transitive", &row[1])?.unwrap_or(false),
+                    symmetric: Util::get_value_bool_option("symmetric", &row[2])?.unwrap_or(false),
+                    inverse_of_id: Util::get_value_bigint_option("inverse_of_id", &row[3])?,
+                },
+            );
+        }
+        Ok(semantics)
+    }
+
+    /// The relations to local entities entered from or to entity_id_in, leaving out any with an
+    /// archived entity at either end unless those are being shown.
+    fn get_relations_touching(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<AssertedRelation>, anyhow::Error> {
+        let not_archived = if self.include_archived_entities {
+            ""
+        } else {
+            " and (not e1.archived) and (not e2.archived)"
+        };
+        let rows = self.db_query(
+            transaction,
+            format!(
+                "select r.rel_type_id, r.entity_id, r.entity_id_2 from RelationToEntity r, Entity e1, Entity e2 \
+                where (r.entity_id={0} or r.entity_id_2={0}) and e1.id=r.entity_id and e2.id=r.entity_id_2{1}",
+                entity_id_in, not_archived
+            )
+            .as_str(),
+            "i64,i64,i64",
+        )?;
+        let mut relations: Vec<AssertedRelation> = Vec::new();
+        for row in rows {
+            relations.push(AssertedRelation {
+                rel_type_id: Util::get_value_bigint("rel_type_id", &row[0])?,
+                from_entity_id: Util::get_value_bigint("entity_id", &row[1])?,
+                to_entity_id: Util::get_value_bigint("entity_id_2", &row[2])?,
+            });
+        }
+        Ok(relations)
+    }
+
+    pub(super) fn infer_relations_from(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+    ) -> Result<Vec<InferredRelation>, anyhow::Error> {
+        let semantics = self.get_relation_semantics_by_type(transaction.clone())?;
+        if semantics.is_empty() {
+            return Ok(Vec::new());
+        }
+        relation_inference::infer_relations(entity_id_in, &semantics, &mut |id| {
+            self.get_relations_touching(transaction.clone(), id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::database::Database;
+    use crate::model::relation_inference::{InferenceReason, InferredRelation, RelationSemantics};
+    use crate::util::Util;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_inferred_relations_from_relation_type_semantics() {
+        Util::initialize_tracing();
+        let db = Util::initialize_test_db().unwrap();
+        let tx = Some(Rc::new(RefCell::new(db.begin_trans().unwrap())));
+        let part_of = db.create_relation_type(tx.clone(), "inference part of", "inference has part", "UNI").unwrap();
+        let contains = db.create_relation_type(tx.clone(), "inference contains", "inference is in", "UNI").unwrap();
+        let sibling = db.create_relation_type(tx.clone(), "inference sibling of", "", "BI").unwrap();
+        let city = db.create_entity(tx.clone(), "inference city", None, None).unwrap();
+        let country = db.create_entity(tx.clone(), "inference country", None, None).unwrap();
+        let continent = db.create_entity(tx.clone(), "inference continent", None, None).unwrap();
+        let neighbour = db.create_entity(tx.clone(), "inference neighbour", None, None).unwrap();
+        db.create_relation_to_local_entity(tx.clone(), part_of, city, country, None, 0, None).unwrap();
+        db.create_relation_to_local_entity(tx.clone(), part_of, country, continent, None, 0, None).unwrap();
+        db.create_relation_to_local_entity(tx.clone(), sibling, neighbour, country, None, 0, None).unwrap();
+        // Nothing is inferred until the types say what they mean:
+        assert!(db.get_inferred_relations(tx.clone(), city).unwrap().is_empty());
+        assert!(db.get_relation_type_semantics(tx.clone(), part_of).unwrap().is_plain());
+
+        let transitive = RelationSemantics {
+            transitive: true,
+            symmetric: false,
+            inverse_of_id: Some(contains),
+        };
+        db.update_relation_type_semantics(tx.clone(), part_of, &transitive).unwrap();
+        let symmetric = RelationSemantics {
+            transitive: false,
+            symmetric: true,
+            inverse_of_id: None,
+        };
+        db.update_relation_type_semantics(tx.clone(), sibling, &symmetric).unwrap();
+        // The inverse points back:
+        assert_eq!(db.get_relation_type_semantics(tx.clone(), contains).unwrap().inverse_of_id, Some(part_of));
+        assert!(db
+            .update_relation_type_semantics(
+                tx.clone(),
+                sibling,
+                &RelationSemantics {
+                    inverse_of_id: Some(sibling),
+                    ..symmetric
+                }
+            )
+            .is_err());
+
+        assert_eq!(
+            db.get_inferred_relations(tx.clone(), city).unwrap(),
+            vec![InferredRelation {
+                rel_type_id: part_of,
+                to_entity_id: continent,
+                reason: InferenceReason::Transitive(vec![country]),
+            }]
+        );
+        let from_country = db.get_inferred_relations(tx.clone(), country).unwrap();
+        assert!(from_country.contains(&InferredRelation {
+            rel_type_id: contains,
+            to_entity_id: city,
+            reason: InferenceReason::Inverse(part_of),
+        }));
+        assert!(from_country.contains(&InferredRelation {
+            rel_type_id: sibling,
+            to_entity_id: neighbour,
+            reason: InferenceReason::Symmetric,
+        }));
+        assert_eq!(from_country.len(), 2);
+
+        // Making another type the inverse of part_of takes it away from contains:
+        let within = db.create_relation_type(tx.clone(), "inference within", "", "UNI").unwrap();
+        db.update_relation_type_semantics(
+            tx.clone(),
+            within,
+            &RelationSemantics {
+                inverse_of_id: Some(part_of),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(db.get_relation_type_semantics(tx.clone(), contains).unwrap().inverse_of_id, None);
+        assert_eq!(db.get_relation_type_semantics(tx.clone(), part_of).unwrap().inverse_of_id, Some(within));
+        assert!(db.get_relation_type_semantics(tx.clone(), part_of).unwrap().transitive);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
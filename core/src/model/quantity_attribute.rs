@@ -177,6 +177,20 @@ impl Attribute for QuantityAttribute {
         };
         let mut result: String =
             format!("{}: {} {}", type_name, self.get_number(None)?, entity_name);
+        // Also show it in the type's canonical unit, if one is set and the units are related.
+        let unit_id = self.get_unit_id(None)?;
+        let canonical_unit_id = self.db.borrow().get_canonical_unit(None, attr_type_id)?;
+        if let Some(canonical_unit_id) = canonical_unit_id.filter(|c| *c != unit_id) {
+            let converter = self.db.borrow().get_unit_converter()?;
+            if let Some(converted) = converter.convert(self.get_number(None)?, unit_id, canonical_unit_id) {
+                let canonical_unit_name = self
+                    .db
+                    .borrow()
+                    .get_entity_name(None, canonical_unit_id)?
+                    .unwrap_or_else(|| "(None)".to_string());
+                result = format!("{} (= {} {})", result, converted, canonical_unit_name);
+            }
+        }
         if !simplify {
             result = format!(
                 "{}; {}",
@@ -0,0 +1,224 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+use crate::model::database::Database;
+use crate::util::Util;
+use chrono::{TimeZone, Utc};
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const SPARKLINE_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const MILLIS_PER_DAY: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// The quantity attributes on one entity that share an attribute type and unit (like repeated
+/// "weight" readings in kg), as a series ordered by date, for seeing how they change over time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantitySeries {
+    pub attr_type_id: i64,
+    pub unit_id: i64,
+    /// (date in milliseconds UTC, number), sorted by date.
+    pub points: Vec<(i64, f64)>,
+}
+
+impl QuantitySeries {
+    /// Returns one series per attribute type and unit among entity_id_in's quantity attributes.
+    /// If by_valid_date_in, a point's date is its valid_on_date (or observation_date when that
+    /// is unspecified or "all time"), otherwise its observation_date.
+    pub fn get_for_entity(
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        entity_id_in: i64,
+        by_valid_date_in: bool,
+    ) -> Result<Vec<QuantitySeries>, anyhow::Error> {
+        let rows = db.borrow().get_quantity_series_data(transaction, entity_id_in, by_valid_date_in)?;
+        let mut all_series: Vec<QuantitySeries> = Vec::new();
+        for row in rows {
+            let attr_type_id = Util::get_value_bigint("attr_type_id", &row[0])?;
+            let unit_id = Util::get_value_bigint("unit_id", &row[1])?;
+            let date = Util::get_value_bigint("date", &row[2])?;
+            let number = Util::get_value_float("quantity_number", &row[3])?;
+            match all_series.last_mut() {
+                Some(s) if s.attr_type_id == attr_type_id && s.unit_id == unit_id => {
+                    s.points.push((date, number))
+                }
+                _ => all_series.push(QuantitySeries {
+                    attr_type_id,
+                    unit_id,
+                    points: vec![(date, number)],
+                }),
+            }
+        }
+        Ok(all_series)
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.points.iter().map(|p| p.1).reduce(f64::min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.points.iter().map(|p| p.1).reduce(f64::max)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.points.is_empty() {
+            None
+        } else {
+            Some(self.points.iter().map(|p| p.1).sum::<f64>() / self.points.len() as f64)
+        }
+    }
+
+    /// The least-squares slope, in units per day, or None if there are not at least two
+    /// distinct dates to draw a line through.
+    pub fn trend_per_day(&self) -> Option<f64> {
+        let n = self.points.len() as f64;
+        if n < 2.0 {
+            return None;
+        }
+        let days: Vec<f64> = self.points.iter().map(|p| p.0 as f64 / MILLIS_PER_DAY).collect();
+        let mean_day = days.iter().sum::<f64>() / n;
+        let mean_value = self.mean()?;
+        let mut numerator = 0.0;
+        let mut denominator = 0.0;
+        for (day, (_, value)) in days.iter().zip(self.points.iter()) {
+            numerator += (day - mean_day) * (value - mean_value);
+            denominator += (day - mean_day) * (day - mean_day);
+        }
+        if denominator == 0.0 {
+            None
+        } else {
+            Some(numerator / denominator)
+        }
+    }
+
+    /// One bar character per point, scaled from the series' min (lowest bar) to its max.
+    pub fn get_sparkline(&self) -> String {
+        let (Some(min), Some(max)) = (self.min(), self.max()) else {
+            return String::new();
+        };
+        self.points
+            .iter()
+            .map(|(_, value)| {
+                let index = if max > min {
+                    ((value - min) / (max - min) * (SPARKLINE_BARS.len() - 1) as f64).round() as usize
+                } else {
+                    SPARKLINE_BARS.len() / 2
+                };
+                SPARKLINE_BARS[index]
+            })
+            .collect()
+    }
+
+    /// A one-line summary: count, min, max, mean, trend, and the sparkline.
+    pub fn get_summary(&self) -> String {
+        let format_option = |n: Option<f64>| n.map_or("-".to_string(), |v| format!("{}", v));
+        let trend = match self.trend_per_day() {
+            None => "-".to_string(),
+            Some(t) if t > 0.0 => format!("rising {}/day", t),
+            Some(t) if t < 0.0 => format!("falling {}/day", -t),
+            Some(_) => "flat".to_string(),
+        };
+        format!(
+            "{} points, min {}, max {}, mean {}, trend {}  {}",
+            self.points.len(),
+            format_option(self.min()),
+            format_option(self.max()),
+            format_option(self.mean()),
+            trend,
+            self.get_sparkline()
+        )
+    }
+
+    /// The series as CSV with a header line: the date (ISO 8601, UTC), the same date in
+    /// milliseconds, and the number.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("date,date_millis,value\n");
+        for (date, value) in &self.points {
+            let iso = match Utc.timestamp_millis_opt(*date).single() {
+                Some(d) => d.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+                None => String::new(),
+            };
+            csv.push_str(&format!("{},{},{}\n", iso, date, value));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+
+    fn make_series(points: Vec<(i64, f64)>) -> QuantitySeries {
+        QuantitySeries {
+            attr_type_id: 1,
+            unit_id: 2,
+            points,
+        }
+    }
+
+    #[test]
+    fn test_statistics_and_sparkline() {
+        let day = MILLIS_PER_DAY as i64;
+        let series = make_series(vec![(0, 10.0), (day, 12.0), (2 * day, 14.0), (3 * day, 16.0)]);
+        assert_eq!(series.min(), Some(10.0));
+        assert_eq!(series.max(), Some(16.0));
+        assert_eq!(series.mean(), Some(13.0));
+        assert!((series.trend_per_day().unwrap() - 2.0).abs() < 1e-9);
+        assert_eq!(series.get_sparkline(), "▁▃▆█");
+        assert!(series.get_summary().contains("rising 2/day"));
+        assert_eq!(
+            series.to_csv(),
+            "date,date_millis,value\n\
+            1970-01-01T00:00:00.000Z,0,10\n\
+            1970-01-02T00:00:00.000Z,86400000,12\n\
+            1970-01-03T00:00:00.000Z,172800000,14\n\
+            1970-01-04T00:00:00.000Z,259200000,16\n"
+        );
+
+        let single = make_series(vec![(5, 3.5)]);
+        assert_eq!(single.trend_per_day(), None);
+        assert_eq!(single.get_sparkline(), "▅");
+        assert_eq!(make_series(vec![]).mean(), None);
+    }
+
+    #[test]
+    fn test_get_for_entity() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> =
+            Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let entity_id = db_b.create_entity(tx.clone(), "series test entity", None, None).unwrap();
+        let weight_id = db_b.create_entity(tx.clone(), "series test weight", None, None).unwrap();
+        let kg_id = db_b.create_entity(tx.clone(), "series test kg", None, None).unwrap();
+        let lb_id = db_b.create_entity(tx.clone(), "series test lb", None, None).unwrap();
+        for (number, valid, observed) in [(72.0, Some(3000), 1000), (70.0, None, 2000), (71.0, Some(0), 500)] {
+            db_b.create_quantity_attribute(tx.clone(), entity_id, weight_id, kg_id, number, valid, observed, None)
+                .unwrap();
+        }
+        db_b.create_quantity_attribute(tx.clone(), entity_id, weight_id, lb_id, 160.0, None, 1500, None)
+            .unwrap();
+
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let by_observed = QuantitySeries::get_for_entity(db_dyn.clone(), tx.clone(), entity_id, false).unwrap();
+        assert_eq!(by_observed.len(), 2);
+        let kg_series = by_observed.iter().find(|s| s.unit_id == kg_id).unwrap();
+        assert_eq!(kg_series.points, vec![(500, 71.0), (1000, 72.0), (2000, 70.0)]);
+        let lb_series = by_observed.iter().find(|s| s.unit_id == lb_id).unwrap();
+        assert_eq!(lb_series.points, vec![(1500, 160.0)]);
+
+        // By valid date, "all time" and unspecified fall back to the observation date:
+        let by_valid = QuantitySeries::get_for_entity(db_dyn, tx.clone(), entity_id, true).unwrap();
+        let kg_series = by_valid.iter().find(|s| s.unit_id == kg_id).unwrap();
+        assert_eq!(kg_series.points, vec![(500, 71.0), (2000, 70.0), (3000, 72.0)]);
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
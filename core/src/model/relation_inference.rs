@@ -0,0 +1,305 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+//! Relations that are implied by the ones entered, given what their relation types mean.  A
+//! relation type can be symmetric (A "is a sibling of" B implies B "is a sibling of" A),
+//! transitive (A "is part of" B and B "is part of" C imply A "is part of" C), and/or the inverse
+//! of another type (A "is part of" B implies B "contains" A).  Inferred relations are never
+//! stored: they are worked out when asked for, from the relations to local entities (remote ones
+//! are not followed).
+use anyhow::Error;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RelationSemantics {
+    pub transitive: bool,
+    pub symmetric: bool,
+    /// The relation type whose relations are the reverse of this one's.  The pair refer to
+    /// each other.
+    pub inverse_of_id: Option<i64>,
+}
+
+impl RelationSemantics {
+    pub fn is_plain(&self) -> bool {
+        !self.transitive && !self.symmetric && self.inverse_of_id.is_none()
+    }
+}
+
+/// A relation as entered (a row of RelationToEntity).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssertedRelation {
+    pub rel_type_id: i64,
+    pub from_entity_id: i64,
+    pub to_entity_id: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InferenceReason {
+    /// The same relation was entered the other way, and its type is symmetric.
+    Symmetric,
+    /// A relation of this (inverse) type was entered the other way.
+    Inverse(i64),
+    /// A chain of relations of the (transitive) type leads there, through these entities in order.
+    Transitive(Vec<i64>),
+}
+
+impl InferenceReason {
+    pub fn describe(&self, name_of: &dyn Fn(i64) -> String) -> String {
+        match self {
+            InferenceReason::Symmetric => "symmetric".to_string(),
+            InferenceReason::Inverse(rel_type_id) => format!("inverse of \"{}\"", name_of(*rel_type_id)),
+            InferenceReason::Transitive(via) => format!(
+                "via {}",
+                via.iter().map(|id| name_of(*id)).collect::<Vec<String>>().join(" > ")
+            ),
+        }
+    }
+}
+
+/// A relation from the entity that was asked about, which was not entered but is implied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferredRelation {
+    pub rel_type_id: i64,
+    pub to_entity_id: i64,
+    pub reason: InferenceReason,
+}
+
+/// How far to follow transitive chains, so a huge (or bad) hierarchy can't hang the display.
+pub const MAX_ENTITIES_TO_VISIT: usize = 10_000;
+
+/// The relations from entity_id (as (rel_type_id, to_entity_id, reason)) that are entered, with
+/// a reason of None, or implied in one step by a symmetric or inverse type.  touching_in is the
+/// relations entered to or from entity_id.
+fn one_step(
+    entity_id: i64,
+    semantics: &HashMap<i64, RelationSemantics>,
+    touching_in: &[AssertedRelation],
+) -> Vec<(i64, i64, Option<InferenceReason>)> {
+    let mut result = Vec::new();
+    for relation in touching_in {
+        if relation.from_entity_id == entity_id {
+            result.push((relation.rel_type_id, relation.to_entity_id, None));
+        }
+        if relation.to_entity_id != entity_id {
+            continue;
+        }
+        if let Some(s) = semantics.get(&relation.rel_type_id) {
+            if s.symmetric {
+                result.push((relation.rel_type_id, relation.from_entity_id, Some(InferenceReason::Symmetric)));
+            }
+            if let Some(inverse_id) = s.inverse_of_id {
+                result.push((
+                    inverse_id,
+                    relation.from_entity_id,
+                    Some(InferenceReason::Inverse(relation.rel_type_id)),
+                ));
+            }
+        }
+    }
+    result
+}
+
+/// Returns the relations from entity_id that are implied but not entered.  semantics only needs
+/// the relation types that are not plain.  relations_touching_in returns the relations entered
+/// to or from an entity, and is called once per entity visited.  Transitive chains are followed
+/// breadth-first, so the shortest chain is the one given as the reason.
+pub fn infer_relations(
+    entity_id: i64,
+    semantics: &HashMap<i64, RelationSemantics>,
+    relations_touching_in: &mut dyn FnMut(i64) -> Result<Vec<AssertedRelation>, Error>,
+) -> Result<Vec<InferredRelation>, Error> {
+    let direct = one_step(entity_id, semantics, &relations_touching_in(entity_id)?);
+    let mut known: HashSet<(i64, i64)> = direct
+        .iter()
+        .filter(|(_, _, reason)| reason.is_none())
+        .map(|(rel_type_id, to, _)| (*rel_type_id, *to))
+        .collect();
+    let mut results: Vec<InferredRelation> = Vec::new();
+    for (rel_type_id, to, reason) in &direct {
+        if let Some(reason) = reason {
+            if known.insert((*rel_type_id, *to)) {
+                results.push(InferredRelation {
+                    rel_type_id: *rel_type_id,
+                    to_entity_id: *to,
+                    reason: reason.clone(),
+                });
+            }
+        }
+    }
+
+    let mut transitive_type_ids: Vec<i64> = direct
+        .iter()
+        .map(|(rel_type_id, _, _)| *rel_type_id)
+        .filter(|id| semantics.get(id).is_some_and(|s| s.transitive))
+        .collect();
+    transitive_type_ids.sort();
+    transitive_type_ids.dedup();
+    let mut touching_cache: HashMap<i64, Vec<AssertedRelation>> = HashMap::new();
+    for rel_type_id in transitive_type_ids {
+        // Each entity reached, and the one it was reached from:
+        let mut came_from: HashMap<i64, i64> = HashMap::new();
+        let mut queue: VecDeque<i64> = VecDeque::new();
+        for (_, to, _) in direct.iter().filter(|(t, to, _)| *t == rel_type_id && *to != entity_id) {
+            if came_from.insert(*to, entity_id).is_none() {
+                queue.push_back(*to);
+            }
+        }
+        while let Some(current) = queue.pop_front() {
+            if came_from.len() >= MAX_ENTITIES_TO_VISIT {
+                break;
+            }
+            let touching = match touching_cache.entry(current) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(relations_touching_in(current)?),
+            };
+            for (t, next, _) in one_step(current, semantics, touching) {
+                if t != rel_type_id || next == entity_id || came_from.contains_key(&next) {
+                    continue;
+                }
+                came_from.insert(next, current);
+                queue.push_back(next);
+                if known.insert((rel_type_id, next)) {
+                    let mut via: Vec<i64> = Vec::new();
+                    let mut step = current;
+                    while step != entity_id {
+                        via.push(step);
+                        step = came_from[&step];
+                    }
+                    via.reverse();
+                    results.push(InferredRelation {
+                        rel_type_id,
+                        to_entity_id: next,
+                        reason: InferenceReason::Transitive(via),
+                    });
+                }
+            }
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PART_OF: i64 = 1;
+    const CONTAINS: i64 = 2;
+    const SIBLING: i64 = 3;
+
+    fn infer(entity_id: i64, relations: &[AssertedRelation]) -> Vec<InferredRelation> {
+        let mut semantics: HashMap<i64, RelationSemantics> = HashMap::new();
+        semantics.insert(
+            PART_OF,
+            RelationSemantics {
+                transitive: true,
+                symmetric: false,
+                inverse_of_id: Some(CONTAINS),
+            },
+        );
+        semantics.insert(
+            CONTAINS,
+            RelationSemantics {
+                transitive: true,
+                symmetric: false,
+                inverse_of_id: Some(PART_OF),
+            },
+        );
+        semantics.insert(
+            SIBLING,
+            RelationSemantics {
+                transitive: false,
+                symmetric: true,
+                inverse_of_id: None,
+            },
+        );
+        infer_relations(entity_id, &semantics, &mut |id| {
+            Ok(relations
+                .iter()
+                .filter(|r| r.from_entity_id == id || r.to_entity_id == id)
+                .copied()
+                .collect())
+        })
+        .unwrap()
+    }
+
+    fn rel(rel_type_id: i64, from_entity_id: i64, to_entity_id: i64) -> AssertedRelation {
+        AssertedRelation {
+            rel_type_id,
+            from_entity_id,
+            to_entity_id,
+        }
+    }
+
+    #[test]
+    fn test_infer_relations() {
+        // 10 (a city) is part of 11 (a country); 12 (a continent) contains 11; 11 and 13 are
+        // siblings, and 13 is also entered as a sibling of 11; 12 is part of 10 by mistake, making
+        // a cycle.
+        let relations = vec![
+            rel(PART_OF, 10, 11),
+            rel(CONTAINS, 12, 11),
+            rel(SIBLING, 13, 11),
+            rel(SIBLING, 11, 13),
+            rel(PART_OF, 12, 10),
+        ];
+        let from_city = infer(10, &relations);
+        assert_eq!(
+            from_city,
+            vec![
+                InferredRelation {
+                    rel_type_id: CONTAINS,
+                    to_entity_id: 12,
+                    reason: InferenceReason::Inverse(PART_OF),
+                },
+                InferredRelation {
+                    rel_type_id: PART_OF,
+                    to_entity_id: 12,
+                    reason: InferenceReason::Transitive(vec![11]),
+                },
+                // (Because of the cycle; but following it stops.)
+                InferredRelation {
+                    rel_type_id: CONTAINS,
+                    to_entity_id: 11,
+                    reason: InferenceReason::Transitive(vec![12]),
+                },
+            ]
+        );
+        let from_country = infer(11, &relations);
+        assert!(from_country.contains(&InferredRelation {
+            rel_type_id: PART_OF,
+            to_entity_id: 12,
+            reason: InferenceReason::Inverse(CONTAINS),
+        }));
+        assert!(from_country.contains(&InferredRelation {
+            rel_type_id: CONTAINS,
+            to_entity_id: 10,
+            reason: InferenceReason::Inverse(PART_OF),
+        }));
+        // Entered both ways, so nothing is inferred about the siblings:
+        assert!(!from_country.iter().any(|r| r.rel_type_id == SIBLING));
+        let from_sibling = infer(13, &relations);
+        assert!(from_sibling.is_empty());
+
+        let relations = vec![rel(SIBLING, 13, 11)];
+        assert_eq!(
+            infer(11, &relations),
+            vec![InferredRelation {
+                rel_type_id: SIBLING,
+                to_entity_id: 13,
+                reason: InferenceReason::Symmetric,
+            }]
+        );
+        assert!(infer(13, &relations).is_empty());
+
+        let name_of = |id: i64| format!("e{}", id);
+        assert_eq!(InferenceReason::Transitive(vec![11, 12]).describe(&name_of), "via e11 > e12");
+        assert_eq!(InferenceReason::Inverse(2).describe(&name_of), "inverse of \"e2\"");
+    }
+}
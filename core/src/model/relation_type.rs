@@ -16,6 +16,7 @@ use anyhow::{anyhow, /*Error, */ Result};
 // use sqlx::{PgPool, Postgres, Row, Transaction};
 //use crate::model::attribute::Attribute;
 use crate::model::entity::Entity;
+use crate::model::relation_inference::RelationSemantics;
 // use crate::model::id_wrapper::IdWrapper;
 //use crate::model::relation_to_entity::RelationToEntity;
 //use crate::model::relation_type::RelationType;
@@ -32,6 +33,15 @@ enum RelationDirectionality {
     NON,
 }
 
+/// The classes that the entities at each end of a relation of a given type must be in (or in
+/// a subclass of): its domain and range.  An empty list allows any entity at that end.  These
+/// are checked when relations to local entities are created or changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RelationClassConstraints {
+    pub source_class_ids: Vec<i64>,
+    pub target_class_ids: Vec<i64>,
+}
+
 pub struct RelationType {
     db: Rc<RefCell<dyn Database>>,
     entity_id: i64,
@@ -215,6 +225,38 @@ impl RelationType {
         Ok(())
     }
 
+    /// Whether this type is transitive, symmetric, or the inverse of another; see
+    /// relation_inference.rs.  (Not cached, since setting an inverse also changes the other type.)
+    pub fn get_semantics(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<RelationSemantics, anyhow::Error> {
+        self.db.borrow().get_relation_type_semantics(transaction, self.entity_id)
+    }
+
+    pub fn update_semantics(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        semantics_in: &RelationSemantics,
+    ) -> Result<(), anyhow::Error> {
+        self.db.borrow().update_relation_type_semantics(transaction, self.entity_id, semantics_in)
+    }
+
+    pub fn get_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<RelationClassConstraints, anyhow::Error> {
+        self.db.borrow().get_relation_type_class_constraints(transaction, self.entity_id)
+    }
+
+    pub fn update_class_constraints(
+        &self,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        constraints_in: &RelationClassConstraints,
+    ) -> Result<(), anyhow::Error> {
+        self.db.borrow().set_relation_type_class_constraints(transaction, self.entity_id, constraints_in)
+    }
+
     /// Removes this object from the system.
     pub fn delete<'a>(
         &'a mut self,
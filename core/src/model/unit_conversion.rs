@@ -0,0 +1,284 @@
+/*  This file is part of OneModel, a program to manage knowledge.
+    Copyright in each year of 2025, Luke A. Call.
+    OneModel is free software, distributed under a license that includes honesty, the Golden Rule,
+    and the GNU Affero General Public License as published by the Free Software Foundation;
+    see the file LICENSE for license version and details.
+    OneModel is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU Affero General Public License for more details.
+    You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
+*/
+use crate::model::database::Database;
+use crate::model::entity_query::EntityQuery;
+use sqlx::{Postgres, Transaction};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+/// Converts numbers between unit entities, using the factors recorded with
+/// Database::set_unit_conversion.  Conversions chain, so with km->m and m->cm recorded,
+/// km->cm also works.
+#[derive(Debug, Default)]
+pub struct UnitConverter {
+    /// For each unit, the units it converts to directly, and the factor.
+    factors: HashMap<i64, Vec<(i64, f64)>>,
+}
+
+impl UnitConverter {
+    pub fn new(conversions: &[(i64, i64, f64)]) -> UnitConverter {
+        let mut factors: HashMap<i64, Vec<(i64, f64)>> = HashMap::new();
+        for (from, to, factor) in conversions {
+            factors.entry(*from).or_default().push((*to, *factor));
+            factors.entry(*to).or_default().push((*from, 1.0 / factor));
+        }
+        UnitConverter { factors }
+    }
+
+    pub fn load(
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+    ) -> Result<UnitConverter, anyhow::Error> {
+        let conversions = db.borrow().get_unit_conversions(transaction)?;
+        Ok(UnitConverter::new(&conversions))
+    }
+
+    /// How many to_unit_in's one from_unit_in is worth, or None if they are not related by any
+    /// recorded conversions.
+    pub fn get_factor(&self, from_unit_in: i64, to_unit_in: i64) -> Option<f64> {
+        if from_unit_in == to_unit_in {
+            return Some(1.0);
+        }
+        // (Breadth-first, so the shortest chain of conversions is used.)
+        let mut visited: HashSet<i64> = HashSet::new();
+        let mut queue: VecDeque<(i64, f64)> = VecDeque::new();
+        visited.insert(from_unit_in);
+        queue.push_back((from_unit_in, 1.0));
+        while let Some((unit, factor_so_far)) = queue.pop_front() {
+            for (next_unit, factor) in self.factors.get(&unit).into_iter().flatten() {
+                if *next_unit == to_unit_in {
+                    return Some(factor_so_far * factor);
+                }
+                if visited.insert(*next_unit) {
+                    queue.push_back((*next_unit, factor_so_far * factor));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn convert(&self, number_in: f64, from_unit_in: i64, to_unit_in: i64) -> Option<f64> {
+        self.get_factor(from_unit_in, to_unit_in).map(|f| number_in * f)
+    }
+}
+
+/// Which entities to total quantities over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuantityScope {
+    /// The entities in a group.
+    Group(i64),
+    /// An entity and everything it contains (via relations and groups), as for the "under"
+    /// clause of an EntityQuery.
+    Subtree(i64),
+}
+
+/// The sum and mean of one quantity attribute type across the entities in a QuantityScope, all
+/// converted to one unit.  Quantities that cannot be converted to that unit are left out of the
+/// totals and listed in mismatched, rather than being silently added in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantityAggregate {
+    pub attr_type_id: i64,
+    /// None if no quantities were found.
+    pub unit_id: Option<i64>,
+    pub count: usize,
+    pub sum: f64,
+    /// (entity_id, unit_id, number) of each quantity that could not be converted to unit_id.
+    pub mismatched: Vec<(i64, i64, f64)>,
+}
+
+impl QuantityAggregate {
+    /// Uses the most recently observed quantity of attr_type_id_in on each entity in scope_in.
+    /// The totals are in the attribute type's canonical unit if it has one, otherwise in the
+    /// unit used most often (on a tie, the one with the lowest id).
+    pub fn compute(
+        db: Rc<RefCell<dyn Database>>,
+        transaction: Option<Rc<RefCell<Transaction<Postgres>>>>,
+        attr_type_id_in: i64,
+        scope_in: QuantityScope,
+    ) -> Result<QuantityAggregate, anyhow::Error> {
+        let entity_ids: Vec<i64> = match scope_in {
+            QuantityScope::Group(group_id) => {
+                db.borrow().get_group_entry_ids(transaction.clone(), group_id, 0, None)?
+            }
+            QuantityScope::Subtree(entity_id) => {
+                let mut contained: HashSet<i64> = HashSet::new();
+                contained.insert(entity_id);
+                db.borrow().find_contained_local_entity_ids(
+                    transaction.clone(),
+                    &mut contained,
+                    entity_id,
+                    "",
+                    EntityQuery::CONTAINMENT_LEVELS,
+                    false,
+                )?;
+                let mut ids: Vec<i64> = contained.into_iter().collect();
+                ids.sort();
+                ids
+            }
+        };
+        let quantities =
+            db.borrow()
+                .get_latest_quantities(transaction.clone(), attr_type_id_in, &entity_ids)?;
+        let canonical_unit = db.borrow().get_canonical_unit(transaction.clone(), attr_type_id_in)?;
+        let converter = UnitConverter::load(db, transaction)?;
+        Ok(Self::from_quantities(
+            attr_type_id_in,
+            &quantities,
+            canonical_unit,
+            &converter,
+        ))
+    }
+
+    pub fn from_quantities(
+        attr_type_id_in: i64,
+        quantities_in: &[(i64, i64, f64)],
+        canonical_unit_in: Option<i64>,
+        converter_in: &UnitConverter,
+    ) -> QuantityAggregate {
+        let unit_id: Option<i64> = canonical_unit_in.or_else(|| {
+            let mut counts: HashMap<i64, usize> = HashMap::new();
+            for (_, unit, _) in quantities_in {
+                *counts.entry(*unit).or_default() += 1;
+            }
+            counts
+                .into_iter()
+                .max_by(|(unit_a, count_a), (unit_b, count_b)| {
+                    count_a.cmp(count_b).then(unit_b.cmp(unit_a))
+                })
+                .map(|(unit, _)| unit)
+        });
+        let mut aggregate = QuantityAggregate {
+            attr_type_id: attr_type_id_in,
+            unit_id,
+            count: 0,
+            sum: 0.0,
+            mismatched: Vec::new(),
+        };
+        let Some(unit_id) = unit_id else {
+            return aggregate;
+        };
+        for (entity_id, unit, number) in quantities_in {
+            match converter_in.convert(*number, *unit, unit_id) {
+                Some(converted) => {
+                    aggregate.count += 1;
+                    aggregate.sum += converted;
+                }
+                None => aggregate.mismatched.push((*entity_id, *unit, *number)),
+            }
+        }
+        aggregate
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum / self.count as f64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::postgres::postgresql_database::PostgreSQLDatabase;
+    use crate::util::Util;
+
+    const KM: i64 = 1;
+    const M: i64 = 2;
+    const CM: i64 = 3;
+    const KG: i64 = 4;
+
+    #[test]
+    fn test_conversions_chain_and_invert() {
+        let converter = UnitConverter::new(&[(KM, M, 1000.0), (M, CM, 100.0)]);
+        assert_eq!(converter.get_factor(KM, KM), Some(1.0));
+        assert_eq!(converter.convert(2.0, KM, M), Some(2000.0));
+        assert_eq!(converter.convert(2000.0, M, KM), Some(2.0));
+        assert_eq!(converter.convert(1.5, KM, CM), Some(150000.0));
+        assert_eq!(converter.get_factor(KM, KG), None);
+    }
+
+    #[test]
+    fn test_aggregate_flags_mismatched_units() {
+        let converter = UnitConverter::new(&[(KM, M, 1000.0)]);
+        let quantities = vec![(10, M, 500.0), (11, M, 250.0), (12, KM, 1.0), (13, KG, 7.0)];
+        let by_most_used = QuantityAggregate::from_quantities(99, &quantities, None, &converter);
+        assert_eq!(by_most_used.unit_id, Some(M));
+        assert_eq!(by_most_used.count, 3);
+        assert_eq!(by_most_used.sum, 1750.0);
+        assert_eq!(by_most_used.mismatched, vec![(13, KG, 7.0)]);
+
+        let by_canonical = QuantityAggregate::from_quantities(99, &quantities, Some(KM), &converter);
+        assert_eq!(by_canonical.sum, 1.75);
+        assert_eq!(by_canonical.mean(), Some(1.75 / 3.0));
+
+        let empty = QuantityAggregate::from_quantities(99, &[], None, &converter);
+        assert_eq!(empty.unit_id, None);
+        assert_eq!(empty.mean(), None);
+    }
+
+    #[test]
+    fn test_compute_over_group() {
+        Util::initialize_tracing();
+        let db: Rc<RefCell<PostgreSQLDatabase>> =
+            Rc::new(RefCell::new(Util::initialize_test_db().unwrap()));
+        let db_b = db.borrow();
+        let tx = db_b.begin_trans().unwrap();
+        let tx = Some(Rc::new(RefCell::new(tx)));
+        let length_id = db_b.create_entity(tx.clone(), "unit test length", None, None).unwrap();
+        let km_id = db_b.create_entity(tx.clone(), "unit test km", None, None).unwrap();
+        let m_id = db_b.create_entity(tx.clone(), "unit test m", None, None).unwrap();
+        let kg_id = db_b.create_entity(tx.clone(), "unit test kg", None, None).unwrap();
+        db_b.set_unit_conversion(tx.clone(), km_id, m_id, 1000.0).unwrap();
+        // Setting it again (even in the other direction) replaces it:
+        db_b.set_unit_conversion(tx.clone(), m_id, km_id, 0.001).unwrap();
+        assert_eq!(db_b.get_unit_conversions(tx.clone()).unwrap(), vec![(m_id, km_id, 0.001)]);
+        assert!(db_b.set_unit_conversion(tx.clone(), m_id, m_id, 1.0).is_err());
+
+        let group_id = db_b.create_group(tx.clone(), "unit test group", false).unwrap();
+        let entries = [(km_id, 2.0, 1000), (m_id, 500.0, 1000), (kg_id, 3.0, 1000)];
+        for (i, (unit, number, observed)) in entries.iter().enumerate() {
+            let entity_id = db_b
+                .create_entity(tx.clone(), &format!("unit test road {}", i), None, None)
+                .unwrap();
+            db_b.create_quantity_attribute(tx.clone(), entity_id, length_id, *unit, *number, None, *observed, None)
+                .unwrap();
+            db_b.add_entity_to_group(tx.clone(), group_id, entity_id, None).unwrap();
+            if i == 0 {
+                // An older observation, which should not be counted:
+                db_b.create_quantity_attribute(tx.clone(), entity_id, length_id, km_id, 9.0, None, 10, None)
+                    .unwrap();
+            }
+        }
+        db_b.set_canonical_unit(tx.clone(), length_id, Some(m_id)).unwrap();
+        assert_eq!(db_b.get_canonical_unit(tx.clone(), length_id).unwrap(), Some(m_id));
+
+        let db_dyn: Rc<RefCell<dyn Database>> = db.clone();
+        let aggregate =
+            QuantityAggregate::compute(db_dyn, tx.clone(), length_id, QuantityScope::Group(group_id)).unwrap();
+        assert_eq!(aggregate.unit_id, Some(m_id));
+        assert_eq!(aggregate.count, 2);
+        assert_eq!(aggregate.sum, 2500.0);
+        assert_eq!(aggregate.mismatched.len(), 1);
+        assert_eq!(aggregate.mismatched[0].1, kg_id);
+
+        db_b.set_canonical_unit(tx.clone(), length_id, None).unwrap();
+        assert_eq!(db_b.get_canonical_unit(tx.clone(), length_id).unwrap(), None);
+
+        // The cached converter is kept until the conversions change:
+        let converter = db_b.get_unit_converter().unwrap();
+        assert!(Rc::ptr_eq(&converter, &db_b.get_unit_converter().unwrap()));
+        db_b.delete_unit_conversion(tx.clone(), km_id, m_id).unwrap();
+        assert!(!Rc::ptr_eq(&converter, &db_b.get_unit_converter().unwrap()));
+        // (No need to roll back tx explicitly: that is automatic when it goes out of scope.)
+    }
+}
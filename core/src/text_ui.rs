@@ -21,6 +21,10 @@ use anyhow::anyhow;
 use rustyline::error::ReadlineError;
 // use rustyline::{Editor, Result as RustyLineResult};
 use rustyline::{DefaultEditor, Result as RustyLineResult};
+use std::io::Write;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use zeroize::Zeroizing;
 //use std::error::Error;
 // use std::io::{Error, ErrorKind};
 
@@ -291,7 +295,8 @@ impl TextUI {
         // where it mentions "FnMut"?
         criteria_in: Option<fn(s: &str /*%%(not needed right?), ui: &TextUI*/) -> Result<(), anyhow::Error>>,
         default_value_in: String,
-        //%%use this parm below, not just as another parameter. For pwd entry, sch crates.ui for "password entry" and/or use dialoguer and/or can rustyline do it/modify it/ask somewhere anyway? (see also other %%cmts below re this like "dialoguer" etc)
+        // If true, the input is masked and kept out of the line editor's history (see
+        // read_masked_line), and criteria_in and default_value_in are ignored.
         is_password_in: bool,
         esc_key_skips_criteria_check_in: bool,
         error_message_in: Option<&str>, /*=None*/
@@ -333,6 +338,16 @@ impl TextUI {
             println!("{last_line_of_prompt}");
         }
 
+        if is_password_in {
+            return match Self::read_masked_line() {
+                Ok(Some(p)) if p.is_empty() && esc_key_skips_criteria_check_in => None,
+                Ok(p) => p.map(|p| p.to_string()),
+                Err(e) => {
+                    eprintln!("Unable to read password: {}", e);
+                    None
+                }
+            };
+        }
         let user_input: Option<String> = loop {
             // `()` can be used when no completer is required?
             //let r = Editor::<()>::new();
@@ -350,9 +365,6 @@ impl TextUI {
                     // then try that w/ username/password forced w/ x parm.
                     //%%why did blank pwd not give any err nor exit? try gdb or how best2debug? (was when util.get_default_user_login alw returned a bad def pwd)
                     //%%see if the editor history has password in it? is there any edi hist or have2specify?see docs
-                    //%%add password mask -- use dialoguer crate? termion (says it can; alr using?)?
-                    //or ask/ck issue tracker for rustyline?
-                    // let line = jlineReader.readLine(null, if is_password_in { '*' } else { null } );
                     //%%make ^C work to get out of prompt! ?  see where trapped ("Error: ..."), just below.
                     let line = editor.readline_with_initial("", (default_value_in.as_str(), ""));
                     match line {
@@ -424,6 +436,41 @@ impl TextUI {
         return user_input;
     }
 
+    /// Reads a line from the terminal showing a '*' for each character instead of echoing it.
+    /// Returns None if the user pressed ESC, Ctrl-C or Ctrl-D.  The buffer is zeroized as it is
+    /// dropped, but the caller should also clear the returned value when done with it (such as
+    /// by wrapping it in a Zeroizing).
+    fn read_masked_line() -> Result<Option<Zeroizing<String>>, std::io::Error> {
+        let mut stdout = std::io::stdout().into_raw_mode()?;
+        let mut input = Zeroizing::new(String::new());
+        let mut result: Option<Zeroizing<String>> = None;
+        for key in std::io::stdin().keys() {
+            match key? {
+                termion::event::Key::Char('\n') | termion::event::Key::Char('\r') => {
+                    result = Some(input);
+                    break;
+                }
+                termion::event::Key::Char(c) => {
+                    input.push(c);
+                    write!(stdout, "*")?;
+                }
+                termion::event::Key::Backspace => {
+                    if input.pop().is_some() {
+                        write!(stdout, "\x08 \x08")?;
+                    }
+                }
+                termion::event::Key::Esc
+                | termion::event::Key::Ctrl('c')
+                | termion::event::Key::Ctrl('d') => break,
+                _ => {}
+            }
+            stdout.flush()?;
+        }
+        write!(stdout, "\r\n")?;
+        stdout.flush()?;
+        Ok(result)
+    }
+
     /// after accounting for leading text and the initial choices.
     fn lines_left(
         num_of_leading_text_lines_in: usize,
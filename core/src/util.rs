@@ -10,6 +10,7 @@
     You should have received a copy of the GNU Affero General Public License along with OneModel.  If not, see <http://www.gnu.org/licenses/>
 */
 
+use crate::clipboard::{self, ClipboardContentKind};
 use crate::model::database::DataType;
 //use crate::model::attribute_with_valid_and_observed_dates::AttributeWithValidAndObservedDates;
 use crate::model::attribute::Attribute;
@@ -80,13 +81,16 @@ impl Util {
     pub const EDITOR_INFO_ENTITY_NAME: &'static str = "editorInfo";
     pub const TEXT_EDITOR_INFO_ENTITY_NAME: &'static str = "textEditorInfo";
     pub const TEXT_EDITOR_COMMAND_ATTRIBUTE_TYPE_NAME: &'static str = "textEditorCommand";
+    // The attribute type for an entity's notes, as imported from and exported to other formats
+    // (org-mode body text, OPML _note, etc):
+    pub const DESCRIPTION_TYPE_NAME: &'static str = "description";
     pub const PREF_TYPE_BOOLEAN: &'static str = "boolean";
     pub const PREF_TYPE_ENTITY_ID: &'static str = "entity_id";
     pub const TEMPLATE_NAME_SUFFIX: &'static str = "-template";
     pub const UNUSED_GROUP_ERR1: &'static str =
         "No available index found which is not already used. How would so many be used?";
     pub const UNUSED_GROUP_ERR2: &'static str = "Very unexpected, but could it be that you are running out of available sorting indexes!?  Have someone check, before you need to create, for example, a thousand more entities.";
-    pub const GET_CLASS_DATA__RESULT_TYPES: &'static str = "String,i64,bool";
+    pub const GET_CLASS_DATA__RESULT_TYPES: &'static str = "String,i64,bool,i64";
     pub const GET_RELATION_TYPE_DATA__RESULT_TYPES: &'static str = "String,String,String";
     pub const GET_OM_INSTANCE_DATA__RESULT_TYPES: &'static str = "bool,String,i64,i64";
     pub const GET_QUANTITY_ATTRIBUTE_DATA__RESULT_TYPES: &'static str =
@@ -217,6 +221,7 @@ impl Util {
         "Should entity lists show public/private status for each?";
     pub const DEFAULT_ENTITY_PREFERENCE: &'static str =
         "Which entity should be displayed as default, when starting the program?";
+    pub const BOOKMARKS_PREFERENCE: &'static str = "Bookmarks";
     // (If change next line, also change the hard-coded use in the file first.exp.)
     const HEADER_CONTENT_TAG: &'static str = "htmlHeaderContent";
     const BODY_CONTENT_TAG: &'static str = "htmlInitialBodyContent";
@@ -226,16 +231,9 @@ impl Util {
     pub const SELECT_ENTITY_START: &'static str =
         "SELECT e.id, e.name, e.class_id, e.insertion_date, e.public, e.archived, e.new_entries_stick_to_top ";
 
-    pub fn get_clipboard_content() -> String {
-        // let clipboard: java.awt.datatransfer.Clipboard = java.awt.Toolkit.getDefaultToolkit.getSystemClipboard;
-        // let contents: String = clipboard.getContents(null).getTransferData(java.awt.datatransfer.DataFlavor.stringFlavor).toString;
-        // contents.trim
-        // //(example of placing data on the clipboard, for future reference:)
-        // //val selection = new java.awt.datatransfer.StringSelection("someString")
-        // //clipboard.setContents(selection, null)
-
-        //%%implement above
-        "not yet implemented".to_string()
+    /// The clipboard's text, trimmed (see clipboard.rs for how it is read).
+    pub fn get_clipboard_content() -> Result<String, anyhow::Error> {
+        Ok(clipboard::get_clipboard_content()?.trim().to_string())
     }
 
     pub fn is_windows() -> bool {
@@ -265,16 +263,12 @@ impl Util {
     //   } else { Some(previously_highlighted_entry_in) }
     // }
 
-    pub fn get_default_user_login() -> Result<(String, &'static str), String> {
+    /// The system username, to try first when logging in.  (There is deliberately no default
+    /// password: see credentials.rs for the ways a password can be supplied without typing it.)
+    pub fn get_default_username() -> Result<String, String> {
         //%%how do this on other platforms? windows at least? some crate? std doesn't seem to have a clear answer.
         //was in scala: (System.getProperty("user.name"), "x")
-        match std::env::var("USER") {
-            Ok(val) => Ok((val, "x")),
-            Err(e) => {
-                let msg = e.to_string();
-                Err(msg)
-            }
-        }
+        std::env::var("USER").map_err(|e| e.to_string())
     }
 
     // ****** MAKE SURE THE NEXT 2 LINES MATCH THE FORMAT of Controller.DATEFORMAT, AND THE USER EXAMPLES IN THIS CLASS' OUTPUT! ******
@@ -303,6 +297,13 @@ impl Util {
 
     pub const MAIN_SEARCH_PROMPT: &'static str =
         "Search all / list existing entities (except quantity units, attr types, & relation types)";
+    pub const QUERY_SYNTAX_HELP: &'static str =
+        "Enter a query: one or more clauses joined by \"and\", each optionally preceded by \"not\".  \
+    Clauses: class = <name>;  name = <name>, or name ~ <regex>;  quantity|text|date|boolean <attribute \
+    type name> [<op> <value>];  related <relation type name> [to <entity id or name>];  under <entity \
+    id or name>;  added <op> <date>;  archived.  Operators: = != < <= > >= ~ (~ is a case-insensitive \
+    regex, for text).  Dates: YYYY-MM-DD, YYYY-MM-DD HH:MM, today, or -Nd (N days ago).  Quote names \
+    with spaces, e.g.:  class = Book and date \"read on\" > 2024-01-01 and under 123 and not archived";
     pub const MENUTEXT_VIEW_PREFERENCES: &'static str = "View preferences";
 
     pub const GENERIC_DATE_PROMPT: &'static str =
@@ -1293,6 +1294,7 @@ impl Util {
             rt,
             pool,
             include_archived_entities: false,
+            unit_converter: RefCell::new(None),
         };
         //Idea: another way to do this might be the crate once_cell. (Or does std::sync::Once
         //superceded that?)
@@ -1462,7 +1464,7 @@ impl Util {
 
     /// Returns None if user wants to cancel.
     pub fn ask_for_text_attribute_text(
-        _: &Controller,
+        controller: &Controller,
         _: Rc<RefCell<dyn Database>>,
         dh_in: &mut AttributeDataHolder, /*::TextAttributeDH*/
         // i.e., vs. adding a new one, right?:
@@ -1481,18 +1483,69 @@ impl Util {
                     observation_date: tadh.observation_date,
                     text: "".to_string(),
                 };
-                let default_text_value: &str = if editing_in {
-                    tadh.text.as_str()
+                let mut default_text_value: String = if editing_in {
+                    tadh.text.clone()
                 } else {
-                    ""
+                    "".to_string()
                 };
+                let keyboard_or_clipboard = ui.ask_which(
+                    Some(vec!["Do you want to enter the text via the keyboard (typing or directly pasting), or \
+                              have OM pull it directly from the clipboard (faster sometimes, especially if \
+                              it's multi-line)?".to_string()]),
+                    &vec!["keyboard".to_string(), "clipboard".to_string()],
+                    &Vec::<String>::new(),
+                    true,
+                    None,
+                    None,
+                    None,
+                    None,
+                );
+                match keyboard_or_clipboard {
+                    None => return Ok(None),
+                    Some(2) => {
+                        let Some(content) = controller.get_clipboard_content_or_show_error() else {
+                            return Ok(None);
+                        };
+                        let content = match clipboard::get_content_kind(&content) {
+                            ClipboardContentKind::Uri => {
+                                let use_anyway = ui.ask_yes_no_question(
+                                    "That looks like a URI.  (To save it as a link, use the \"external \
+                                    web page\" kind of attribute instead.)  Use it as text anyway?",
+                                    "y",
+                                    false,
+                                );
+                                if use_anyway != Some(true) {
+                                    return Ok(None);
+                                }
+                                content
+                            }
+                            ClipboardContentKind::Quote => clipboard::strip_quotation_marks(&content).to_string(),
+                            ClipboardContentKind::PlainText => content,
+                        };
+                        if content.contains('\n') {
+                            // (Too many lines to edit at the prompt below, so just confirm it.)
+                            let ok = ui.ask_yes_no_question(
+                                &format!("Use this text?:\n{}\n", content),
+                                "y",
+                                false,
+                            );
+                            if ok != Some(true) {
+                                return Ok(None);
+                            }
+                            tadh_out.text = content;
+                            return Ok(Some(AttributeDataHolder::TextAttributeDH { tadh: tadh_out }));
+                        }
+                        default_text_value = content;
+                    }
+                    Some(_) => {}
+                }
                 let leading_text = vec!["Type or paste a single-line attribute value, then press Enter; ESC to cancel.  \
                                      (If you need to add or edit multiple lines, just put in a single line or letter for \
                                      now (or leave the multiple lines if already in place), then you can edit it afterward \
                                      to add the full text.  But consider if a 'file' attribute or some other way of \
                                      modeling the info would be better at representing what it really *is*.  Legitimate \
                                      use cases for a text attribute might include a quote or a stack trace.)"];
-                let answer = ui.ask_for_string3(leading_text, None, default_text_value.to_string());
+                let answer = ui.ask_for_string3(leading_text, None, default_text_value);
                 match answer {
                     None => Ok(None),
                     Some(ans) => {
@@ -1850,6 +1903,21 @@ impl Util {
     import scala.annotation.tailrec
     %%*/
 
+    /// The first-created entity with exactly the name (as when finding an attribute type by
+    /// name), if any.
+    pub fn find_entity_id_by_name(
+        db: &dyn Database,
+        transaction: Option<Rc<RefCell<sqlx::Transaction<sqlx::Postgres>>>>,
+        name_in: &str,
+    ) -> Result<Option<i64>, anyhow::Error> {
+        let ids = db.find_all_entity_ids_by_name(
+            transaction,
+            PostgreSQLDatabase::escape_quotes_etc(name_in.to_string()),
+            true,
+        )?;
+        Ok(ids.into_iter().min())
+    }
+
     pub fn current_or_remote_db(relation_to_entity_in: Rc<RefCell<dyn Attribute>>, current_db: Rc<RefCell<dyn Database>>) 
         -> Result<Rc<RefCell<dyn Database>>, anyhow::Error> 
     {